@@ -0,0 +1,10821 @@
+//! Word-by-word byte spans for string literals in Rust source and for raw text.
+//!
+//! The typical flow is to locate a string literal with [`find_string_in_source`], split it with
+//! [`get_word_spans`], and narrow the result with [`filter_word_spans`] / [`exclude_word_spans`].
+//!
+//! ```
+//! use rust_span_counter::{find_string_in_source, get_word_spans, Granularity};
+//!
+//! let source = "fn main() {\n    let s = \"hello world\";\n}\n";
+//! let content = find_string_in_source(source, 2, None).unwrap();
+//! let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+//! assert_eq!(spans.len(), 2);
+//! ```
+
+use proc_macro2::{Delimiter, LineColumn, Literal, Span, TokenStream, TokenTree};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{
+    visit::Visit, AttrStyle, Attribute, Block, Expr, ExprLit, FieldValue, File, Ident,
+    ImplItemConst, ImplItemFn, Item, ItemConst, ItemFn, ItemStatic, Lit, LitByteStr, LitCStr,
+    LitChar, LitStr, Local, Macro, Member, Meta, MetaNameValue, Pat, Signature, TraitItemFn,
+};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::{UWordBounds, UnicodeSegmentation};
+
+/// How filter patterns are matched against span words
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum FilterMode {
+    /// Exact word match
+    #[default]
+    Exact,
+    /// Word contains the filter string
+    Contains,
+    /// Word matches the regex pattern
+    Regex,
+    /// Word's embedding is close to the filter word's, see [`SpanExtractor::embeddings`]
+    #[cfg_attr(
+        feature = "cli",
+        value(help = "Word's embedding is close to the filter word's (needs --embeddings)")
+    )]
+    Semantic,
+    /// Word is within a Levenshtein distance of the filter string, see [`SpanExtractor::fuzzy_distance`]
+    #[cfg_attr(
+        feature = "cli",
+        value(help = "Word is within --fuzzy-distance edits of the filter string")
+    )]
+    Fuzzy,
+    /// Whole word matches the shell-style glob (`*`, `?`, `[...]`)
+    #[cfg_attr(
+        feature = "cli",
+        value(help = "Whole word matches the shell-style glob (*, ?, [...])")
+    )]
+    Glob,
+}
+
+/// Order in which [`sort_spans`] arranges spans
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SortOrder {
+    /// Token stream order
+    #[default]
+    Position,
+    /// Alphabetical by word
+    Word,
+    /// Reverse alphabetical by word
+    WordDesc,
+    /// Shortest word first, in characters
+    Length,
+    /// By start byte offset
+    Start,
+}
+
+/// Normalization applied to a word when computing its cluster key in [`cluster_spans`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ClusterKey {
+    /// Lowercase the word
+    Lowercase,
+    /// Remove diacritics, so `é` becomes `e`
+    StripAccents,
+    /// Trim leading and trailing punctuation
+    TrimPunctuation,
+}
+
+/// Transformation of each span's word by [`normalize_spans`]; offsets keep pointing at the original text
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum WordNormalization {
+    /// Leave words as they appear
+    #[default]
+    None,
+    /// Lowercase the word
+    Lowercase,
+    /// Uppercase the word
+    Uppercase,
+    /// Unicode canonical composition, so `e` followed by a combining accent becomes `é`
+    Nfc,
+    /// Unicode compatibility composition, which also folds forms like `ﬁ` into `fi`
+    Nfkc,
+}
+
+/// Which items [`retain_test_code`] keeps
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum TestCode {
+    /// Keep everything
+    #[default]
+    Include,
+    /// Drop test functions and `#[cfg(test)]` items
+    Skip,
+    /// Keep only test functions and `#[cfg(test)]` items
+    Only,
+}
+
+/// What span offsets count, see [`unit_offsets`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OffsetUnit {
+    /// UTF-8 bytes
+    #[default]
+    Bytes,
+    /// Unicode scalar values
+    Chars,
+    /// Extended grapheme clusters
+    Graphemes,
+    /// UTF-16 code units, as JavaScript string indices count
+    Utf16,
+}
+
+/// The form of a string literal found in Rust source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiteralKind {
+    /// `"..."` or `r"..."`
+    Str,
+    /// `b"..."` or `br"..."`
+    ByteStr,
+    /// `c"..."` or `cr"..."`
+    CStr,
+    /// `'x'`
+    Char,
+    /// A doc comment (`///`, `//!`, `/** */`, `/*! */`) or `#[doc = "..."]` attribute
+    Doc,
+}
+
+impl LiteralKind {
+    /// Name used in structured output: `str`, `byte_str`, `c_str`, `char`, or `doc`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiteralKind::Str => "str",
+            LiteralKind::ByteStr => "byte_str",
+            LiteralKind::CStr => "c_str",
+            LiteralKind::Char => "char",
+            LiteralKind::Doc => "doc",
+        }
+    }
+}
+
+/// Size of the spans produced by [`get_word_spans`]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Granularity {
+    /// One span per word or punctuation token
+    #[default]
+    Word,
+    /// One span per sentence
+    Sentence,
+    /// One span per non-blank line
+    Line,
+}
+
+/// Errors produced while extracting or filtering spans
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    /// Reading or writing `path` failed
+    IoError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    ParseError(syn::Error),
+    /// No literal covers `line`; up to three of the closest lines that do hold one are listed
+    NoStringFound {
+        line: usize,
+        nearest_lines_with_strings: Vec<usize>,
+    },
+    MultipleStringsFound,
+    RegexError(regex::Error),
+    JsonError(serde_json::Error),
+    JsonPointerNotFound(String),
+    JsonPointerNotString(String),
+    TomlError(toml::de::Error),
+    TomlKeyNotFound(String),
+    TomlKeyNotString(String),
+    InvalidLineNumber(String),
+    /// A C string literal whose bytes are not valid UTF-8, with the line it starts on
+    InvalidCString(usize),
+    /// `--index` asked for literal `index` on `line`, but only `found` literals are there
+    LiteralIndexOutOfRange {
+        line: usize,
+        index: usize,
+        found: usize,
+    },
+    /// A span's byte range runs past the content or splits a character
+    InvalidSpanBoundary {
+        start: usize,
+        end: usize,
+    },
+    /// A byte string literal whose bytes are not valid UTF-8, with the line it starts on
+    NonUtf8Literal(usize),
+    /// An embeddings line that is not a word followed by numbers of the common dimension
+    InvalidEmbeddings(usize),
+    /// [`FilterMode::Semantic`] was requested without any embeddings to compare words with
+    MissingEmbeddings,
+    /// No string literal is bound to the name, on `line` when one was given
+    NameNotFound {
+        name: String,
+        line: Option<usize>,
+    },
+    /// Several string literals are bound to the name; the lines they start on are listed
+    AmbiguousName {
+        name: String,
+        lines: Vec<usize>,
+    },
+    /// No function, method, or trait method has this name
+    FunctionNotFound(String),
+    /// An input file isn't valid UTF-8; `byte_offset` is where the first invalid sequence starts
+    InvalidEncoding {
+        path: PathBuf,
+        byte_offset: usize,
+    },
+    /// A config file of default options doesn't parse, or sets an unknown option or a bad value
+    InvalidConfig {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::IoError { path, source } => {
+                write!(f, "File error: {}: {}", path.display(), source)
+            }
+            Error::ParseError(err) => write!(f, "Parse error: {}", err),
+            Error::NoStringFound {
+                line,
+                nearest_lines_with_strings,
+            } => {
+                write!(f, "No string found on line {}", line)?;
+                if !nearest_lines_with_strings.is_empty() {
+                    let lines: Vec<String> = nearest_lines_with_strings
+                        .iter()
+                        .map(|line| line.to_string())
+                        .collect();
+                    write!(f, " (nearest lines with strings: {})", lines.join(", "))?;
+                }
+                Ok(())
+            }
+            Error::MultipleStringsFound => write!(f, "Multiple strings found on the same line"),
+            Error::RegexError(err) => write!(f, "Regex error: {}", err),
+            Error::JsonError(err) => write!(f, "JSON error: {}", err),
+            Error::JsonPointerNotFound(pointer) => {
+                write!(f, "JSON pointer {} does not resolve to a value", pointer)
+            }
+            Error::JsonPointerNotString(pointer) => {
+                write!(f, "JSON pointer {} does not resolve to a string", pointer)
+            }
+            Error::TomlError(err) => write!(f, "TOML error: {}", err),
+            Error::TomlKeyNotFound(key) => write!(f, "TOML key {} does not exist", key),
+            Error::TomlKeyNotString(key) => write!(f, "TOML key {} is not a string", key),
+            Error::InvalidLineNumber(line) => write!(f, "Invalid line number: {}", line),
+            Error::InvalidCString(line) => {
+                write!(f, "C string on line {} is not valid UTF-8", line)
+            }
+            Error::LiteralIndexOutOfRange { line, index, found } => {
+                write!(
+                    f,
+                    "No string literal at index {} on line {}; found {}",
+                    index, line, found
+                )
+            }
+            Error::InvalidSpanBoundary { start, end } => {
+                write!(
+                    f,
+                    "Span {}-{} does not fall on character boundaries of the content",
+                    start, end
+                )
+            }
+            Error::NonUtf8Literal(line) => {
+                write!(f, "Byte string on line {} is not valid UTF-8", line)
+            }
+            Error::InvalidEmbeddings(line) => write!(f, "Invalid embeddings on line {}", line),
+            Error::MissingEmbeddings => write!(f, "Semantic filtering needs an embeddings file"),
+            Error::NameNotFound { name, line } => {
+                write!(f, "No string literal bound to `{}`", name)?;
+                match line {
+                    Some(line) => write!(f, " on line {}", line),
+                    None => Ok(()),
+                }
+            }
+            Error::AmbiguousName { name, lines } => {
+                let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+                write!(f, "Several string literals are bound to `{}` (lines {}); give a line number to pick one", name, lines.join(", "))
+            }
+            Error::FunctionNotFound(name) => write!(f, "No function named `{}`", name),
+            Error::InvalidEncoding { path, byte_offset } => {
+                write!(
+                    f,
+                    "{} is not valid UTF-8: invalid byte at offset {}",
+                    path.display(),
+                    byte_offset
+                )
+            }
+            Error::InvalidConfig { path, message } => {
+                write!(f, "Invalid config {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError { source, .. } => Some(source),
+            Error::ParseError(err) => Some(err),
+            Error::RegexError(err) => Some(err),
+            Error::JsonError(err) => Some(err),
+            Error::TomlError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A token and its byte range within the string it was extracted from (end is exclusive).
+/// With the `serde` feature it (de)serializes with the stable field names `word`, `start`, and `end`.
+/// Spans order by start, then end, then word.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WordSpan {
+    pub word: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A span paired with its position in the unfiltered token stream
+pub type IndexedSpan = (usize, WordSpan);
+
+impl WordSpan {
+    /// Length of the span in bytes
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the byte at `byte_offset` lies inside the span
+    pub fn contains(&self, byte_offset: usize) -> bool {
+        (self.start..self.end).contains(&byte_offset)
+    }
+
+    /// Whether the two spans share at least one byte
+    pub fn overlaps(&self, other: &WordSpan) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// The text the span covers in `content`, or `None` if the span runs past the end
+    /// or doesn't fall on char boundaries
+    ///
+    /// ```
+    /// use rust_span_counter::WordSpan;
+    ///
+    /// let span = WordSpan { word: "world".to_string(), start: 6, end: 11 };
+    /// assert_eq!(span.slice("hello world"), Some("world"));
+    /// assert_eq!(span.slice("hello"), None);
+    /// ```
+    pub fn slice<'a>(&self, content: &'a str) -> Option<&'a str> {
+        safe_slice(content, self.start, self.end).ok()
+    }
+}
+
+/// Slice `content[start..end]`, returning [`Error::InvalidSpanBoundary`] instead of panicking when the
+/// range is reversed, runs past the end, or splits a character
+///
+/// ```
+/// use rust_span_counter::{safe_slice, Error};
+///
+/// assert_eq!(safe_slice("café", 0, 3).unwrap(), "caf");
+/// assert!(matches!(safe_slice("café", 0, 4), Err(Error::InvalidSpanBoundary { start: 0, end: 4 })));
+/// ```
+pub fn safe_slice(content: &str, start: usize, end: usize) -> Result<&str, Error> {
+    content
+        .get(start..end)
+        .ok_or(Error::InvalidSpanBoundary { start, end })
+}
+
+impl Ord for WordSpan {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.start, self.end, &self.word).cmp(&(other.start, other.end, &other.word))
+    }
+}
+
+impl PartialOrd for WordSpan {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for WordSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\" | {}-{}", self.word, self.start, self.end)
+    }
+}
+
+/// Split string content into spans at the requested granularity.
+///
+/// With `strings_as_tokens`, quoted content (`"..."`, `'...'`, `` `...` ``) is kept as a single
+/// word token including its quotes.
+///
+/// ```
+/// use rust_span_counter::{get_word_spans, Granularity, WordSpan};
+///
+/// let spans = get_word_spans("hello world", false, &Granularity::Word).unwrap();
+/// assert_eq!(spans, vec![
+///     WordSpan { word: "hello".to_string(), start: 0, end: 5 },
+///     WordSpan { word: "world".to_string(), start: 6, end: 11 },
+/// ]);
+/// ```
+pub fn get_word_spans(
+    string_content: &str,
+    strings_as_tokens: bool,
+    granularity: &Granularity,
+) -> Result<Vec<WordSpan>, Error> {
+    match granularity {
+        Granularity::Sentence => get_sentence_spans(string_content),
+        Granularity::Line => get_line_spans(string_content),
+        Granularity::Word if strings_as_tokens => {
+            Ok(QuotedStringsTokenizer.tokenize(string_content))
+        }
+        Granularity::Word => Ok(WordBoundTokenizer.tokenize(string_content)),
+    }
+}
+
+fn get_sentence_spans(string_content: &str) -> Result<Vec<WordSpan>, Error> {
+    Ok(sentence_spans(string_content).collect())
+}
+
+fn sentence_spans(string_content: &str) -> impl Iterator<Item = WordSpan> + '_ {
+    let mut byte_pos = 0;
+    string_content
+        .split_sentence_bounds()
+        .filter_map(move |sentence| {
+            let sentence_start = byte_pos;
+            byte_pos += sentence.len();
+            // Report the range without the surrounding whitespace that sentence bounds attach
+            let trimmed_start = sentence.len() - sentence.trim_start().len();
+            let trimmed = sentence.trim();
+            (!trimmed.is_empty()).then(|| WordSpan {
+                word: trimmed.to_string(),
+                start: sentence_start + trimmed_start,
+                end: sentence_start + trimmed_start + trimmed.len(),
+            })
+        })
+}
+
+fn get_line_spans(string_content: &str) -> Result<Vec<WordSpan>, Error> {
+    Ok(line_spans(string_content).collect())
+}
+
+fn line_spans(string_content: &str) -> impl Iterator<Item = WordSpan> + '_ {
+    let mut byte_pos = 0;
+    string_content.split('\n').filter_map(move |line| {
+        let line_start = byte_pos;
+        byte_pos += line.len() + 1;
+        let text = line.strip_suffix('\r').unwrap_or(line);
+        // Skip blank lines, matching how whitespace-only segments are skipped for words
+        (!text.chars().all(|c| c.is_whitespace())).then(|| WordSpan {
+            word: text.to_string(),
+            start: line_start,
+            end: line_start + text.len(),
+        })
+    })
+}
+
+/// Default word-bound tokenization, except that double-quoted regions (including their quotes) stay single spans
+pub fn get_word_spans_protecting_quotes(string_content: &str) -> Result<Vec<WordSpan>, Error> {
+    Ok(spans_protecting_quotes(string_content).collect())
+}
+
+fn spans_protecting_quotes(string_content: &str) -> impl Iterator<Item = WordSpan> + '_ {
+    let bytes = string_content.as_bytes();
+    let mut segment_start = 0;
+    // Each step yields an unquoted run and the quoted region that ends it, if any
+    let segments = std::iter::from_fn(move || {
+        if segment_start > bytes.len() {
+            return None;
+        }
+        let start = segment_start;
+        let Some(quote_start) = bytes[start..]
+            .iter()
+            .position(|&byte| byte == b'"')
+            .map(|offset| start + offset)
+        else {
+            segment_start = bytes.len() + 1;
+            return Some((start, bytes.len(), None));
+        };
+
+        // Find the matching closing quote, handling escapes; unclosed quotes consume the rest
+        let mut i = quote_start + 1;
+        while i < bytes.len() {
+            if bytes[i] == b'\\' && i + 1 < bytes.len() {
+                i += 2;
+            } else if bytes[i] == b'"' {
+                i += 1;
+                break;
+            } else {
+                i += 1;
+            }
+        }
+        segment_start = i;
+        Some((start, quote_start, Some(i)))
+    });
+
+    segments.flat_map(move |(start, quote_start, quote_end)| {
+        // Tokenize the unquoted text before the quote with normal word bounds
+        let words =
+            word_spans(&string_content[start..quote_start], false).map(move |span| WordSpan {
+                start: span.start + start,
+                end: span.end + start,
+                ..span
+            });
+        // UTF-8 continuation bytes never equal `"` or `\\`, so the quote end always lands on a char boundary
+        let quoted = quote_end.map(|end| WordSpan {
+            word: string_content[quote_start..end].to_string(),
+            start: quote_start,
+            end,
+        });
+        words.chain(quoted)
+    })
+}
+
+/// A strategy for splitting content into word spans. Implement it for domain-specific tokenization
+/// and pass the result through [`filter_word_spans`] like any built-in tokenizer's output.
+///
+/// ```
+/// use rust_span_counter::{Tokenizer, WordSpan};
+///
+/// struct Commas;
+///
+/// impl Tokenizer for Commas {
+///     fn tokenize(&self, content: &str) -> Vec<WordSpan> {
+///         let mut start = 0;
+///         content.split(',').map(|field| {
+///             let span = WordSpan { word: field.to_string(), start, end: start + field.len() };
+///             start += field.len() + 1;
+///             span
+///         }).collect()
+///     }
+/// }
+///
+/// assert_eq!(Commas.tokenize("a,bc")[1].start, 2);
+/// ```
+pub trait Tokenizer {
+    fn tokenize(&self, content: &str) -> Vec<WordSpan>;
+}
+
+/// Unicode word-bound segmentation, skipping whitespace; the default word tokenizer
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WordBoundTokenizer;
+
+impl Tokenizer for WordBoundTokenizer {
+    fn tokenize(&self, content: &str) -> Vec<WordSpan> {
+        word_spans(content, false).collect()
+    }
+}
+
+/// Word-bound segmentation that keeps quoted content (`"..."`, `'...'`, `` `...` ``, and raw
+/// strings like `r#"..."#`) as single tokens including the quotes; the tokenizer behind `strings_as_tokens`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuotedStringsTokenizer;
+
+impl Tokenizer for QuotedStringsTokenizer {
+    fn tokenize(&self, content: &str) -> Vec<WordSpan> {
+        word_spans(content, true).collect()
+    }
+}
+
+/// Lazily split string content into word spans; the iterator behind [`get_word_spans`] at word granularity.
+///
+/// ```
+/// use rust_span_counter::word_spans;
+///
+/// let first = word_spans("hello world", false).next().unwrap();
+/// assert_eq!((first.word.as_str(), first.start, first.end), ("hello", 0, 5));
+/// ```
+pub fn word_spans(string_content: &str, strings_as_tokens: bool) -> WordSpans<'_> {
+    WordSpans::new(string_content, strings_as_tokens, DEFAULT_QUOTE_PAIRS)
+}
+
+/// The quotes recognized by `strings_as_tokens` unless configured otherwise, as `(open, close)` pairs
+pub const DEFAULT_QUOTE_PAIRS: &[(char, char)] = &[('"', '"'), ('\'', '\''), ('`', '`')];
+
+/// Like [`word_spans`] with `strings_as_tokens`, but quoted regions open and close with the given
+/// `(open, close)` pairs; an opener is only closed by its own closer. Raw strings (`r#"..."#`) are
+/// recognized only when `"` is one of the symmetric pairs.
+///
+/// ```
+/// use rust_span_counter::word_spans_with_quotes;
+///
+/// let words: Vec<String> = word_spans_with_quotes("say “hi there” now", &[('“', '”')]).map(|span| span.word).collect();
+/// assert_eq!(words, ["say", "“hi there”", "now"]);
+/// ```
+pub fn word_spans_with_quotes<'a>(
+    string_content: &'a str,
+    quote_pairs: &'a [(char, char)],
+) -> WordSpans<'a> {
+    WordSpans::new(string_content, true, quote_pairs)
+}
+
+/// Only the quoted regions of the content, as recognized by [`word_spans_with_quotes`], with the quotes
+/// left out: each span's word and range cover just the text between them, so `""` yields an empty span
+/// unless `skip_empty`. Escapes are left as written so offsets still index the content, and an unclosed
+/// quote runs to the end.
+///
+/// ```
+/// use rust_span_counter::{get_quoted_spans, DEFAULT_QUOTE_PAIRS};
+///
+/// let spans = get_quoted_spans(r#"level=info msg="disk full" code="""#, DEFAULT_QUOTE_PAIRS, false);
+/// assert_eq!((spans[0].word.as_str(), spans[0].start, spans[0].end), ("disk full", 16, 25));
+/// assert_eq!((spans[1].word.as_str(), spans[1].start, spans[1].end), ("", 33, 33));
+/// ```
+pub fn get_quoted_spans(
+    string_content: &str,
+    quote_pairs: &[(char, char)],
+    skip_empty: bool,
+) -> Vec<WordSpan> {
+    quoted_spans(string_content, quote_pairs, skip_empty).collect()
+}
+
+fn quoted_spans<'a>(
+    string_content: &'a str,
+    quote_pairs: &'a [(char, char)],
+    skip_empty: bool,
+) -> impl Iterator<Item = WordSpan> + 'a {
+    word_spans_with_quotes(string_content, quote_pairs).filter_map(move |token| {
+        let (inner_start, inner_end) = quoted_inner_range(&token.word, quote_pairs)?;
+        if skip_empty && inner_start == inner_end {
+            return None;
+        }
+        Some(WordSpan {
+            word: token.word[inner_start..inner_end].to_string(),
+            start: token.start + inner_start,
+            end: token.start + inner_end,
+        })
+    })
+}
+
+/// The segments between runs of the `delimiters` characters, instead of Unicode word bounds. Segments are
+/// kept as written, surrounding whitespace included; empty and whitespace-only segments are skipped, so
+/// consecutive delimiters produce no spans.
+///
+/// ```
+/// use rust_span_counter::get_delimited_spans;
+///
+/// let spans = get_delimited_spans("id|name||city|", &['|']);
+/// let words: Vec<(&str, usize)> = spans.iter().map(|span| (span.word.as_str(), span.start)).collect();
+/// assert_eq!(words, [("id", 0), ("name", 3), ("city", 9)]);
+/// ```
+pub fn get_delimited_spans(string_content: &str, delimiters: &[char]) -> Vec<WordSpan> {
+    delimited_spans(string_content, delimiters).collect()
+}
+
+fn delimited_spans<'a>(
+    string_content: &'a str,
+    delimiters: &'a [char],
+) -> impl Iterator<Item = WordSpan> + 'a {
+    let mut byte_pos = 0;
+    string_content
+        .split(move |c: char| delimiters.contains(&c))
+        .filter_map(move |segment| {
+            let segment_start = byte_pos;
+            // Step over the segment and the delimiter that ended it
+            byte_pos += segment.len()
+                + string_content[segment_start + segment.len()..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+            (!segment.chars().all(|c| c.is_whitespace())).then(|| WordSpan {
+                word: segment.to_string(),
+                start: segment_start,
+                end: segment_start + segment.len(),
+            })
+        })
+}
+
+/// Byte range of the text between the quotes of a quoted token, or `None` if the token is unquoted
+fn quoted_inner_range(token: &str, quote_pairs: &[(char, char)]) -> Option<(usize, usize)> {
+    if quote_pairs.contains(&('"', '"')) && raw_string_len(token).is_some() {
+        let hashes = token[1..].len() - token[1..].trim_start_matches('#').len();
+        let inner_start = hashes + 2;
+        let closing = format!("\"{}", "#".repeat(hashes));
+        let closed = token.len() >= inner_start + closing.len() && token.ends_with(&closing);
+        return Some((
+            inner_start,
+            if closed {
+                token.len() - closing.len()
+            } else {
+                token.len()
+            },
+        ));
+    }
+
+    let open = token.chars().next()?;
+    let close = quote_pairs
+        .iter()
+        .find(|(quote_open, _)| *quote_open == open)
+        .map(|(_, close)| *close)?;
+    let inner_start = open.len_utf8();
+    let mut chars = token[inner_start..].char_indices();
+    while let Some((offset, current)) = chars.next() {
+        if current == '\\' {
+            chars.next();
+        } else if current == close {
+            return Some((inner_start, inner_start + offset));
+        }
+    }
+    Some((inner_start, token.len()))
+}
+
+/// Iterator over the word spans of a string, created by [`word_spans`]
+pub struct WordSpans<'a> {
+    content: &'a str,
+    strings_as_tokens: bool,
+    quote_pairs: &'a [(char, char)],
+    // Byte offset of the next unscanned character
+    pos: usize,
+    // Word-bound segments of the current unquoted run, with the byte offset of the next segment
+    segments: Option<(usize, UWordBounds<'a>)>,
+}
+
+impl<'a> WordSpans<'a> {
+    fn new(content: &'a str, strings_as_tokens: bool, quote_pairs: &'a [(char, char)]) -> Self {
+        if strings_as_tokens {
+            WordSpans {
+                content,
+                strings_as_tokens,
+                quote_pairs,
+                pos: 0,
+                segments: None,
+            }
+        } else {
+            // Without quote handling the whole content is a single unquoted run
+            let segments = Some((0, content.split_word_bounds()));
+            WordSpans {
+                content,
+                strings_as_tokens,
+                quote_pairs,
+                pos: content.len(),
+                segments,
+            }
+        }
+    }
+
+    fn closing_quote(&self, open: char) -> Option<char> {
+        self.quote_pairs
+            .iter()
+            .find(|(quote_open, _)| *quote_open == open)
+            .map(|(_, close)| *close)
+    }
+
+    fn raw_string_len(&self, content: &str) -> Option<usize> {
+        if self.quote_pairs.contains(&('"', '"')) {
+            raw_string_len(content)
+        } else {
+            None
+        }
+    }
+
+    fn next_segment(&mut self) -> Option<WordSpan> {
+        let (byte_pos, segments) = self.segments.as_mut()?;
+        for segment in segments.by_ref() {
+            let start = *byte_pos;
+            *byte_pos += segment.len();
+            // Only include non-whitespace segments as tokens
+            if !segment.chars().all(|c| c.is_whitespace()) {
+                return Some(WordSpan {
+                    word: segment.to_string(),
+                    start,
+                    end: *byte_pos,
+                });
+            }
+        }
+        self.segments = None;
+        None
+    }
+}
+
+impl Iterator for WordSpans<'_> {
+    type Item = WordSpan;
+
+    fn next(&mut self) -> Option<WordSpan> {
+        loop {
+            if let Some(span) = self.next_segment() {
+                return Some(span);
+            }
+            if !self.strings_as_tokens {
+                return None;
+            }
+
+            let rest = &self.content[self.pos..];
+            let mut chars = rest.char_indices();
+            let (_, ch) = chars.next()?;
+
+            if let Some(raw_len) = self.raw_string_len(rest) {
+                // Raw strings have no escapes and only close on a quote followed by the same number of hashes
+                let start = self.pos;
+                self.pos += raw_len;
+                return Some(WordSpan {
+                    word: rest[..raw_len].to_string(),
+                    start,
+                    end: self.pos,
+                });
+            } else if let Some(close) = self.closing_quote(ch) {
+                // Find the matching closing quote, handling escapes; unclosed quotes consume the rest
+                let mut quote_end = rest.len();
+                while let Some((offset, current)) = chars.next() {
+                    if current == '\\' {
+                        // Skip escaped character
+                        if chars.next().is_none() {
+                            break;
+                        }
+                    } else if current == close {
+                        quote_end = offset + current.len_utf8();
+                        break;
+                    }
+                }
+
+                // Create a span for the entire quoted string (including quotes)
+                let start = self.pos;
+                self.pos += quote_end;
+                return Some(WordSpan {
+                    word: rest[..quote_end].to_string(),
+                    start,
+                    end: self.pos,
+                });
+            } else if ch.is_whitespace() {
+                self.pos += ch.len_utf8();
+            } else {
+                // Unquoted text runs until whitespace, a quote, or a raw string opener, then is split on word bounds
+                let token_end = rest
+                    .char_indices()
+                    .find(|&(offset, c)| {
+                        c.is_whitespace()
+                            || self.closing_quote(c).is_some()
+                            || (offset > 0
+                                && self.raw_string_len(&rest[offset..]).is_some()
+                                && !follows_identifier(rest, offset))
+                    })
+                    .map_or(rest.len(), |(offset, _)| offset);
+                self.segments = Some((self.pos, rest[..token_end].split_word_bounds()));
+                self.pos += token_end;
+            }
+        }
+    }
+}
+
+// A raw string opener doesn't count when it is the tail of an identifier like `for"x"` or `bar#"x"`
+fn follows_identifier(content: &str, offset: usize) -> bool {
+    content[..offset]
+        .chars()
+        .next_back()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Byte length of the raw string (`r"..."`, `r#"..."#`, ...) at the start of `content`, if there is
+/// one; an unclosed raw string runs to the end of the content
+fn raw_string_len(content: &str) -> Option<usize> {
+    let after_r = content.strip_prefix('r')?;
+    let hashes = after_r.len() - after_r.trim_start_matches('#').len();
+    let body = after_r[hashes..].strip_prefix('"')?;
+    let closing = format!("\"{}", "#".repeat(hashes));
+    let body_start = content.len() - body.len();
+    Some(
+        body.find(&closing)
+            .map_or(content.len(), |offset| body_start + offset + closing.len()),
+    )
+}
+
+/// Tokenize content as loose JSON: each string literal is one span, each structural character
+/// (`{`, `}`, `[`, `]`, `:`, `,`) is its own span, and any other run such as a number, `true`,
+/// or `null` is a single span. With `strip_quotes`, string spans cover only the text between
+/// the quotes; escapes are left as written so offsets still index the content.
+///
+/// ```
+/// use rust_span_counter::get_json_aware_spans;
+///
+/// let words: Vec<String> = get_json_aware_spans(r#"{"a": 1}"#, false).into_iter().map(|span| span.word).collect();
+/// assert_eq!(words, ["{", "\"a\"", ":", "1", "}"]);
+/// ```
+pub fn get_json_aware_spans(string_content: &str, strip_quotes: bool) -> Vec<WordSpan> {
+    json_aware_spans(string_content, strip_quotes).collect()
+}
+
+fn json_aware_spans(
+    string_content: &str,
+    strip_quotes: bool,
+) -> impl Iterator<Item = WordSpan> + '_ {
+    let is_structural = |c: char| matches!(c, '{' | '}' | '[' | ']' | ':' | ',');
+    let span_at = |start: usize, end: usize| WordSpan {
+        word: string_content[start..end].to_string(),
+        start,
+        end,
+    };
+
+    let mut chars = string_content.char_indices().peekable();
+    std::iter::from_fn(move || loop {
+        let (start, ch) = chars.next()?;
+        if ch.is_whitespace() {
+            continue;
+        }
+
+        if is_structural(ch) {
+            return Some(span_at(start, start + ch.len_utf8()));
+        } else if ch == '"' {
+            // Find the closing quote, handling escapes; unclosed strings consume the rest
+            let mut closed = false;
+            let mut end = string_content.len();
+            while let Some((offset, current)) = chars.next() {
+                if current == '\\' {
+                    chars.next();
+                } else if current == '"' {
+                    closed = true;
+                    end = offset + 1;
+                    break;
+                }
+            }
+
+            return Some(if strip_quotes {
+                span_at(start + 1, if closed { end - 1 } else { end })
+            } else {
+                span_at(start, end)
+            });
+        } else {
+            let mut end = start + ch.len_utf8();
+            while let Some(&(offset, current)) = chars.peek() {
+                if current.is_whitespace() || current == '"' || is_structural(current) {
+                    break;
+                }
+                end = offset + current.len_utf8();
+                chars.next();
+            }
+            return Some(span_at(start, end));
+        }
+    })
+}
+
+/// Merge adjacent `word` `.` `word` spans into single dotted identifiers such as `team.sub` or `host1.example`.
+/// Word bounds already join plain letter runs, but split wherever a digit meets the dot.
+/// A dot is only absorbed when an identifier follows it directly, so a trailing `.` stays separate.
+pub fn merge_dotted_identifiers(spans: Vec<WordSpan>) -> Vec<WordSpan> {
+    let is_identifier = |word: &str| {
+        !word.is_empty()
+            && !word.starts_with('.')
+            && !word.ends_with('.')
+            && !word.contains("..")
+            && word
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    };
+
+    let mut merged: Vec<WordSpan> = Vec::with_capacity(spans.len());
+    let mut i = 0;
+    while i < spans.len() {
+        let mut current = spans[i].clone();
+        i += 1;
+
+        if is_identifier(&current.word) {
+            while i + 1 < spans.len()
+                && spans[i].word == "."
+                && spans[i].start == current.end
+                && spans[i + 1].start == spans[i].end
+                && is_identifier(&spans[i + 1].word)
+            {
+                current.word.push('.');
+                current.word.push_str(&spans[i + 1].word);
+                current.end = spans[i + 1].end;
+                i += 2;
+            }
+        }
+
+        merged.push(current);
+    }
+
+    merged
+}
+
+/// Merge the parts of numbers that word bounds split apart: a leading-dot decimal such as `.5` and a signed
+/// exponent such as `3.14e-10`. Decimals, versions, and comma groupings like `3.14`, `192.168.0.1`, and
+/// `1,000` already stay whole. With `thousands_separators`, digit groups split by a single space, no-break
+/// space, or narrow no-break space (`1 000 000`) are joined as well. A dot only starts a number at a token
+/// boundary, so sentence punctuation such as `end.5` is left alone.
+pub fn merge_numbers(
+    content: &str,
+    spans: Vec<WordSpan>,
+    thousands_separators: bool,
+) -> Vec<WordSpan> {
+    let is_number = |word: &str| {
+        word.starts_with(|c: char| c.is_ascii_digit())
+            && word
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    };
+    let is_digits = |word: &str, max_len: usize| {
+        !word.is_empty() && word.len() <= max_len && word.chars().all(|c| c.is_ascii_digit())
+    };
+    // Whether the span at `index` exists and directly follows the one before it
+    let adjacent = |index: usize| index < spans.len() && spans[index].start == spans[index - 1].end;
+
+    let mut merged: Vec<WordSpan> = Vec::with_capacity(spans.len());
+    let mut i = 0;
+    while i < spans.len() {
+        let start = i;
+        let at_boundary = merged.last().is_none_or(|prev| prev.end < spans[i].start);
+        if at_boundary
+            && spans[i].word == "."
+            && adjacent(i + 1)
+            && is_number(spans[i + 1].word.trim_end_matches(['e', 'E']))
+        {
+            i += 1;
+        }
+        let mantissa = spans[i].word.strip_suffix(['e', 'E']);
+        if mantissa.is_some_and(is_number)
+            && adjacent(i + 1)
+            && (spans[i + 1].word == "-" || spans[i + 1].word == "+")
+            && adjacent(i + 2)
+            && is_digits(&spans[i + 2].word, usize::MAX)
+        {
+            i += 2;
+        } else if thousands_separators && i == start && is_digits(&spans[i].word, 3) {
+            while i + 1 < spans.len()
+                && is_digits(&spans[i + 1].word, 3)
+                && spans[i + 1].word.len() == 3
+                && matches!(
+                    content.get(spans[i].end..spans[i + 1].start),
+                    Some(" " | "\u{a0}" | "\u{202f}")
+                )
+            {
+                i += 1;
+            }
+        }
+
+        let (first, last) = (&spans[start], &spans[i]);
+        merged.push(WordSpan {
+            word: content[first.start..last.end].to_string(),
+            start: first.start,
+            end: last.end,
+        });
+        i += 1;
+    }
+
+    merged
+}
+
+/// Strip leading and trailing punctuation (anything that isn't a letter or digit, as in
+/// [`ClusterKey::TrimPunctuation`]) from each span, narrowing its range to match, and drop spans that
+/// are only punctuation. Interior punctuation stays, so `user_id` and `don't` are kept whole.
+///
+/// ```
+/// use rust_span_counter::{get_word_spans, trim_punctuation, Granularity};
+///
+/// let spans = trim_punctuation(get_word_spans("\"hello,\" she said", true, &Granularity::Word).unwrap());
+/// assert_eq!((spans[0].word.as_str(), spans[0].start, spans[0].end), ("hello", 1, 6));
+/// ```
+pub fn trim_punctuation(spans: Vec<WordSpan>) -> Vec<WordSpan> {
+    spans
+        .into_iter()
+        .filter_map(|span| {
+            let is_punctuation = |c: char| !c.is_alphanumeric();
+            let trimmed = span.word.trim_matches(is_punctuation);
+            if trimmed.is_empty() {
+                return None;
+            }
+            let start =
+                span.start + (span.word.len() - span.word.trim_start_matches(is_punctuation).len());
+            Some(WordSpan {
+                word: trimmed.to_string(),
+                start,
+                end: start + trimmed.len(),
+            })
+        })
+        .collect()
+}
+
+/// Add a span for every stretch of `content` between, before, and after the given spans, so the
+/// result tiles the content with no gaps. Each gap is split into runs of whitespace and runs of
+/// anything else, so a whitespace span never holds other text: after [`trim_punctuation`] the
+/// trimmed punctuation gets spans of its own.
+///
+/// ```
+/// use rust_span_counter::{fill_gaps, word_spans};
+///
+/// let content = "hello  world\n";
+/// let spans = fill_gaps(content, word_spans(content, false).collect());
+/// let words: Vec<&str> = spans.iter().map(|span| span.word.as_str()).collect();
+/// assert_eq!(words, vec!["hello", "  ", "world", "\n"]);
+/// ```
+pub fn fill_gaps(content: &str, spans: Vec<WordSpan>) -> Vec<WordSpan> {
+    let mut filled = Vec::with_capacity(spans.len() * 2 + 1);
+    let mut pos = 0;
+    for span in spans {
+        if span.start > pos {
+            push_gap(&mut filled, content, pos, span.start);
+        }
+        pos = pos.max(span.end);
+        filled.push(span);
+    }
+    if pos < content.len() {
+        push_gap(&mut filled, content, pos, content.len());
+    }
+    filled
+}
+
+/// Push `content[start..end]` as spans split at every change between whitespace and other text
+fn push_gap(filled: &mut Vec<WordSpan>, content: &str, start: usize, end: usize) {
+    let mut run_start = start;
+    let mut run_is_whitespace = None;
+    for (offset, c) in content[start..end].char_indices() {
+        let is_whitespace = c.is_whitespace();
+        if run_is_whitespace.is_some_and(|run| run != is_whitespace) {
+            filled.push(WordSpan {
+                word: content[run_start..start + offset].to_string(),
+                start: run_start,
+                end: start + offset,
+            });
+            run_start = start + offset;
+        }
+        run_is_whitespace = Some(is_whitespace);
+    }
+    filled.push(WordSpan {
+        word: content[run_start..end].to_string(),
+        start: run_start,
+        end,
+    });
+}
+
+/// Rewrite each span's word with the given normalization, leaving `start` and `end` on the
+/// original text, so a word's length may no longer match its range.
+///
+/// ```
+/// use rust_span_counter::{normalize_spans, WordNormalization, WordSpan};
+///
+/// let decomposed = WordSpan { word: "cafe\u{301}".to_string(), start: 0, end: 6 };
+/// let spans = normalize_spans(vec![decomposed], &WordNormalization::Nfc);
+/// assert_eq!((spans[0].word.as_str(), spans[0].end), ("café", 6));
+/// ```
+pub fn normalize_spans(spans: Vec<WordSpan>, normalization: &WordNormalization) -> Vec<WordSpan> {
+    let normalize: fn(&str) -> String = match normalization {
+        WordNormalization::None => return spans,
+        WordNormalization::Lowercase => str::to_lowercase,
+        WordNormalization::Uppercase => str::to_uppercase,
+        WordNormalization::Nfc => |word| word.nfc().collect(),
+        WordNormalization::Nfkc => |word| word.nfkc().collect(),
+    };
+    spans
+        .into_iter()
+        .map(|span| WordSpan {
+            word: normalize(&span.word),
+            ..span
+        })
+        .collect()
+}
+
+/// Merge a number directly followed by `%` or `‰` into one span, so `50%` and `3.5%` stay whole.
+pub fn merge_percentages(spans: Vec<WordSpan>) -> Vec<WordSpan> {
+    let is_number = |word: &str| {
+        word.starts_with(|c: char| c.is_ascii_digit())
+            && word
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    };
+
+    let mut merged: Vec<WordSpan> = Vec::with_capacity(spans.len());
+    let mut spans = spans.into_iter().peekable();
+    while let Some(mut span) = spans.next() {
+        if is_number(&span.word) {
+            if let Some(sign) = spans
+                .next_if(|next| (next.word == "%" || next.word == "‰") && next.start == span.end)
+            {
+                span.word.push_str(&sign.word);
+                span.end = sign.end;
+            }
+        }
+        merged.push(span);
+    }
+
+    merged
+}
+
+/// Merge command-line flags into one span including their dashes: `-x`, combined short flags like `-rf`,
+/// and long flags like `--long-flag`. The dashes must start a token, so `a-b` stays a subtraction.
+pub fn merge_command_flags(spans: Vec<WordSpan>) -> Vec<WordSpan> {
+    let is_dash = |index: usize| spans[index].word == "-";
+    let is_name = |index: usize| spans[index].word.starts_with(|c: char| c.is_alphanumeric());
+    // Whether the span at `index` exists and directly follows the one before it
+    let adjacent = |index: usize| index < spans.len() && spans[index].start == spans[index - 1].end;
+
+    let mut merged: Vec<WordSpan> = Vec::with_capacity(spans.len());
+    let mut i = 0;
+    while i < spans.len() {
+        let at_boundary = merged.last().is_none_or(|prev| prev.end < spans[i].start);
+        let mut flag_len = 0;
+        if at_boundary && is_dash(i) {
+            let dashes = if adjacent(i + 1) && is_dash(i + 1) {
+                2
+            } else {
+                1
+            };
+            if adjacent(i + dashes) && is_name(i + dashes) {
+                flag_len = dashes + 1;
+                // Long flags continue through inner hyphens: --long-flag
+                while dashes == 2
+                    && adjacent(i + flag_len)
+                    && is_dash(i + flag_len)
+                    && adjacent(i + flag_len + 1)
+                    && is_name(i + flag_len + 1)
+                {
+                    flag_len += 2;
+                }
+            }
+        }
+
+        if flag_len == 0 {
+            merged.push(spans[i].clone());
+            i += 1;
+        } else {
+            let flag = &spans[i..i + flag_len];
+            merged.push(WordSpan {
+                word: flag.iter().map(|span| span.word.as_str()).collect(),
+                start: flag[0].start,
+                end: flag[flag_len - 1].end,
+            });
+            i += flag_len;
+        }
+    }
+
+    merged
+}
+
+/// Keep spans whose word matches any filter. Each span carries its index in the unfiltered
+/// token stream so callers can still refer to the original position after filtering.
+///
+/// ```
+/// use rust_span_counter::{filter_word_spans, get_word_spans, FilterMode, Granularity};
+///
+/// let spans = get_word_spans("hello world", false, &Granularity::Word).unwrap();
+/// let indexed = spans.into_iter().enumerate().collect();
+/// let filtered = filter_word_spans(indexed, &["world".to_string()], &FilterMode::Exact, false).unwrap();
+/// assert_eq!(filtered[0].0, 1);
+/// assert_eq!(filtered[0].1.word, "world");
+/// ```
+pub fn filter_word_spans(
+    spans: Vec<IndexedSpan>,
+    filters: &[String],
+    filter_mode: &FilterMode,
+    ignore_case: bool,
+) -> Result<Vec<IndexedSpan>, Error> {
+    Ok(filter_spans(spans, filters, filter_mode, ignore_case)?.collect())
+}
+
+/// Drop spans whose word matches any exclude pattern, preserving the original indices
+pub fn exclude_word_spans(
+    spans: Vec<IndexedSpan>,
+    excludes: &[String],
+    filter_mode: &FilterMode,
+    ignore_case: bool,
+) -> Result<Vec<IndexedSpan>, Error> {
+    Ok(exclude_spans(spans, excludes, filter_mode, ignore_case)?.collect())
+}
+
+/// Lazy form of [`filter_word_spans`]: yields only the spans whose word matches any filter.
+/// No filters keeps every span.
+///
+/// ```
+/// use rust_span_counter::{filter_spans, word_spans, FilterMode};
+///
+/// let mut matches = filter_spans(word_spans("hello world", false).enumerate(), &["world".to_string()], &FilterMode::Exact, false).unwrap();
+/// assert_eq!(matches.next().map(|(index, span)| (index, span.word)), Some((1, "world".to_string())));
+/// ```
+pub fn filter_spans<I>(
+    spans: I,
+    filters: &[String],
+    filter_mode: &FilterMode,
+    ignore_case: bool,
+) -> Result<FilteredSpans<I::IntoIter>, Error>
+where
+    I: IntoIterator<Item = IndexedSpan>,
+{
+    FilteredSpans::new(
+        spans.into_iter(),
+        filters,
+        filter_mode,
+        ignore_case,
+        None,
+        DEFAULT_FUZZY_DISTANCE,
+        true,
+    )
+}
+
+/// Lazy form of [`exclude_word_spans`]: skips the spans whose word matches any exclude pattern
+pub fn exclude_spans<I>(
+    spans: I,
+    excludes: &[String],
+    filter_mode: &FilterMode,
+    ignore_case: bool,
+) -> Result<FilteredSpans<I::IntoIter>, Error>
+where
+    I: IntoIterator<Item = IndexedSpan>,
+{
+    FilteredSpans::new(
+        spans.into_iter(),
+        excludes,
+        filter_mode,
+        ignore_case,
+        None,
+        DEFAULT_FUZZY_DISTANCE,
+        false,
+    )
+}
+
+/// Iterator adapter created by [`filter_spans`] and [`exclude_spans`]
+pub struct FilteredSpans<I> {
+    spans: I,
+    // `None` when there are no patterns, in which case every span passes through
+    matcher: Option<WordMatcher>,
+    keep_matches: bool,
+}
+
+impl<I> FilteredSpans<I> {
+    fn new(
+        spans: I,
+        patterns: &[String],
+        filter_mode: &FilterMode,
+        ignore_case: bool,
+        semantic: Option<&SemanticOptions>,
+        fuzzy_distance: usize,
+        keep_matches: bool,
+    ) -> Result<Self, Error> {
+        let matcher = if patterns.is_empty() {
+            None
+        } else {
+            Some(WordMatcher::new(
+                patterns,
+                filter_mode,
+                ignore_case,
+                semantic,
+                fuzzy_distance,
+            )?)
+        };
+        Ok(FilteredSpans {
+            spans,
+            matcher,
+            keep_matches,
+        })
+    }
+}
+
+impl<I: Iterator<Item = IndexedSpan>> Iterator for FilteredSpans<I> {
+    type Item = IndexedSpan;
+
+    fn next(&mut self) -> Option<IndexedSpan> {
+        let Some(matcher) = &self.matcher else {
+            return self.spans.next();
+        };
+        self.spans
+            .by_ref()
+            .find(|(_, span)| matcher.is_match(&span.word) == self.keep_matches)
+    }
+}
+
+/// Tokenizing and filtering options gathered in one place, so new options can be added
+/// without changing function signatures. Without filters or merges it tokenizes exactly like
+/// [`get_word_spans`], which stays available for callers that only need a granularity.
+///
+/// ```
+/// use rust_span_counter::{FilterMode, SpanExtractor};
+///
+/// let spans = SpanExtractor::new()
+///     .filters(vec!["^w".to_string()])
+///     .filter_mode(FilterMode::Regex)
+///     .ignore_case(true)
+///     .extract("Hello World wide")
+///     .unwrap();
+/// assert_eq!(spans.len(), 2);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SpanExtractor {
+    strings_as_tokens: bool,
+    quote_pairs: Option<Vec<(char, char)>>,
+    protect_quotes: bool,
+    json_aware: bool,
+    json_strip_quotes: bool,
+    dotted_identifiers: bool,
+    preserve_percentages: bool,
+    preserve_flags: bool,
+    keep_numbers: bool,
+    thousands_separators: bool,
+    trim_punctuation: bool,
+    quoted_only: bool,
+    skip_empty_quotes: bool,
+    delimiters: Option<Vec<char>>,
+    include_whitespace: bool,
+    normalization: WordNormalization,
+    filter_normalized: bool,
+    max_spans: Option<usize>,
+    granularity: Granularity,
+    filters: Vec<String>,
+    excludes: Vec<String>,
+    invert_match: bool,
+    filter_mode: FilterMode,
+    ignore_case: bool,
+    embeddings: Option<Arc<Embeddings>>,
+    similarity_threshold: Option<f32>,
+    fuzzy_distance: Option<usize>,
+    extract_captures: bool,
+    captures_only: bool,
+    offset_unit: OffsetUnit,
+}
+
+impl SpanExtractor {
+    /// Default configuration: word granularity, no filters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep quoted content as single tokens, see [`get_word_spans`]
+    pub fn strings_as_tokens(mut self, strings_as_tokens: bool) -> Self {
+        self.strings_as_tokens = strings_as_tokens;
+        self
+    }
+
+    /// With `strings_as_tokens`, recognize these `(open, close)` pairs instead of [`DEFAULT_QUOTE_PAIRS`].
+    /// Like `strings_as_tokens` itself, they only apply at word granularity
+    pub fn quote_pairs(mut self, quote_pairs: Vec<(char, char)>) -> Self {
+        self.quote_pairs = Some(quote_pairs);
+        self
+    }
+
+    /// Keep double-quoted regions whole using [`get_word_spans_protecting_quotes`]; takes precedence
+    /// over `strings_as_tokens` and `granularity`
+    pub fn protect_quotes(mut self, protect_quotes: bool) -> Self {
+        self.protect_quotes = protect_quotes;
+        self
+    }
+
+    /// Tokenize as loose JSON with [`get_json_aware_spans`]; takes precedence over `strings_as_tokens`
+    /// and `granularity`
+    pub fn json_aware(mut self, json_aware: bool) -> Self {
+        self.json_aware = json_aware;
+        self
+    }
+
+    /// In JSON-aware mode, leave the surrounding quotes out of string spans
+    pub fn json_strip_quotes(mut self, json_strip_quotes: bool) -> Self {
+        self.json_strip_quotes = json_strip_quotes;
+        self
+    }
+
+    /// Merge dotted identifiers with [`merge_dotted_identifiers`]
+    pub fn dotted_identifiers(mut self, dotted_identifiers: bool) -> Self {
+        self.dotted_identifiers = dotted_identifiers;
+        self
+    }
+
+    /// Strip punctuation from span edges and drop punctuation-only spans with [`trim_punctuation`],
+    /// after any of the merges above
+    pub fn trim_punctuation(mut self, trim_punctuation: bool) -> Self {
+        self.trim_punctuation = trim_punctuation;
+        self
+    }
+
+    /// Rejoin numbers that word bounds split, like `.5` and `3.14e-10`, with [`merge_numbers`]
+    pub fn keep_numbers(mut self, keep_numbers: bool) -> Self {
+        self.keep_numbers = keep_numbers;
+        self
+    }
+
+    /// With `keep_numbers`, also join digit groups separated by spaces, like `1 000 000`
+    pub fn thousands_separators(mut self, thousands_separators: bool) -> Self {
+        self.thousands_separators = thousands_separators;
+        self
+    }
+
+    /// Keep numbers and a following `%` or `‰` together with [`merge_percentages`]
+    pub fn preserve_percentages(mut self, preserve_percentages: bool) -> Self {
+        self.preserve_percentages = preserve_percentages;
+        self
+    }
+
+    /// Keep command-line flags like `--verbose` and `-rf` whole with [`merge_command_flags`]
+    pub fn preserve_flags(mut self, preserve_flags: bool) -> Self {
+        self.preserve_flags = preserve_flags;
+        self
+    }
+
+    /// Emit only the quoted regions, without their quotes, using [`get_quoted_spans`]; takes precedence
+    /// over `strings_as_tokens` and `granularity`
+    pub fn quoted_only(mut self, quoted_only: bool) -> Self {
+        self.quoted_only = quoted_only;
+        self
+    }
+
+    /// With `quoted_only`, drop the empty spans of `""` instead of emitting them
+    pub fn skip_empty_quotes(mut self, skip_empty_quotes: bool) -> Self {
+        self.skip_empty_quotes = skip_empty_quotes;
+        self
+    }
+
+    /// Split on runs of these characters with [`get_delimited_spans`]; takes precedence over
+    /// `strings_as_tokens` and `granularity`
+    pub fn delimiters(mut self, delimiters: Vec<char>) -> Self {
+        self.delimiters = Some(delimiters);
+        self
+    }
+
+    /// Also emit the text between tokens as spans with [`fill_gaps`], so the spans tile the
+    /// content; whitespace and any other skipped text (such as trimmed punctuation) get separate spans
+    pub fn include_whitespace(mut self, include_whitespace: bool) -> Self {
+        self.include_whitespace = include_whitespace;
+        self
+    }
+
+    /// Rewrite each span's word with [`normalize_spans`] after tokenizing; filters still see the
+    /// original words unless `filter_normalized` is set
+    pub fn normalization(mut self, normalization: WordNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Match filters and excludes against the normalized words instead of the original ones
+    pub fn filter_normalized(mut self, filter_normalized: bool) -> Self {
+        self.filter_normalized = filter_normalized;
+        self
+    }
+
+    /// Stop tokenizing once this many spans are produced. Word tokenizing stops early instead of
+    /// splitting the rest of the content; see [`SpanExtractor::extract_indexed_truncated`]
+    pub fn max_spans(mut self, max_spans: usize) -> Self {
+        self.max_spans = Some(max_spans);
+        self
+    }
+
+    pub fn granularity(mut self, granularity: Granularity) -> Self {
+        self.granularity = granularity;
+        self
+    }
+
+    /// Keep only spans matching any of these patterns
+    pub fn filters(mut self, filters: Vec<String>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Drop spans matching any of these patterns
+    pub fn excludes(mut self, excludes: Vec<String>) -> Self {
+        self.excludes = excludes;
+        self
+    }
+
+    /// Keep the spans matching none of the filters instead, like `grep -v`. Without filters nothing
+    /// is kept, and capture groups are not extracted
+    pub fn invert_match(mut self, invert_match: bool) -> Self {
+        self.invert_match = invert_match;
+        self
+    }
+
+    pub fn filter_mode(mut self, filter_mode: FilterMode) -> Self {
+        self.filter_mode = filter_mode;
+        self
+    }
+
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Word vectors compared by [`FilterMode::Semantic`]
+    pub fn embeddings(mut self, embeddings: Embeddings) -> Self {
+        self.embeddings = Some(Arc::new(embeddings));
+        self
+    }
+
+    /// With [`FilterMode::Semantic`], a word matches a filter when their cosine similarity exceeds this;
+    /// defaults to [`DEFAULT_SIMILARITY_THRESHOLD`]
+    pub fn similarity_threshold(mut self, similarity_threshold: f32) -> Self {
+        self.similarity_threshold = Some(similarity_threshold);
+        self
+    }
+
+    /// With [`FilterMode::Fuzzy`], a word matches a filter when at most this many single-character
+    /// edits turn one into the other; defaults to [`DEFAULT_FUZZY_DISTANCE`]
+    pub fn fuzzy_distance(mut self, fuzzy_distance: usize) -> Self {
+        self.fuzzy_distance = Some(fuzzy_distance);
+        self
+    }
+
+    /// With [`FilterMode::Regex`], replace each kept span with its capture groups using [`capture_spans`]
+    pub fn extract_captures(mut self, extract_captures: bool) -> Self {
+        self.extract_captures = extract_captures;
+        self
+    }
+
+    /// With `extract_captures`, leave out the whole match (group 0)
+    pub fn captures_only(mut self, captures_only: bool) -> Self {
+        self.captures_only = captures_only;
+        self
+    }
+
+    /// Count the start and end of returned spans in this unit instead of bytes, as [`unit_offsets`] does.
+    /// Slicing the content needs byte offsets, so the CLI keeps bytes here and converts when printing.
+    ///
+    /// ```
+    /// use rust_span_counter::{OffsetUnit, SpanExtractor};
+    ///
+    /// let spans = SpanExtractor::new().offset_unit(OffsetUnit::Chars).extract("naïve café").unwrap();
+    /// assert_eq!((spans[1].start, spans[1].end), (6, 10));
+    /// ```
+    pub fn offset_unit(mut self, offset_unit: OffsetUnit) -> Self {
+        self.offset_unit = offset_unit;
+        self
+    }
+
+    /// Tokenize and filter the content
+    pub fn extract(&self, content: &str) -> Result<Vec<WordSpan>, Error> {
+        Ok(self
+            .extract_indexed(content)?
+            .into_iter()
+            .map(|(_, span)| span)
+            .collect())
+    }
+
+    /// Like [`SpanExtractor::extract`], but each span keeps its index in the unfiltered token stream
+    pub fn extract_indexed(&self, content: &str) -> Result<Vec<IndexedSpan>, Error> {
+        Ok(self.extract_indexed_truncated(content)?.0)
+    }
+
+    /// Like [`SpanExtractor::extract_indexed`], and whether `max_spans` cut tokenizing short
+    ///
+    /// ```
+    /// use rust_span_counter::SpanExtractor;
+    ///
+    /// let (spans, truncated) = SpanExtractor::new().max_spans(2).extract_indexed_truncated("a b c").unwrap();
+    /// assert_eq!((spans.len(), truncated), (2, true));
+    /// ```
+    pub fn extract_indexed_truncated(
+        &self,
+        content: &str,
+    ) -> Result<(Vec<IndexedSpan>, bool), Error> {
+        let (spans, truncated) = self.extract_bytes(content)?;
+        Ok((self.in_offset_unit(content, spans), truncated))
+    }
+
+    /// [`SpanExtractor::extract_indexed_truncated`] with offsets in bytes, whatever `offset_unit` is
+    fn extract_bytes(&self, content: &str) -> Result<(Vec<IndexedSpan>, bool), Error> {
+        // One span past the limit tells a truncated stream from one that ends exactly at it
+        let limit = self
+            .max_spans
+            .map_or(usize::MAX, |max_spans| max_spans.saturating_add(1));
+        // Every tokenizer is lazy, so a capped stream stops scanning at the limit
+        let tokens: Box<dyn Iterator<Item = WordSpan> + '_> = if self.protect_quotes {
+            Box::new(spans_protecting_quotes(content))
+        } else if self.json_aware {
+            Box::new(json_aware_spans(content, self.json_strip_quotes))
+        } else if self.quoted_only {
+            Box::new(quoted_spans(
+                content,
+                self.quote_pairs.as_deref().unwrap_or(DEFAULT_QUOTE_PAIRS),
+                self.skip_empty_quotes,
+            ))
+        } else if let Some(delimiters) = &self.delimiters {
+            Box::new(delimited_spans(content, delimiters))
+        } else if let (Some(quote_pairs), true, Granularity::Word) =
+            (&self.quote_pairs, self.strings_as_tokens, &self.granularity)
+        {
+            Box::new(word_spans_with_quotes(content, quote_pairs))
+        } else {
+            match self.granularity {
+                Granularity::Word => Box::new(word_spans(content, self.strings_as_tokens)),
+                Granularity::Sentence => Box::new(sentence_spans(content)),
+                Granularity::Line => Box::new(line_spans(content)),
+            }
+        };
+        let mut spans: Vec<WordSpan> = tokens.take(limit).collect();
+        let mut truncated = spans.len() >= limit;
+        spans.truncate(self.max_spans.unwrap_or(usize::MAX));
+        let spans = if self.keep_numbers {
+            merge_numbers(content, spans, self.thousands_separators)
+        } else {
+            spans
+        };
+        let spans = if self.dotted_identifiers {
+            merge_dotted_identifiers(spans)
+        } else {
+            spans
+        };
+        let spans = if self.preserve_percentages {
+            merge_percentages(spans)
+        } else {
+            spans
+        };
+        let spans = if self.preserve_flags {
+            merge_command_flags(spans)
+        } else {
+            spans
+        };
+        let spans = if self.trim_punctuation {
+            trim_punctuation(spans)
+        } else {
+            spans
+        };
+        let spans = if self.include_whitespace {
+            // A truncated stream gets no gap after its last span, which would cover the rest of the content
+            let end = if truncated {
+                spans.last().map_or(0, |span| span.end)
+            } else {
+                content.len()
+            };
+            let mut spans = fill_gaps(&content[..end], spans);
+            // Gap spans count toward the limit like any other span
+            if spans.len() > self.max_spans.unwrap_or(usize::MAX) {
+                spans.truncate(self.max_spans.unwrap_or(usize::MAX));
+                truncated = true;
+            }
+            spans
+        } else {
+            spans
+        };
+        Ok((self.normalize_and_filter(spans)?, truncated))
+    }
+
+    /// Like [`SpanExtractor::extract_indexed`] on the literal's value, except that a char literal is
+    /// always a single span, even when it is whitespace
+    pub fn extract_literal(&self, literal: &StringLiteralInfo) -> Result<Vec<IndexedSpan>, Error> {
+        Ok(self.extract_literal_truncated(literal)?.0)
+    }
+
+    /// Like [`SpanExtractor::extract_literal`], and whether `max_spans` cut tokenizing short
+    pub fn extract_literal_truncated(
+        &self,
+        literal: &StringLiteralInfo,
+    ) -> Result<(Vec<IndexedSpan>, bool), Error> {
+        match literal.kind {
+            LiteralKind::Char => {
+                let span = WordSpan {
+                    word: literal.value.clone(),
+                    start: 0,
+                    end: literal.value.len(),
+                };
+                let spans = self.normalize_and_filter(vec![span])?;
+                Ok((self.in_offset_unit(&literal.value, spans), false))
+            }
+            _ => self.extract_indexed_truncated(&literal.value),
+        }
+    }
+
+    /// Tokenize and filter a source's literal, its `within` range, or its whole content, and tell
+    /// whether `max_spans` cut tokenizing short. Offsets count from the start of the content.
+    pub fn extract_source_truncated(
+        &self,
+        source: &Source,
+    ) -> Result<(Vec<IndexedSpan>, bool), Error> {
+        match (&source.origin, &source.within) {
+            (Some(literal), _) => self.extract_literal_truncated(literal),
+            (None, Some(range)) => {
+                let (spans, truncated) = self.extract_bytes(&source.content[range.clone()])?;
+                let spans: Vec<IndexedSpan> = spans
+                    .into_iter()
+                    .map(|(index, span)| {
+                        (
+                            index,
+                            WordSpan {
+                                start: span.start + range.start,
+                                end: span.end + range.start,
+                                ..span
+                            },
+                        )
+                    })
+                    .collect();
+                Ok((self.in_offset_unit(&source.content, spans), truncated))
+            }
+            (None, None) => self.extract_indexed_truncated(&source.content),
+        }
+    }
+
+    /// Count the byte offsets of spans of `content` in `offset_unit`
+    fn in_offset_unit(&self, content: &str, spans: Vec<IndexedSpan>) -> Vec<IndexedSpan> {
+        if self.offset_unit == OffsetUnit::Bytes {
+            return spans;
+        }
+        let units = unit_offsets(content, self.offset_unit);
+        spans
+            .into_iter()
+            .map(|(index, span)| {
+                let (start, end) = (units[span.start], units[span.end]);
+                (index, WordSpan { start, end, ..span })
+            })
+            .collect()
+    }
+
+    /// Whether `content` passes the filters and excludes as a single span, without being tokenized
+    pub fn keeps_whole(&self, content: &str) -> Result<bool, Error> {
+        let span = WordSpan {
+            word: content.to_string(),
+            start: 0,
+            end: content.len(),
+        };
+        Ok(!self.normalize_and_filter(vec![span])?.is_empty())
+    }
+
+    fn normalize_and_filter(&self, spans: Vec<WordSpan>) -> Result<Vec<IndexedSpan>, Error> {
+        if self.filter_normalized {
+            return self.filter_indexed(normalize_spans(spans, &self.normalization));
+        }
+        let spans = self.filter_indexed(spans)?;
+        let (indices, spans): (Vec<usize>, Vec<WordSpan>) = spans.into_iter().unzip();
+        Ok(indices
+            .into_iter()
+            .zip(normalize_spans(spans, &self.normalization))
+            .collect())
+    }
+
+    /// Rebuild the content like [`replace_spans`], matching FROM patterns with this extractor's
+    /// filter mode, case sensitivity, embeddings and fuzzy distance
+    pub fn replace_spans(
+        &self,
+        content: &str,
+        spans: &[WordSpan],
+        replacements: &[(String, String)],
+    ) -> Result<String, Error> {
+        replace_matching(
+            content,
+            spans,
+            replacements,
+            &self.filter_mode,
+            self.ignore_case,
+            self.semantic_options().as_ref(),
+            self.fuzzy_distance.unwrap_or(DEFAULT_FUZZY_DISTANCE),
+        )
+    }
+
+    fn semantic_options(&self) -> Option<SemanticOptions> {
+        self.embeddings.as_ref().map(|embeddings| SemanticOptions {
+            embeddings: Arc::clone(embeddings),
+            threshold: self
+                .similarity_threshold
+                .unwrap_or(DEFAULT_SIMILARITY_THRESHOLD),
+        })
+    }
+
+    fn filter_indexed(&self, spans: Vec<WordSpan>) -> Result<Vec<IndexedSpan>, Error> {
+        let semantic = self.semantic_options();
+        if self.invert_match && self.filters.is_empty() {
+            return Ok(Vec::new());
+        }
+        let fuzzy_distance = self.fuzzy_distance.unwrap_or(DEFAULT_FUZZY_DISTANCE);
+        let spans = FilteredSpans::new(
+            spans.into_iter().enumerate(),
+            &self.filters,
+            &self.filter_mode,
+            self.ignore_case,
+            semantic.as_ref(),
+            fuzzy_distance,
+            !self.invert_match,
+        )?;
+        let spans = FilteredSpans::new(
+            spans,
+            &self.excludes,
+            &self.filter_mode,
+            self.ignore_case,
+            semantic.as_ref(),
+            fuzzy_distance,
+            false,
+        )?
+        .collect();
+        if self.extract_captures
+            && !self.invert_match
+            && matches!(self.filter_mode, FilterMode::Regex)
+        {
+            return capture_spans(spans, &self.filters, self.ignore_case, self.captures_only);
+        }
+        Ok(spans)
+    }
+}
+
+/// Cosine similarity a word must exceed to match a filter in [`FilterMode::Semantic`]
+pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.5;
+
+/// Edit distance within which a word matches a filter in [`FilterMode::Fuzzy`]
+pub const DEFAULT_FUZZY_DISTANCE: usize = 1;
+
+/// Words or filters longer than this many characters only match [`FilterMode::Fuzzy`] filters
+/// exactly, so a huge token can't make the edit distance computation blow up
+pub const MAX_FUZZY_WORD_LEN: usize = 64;
+
+/// Word vectors for [`FilterMode::Semantic`], one `word v1 v2 ...` line per word as in
+/// GloVe or word2vec text files. A leading `count dimension` header line is skipped.
+///
+/// ```
+/// use rust_span_counter::Embeddings;
+///
+/// let embeddings = Embeddings::parse("cat 1 0\ndog 0.9 0.1\ncar 0 1\n").unwrap();
+/// assert!(embeddings.similarity("cat", "dog").unwrap() > 0.9);
+/// assert_eq!(embeddings.similarity("cat", "car"), Some(0.0));
+/// assert_eq!(embeddings.similarity("cat", "bird"), None);
+/// ```
+#[derive(Clone)]
+pub struct Embeddings {
+    // Normalized to unit length, so the dot product is the cosine similarity
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl Embeddings {
+    /// Parse embeddings text; blank lines are ignored and every vector must have the same dimension
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut vectors = HashMap::new();
+        let mut dimension = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let Some(word) = fields.next() else {
+                continue;
+            };
+            let fields: Vec<&str> = fields.collect();
+            if index == 0
+                && fields.len() == 1
+                && word.parse::<usize>().is_ok()
+                && fields[0].parse::<usize>().is_ok()
+            {
+                continue;
+            }
+
+            let values: Vec<f32> = fields
+                .iter()
+                .map(|field| {
+                    field
+                        .parse()
+                        .map_err(|_| Error::InvalidEmbeddings(index + 1))
+                })
+                .collect::<Result<_, _>>()?;
+            if values.is_empty() || *dimension.get_or_insert(values.len()) != values.len() {
+                return Err(Error::InvalidEmbeddings(index + 1));
+            }
+
+            let norm = values.iter().map(|value| value * value).sum::<f32>().sqrt();
+            let normalized = if norm > 0.0 {
+                values.iter().map(|value| value / norm).collect()
+            } else {
+                values
+            };
+            vectors.insert(word.to_string(), normalized);
+        }
+
+        Ok(Embeddings { vectors })
+    }
+
+    /// Number of words with a vector
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    /// Cosine similarity of two words' vectors, or `None` if either has no vector. A word without
+    /// a vector of its own falls back to its lowercase form.
+    pub fn similarity(&self, a: &str, b: &str) -> Option<f32> {
+        let (a, b) = (self.vector(a)?, self.vector(b)?);
+        Some(a.iter().zip(b).map(|(x, y)| x * y).sum())
+    }
+
+    fn vector(&self, word: &str) -> Option<&[f32]> {
+        self.vectors
+            .get(word)
+            .or_else(|| self.vectors.get(&word.to_lowercase()))
+            .map(Vec::as_slice)
+    }
+}
+
+impl std::fmt::Debug for Embeddings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Embeddings")
+            .field("words", &self.vectors.len())
+            .finish()
+    }
+}
+
+/// What [`FilterMode::Semantic`] compares words with
+#[derive(Clone)]
+struct SemanticOptions {
+    embeddings: Arc<Embeddings>,
+    threshold: f32,
+}
+
+/// Compiled set of filter patterns; a word matches if any pattern matches (OR logic)
+enum WordMatcher {
+    Exact {
+        patterns: Vec<String>,
+        ignore_case: bool,
+    },
+    Contains {
+        patterns: Vec<String>,
+        ignore_case: bool,
+    },
+    Regex(Vec<Regex>),
+    Semantic {
+        patterns: Vec<String>,
+        options: SemanticOptions,
+    },
+    Fuzzy {
+        patterns: Vec<Vec<char>>,
+        ignore_case: bool,
+        max_distance: usize,
+    },
+}
+
+impl WordMatcher {
+    fn new(
+        patterns: &[String],
+        filter_mode: &FilterMode,
+        ignore_case: bool,
+        semantic: Option<&SemanticOptions>,
+        fuzzy_distance: usize,
+    ) -> Result<Self, Error> {
+        let fold = |pattern: &String| {
+            if ignore_case {
+                pattern.to_lowercase()
+            } else {
+                pattern.clone()
+            }
+        };
+
+        match filter_mode {
+            FilterMode::Exact => Ok(WordMatcher::Exact {
+                patterns: patterns.iter().map(fold).collect(),
+                ignore_case,
+            }),
+            FilterMode::Contains => Ok(WordMatcher::Contains {
+                patterns: patterns.iter().map(fold).collect(),
+                ignore_case,
+            }),
+            FilterMode::Regex => Ok(WordMatcher::Regex(compile_regexes(patterns, ignore_case)?)),
+            FilterMode::Glob => {
+                let patterns: Vec<String> = patterns
+                    .iter()
+                    .map(|pattern| glob_to_regex(pattern))
+                    .collect();
+                Ok(WordMatcher::Regex(compile_regexes(&patterns, ignore_case)?))
+            }
+            FilterMode::Semantic => Ok(WordMatcher::Semantic {
+                patterns: patterns.to_vec(),
+                options: semantic.cloned().ok_or(Error::MissingEmbeddings)?,
+            }),
+            FilterMode::Fuzzy => Ok(WordMatcher::Fuzzy {
+                patterns: patterns
+                    .iter()
+                    .map(|pattern| fold(pattern).chars().collect())
+                    .collect(),
+                ignore_case,
+                max_distance: fuzzy_distance,
+            }),
+        }
+    }
+
+    fn is_match(&self, word: &str) -> bool {
+        match self {
+            WordMatcher::Exact {
+                patterns,
+                ignore_case,
+            } => {
+                let word = fold_case(word, *ignore_case);
+                patterns.iter().any(|pattern| *pattern == word)
+            }
+            WordMatcher::Contains {
+                patterns,
+                ignore_case,
+            } => {
+                let word = fold_case(word, *ignore_case);
+                patterns
+                    .iter()
+                    .any(|pattern| word.contains(pattern.as_str()))
+            }
+            WordMatcher::Regex(regexes) => regexes.iter().any(|regex| regex.is_match(word)),
+            WordMatcher::Semantic { patterns, options } => patterns.iter().any(|pattern| {
+                options
+                    .embeddings
+                    .similarity(word, pattern)
+                    .is_some_and(|similarity| similarity > options.threshold)
+            }),
+            WordMatcher::Fuzzy {
+                patterns,
+                ignore_case,
+                max_distance,
+            } => {
+                let word: Vec<char> = fold_case(word, *ignore_case).chars().collect();
+                patterns
+                    .iter()
+                    .any(|pattern| within_edit_distance(&word, pattern, *max_distance))
+            }
+        }
+    }
+}
+
+/// Whether the Levenshtein distance between `a` and `b` is at most `max_distance`. Takes
+/// O(len(a) × len(b)) time, stopping early once every path exceeds the limit; beyond
+/// [`MAX_FUZZY_WORD_LEN`] characters only equal words are within any distance.
+fn within_edit_distance(a: &[char], b: &[char], max_distance: usize) -> bool {
+    if a.len().abs_diff(b.len()) > max_distance {
+        return false;
+    }
+    if a.len() > MAX_FUZZY_WORD_LEN || b.len() > MAX_FUZZY_WORD_LEN {
+        return a == b;
+    }
+
+    // Each row holds the distances from a prefix of `a` to every prefix of `b`
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().all(|&distance| distance > max_distance) {
+            return false;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()] <= max_distance
+}
+
+/// Translate a shell-style glob into a regex anchored to the whole word: `*` matches any run of
+/// characters (including none), `?` exactly one, and `[...]` (or `[!...]`) one character from a set.
+/// A `[` without a closing `]` is literal.
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("^");
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let negated = matches!(chars.get(i + 1), Some('!' | '^'));
+                let set_start = i + 1 + usize::from(negated);
+                // A `]` right after the opening bracket is part of the set
+                let close = (set_start + 1..chars.len()).find(|&j| chars[j] == ']');
+                let Some(close) = close.filter(|_| set_start < chars.len()) else {
+                    regex.push_str(r"\[");
+                    i += 1;
+                    continue;
+                };
+                regex.push('[');
+                if negated {
+                    regex.push('^');
+                }
+                for (j, &c) in chars.iter().enumerate().take(close).skip(set_start) {
+                    // Dashes between two set members form a range; any other punctuation is literal
+                    let is_range = c == '-' && j > set_start && j + 1 < close;
+                    if !is_range && c.is_ascii_punctuation() {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+                i = close;
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex.push('$');
+    regex
+}
+
+fn compile_regexes(patterns: &[String], ignore_case: bool) -> Result<Vec<Regex>, Error> {
+    let mut compiled_regexes = Vec::new();
+    for pattern in patterns {
+        let regex = if ignore_case {
+            Regex::new(&format!("(?i){}", pattern)).map_err(Error::RegexError)?
+        } else {
+            Regex::new(pattern).map_err(Error::RegexError)?
+        };
+        compiled_regexes.push(regex);
+    }
+    Ok(compiled_regexes)
+}
+
+/// Replace each span whose word matches one of the regex `patterns` with a span per capture group of
+/// every match, the whole match (group 0) first unless `captures_only`. The new spans keep the original
+/// span's index and are offset into the same content; groups that took no part in a match are skipped.
+/// The first matching pattern is used, and spans no pattern matches are kept unchanged.
+pub fn capture_spans(
+    spans: Vec<IndexedSpan>,
+    patterns: &[String],
+    ignore_case: bool,
+    captures_only: bool,
+) -> Result<Vec<IndexedSpan>, Error> {
+    let regexes = compile_regexes(patterns, ignore_case)?;
+    let first_group = if captures_only { 1 } else { 0 };
+
+    let mut captured = Vec::new();
+    for (index, span) in spans {
+        let Some(regex) = regexes.iter().find(|regex| regex.is_match(&span.word)) else {
+            captured.push((index, span));
+            continue;
+        };
+        for captures in regex.captures_iter(&span.word) {
+            for group in captures.iter().skip(first_group).flatten() {
+                captured.push((
+                    index,
+                    WordSpan {
+                        word: group.as_str().to_string(),
+                        start: span.start + group.start(),
+                        end: span.start + group.end(),
+                    },
+                ));
+            }
+        }
+    }
+    Ok(captured)
+}
+
+/// Rebuild the content with each span whose word matches a replacement's FROM pattern swapped for its TO text.
+/// Text outside the replaced spans is copied verbatim; the first matching replacement wins.
+pub fn replace_spans(
+    content: &str,
+    spans: &[WordSpan],
+    replacements: &[(String, String)],
+    filter_mode: &FilterMode,
+    ignore_case: bool,
+) -> Result<String, Error> {
+    replace_matching(
+        content,
+        spans,
+        replacements,
+        filter_mode,
+        ignore_case,
+        None,
+        DEFAULT_FUZZY_DISTANCE,
+    )
+}
+
+fn replace_matching(
+    content: &str,
+    spans: &[WordSpan],
+    replacements: &[(String, String)],
+    filter_mode: &FilterMode,
+    ignore_case: bool,
+    semantic: Option<&SemanticOptions>,
+    fuzzy_distance: usize,
+) -> Result<String, Error> {
+    let mut matchers = Vec::new();
+    for (from, to) in replacements {
+        matchers.push((
+            WordMatcher::new(
+                std::slice::from_ref(from),
+                filter_mode,
+                ignore_case,
+                semantic,
+                fuzzy_distance,
+            )?,
+            to,
+        ));
+    }
+
+    let mut rebuilt = String::with_capacity(content.len());
+    let mut copied_up_to = 0;
+
+    for span in spans {
+        if let Some((_, to)) = matchers
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(&span.word))
+        {
+            // Spans that overlap an earlier replacement or don't fit the content can't be replaced
+            let (Ok(before), Ok(_)) = (
+                safe_slice(content, copied_up_to, span.start),
+                safe_slice(content, span.start, span.end),
+            ) else {
+                continue;
+            };
+            rebuilt.push_str(before);
+            rebuilt.push_str(to);
+            copied_up_to = span.end;
+        }
+    }
+    rebuilt.push_str(&content[copied_up_to..]);
+
+    Ok(rebuilt)
+}
+
+/// Reorder spans; ties fall back to the start offset so the result is deterministic.
+/// Word orders compare case-insensitively when `ignore_case` is set.
+pub fn sort_spans(spans: &mut [IndexedSpan], order: &SortOrder, ignore_case: bool) {
+    match order {
+        SortOrder::Position => spans.sort_by_key(|(index, span)| (*index, span.start)),
+        SortOrder::Word => spans.sort_by(|(_, a), (_, b)| {
+            fold_case(&a.word, ignore_case)
+                .cmp(&fold_case(&b.word, ignore_case))
+                .then(a.start.cmp(&b.start))
+        }),
+        SortOrder::WordDesc => spans.sort_by(|(_, a), (_, b)| {
+            fold_case(&b.word, ignore_case)
+                .cmp(&fold_case(&a.word, ignore_case))
+                .then(a.start.cmp(&b.start))
+        }),
+        SortOrder::Length => spans.sort_by_key(|(_, span)| (span.word.chars().count(), span.start)),
+        SortOrder::Start => spans.sort_by_key(|(_, span)| span.start),
+    }
+}
+
+/// Drop repeated words, keeping the first occurrence of each by start offset.
+/// With `ignore_case`, words differing only in case count as duplicates.
+pub fn dedup_spans(mut spans: Vec<IndexedSpan>, ignore_case: bool) -> Vec<IndexedSpan> {
+    spans.sort_by_key(|(_, span)| span.start);
+    let mut seen = HashSet::new();
+    spans.retain(|(_, span)| seen.insert(fold_case(&span.word, ignore_case).into_owned()));
+    spans
+}
+
+/// Keep the spans in the longest `top` percent and/or the shortest `bottom` percent by grapheme length.
+/// Cutoffs use the nearest-rank method and spans tied with the cutoff length are kept. When both
+/// bands are given, a span in either one is kept.
+pub fn filter_by_length_percentile(
+    spans: Vec<IndexedSpan>,
+    top: Option<f64>,
+    bottom: Option<f64>,
+) -> Vec<IndexedSpan> {
+    if top.is_none() && bottom.is_none() {
+        return spans;
+    }
+
+    let grapheme_len = |span: &WordSpan| span.word.graphemes(true).count();
+    let mut lengths: Vec<usize> = spans.iter().map(|(_, span)| grapheme_len(span)).collect();
+    lengths.sort_unstable();
+    // Number of spans that fall inside a band of `percent`, rounded up
+    let rank = |percent: f64| {
+        ((lengths.len() as f64 * percent / 100.0).ceil() as usize).min(lengths.len())
+    };
+
+    let top_cutoff = top
+        .map(rank)
+        .filter(|&count| count > 0)
+        .map(|count| lengths[lengths.len() - count]);
+    let bottom_cutoff = bottom
+        .map(rank)
+        .filter(|&count| count > 0)
+        .map(|count| lengths[count - 1]);
+
+    spans
+        .into_iter()
+        .filter(|(_, span)| {
+            let len = grapheme_len(span);
+            top_cutoff.is_some_and(|cutoff| len >= cutoff)
+                || bottom_cutoff.is_some_and(|cutoff| len <= cutoff)
+        })
+        .collect()
+}
+
+/// Keep the spans that lie within the byte range from `start` to `end` (exclusive); either bound may be
+/// left open. With `overlap`, spans that only partly overlap the range are kept as well.
+pub fn filter_by_offset_range(
+    spans: Vec<IndexedSpan>,
+    start: Option<usize>,
+    end: Option<usize>,
+    overlap: bool,
+) -> Vec<IndexedSpan> {
+    let (start, end) = (start.unwrap_or(0), end.unwrap_or(usize::MAX));
+    spans
+        .into_iter()
+        .filter(|(_, span)| {
+            (span.start >= start && span.end <= end)
+                || (overlap && span.start < end && span.end > start)
+        })
+        .collect()
+}
+
+/// Count how often each word is directly followed by another, as `((word, next_word), count)`
+/// pairs in order of first appearance. Spans are taken in the order given.
+pub fn word_adjacency(spans: &[WordSpan]) -> Vec<((String, String), usize)> {
+    let mut edges: Vec<((String, String), usize)> = Vec::new();
+    let mut positions: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for pair in spans.windows(2) {
+        let key = (pair[0].word.as_str(), pair[1].word.as_str());
+        match positions.get(&key) {
+            Some(&position) => edges[position].1 += 1,
+            None => {
+                positions.insert(key, edges.len());
+                edges.push(((pair[0].word.clone(), pair[1].word.clone()), 1));
+            }
+        }
+    }
+
+    edges
+}
+
+/// A span together with the words directly before and after it in the unfiltered token stream
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanNeighbors {
+    pub span: WordSpan,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Look up the neighbors of each indexed span in `stream`, the unfiltered spans its index refers to
+/// (as produced by [`SpanExtractor`] without filters or excludes)
+///
+/// ```
+/// use rust_span_counter::{span_neighbors, SpanExtractor};
+///
+/// let extractor = SpanExtractor::new();
+/// let stream = extractor.extract("a b c").unwrap();
+/// let matched = extractor.filters(vec!["b".to_string()]).extract_indexed("a b c").unwrap();
+///
+/// let neighbors = span_neighbors(&stream, &matched);
+/// assert_eq!((neighbors[0].before.as_deref(), neighbors[0].after.as_deref()), (Some("a"), Some("c")));
+/// ```
+pub fn span_neighbors(stream: &[WordSpan], spans: &[IndexedSpan]) -> Vec<SpanNeighbors> {
+    let word_at = |index: Option<usize>| {
+        index
+            .and_then(|index| stream.get(index))
+            .map(|span| span.word.clone())
+    };
+    spans
+        .iter()
+        .map(|(index, span)| SpanNeighbors {
+            span: span.clone(),
+            before: word_at(index.checked_sub(1)),
+            after: word_at(Some(index + 1)),
+        })
+        .collect()
+}
+
+/// Add the `context` spans before and after each indexed span, taken from `stream`, the unfiltered
+/// spans the indices refer to. Overlapping windows are merged, so each stream span appears at most
+/// once, and the result is in stream order.
+///
+/// ```
+/// use rust_span_counter::{spans_with_context, SpanExtractor};
+///
+/// let extractor = SpanExtractor::new();
+/// let stream = extractor.extract("a b c d e f").unwrap();
+/// let matched = extractor.filters(vec!["b".to_string(), "c".to_string()]).extract_indexed("a b c d e f").unwrap();
+///
+/// let words: Vec<String> = spans_with_context(&stream, &matched, 1).into_iter().map(|(_, span)| span.word).collect();
+/// assert_eq!(words, ["a", "b", "c", "d"]);
+/// ```
+pub fn spans_with_context(
+    stream: &[WordSpan],
+    spans: &[IndexedSpan],
+    context: usize,
+) -> Vec<IndexedSpan> {
+    let mut indices: Vec<usize> = spans
+        .iter()
+        .flat_map(|(index, _)| {
+            index.saturating_sub(context)
+                ..=index
+                    .saturating_add(context)
+                    .min(stream.len().saturating_sub(1))
+        })
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+        .into_iter()
+        .filter_map(|index| stream.get(index).map(|span| (index, span.clone())))
+        .collect()
+}
+
+/// Count occurrences of each distinct word, most frequent first and alphabetical among equal counts.
+/// With `ignore_case`, words are folded to lowercase and reported by their folded form.
+pub fn word_frequencies(spans: &[WordSpan], ignore_case: bool) -> Vec<(String, usize)> {
+    let mut counts: HashMap<Cow<'_, str>, usize> = HashMap::new();
+    for span in spans {
+        *counts
+            .entry(fold_case(&span.word, ignore_case))
+            .or_default() += 1;
+    }
+
+    let mut frequencies: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(word, count)| (word.into_owned(), count))
+        .collect();
+    frequencies.sort_by(|(word_a, count_a), (word_b, count_b)| {
+        count_b.cmp(count_a).then_with(|| word_a.cmp(word_b))
+    });
+    frequencies
+}
+
+/// Maps byte offsets in a piece of content to 1-based line and column numbers.
+/// Columns count characters, so multibyte text lines up with what an editor shows.
+///
+/// ```
+/// use rust_span_counter::LineIndex;
+///
+/// let index = LineIndex::new("a\nbc");
+/// assert_eq!(index.position(3), (2, 2));
+/// ```
+pub struct LineIndex<'a> {
+    content: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(content: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        LineIndex {
+            content,
+            line_starts,
+        }
+    }
+
+    /// Line and column of the character starting at `offset`
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self
+            .content
+            .get(line_start..offset)
+            .map_or(0, |text| text.chars().count())
+            + 1;
+        (line, column)
+    }
+
+    /// Byte offset of the 1-based `column` on the 1-based `line`, the inverse of [`LineIndex::position`].
+    /// The column just past the line's last character is its end
+    ///
+    /// ```
+    /// use rust_span_counter::LineIndex;
+    ///
+    /// let index = LineIndex::new("é\nbc");
+    /// assert_eq!(index.offset(1, 2), Some(2));
+    /// assert_eq!(index.offset(2, 2), Some(4));
+    /// assert_eq!(index.offset(2, 4), None);
+    /// ```
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.content.len(), |next| next - 1);
+        let text = &self.content[start..end];
+        text.char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(text.len()))
+            .nth(column.checked_sub(1)?)
+            .map(|offset| start + offset)
+    }
+
+    /// Number of lines; a final line break ends the last line rather than starting an empty one
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len() - usize::from(self.content.ends_with('\n'))
+    }
+
+    /// Byte range of the 1-based `line`, without its `\n` or `\r\n` line break
+    ///
+    /// ```
+    /// use rust_span_counter::LineIndex;
+    ///
+    /// let content = "first\r\nsecond";
+    /// assert_eq!(LineIndex::new(content).line_range(1), Some(0..5));
+    /// assert_eq!(LineIndex::new(content).line_range(2), Some(7..13));
+    /// ```
+    pub fn line_range(&self, line: usize) -> Option<std::ops::Range<usize>> {
+        if line == 0 || line > self.line_count() {
+            return None;
+        }
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.content.len(), |next| next - 1);
+        let text = &self.content[start..end];
+        Some(start..start + text.strip_suffix('\r').unwrap_or(text).len())
+    }
+}
+
+/// For every byte offset of `content` up to and including its end, the same position counted in `unit`,
+/// built in one pass so converting each span is a lookup. An offset inside a character or grapheme
+/// cluster maps to where that character or cluster starts.
+///
+/// ```
+/// use rust_span_counter::{unit_offsets, OffsetUnit};
+///
+/// let content = "日😀";
+/// assert_eq!(unit_offsets(content, OffsetUnit::Chars)[content.len()], 2);
+/// assert_eq!(unit_offsets(content, OffsetUnit::Utf16)[content.len()], 3);
+/// ```
+pub fn unit_offsets(content: &str, unit: OffsetUnit) -> Vec<usize> {
+    // Each unit as its byte length and how many units it counts for
+    let units: Box<dyn Iterator<Item = (usize, usize)>> = match unit {
+        OffsetUnit::Bytes => return (0..=content.len()).collect(),
+        OffsetUnit::Chars => Box::new(content.chars().map(|c| (c.len_utf8(), 1))),
+        OffsetUnit::Graphemes => {
+            Box::new(content.graphemes(true).map(|grapheme| (grapheme.len(), 1)))
+        }
+        OffsetUnit::Utf16 => Box::new(content.chars().map(|c| (c.len_utf8(), c.len_utf16()))),
+    };
+
+    let mut table = Vec::with_capacity(content.len() + 1);
+    let mut count = 0;
+    for (len, weight) in units {
+        table.extend(std::iter::repeat_n(count, len));
+        count += weight;
+    }
+    table.push(count);
+    table
+}
+
+/// How [`OffsetStyle::report`] prints a range: counted from 0 or 1, with the end one past the last unit
+/// or naming it. The default is 0-based and half-open, like Rust ranges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OffsetStyle {
+    /// Add 1 to the start and end
+    pub one_based: bool,
+    /// Report the end as the offset of the last unit instead of one past it
+    pub end_inclusive: bool,
+}
+
+impl OffsetStyle {
+    /// The printed start and end of the byte range `start..end`. With `units`, the offset of each byte
+    /// offset in another unit (see [`unit_offsets`]), the range is counted in that unit. An empty range
+    /// has no last unit, so an inclusive end stays at its start rather than before it.
+    ///
+    /// ```
+    /// use rust_span_counter::{unit_offsets, OffsetStyle, OffsetUnit};
+    ///
+    /// let inclusive = OffsetStyle { one_based: true, end_inclusive: true };
+    /// assert_eq!(inclusive.report(0, 1, None), (1, 1));
+    ///
+    /// let units = unit_offsets("é a", OffsetUnit::Chars);
+    /// assert_eq!(OffsetStyle::default().report(3, 4, Some(&units)), (2, 3));
+    /// ```
+    pub fn report(&self, start: usize, end: usize, units: Option<&[usize]>) -> (usize, usize) {
+        let (start, end) = match units {
+            Some(units) => {
+                let convert = |offset: usize| units.get(offset).or(units.last()).copied();
+                (convert(start).unwrap_or(start), convert(end).unwrap_or(end))
+            }
+            None => (start, end),
+        };
+        let end = if self.end_inclusive && end > start {
+            end - 1
+        } else {
+            end
+        };
+        let base = usize::from(self.one_based);
+        (start + base, end + base)
+    }
+}
+
+/// Totals over a span list, as reported by `--summary`
+#[derive(Debug, PartialEq)]
+pub struct SpanSummary {
+    /// Number of spans
+    pub spans: usize,
+    /// Number of distinct span words
+    pub unique_words: usize,
+    /// Sum of the span lengths in characters
+    pub covered_chars: usize,
+}
+
+/// Count the spans, their distinct words, and the characters they cover
+pub fn summarize_spans(spans: &[WordSpan]) -> SpanSummary {
+    let unique_words: HashSet<&str> = spans.iter().map(|span| span.word.as_str()).collect();
+    SpanSummary {
+        spans: spans.len(),
+        unique_words: unique_words.len(),
+        covered_chars: spans.iter().map(|span| span.word.chars().count()).sum(),
+    }
+}
+
+/// Byte window `start..end` holding the largest number of whole spans
+#[derive(Debug, PartialEq)]
+pub struct DensestWindow {
+    pub start: usize,
+    pub end: usize,
+    pub count: usize,
+}
+
+/// Slide a `window_size`-byte window over the spans and return the position containing the most spans.
+/// Only windows starting at a span start need checking; a two-pointer sweep over the sorted spans
+/// finds the best one in linear time. Ties keep the earliest window.
+pub fn find_densest_window(spans: &[WordSpan], window_size: usize) -> Option<DensestWindow> {
+    let mut sorted: Vec<&WordSpan> = spans.iter().collect();
+    sorted.sort_by_key(|span| (span.start, span.end));
+
+    let mut best: Option<DensestWindow> = None;
+    let mut right = 0;
+    for (left, span) in sorted.iter().enumerate() {
+        let window_end = span.start + window_size;
+        right = right.max(left);
+        while right < sorted.len() && sorted[right].end <= window_end {
+            right += 1;
+        }
+
+        let count = right - left;
+        if count > 0 && best.as_ref().is_none_or(|best| count > best.count) {
+            best = Some(DensestWindow {
+                start: span.start,
+                end: window_end,
+                count,
+            });
+        }
+    }
+
+    best
+}
+
+/// Compute the cluster key of a word by applying the given normalizations
+pub fn normalize_word(word: &str, keys: &[ClusterKey]) -> String {
+    let mut normalized = word.to_string();
+    if keys.contains(&ClusterKey::TrimPunctuation) {
+        // Punctuation-only tokens keep their text rather than all collapsing into one empty key
+        let trimmed = normalized.trim_matches(|c: char| !c.is_alphanumeric());
+        if !trimmed.is_empty() {
+            normalized = trimmed.to_string();
+        }
+    }
+    if keys.contains(&ClusterKey::StripAccents) {
+        // Decompose so accents become separate combining marks, then drop them
+        normalized = normalized
+            .nfd()
+            .filter(|&c| !is_combining_mark(c))
+            .collect();
+    }
+    if keys.contains(&ClusterKey::Lowercase) {
+        normalized = normalized.to_lowercase();
+    }
+    normalized
+}
+
+/// Group spans by their normalized word, so `Café`, `cafe`, and `café!` share a cluster.
+/// Clusters are returned as `(key, members)` in order of first appearance.
+pub fn cluster_spans(
+    spans: Vec<IndexedSpan>,
+    keys: &[ClusterKey],
+) -> Vec<(String, Vec<IndexedSpan>)> {
+    let mut clusters: Vec<(String, Vec<IndexedSpan>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for indexed_span in spans {
+        let key = normalize_word(&indexed_span.1.word, keys);
+        match positions.get(&key) {
+            Some(&position) => clusters[position].1.push(indexed_span),
+            None => {
+                positions.insert(key.clone(), clusters.len());
+                clusters.push((key, vec![indexed_span]));
+            }
+        }
+    }
+
+    clusters
+}
+
+fn fold_case(word: &str, ignore_case: bool) -> Cow<'_, str> {
+    if ignore_case {
+        Cow::Owned(word.to_lowercase())
+    } else {
+        Cow::Borrowed(word)
+    }
+}
+
+/// Detect the language of a span's text as an ISO 639-3 code.
+/// Text shorter than `min_len` characters is skipped since detection on it is unreliable.
+pub fn detect_language(text: &str, min_len: usize) -> Option<&'static str> {
+    if text.chars().count() < min_len {
+        return None;
+    }
+    whatlang::detect_lang(text).map(|lang| lang.code())
+}
+
+/// Parse a JSON document and resolve a JSON Pointer (RFC 6901) in it to a string value
+pub fn find_json_string_in_source(source: &str, json_pointer: &str) -> Result<String, Error> {
+    let document: serde_json::Value = serde_json::from_str(source).map_err(Error::JsonError)?;
+    find_json_string(&document, json_pointer)
+}
+
+/// Resolve a JSON Pointer (RFC 6901) in a parsed document to a string value
+pub fn find_json_string(document: &serde_json::Value, json_pointer: &str) -> Result<String, Error> {
+    match document.pointer(json_pointer) {
+        Some(serde_json::Value::String(value)) => Ok(value.clone()),
+        Some(_) => Err(Error::JsonPointerNotString(json_pointer.to_string())),
+        None => Err(Error::JsonPointerNotFound(json_pointer.to_string())),
+    }
+}
+
+/// Every string value in a JSON document with the JSON Pointer that resolves to it, in document order.
+/// `~` and `/` in keys are escaped as `~0` and `~1`.
+///
+/// ```
+/// use rust_span_counter::json_string_pointers;
+///
+/// let document = serde_json::json!({ "a/b": ["x", 1, { "c": "y" }] });
+/// assert_eq!(json_string_pointers(&document), vec![
+///     ("/a~1b/0".to_string(), "x".to_string()),
+///     ("/a~1b/2/c".to_string(), "y".to_string()),
+/// ]);
+/// ```
+pub fn json_string_pointers(document: &serde_json::Value) -> Vec<(String, String)> {
+    let mut strings = Vec::new();
+    collect_json_strings(document, String::new(), &mut strings);
+    strings
+}
+
+fn collect_json_strings(
+    value: &serde_json::Value,
+    pointer: String,
+    strings: &mut Vec<(String, String)>,
+) {
+    match value {
+        serde_json::Value::String(text) => strings.push((pointer, text.clone())),
+        serde_json::Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_json_strings(item, format!("{}/{}", pointer, index), strings);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for (key, field) in fields {
+                let token = key.replace('~', "~0").replace('/', "~1");
+                collect_json_strings(field, format!("{}/{}", pointer, token), strings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A string value in a TOML document. Its key is the dotted path to it, with array elements as their
+/// index (`package.authors.0`) and keys that aren't bare, like `"a.b"`, quoted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TomlString {
+    pub key: String,
+    /// The decoded value, so offsets into it match the `json` and `file` subcommands
+    pub value: String,
+    /// 1-based line and character column where the string starts, quotes included
+    pub start_line: usize,
+    pub start_column: usize,
+    /// 1-based line of the string's closing quote
+    pub end_line: usize,
+}
+
+/// Every string value in a TOML document, in document order, with where it is written. Basic,
+/// literal, and multi-line strings are all decoded.
+///
+/// ```
+/// use rust_span_counter::toml_strings;
+///
+/// let strings = toml_strings("[package]\nname = 'demo'\nauthors = [\"a\", \"b\"]\n").unwrap();
+/// let keys: Vec<&str> = strings.iter().map(|string| string.key.as_str()).collect();
+/// assert_eq!(keys, vec!["package.name", "package.authors.0", "package.authors.1"]);
+/// assert_eq!((strings[0].value.as_str(), strings[0].start_line), ("demo", 2));
+/// ```
+pub fn toml_strings(source: &str) -> Result<Vec<TomlString>, Error> {
+    Ok(toml_entries(source)?
+        .into_iter()
+        .filter_map(|(_, string)| string)
+        .collect())
+}
+
+/// The string value at a dotted key, as listed by [`toml_strings`]
+pub fn find_toml_string(source: &str, key: &str) -> Result<TomlString, Error> {
+    match toml_entries(source)?
+        .into_iter()
+        .find(|(entry_key, _)| entry_key == key)
+    {
+        Some((_, Some(string))) => Ok(string),
+        Some((_, None)) => Err(Error::TomlKeyNotString(key.to_string())),
+        None => Err(Error::TomlKeyNotFound(key.to_string())),
+    }
+}
+
+/// The single TOML string value covering `target_line`
+pub fn find_toml_string_on_line(source: &str, target_line: usize) -> Result<TomlString, Error> {
+    let strings = toml_strings(source)?;
+    let mut on_line = strings
+        .iter()
+        .filter(|string| string.start_line <= target_line && target_line <= string.end_line);
+    match (on_line.next(), on_line.next()) {
+        (Some(string), None) => Ok(string.clone()),
+        (Some(_), Some(_)) => Err(Error::MultipleStringsFound),
+        (None, _) => {
+            let lines = strings
+                .iter()
+                .flat_map(|string| string.start_line..=string.end_line)
+                .collect();
+            Err(Error::NoStringFound {
+                line: target_line,
+                nearest_lines_with_strings: nearest_lines(lines, target_line),
+            })
+        }
+    }
+}
+
+/// Every key in a TOML document with its string, or `None` for tables, arrays, and other values
+fn toml_entries(source: &str) -> Result<Vec<(String, Option<TomlString>)>, Error> {
+    let document = toml::de::DeTable::parse(source).map_err(Error::TomlError)?;
+    let index = LineIndex::new(source);
+    let mut entries = Vec::new();
+    for (key, value) in document.get_ref() {
+        collect_toml_entries(value, toml_key_segment(key.get_ref()), &index, &mut entries);
+    }
+    Ok(entries)
+}
+
+fn collect_toml_entries(
+    value: &toml::Spanned<toml::de::DeValue>,
+    key: String,
+    index: &LineIndex,
+    entries: &mut Vec<(String, Option<TomlString>)>,
+) {
+    match value.get_ref() {
+        toml::de::DeValue::String(text) => {
+            let span = value.span();
+            let (start_line, start_column) = index.position(span.start);
+            let (end_line, _) = index.position(span.end.saturating_sub(1).max(span.start));
+            let string = TomlString {
+                key: key.clone(),
+                value: text.to_string(),
+                start_line,
+                start_column,
+                end_line,
+            };
+            entries.push((key, Some(string)));
+        }
+        toml::de::DeValue::Array(items) => {
+            entries.push((key.clone(), None));
+            for (position, item) in items.iter().enumerate() {
+                collect_toml_entries(item, format!("{}.{}", key, position), index, entries);
+            }
+        }
+        toml::de::DeValue::Table(table) => {
+            entries.push((key.clone(), None));
+            for (field, item) in table {
+                collect_toml_entries(
+                    item,
+                    format!("{}.{}", key, toml_key_segment(field.get_ref())),
+                    index,
+                    entries,
+                );
+            }
+        }
+        _ => entries.push((key, None)),
+    }
+}
+
+/// A key as written in a dotted path: bare when it can be, otherwise quoted
+fn toml_key_segment(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        format!("{:?}", key)
+    }
+}
+
+/// A fenced code block or inline code span in a Markdown document
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarkdownCode {
+    /// First word of a fenced block's info string, like `rust` in ```` ```rust ````
+    pub language: Option<String>,
+    /// Whether this is an inline `` `code` `` span rather than a fenced block
+    pub inline: bool,
+    /// 1-based lines of the code, fences included
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Byte range of the code in the document: a block's lines between its fences as written,
+    /// indentation included and without the final line break, or the text between inline backticks
+    pub content: std::ops::Range<usize>,
+}
+
+/// A fence line's marker character, its length, and the info string after it. Fences may be
+/// indented by any amount, so blocks nested in list items are found too.
+fn markdown_fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start_matches([' ', '\t']);
+    let marker = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(marker).len();
+    let info = &trimmed[len..];
+    // A backtick fence's info string can't hold backticks, or the line would be inline code
+    if len < 3 || (marker == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((marker, len, info))
+}
+
+/// Lines of the content as `(1-based number, byte offset, text without its line break)`
+fn numbered_lines(content: &str) -> impl Iterator<Item = (usize, usize, &str)> {
+    let mut offset = 0;
+    content
+        .split_inclusive('\n')
+        .enumerate()
+        .map(move |(index, raw_line)| {
+            let line = raw_line.strip_suffix('\n').unwrap_or(raw_line);
+            let line_offset = offset;
+            offset += raw_line.len();
+            (
+                index + 1,
+                line_offset,
+                line.strip_suffix('\r').unwrap_or(line),
+            )
+        })
+}
+
+/// The fenced code blocks of a Markdown document, with ```` ``` ```` or `~~~` fences. A block closes at
+/// a fence of the same character that is at least as long and has nothing after it, so a ```` ```` ````
+/// block can hold ```` ``` ```` lines. An unclosed block runs to the end of the document.
+///
+/// ```
+/// use rust_span_counter::markdown_code_blocks;
+///
+/// let source = "Intro\n\n```rust\nlet x = 1;\n```\n";
+/// let blocks = markdown_code_blocks(source);
+/// assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+/// assert_eq!((blocks[0].start_line, blocks[0].end_line), (3, 5));
+/// assert_eq!(&source[blocks[0].content.clone()], "let x = 1;");
+/// ```
+pub fn markdown_code_blocks(source: &str) -> Vec<MarkdownCode> {
+    let mut blocks = Vec::new();
+    // The open fence's marker and length, and the block it starts
+    let mut open: Option<(char, usize, MarkdownCode)> = None;
+    let mut previous_line_end = 0;
+    let mut last_line = 0;
+
+    for (number, offset, line) in numbered_lines(source) {
+        let fence = markdown_fence(line);
+        match (&mut open, fence) {
+            (None, Some((marker, len, info))) => {
+                let language = info.split_whitespace().next().map(str::to_string);
+                let content_start = source[offset..]
+                    .find('\n')
+                    .map_or(source.len(), |end| offset + end + 1);
+                let block = MarkdownCode {
+                    language,
+                    inline: false,
+                    start_line: number,
+                    end_line: number,
+                    content: content_start..content_start,
+                };
+                open = Some((marker, len, block));
+            }
+            (Some((open_marker, open_len, _)), Some((marker, len, info)))
+                if marker == *open_marker && len >= *open_len && info.trim().is_empty() =>
+            {
+                let (_, _, mut block) = open.take().expect("open fence");
+                block.end_line = number;
+                block.content.end = previous_line_end.max(block.content.start);
+                blocks.push(block);
+            }
+            _ => {}
+        }
+        previous_line_end = offset + line.len();
+        last_line = number;
+    }
+
+    if let Some((_, _, mut block)) = open {
+        block.end_line = last_line;
+        block.content.end = previous_line_end.max(block.content.start);
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// The inline code spans of a Markdown document outside its fenced blocks. A span opens with a run
+/// of backticks and closes at the next run of the same length on the same line; one space just inside
+/// both ends is stripped, as in `` ` `code` ` ``.
+///
+/// ```
+/// use rust_span_counter::markdown_inline_code;
+///
+/// let source = "Call `run()` or ``a `b` c``.";
+/// let spans: Vec<&str> = markdown_inline_code(source).into_iter().map(|code| &source[code.content]).collect();
+/// assert_eq!(spans, vec!["run()", "a `b` c"]);
+/// ```
+pub fn markdown_inline_code(source: &str) -> Vec<MarkdownCode> {
+    let blocks = markdown_code_blocks(source);
+    let mut spans = Vec::new();
+
+    for (number, offset, line) in numbered_lines(source) {
+        if blocks
+            .iter()
+            .any(|block| block.start_line <= number && number <= block.end_line)
+        {
+            continue;
+        }
+        let bytes = line.as_bytes();
+        let run_at = |start: usize| {
+            bytes[start..]
+                .iter()
+                .take_while(|&&byte| byte == b'`')
+                .count()
+        };
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] != b'`' {
+                i += 1;
+                continue;
+            }
+            let run = run_at(i);
+            let mut j = i + run;
+            // Find the closing run of exactly the same length
+            let close = loop {
+                match bytes[j..].iter().position(|&byte| byte == b'`') {
+                    Some(found) => {
+                        let other = run_at(j + found);
+                        if other == run {
+                            break Some(j + found);
+                        }
+                        j += found + other;
+                    }
+                    None => break None,
+                }
+            };
+            let Some(close) = close else {
+                i += run;
+                continue;
+            };
+
+            let (mut start, mut end) = (i + run, close);
+            let text = &line[start..end];
+            if text.len() >= 2
+                && text.starts_with(' ')
+                && text.ends_with(' ')
+                && !text.trim().is_empty()
+            {
+                start += 1;
+                end -= 1;
+            }
+            spans.push(MarkdownCode {
+                language: None,
+                inline: true,
+                start_line: number,
+                end_line: number,
+                content: offset + start..offset + end,
+            });
+            i = close + run;
+        }
+    }
+    spans
+}
+
+/// The fenced block covering `target_line`, or else the single inline code span on it
+pub fn find_markdown_code(source: &str, target_line: usize) -> Result<MarkdownCode, Error> {
+    let blocks = markdown_code_blocks(source);
+    if let Some(block) = blocks
+        .iter()
+        .find(|block| block.start_line <= target_line && target_line <= block.end_line)
+    {
+        return Ok(block.clone());
+    }
+
+    let inline = markdown_inline_code(source);
+    let mut on_line = inline.iter().filter(|code| code.start_line == target_line);
+    match (on_line.next(), on_line.next()) {
+        (Some(code), None) => Ok(code.clone()),
+        (Some(_), Some(_)) => Err(Error::MultipleStringsFound),
+        (None, _) => {
+            let lines = blocks
+                .iter()
+                .chain(&inline)
+                .flat_map(|code| code.start_line..=code.end_line)
+                .collect();
+            Err(Error::NoStringFound {
+                line: target_line,
+                nearest_lines_with_strings: nearest_lines(lines, target_line),
+            })
+        }
+    }
+}
+
+/// The value of the single string literal covering `target_line` (and `target_column`, 1-based, when given)
+pub fn find_strings_on_line(
+    file: &File,
+    target_line: usize,
+    target_column: Option<usize>,
+) -> Result<String, Error> {
+    find_literal_on_line(file, target_line, target_column).map(|literal| literal.value)
+}
+
+/// Like [`find_strings_on_line`], but returns the whole [`StringLiteralInfo`]
+pub fn find_literal_on_line(
+    file: &File,
+    target_line: usize,
+    target_column: Option<usize>,
+) -> Result<StringLiteralInfo, Error> {
+    let mut visitor = StringVisitor::new(target_line);
+    visitor.target_column = target_column;
+    visitor.visit_file(file);
+    if let Some(err) = visitor.error {
+        return Err(err);
+    }
+
+    let mut found_strings = visitor.found_strings.into_iter();
+    match (found_strings.next(), found_strings.next()) {
+        (None, _) => Err(Error::NoStringFound {
+            line: target_line,
+            nearest_lines_with_strings: nearest_lines_with_strings(file, target_line),
+        }),
+        (Some(literal), None) => Ok(literal),
+        _ => Err(Error::MultipleStringsFound),
+    }
+}
+
+/// Like [`find_strings_on_line`] with a column, but when no literal covers the 1-based `target_column`
+/// the literal starting closest after it on the line is used instead
+pub fn find_string_near_column(
+    file: &File,
+    target_line: usize,
+    target_column: usize,
+) -> Result<String, Error> {
+    find_literal_near_column(file, target_line, target_column).map(|literal| literal.value)
+}
+
+/// Like [`find_string_near_column`], but returns the whole [`StringLiteralInfo`]
+pub fn find_literal_near_column(
+    file: &File,
+    target_line: usize,
+    target_column: usize,
+) -> Result<StringLiteralInfo, Error> {
+    let mut visitor = StringVisitor::new(target_line);
+    visitor.visit_file(file);
+    if let Some(err) = visitor.error {
+        return Err(err);
+    }
+
+    let literals = visitor.found_strings;
+    let covering = literals
+        .iter()
+        .find(|literal| literal.covers(target_line, target_column));
+    let following = || {
+        literals
+            .iter()
+            .filter(|literal| {
+                literal.start_line == target_line && literal.start_column >= target_column
+            })
+            .min_by_key(|literal| literal.start_column)
+    };
+    match covering.or_else(following) {
+        Some(literal) => Ok(literal.clone()),
+        None => Err(Error::NoStringFound {
+            line: target_line,
+            nearest_lines_with_strings: nearest_lines_with_strings(file, target_line),
+        }),
+    }
+}
+
+/// The `index`th (zero-based) literal covering `target_line`, ordered by where the literals start
+pub fn find_string_by_index(
+    file: &File,
+    target_line: usize,
+    index: usize,
+) -> Result<String, Error> {
+    find_literal_by_index(file, target_line, index).map(|literal| literal.value)
+}
+
+/// Like [`find_string_by_index`], but returns the whole [`StringLiteralInfo`]
+pub fn find_literal_by_index(
+    file: &File,
+    target_line: usize,
+    index: usize,
+) -> Result<StringLiteralInfo, Error> {
+    let mut visitor = StringVisitor::new(target_line);
+    visitor.visit_file(file);
+    if let Some(err) = visitor.error {
+        return Err(err);
+    }
+
+    let mut literals = visitor.found_strings;
+    sort_by_start(&mut literals);
+    let found = literals.len();
+    literals
+        .into_iter()
+        .nth(index)
+        .ok_or(Error::LiteralIndexOutOfRange {
+            line: target_line,
+            index,
+            found,
+        })
+}
+
+/// Every literal covering `target_line`, ordered by where the literals start
+pub fn find_strings_on_line_all(
+    file: &File,
+    target_line: usize,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    let mut visitor = StringVisitor::new(target_line);
+    visitor.visit_file(file);
+    if let Some(err) = visitor.error {
+        return Err(err);
+    }
+
+    let mut literals = visitor.found_strings;
+    if literals.is_empty() {
+        return Err(Error::NoStringFound {
+            line: target_line,
+            nearest_lines_with_strings: nearest_lines_with_strings(file, target_line),
+        });
+    }
+    sort_by_start(&mut literals);
+    Ok(literals)
+}
+
+/// Parse Rust source and return every literal found by [`find_strings_on_line_all`]
+pub fn find_strings_on_line_all_in_source(
+    source: &str,
+    target_line: usize,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_strings_on_line_all(&file, target_line)
+}
+
+/// Every literal covering any line from `start_line` through `end_line`, ordered by where the literals start.
+/// Fails with [`Error::InvalidLineNumber`] if the range is reversed.
+pub fn find_strings_in_range(
+    file: &File,
+    start_line: usize,
+    end_line: usize,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    if end_line < start_line {
+        return Err(Error::InvalidLineNumber(format!(
+            "{}-{}",
+            start_line, end_line
+        )));
+    }
+
+    let mut visitor = StringVisitor::in_range(start_line, end_line);
+    visitor.visit_file(file);
+    if let Some(err) = visitor.error {
+        return Err(err);
+    }
+
+    let mut literals = visitor.found_strings;
+    if literals.is_empty() {
+        return Err(Error::NoStringFound {
+            line: start_line,
+            nearest_lines_with_strings: nearest_lines_with_strings(file, start_line),
+        });
+    }
+    sort_by_start(&mut literals);
+    Ok(literals)
+}
+
+/// Parse Rust source and return every literal found by [`find_strings_in_range`]
+pub fn find_strings_in_range_in_source(
+    source: &str,
+    start_line: usize,
+    end_line: usize,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_strings_in_range(&file, start_line, end_line)
+}
+
+/// Parse Rust source and return the literal selected by [`find_literal_on_line`]
+pub fn find_literal_in_source(
+    source: &str,
+    target_line: usize,
+    target_column: Option<usize>,
+) -> Result<StringLiteralInfo, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_literal_on_line(&file, target_line, target_column)
+}
+
+/// Parse Rust source and return the literal selected by [`find_literal_near_column`]
+pub fn find_literal_near_column_in_source(
+    source: &str,
+    target_line: usize,
+    target_column: usize,
+) -> Result<StringLiteralInfo, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_literal_near_column(&file, target_line, target_column)
+}
+
+/// Parse Rust source and return the literal selected by [`find_literal_by_index`]
+pub fn find_literal_by_index_in_source(
+    source: &str,
+    target_line: usize,
+    index: usize,
+) -> Result<StringLiteralInfo, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_literal_by_index(&file, target_line, index)
+}
+
+/// Look up the single literal on each of the given lines with [`find_literal_on_line`],
+/// returning `(line, literal)` pairs in the order the lines were requested
+pub fn find_literals_on_lines(
+    file: &File,
+    target_lines: &[usize],
+) -> Result<Vec<(usize, StringLiteralInfo)>, Error> {
+    target_lines
+        .iter()
+        .map(|&line| find_literal_on_line(file, line, None).map(|literal| (line, literal)))
+        .collect()
+}
+
+/// The string literal bound to `name` by `let NAME = "..."`, `const NAME: &str = "..."`, `static NAME`,
+/// or a struct field `NAME: "..."`. With `target_line`, the literal must also cover that line.
+pub fn find_literal_by_name(
+    file: &File,
+    name: &str,
+    target_line: Option<usize>,
+) -> Result<StringLiteralInfo, Error> {
+    let literals = match target_line {
+        Some(line) => StringVisitor::new(line),
+        None => StringVisitor::all(),
+    };
+    let mut visitor = BindingVisitor { name, literals };
+    visitor.visit_file(file);
+    if let Some(err) = visitor.literals.error {
+        return Err(err);
+    }
+    visitor.literals.fall_back_to_chars();
+
+    let mut literals = visitor.literals.found_strings;
+    sort_by_start(&mut literals);
+    if literals.len() > 1 {
+        let lines = literals.iter().map(|literal| literal.start_line).collect();
+        return Err(Error::AmbiguousName {
+            name: name.to_string(),
+            lines,
+        });
+    }
+    literals.pop().ok_or_else(|| Error::NameNotFound {
+        name: name.to_string(),
+        line: target_line,
+    })
+}
+
+/// Parse Rust source once and return every literal found by [`find_literals_on_lines`]
+pub fn find_literals_on_lines_in_source(
+    source: &str,
+    target_lines: &[usize],
+) -> Result<Vec<(usize, StringLiteralInfo)>, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_literals_on_lines(&file, target_lines)
+}
+
+/// Parse Rust source, falling back to lexing it when it isn't valid Rust, e.g. mid-edit. The fallback
+/// wraps the tokens in a single [`Item::Verbatim`] whose literals the `find_*` functions still visit,
+/// so a syntax error elsewhere doesn't hide them; the flag is `true` when it was used. Source that
+/// doesn't even lex, such as an unclosed delimiter, fails with the original parse error.
+///
+/// ```
+/// use rust_span_counter::{find_literal_on_line, parse_source_lenient};
+///
+/// let (file, lexed) = parse_source_lenient("fn main() {\n    let s = \"fine\";\n    let x = ;\n}\n").unwrap();
+/// assert!(lexed);
+/// assert_eq!(find_literal_on_line(&file, 2, None).unwrap().value, "fine");
+/// ```
+pub fn parse_source_lenient(source: &str) -> Result<(File, bool), Error> {
+    let err = match syn::parse_file(source) {
+        Ok(file) => return Ok((file, false)),
+        Err(err) => err,
+    };
+    // Like `syn::parse_file`, leave out a BOM and a shebang line, but keep the line break after the
+    // shebang so line numbers still match the file
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let (shebang, source) = match source.strip_prefix("#!") {
+        Some(rest) if !rest.trim_start().starts_with('[') => {
+            let end = source.find('\n').unwrap_or(source.len());
+            (Some(source[..end].to_string()), &source[end..])
+        }
+        _ => (None, source),
+    };
+    let tokens: TokenStream = source.parse().map_err(|_| Error::ParseError(err))?;
+    let file = File {
+        shebang,
+        attrs: Vec::new(),
+        items: vec![Item::Verbatim(tokens)],
+    };
+    Ok((file, true))
+}
+
+/// Whether these attributes mark an item as test code: a `#[test]` attribute, including path forms
+/// like `#[tokio::test]`, or a `#[cfg(...)]` that only holds under `test`, like `cfg(all(test, unix))`
+pub fn is_test_code(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        if attr.path().is_ident("cfg") {
+            attr.parse_args::<Meta>()
+                .is_ok_and(|predicate| cfg_requires_test(&predicate))
+        } else {
+            attr.path()
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "test")
+        }
+    })
+}
+
+/// Whether a `cfg` predicate can only be true in test builds
+fn cfg_requires_test(predicate: &Meta) -> bool {
+    let Meta::List(list) = predicate else {
+        return predicate.path().is_ident("test");
+    };
+    let Ok(nested) = list.parse_args_with(Punctuated::<Meta, syn::Token![,]>::parse_terminated)
+    else {
+        return false;
+    };
+    if list.path.is_ident("all") {
+        nested.iter().any(cfg_requires_test)
+    } else if list.path.is_ident("any") {
+        !nested.is_empty() && nested.iter().all(cfg_requires_test)
+    } else {
+        // `not(test)` and unknown predicates don't make code test-only
+        false
+    }
+}
+
+/// Drop test code from a parsed file, or everything but test code, before looking up literals.
+/// Test code is any item [`is_test_code`] accepts, along with everything inside it; `impl` blocks
+/// and inline modules are searched for test items too. A file whose inner attributes mark it as
+/// test code, like `#![cfg(test)]`, is kept or emptied as a whole.
+///
+/// ```
+/// use rust_span_counter::{find_string_literals, retain_test_code, TestCode};
+///
+/// let source = "const A: &str = \"app\";\n#[cfg(test)]\nmod tests {\n    const B: &str = \"test\";\n}\n";
+/// let mut file = syn::parse_file(source).unwrap();
+/// retain_test_code(&mut file, TestCode::Skip);
+/// assert_eq!(find_string_literals(&file).unwrap()[0].value, "app");
+/// ```
+pub fn retain_test_code(file: &mut File, tests: TestCode) {
+    let whole_file = is_test_code(&file.attrs);
+    match tests {
+        TestCode::Include => {}
+        TestCode::Skip if whole_file => {
+            file.attrs.clear();
+            file.items.clear();
+        }
+        TestCode::Only if whole_file => {}
+        TestCode::Skip => skip_test_items(&mut file.items),
+        TestCode::Only => {
+            file.attrs.clear();
+            only_test_items(&mut file.items);
+        }
+    }
+}
+
+fn skip_test_items(items: &mut Vec<Item>) {
+    items.retain(|item| !is_test_code(item_attrs(item)));
+    for item in items {
+        match item {
+            Item::Mod(module) => {
+                if let Some((_, items)) = &mut module.content {
+                    skip_test_items(items);
+                }
+            }
+            Item::Impl(item_impl) => {
+                item_impl.items.retain(|item| !matches!(item, syn::ImplItem::Fn(method) if is_test_code(&method.attrs)));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Keep test items whole, and the modules and `impl` blocks that contain some, without their own attributes
+fn only_test_items(items: &mut Vec<Item>) {
+    items.retain_mut(|item| {
+        if is_test_code(item_attrs(item)) {
+            return true;
+        }
+        match item {
+            Item::Mod(module) => {
+                module.attrs.clear();
+                match &mut module.content {
+                    Some((_, items)) => {
+                        only_test_items(items);
+                        !items.is_empty()
+                    }
+                    None => false,
+                }
+            }
+            Item::Impl(item_impl) => {
+                item_impl.attrs.clear();
+                item_impl.items.retain(
+                    |item| matches!(item, syn::ImplItem::Fn(method) if is_test_code(&method.attrs)),
+                );
+                !item_impl.items.is_empty()
+            }
+            _ => false,
+        }
+    });
+}
+
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+/// Parse Rust source and return every literal found by [`find_string_literals`]
+pub fn find_string_literals_in_source(source: &str) -> Result<Vec<StringLiteralInfo>, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_string_literals(&file)
+}
+
+/// Parse Rust source and return the literal selected by [`find_string_by_index`]
+pub fn find_string_by_index_in_source(
+    source: &str,
+    target_line: usize,
+    index: usize,
+) -> Result<String, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_string_by_index(&file, target_line, index)
+}
+
+/// Parse Rust source and return the literal selected by [`find_string_near_column`]
+pub fn find_string_near_column_in_source(
+    source: &str,
+    target_line: usize,
+    target_column: usize,
+) -> Result<String, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_string_near_column(&file, target_line, target_column)
+}
+
+/// Up to three lines closest to `target_line` that hold part of a string literal, in ascending order
+fn nearest_lines_with_strings(file: &File, target_line: usize) -> Vec<usize> {
+    let mut visitor = StringVisitor::all();
+    visitor.visit_file(file);
+
+    let lines = visitor
+        .found_strings
+        .iter()
+        .chain(&visitor.found_chars)
+        .flat_map(|literal| literal.start_line..=literal.end_line)
+        .collect();
+    nearest_lines(lines, target_line)
+}
+
+/// Up to three of `lines` closest to `target_line`, in ascending order
+fn nearest_lines(mut lines: Vec<usize>, target_line: usize) -> Vec<usize> {
+    lines.sort_by_key(|&line| (line.abs_diff(target_line), line));
+    lines.dedup();
+    lines.truncate(3);
+    lines.sort_unstable();
+    lines
+}
+
+/// Parse Rust source and return the value of the single string literal covering the given line (and column)
+pub fn find_string_in_source(
+    source: &str,
+    target_line: usize,
+    target_column: Option<usize>,
+) -> Result<String, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_strings_on_line(&file, target_line, target_column)
+}
+
+/// Parse Rust source once and look up the single string literal on each of the given lines,
+/// returning `(line, value)` pairs in the order the lines were requested
+pub fn find_strings_on_lines_in_source(
+    source: &str,
+    target_lines: &[usize],
+) -> Result<Vec<(usize, String)>, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    target_lines
+        .iter()
+        .map(|&line| find_strings_on_line(&file, line, None).map(|value| (line, value)))
+        .collect()
+}
+
+/// Parse Rust source and return every string literal as `(starting line, value)` pairs
+pub fn find_all_strings_in_source(source: &str) -> Result<Vec<(usize, String)>, Error> {
+    let file = syn::parse_file(source).map_err(Error::ParseError)?;
+    find_all_strings(&file)
+}
+
+/// Every string literal in the file as `(starting line, value)` pairs, in source order
+pub fn find_all_strings(file: &File) -> Result<Vec<(usize, String)>, Error> {
+    Ok(find_string_literals(file)?
+        .into_iter()
+        .map(|literal| (literal.start_line, literal.value))
+        .collect())
+}
+
+/// A string literal's decoded value and where it sits in the source.
+/// Lines are 1-based; columns are 0-based character offsets as reported by proc-macro2,
+/// with `end_column` pointing just past the closing quote.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StringLiteralInfo {
+    pub value: String,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    /// Whether the literal was written in raw form (`r"..."`, `br#"..."#`, ...)
+    pub raw: bool,
+    /// The literal as written in the source, prefix and quotes included
+    pub token: String,
+    pub kind: LiteralKind,
+    /// Whether invalid UTF-8 in a byte string was replaced with U+FFFD to produce `value`
+    pub lossy: bool,
+}
+
+impl StringLiteralInfo {
+    /// Whether the literal covers the 1-based `column` on `line`
+    fn covers(&self, line: usize, column: usize) -> bool {
+        (line > self.start_line || (line == self.start_line && column > self.start_column))
+            && (line < self.end_line || (line == self.end_line && column <= self.end_column))
+    }
+
+    /// The text between the quotes exactly as written, escapes included, with the prefix, quotes, and
+    /// any `#`s stripped. Doc comments have no quotes to strip and give their value instead.
+    pub fn source_text(&self) -> &str {
+        if self.kind == LiteralKind::Doc {
+            return &self.value;
+        }
+        let token = self
+            .token
+            .trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        let hashes = token.len() - token.trim_start_matches('#').len();
+        &token[hashes + 1..token.len() - hashes - 1]
+    }
+
+    /// Byte offsets of the literal as written in `content`, the source it was parsed from: from its
+    /// prefix or opening quote to just past its closing quote. Span columns count characters, so
+    /// multi-byte characters earlier on a line are converted; a leading byte order mark is counted.
+    /// `None` when the literal's lines aren't in `content`.
+    ///
+    /// ```
+    /// use rust_span_counter::find_string_literals;
+    ///
+    /// let content = "const É: &str = \"é\";";
+    /// let literal = &find_string_literals(&syn::parse_file(content).unwrap()).unwrap()[0];
+    /// let range = literal.file_byte_range(content).unwrap();
+    /// assert_eq!(range, 17..21);
+    /// assert_eq!(&content[range], "\"é\"");
+    /// ```
+    pub fn file_byte_range(&self, content: &str) -> Option<std::ops::Range<usize>> {
+        // syn strips a byte order mark before lexing, so columns on the first line don't count it
+        let bom = if content.starts_with('\u{feff}') {
+            '\u{feff}'.len_utf8()
+        } else {
+            0
+        };
+        let index = LineIndex::new(&content[bom..]);
+        let start = index.offset(self.start_line, self.start_column + 1)?;
+        let end = index.offset(self.end_line, self.end_column + 1)?;
+        Some(start + bom..end + bom)
+    }
+
+    /// Byte offset in `content`, the source the literal was parsed from, of every byte offset of the
+    /// value and of its end, following escapes and line continuations like [`Self::source_positions`].
+    /// An escape's bytes all map to where it starts and the value's end maps to the closing quote.
+    /// `None` when the literal's lines aren't in `content`.
+    ///
+    /// ```
+    /// use rust_span_counter::find_string_literals;
+    ///
+    /// let content = "const S: &str = \"a\\tb\";";
+    /// let literal = &find_string_literals(&syn::parse_file(content).unwrap()).unwrap()[0];
+    /// assert_eq!(literal.file_offsets(content).unwrap(), vec![17, 18, 20, 21]);
+    /// ```
+    pub fn file_offsets(&self, content: &str) -> Option<Vec<usize>> {
+        let bom = if content.starts_with('\u{feff}') {
+            '\u{feff}'.len_utf8()
+        } else {
+            0
+        };
+        let index = LineIndex::new(&content[bom..]);
+        self.source_positions()
+            .positions
+            .iter()
+            .map(|&(line, column)| index.offset(line, column).map(|offset| offset + bom))
+            .collect()
+    }
+
+    /// The kind's name from [`LiteralKind::as_str`], prefixed with `raw_` for raw literals such as `br"..."`
+    pub fn kind_label(&self) -> String {
+        if self.raw && self.kind != LiteralKind::Doc {
+            format!("raw_{}", self.kind.as_str())
+        } else {
+            self.kind.as_str().to_string()
+        }
+    }
+
+    /// Fail with [`Error::NonUtf8Literal`] if `value` needed lossy decoding
+    pub fn check_utf8(&self) -> Result<(), Error> {
+        if self.lossy {
+            Err(Error::NonUtf8Literal(self.start_line))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Map byte offsets in [`StringLiteralInfo::value`] back to where they were written in the source.
+    /// Escapes map to their backslash, and line breaks inside the literal advance the line.
+    ///
+    /// ```
+    /// use rust_span_counter::find_string_literals;
+    ///
+    /// let file = syn::parse_file("const S: &str = \"a\\tb\n c\";").unwrap();
+    /// let positions = find_string_literals(&file).unwrap()[0].source_positions();
+    /// assert_eq!(positions.position(1), (1, 19)); // the `\t` escape
+    /// assert_eq!(positions.position(3), (1, 22)); // the line break
+    /// assert_eq!(positions.position(5), (2, 2));  // `c`
+    /// ```
+    pub fn source_positions(&self) -> SourcePositions {
+        if let Some(positions) = self.doc_comment_positions() {
+            return positions;
+        }
+
+        let mut line = self.start_line;
+        let mut column = self.start_column + 1;
+        let mut advance = |c: char| -> (usize, usize) {
+            let position = (line, column);
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+            position
+        };
+
+        // Step over the prefix and opening quote, remembering how many hashes close a raw literal
+        let mut chars = self.token.chars();
+        let mut hashes = 0;
+        for c in chars.by_ref() {
+            advance(c);
+            if c == '"' || c == '\'' {
+                break;
+            }
+            if c == '#' {
+                hashes += 1;
+            }
+        }
+        let body: Vec<char> = chars.collect();
+        let body = &body[..body.len().saturating_sub(1 + hashes)];
+
+        // Decoded bytes with the source position each came from
+        let mut bytes = Vec::new();
+        let mut byte_positions = Vec::new();
+        let mut push = |decoded: &[u8], position: (usize, usize)| {
+            bytes.extend_from_slice(decoded);
+            byte_positions.extend(std::iter::repeat_n(position, decoded.len()));
+        };
+        let mut buf = [0; 4];
+        let mut body = body.iter().copied().peekable();
+        while let Some(c) = body.next() {
+            let position = advance(c);
+            if self.raw || (c != '\\' && c != '\r') {
+                push(c.encode_utf8(&mut buf).as_bytes(), position);
+                continue;
+            }
+            if c == '\r' {
+                // A CRLF line ending reads as a single `\n`
+                if body.next_if_eq(&'\n').is_some() {
+                    advance('\n');
+                }
+                push(b"\n", position);
+                continue;
+            }
+
+            let Some(escape) = body.next() else { break };
+            advance(escape);
+            match escape {
+                // Line continuation: the break and the next line's leading whitespace are dropped
+                '\n' => {
+                    while let Some(c) = body.next_if(|c| c.is_whitespace()) {
+                        advance(c);
+                    }
+                }
+                'x' => {
+                    let digits: String = (0..2)
+                        .filter_map(|_| body.next())
+                        .inspect(|&c| {
+                            advance(c);
+                        })
+                        .collect();
+                    push(&[u8::from_str_radix(&digits, 16).unwrap_or(0)], position);
+                }
+                'u' => {
+                    let mut digits = String::new();
+                    for c in body.by_ref() {
+                        advance(c);
+                        match c {
+                            '{' | '_' => {}
+                            '}' => break,
+                            _ => digits.push(c),
+                        }
+                    }
+                    let decoded = u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER);
+                    push(decoded.encode_utf8(&mut buf).as_bytes(), position);
+                }
+                'n' => push(b"\n", position),
+                'r' => push(b"\r", position),
+                't' => push(b"\t", position),
+                '0' => push(b"\0", position),
+                other => push(other.encode_utf8(&mut buf).as_bytes(), position),
+            }
+        }
+
+        // Byte strings that aren't valid UTF-8 were decoded lossily, so each invalid run became U+FFFD
+        let mut positions = Vec::with_capacity(bytes.len() + 1);
+        let mut offset = 0;
+        for chunk in bytes.utf8_chunks() {
+            positions.extend_from_slice(&byte_positions[offset..offset + chunk.valid().len()]);
+            offset += chunk.valid().len();
+            if !chunk.invalid().is_empty() {
+                positions.extend(std::iter::repeat_n(
+                    byte_positions[offset],
+                    char::REPLACEMENT_CHARACTER.len_utf8(),
+                ));
+                offset += chunk.invalid().len();
+            }
+        }
+        // The end of the value maps to the closing quote
+        positions.push((line, column));
+
+        SourcePositions { positions }
+    }
+}
+
+impl StringLiteralInfo {
+    /// Positions for a doc comment, whose text is written as is after its `///`, `//!`, `/**`, or `/*!`;
+    /// each line of a `///` block has its own prefix and indentation
+    fn doc_comment_positions(&self) -> Option<SourcePositions> {
+        if self.kind != LiteralKind::Doc || !self.token.starts_with('/') {
+            return None;
+        }
+        let line_comment = self.token.starts_with("//");
+        let body = if line_comment {
+            &self.token[3..]
+        } else {
+            &self.token[3..self.token.len() - 2]
+        };
+
+        let mut positions = Vec::with_capacity(self.value.len() + 1);
+        let (mut line, mut column) = (self.start_line, self.start_column + 4);
+        for (index, segment) in body.split('\n').enumerate() {
+            let mut segment = segment;
+            if index > 0 {
+                positions.push((line, column));
+                line += 1;
+                column = 1;
+                if line_comment {
+                    let indent = segment.len() - segment.trim_start_matches(' ').len();
+                    segment = &segment[indent + 3..];
+                    column += indent + 3;
+                }
+            }
+            for c in segment.chars() {
+                positions.extend(std::iter::repeat_n((line, column), c.len_utf8()));
+                column += 1;
+            }
+        }
+        positions.push((line, column));
+
+        Some(SourcePositions { positions })
+    }
+
+    /// For a block of `///` or `//!` lines, just the comment on `line`, as if it stood alone.
+    /// `None` for other literals, or when the block doesn't cover `line`.
+    pub fn doc_comment_line(&self, line: usize) -> Option<StringLiteralInfo> {
+        if self.kind != LiteralKind::Doc
+            || !self.token.starts_with("//")
+            || line < self.start_line
+            || line > self.end_line
+        {
+            return None;
+        }
+        let index = line - self.start_line;
+        let value = self.value.split('\n').nth(index)?.to_string();
+        // Later lines of the block token are indented to their comment's column
+        let written = self.token.split('\n').nth(index)?;
+        let token = written.trim_start_matches(' ').to_string();
+        let start_column = if index == 0 {
+            self.start_column
+        } else {
+            written.len() - token.len()
+        };
+        Some(StringLiteralInfo {
+            end_column: start_column + token.chars().count(),
+            value,
+            start_line: line,
+            start_column,
+            end_line: line,
+            token,
+            ..self.clone()
+        })
+    }
+}
+
+/// 1-based `(line, column)` source positions for each byte offset of a literal's value, with columns
+/// counted in characters like [`LineIndex`]; created by [`StringLiteralInfo::source_positions`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourcePositions {
+    positions: Vec<(usize, usize)>,
+}
+
+impl SourcePositions {
+    /// Position of the character containing byte `offset`; offsets past the value map to the closing quote
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        self.positions
+            .get(offset)
+            .or(self.positions.last())
+            .copied()
+            .unwrap_or((0, 0))
+    }
+}
+
+/// Every literal inside the function, method, or trait method named `fn_name`, including nested
+/// closures and functions, ordered by where the literals start. Several functions with the name,
+/// like methods of different types, are all searched.
+pub fn find_string_literals_in_fn(
+    file: &File,
+    fn_name: &str,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    let mut visitor = StringVisitor {
+        target_fn: Some(fn_name.to_string()),
+        ..StringVisitor::all()
+    };
+    visitor.visit_file(file);
+    if let Some(err) = visitor.error {
+        return Err(err);
+    }
+    if !visitor.found_fn {
+        return Err(Error::FunctionNotFound(fn_name.to_string()));
+    }
+
+    let mut literals = visitor.found_strings;
+    sort_by_start(&mut literals);
+    Ok(literals)
+}
+
+/// Like [`find_literal_on_line`], but only literals inside the function named `fn_name` count, so the
+/// nearest lines reported when none matches are lines in that function
+pub fn find_literal_on_line_in_fn(
+    file: &File,
+    target_line: usize,
+    target_column: Option<usize>,
+    fn_name: &str,
+) -> Result<StringLiteralInfo, Error> {
+    let in_fn = find_string_literals_in_fn(file, fn_name)?;
+    let mut visitor = StringVisitor {
+        target_fn: Some(fn_name.to_string()),
+        target_column,
+        ..StringVisitor::new(target_line)
+    };
+    visitor.visit_file(file);
+    if let Some(err) = visitor.error {
+        return Err(err);
+    }
+
+    let mut found_strings = visitor.found_strings.into_iter();
+    match (found_strings.next(), found_strings.next()) {
+        (None, _) => {
+            let lines = in_fn
+                .iter()
+                .flat_map(|literal| literal.start_line..=literal.end_line)
+                .collect();
+            Err(Error::NoStringFound {
+                line: target_line,
+                nearest_lines_with_strings: nearest_lines(lines, target_line),
+            })
+        }
+        (Some(literal), None) => Ok(literal),
+        _ => Err(Error::MultipleStringsFound),
+    }
+}
+
+/// Every string, byte string, C string, and char literal in the file, in source order
+pub fn find_string_literals(file: &File) -> Result<Vec<StringLiteralInfo>, Error> {
+    let mut visitor = StringVisitor::all();
+    visitor.visit_file(file);
+    match visitor.error {
+        Some(err) => Err(err),
+        None => {
+            let mut literals = visitor.found_strings;
+            sort_by_start(&mut literals);
+            Ok(literals)
+        }
+    }
+}
+
+/// Visiting mostly yields literals in source order, but macro and attribute traversal can differ
+fn sort_by_start(literals: &mut [StringLiteralInfo]) {
+    literals.sort_by_key(|literal| (literal.start_line, literal.start_column));
+}
+
+/// The literals from [`find_string_literals`] that span the given line
+pub fn find_on_line(literals: &[StringLiteralInfo], line: usize) -> Vec<&StringLiteralInfo> {
+    literals
+        .iter()
+        .filter(|literal| literal.start_line <= line && line <= literal.end_line)
+        .collect()
+}
+
+struct StringVisitor {
+    /// Only collect literals covering this line; `None` collects every literal
+    target_line: Option<usize>,
+    /// When set, literals covering any line from `target_line` through this one are collected
+    target_end_line: Option<usize>,
+    /// When set, literals must also cover this 1-based column on the target line
+    target_column: Option<usize>,
+    /// When set, only literals inside a function, method, or trait method with this name are collected
+    target_fn: Option<String>,
+    /// Names of the functions enclosing the current position, innermost last
+    fn_stack: Vec<String>,
+    /// Whether a function named `target_fn` was seen
+    found_fn: bool,
+    found_strings: Vec<StringLiteralInfo>,
+    /// Char literals, which only count for a targeted lookup that found no other literal
+    found_chars: Vec<StringLiteralInfo>,
+    /// Consecutive `///` or `//!` lines read so far, combined into one literal
+    doc_block: Option<StringLiteralInfo>,
+    /// Whether `doc_block` started inside the target function; the block is finished after leaving it
+    doc_block_in_fn: bool,
+    /// First literal that matched but could not be decoded
+    error: Option<Error>,
+}
+
+impl StringVisitor {
+    fn new(target_line: usize) -> Self {
+        Self {
+            target_line: Some(target_line),
+            target_end_line: None,
+            target_column: None,
+            target_fn: None,
+            fn_stack: Vec::new(),
+            found_fn: false,
+            found_strings: Vec::new(),
+            found_chars: Vec::new(),
+            doc_block: None,
+            doc_block_in_fn: false,
+            error: None,
+        }
+    }
+
+    fn all() -> Self {
+        Self {
+            target_line: None,
+            target_end_line: None,
+            target_column: None,
+            target_fn: None,
+            fn_stack: Vec::new(),
+            found_fn: false,
+            found_strings: Vec::new(),
+            found_chars: Vec::new(),
+            doc_block: None,
+            doc_block_in_fn: false,
+            error: None,
+        }
+    }
+
+    fn in_range(start_line: usize, end_line: usize) -> Self {
+        Self {
+            target_end_line: Some(end_line),
+            ..Self::new(start_line)
+        }
+    }
+
+    fn covers_target(&self, start: LineColumn, end: LineColumn) -> bool {
+        self.in_target_fn() && self.covers_lines(start, end)
+    }
+
+    fn in_target_fn(&self) -> bool {
+        match &self.target_fn {
+            Some(target_fn) => self.fn_stack.contains(target_fn),
+            None => true,
+        }
+    }
+
+    /// Visit a function's signature and body with its name on the stack. Its attributes, doc
+    /// comments included, are outside it.
+    fn visit_fn(&mut self, attrs: &[Attribute], sig: &Signature, block: Option<&Block>) {
+        attrs.iter().for_each(|attr| self.visit_attribute(attr));
+        let name = sig.ident.to_string();
+        self.found_fn |= self.target_fn.as_ref() == Some(&name);
+        self.fn_stack.push(name);
+        self.visit_signature(sig);
+        if let Some(block) = block {
+            self.visit_block(block);
+        }
+        self.fn_stack.pop();
+    }
+
+    fn covers_lines(&self, start: LineColumn, end: LineColumn) -> bool {
+        let Some(target_line) = self.target_line else {
+            return true;
+        };
+        if self.target_end_line.unwrap_or(target_line) < start.line || target_line > end.line {
+            return false;
+        }
+
+        // proc-macro2 columns are 0-based with an exclusive end, so a literal starting at
+        // column 4 covers 1-based columns 5 through `end.column`
+        match self.target_column {
+            Some(target_column) => {
+                (target_line > start.line || target_column > start.column)
+                    && (target_line < end.line || target_column <= end.column)
+            }
+            None => true,
+        }
+    }
+
+    fn push(&mut self, span: Span, token: &Literal, kind: LiteralKind, value: String, lossy: bool) {
+        let literal = Self::literal_info(span, token, kind, value, lossy);
+        self.found_strings.push(literal);
+    }
+
+    fn literal_info(
+        span: Span,
+        token: &Literal,
+        kind: LiteralKind,
+        value: String,
+        lossy: bool,
+    ) -> StringLiteralInfo {
+        let (start, end) = (span.start(), span.end());
+        // Raw literals start with `r`, after an optional `b` or `c` prefix
+        let token = token.to_string();
+        let raw = token.trim_start_matches(['b', 'c']).starts_with('r');
+        StringLiteralInfo {
+            value,
+            start_line: start.line,
+            start_column: start.column,
+            end_line: end.line,
+            end_column: end.column,
+            raw,
+            token,
+            kind,
+            lossy,
+        }
+    }
+
+    /// Use the char literals found when nothing else matched, so a char is only picked on a line or
+    /// column without a string
+    fn fall_back_to_chars(&mut self) {
+        if self.found_strings.is_empty() {
+            self.found_strings = std::mem::take(&mut self.found_chars);
+        }
+    }
+
+    /// Add a doc comment to the current block if it continues it on the next line, otherwise
+    /// finish the block and start a new one. Block comments are never combined.
+    fn push_doc_comment(&mut self, comment: StringLiteralInfo) {
+        if let Some(block) = &mut self.doc_block {
+            if block.token.starts_with(&comment.token[..3])
+                && !comment.token.starts_with("/*")
+                && block.end_line + 1 == comment.start_line
+            {
+                block.value = format!("{}\n{}", block.value, comment.value);
+                block.token = format!(
+                    "{}\n{}{}",
+                    block.token,
+                    " ".repeat(comment.start_column),
+                    comment.token
+                );
+                block.end_line = comment.end_line;
+                block.end_column = comment.end_column;
+                return;
+            }
+        }
+        self.finish_doc_block();
+        self.doc_block = Some(comment);
+        self.doc_block_in_fn = self.in_target_fn();
+    }
+
+    fn finish_doc_block(&mut self) {
+        let Some(block) = self.doc_block.take() else {
+            return;
+        };
+        let start = LineColumn {
+            line: block.start_line,
+            column: block.start_column,
+        };
+        let end = LineColumn {
+            line: block.end_line,
+            column: block.end_column,
+        };
+        if self.doc_block_in_fn && self.covers_lines(start, end) {
+            self.found_strings.push(block);
+        }
+    }
+
+    /// Visit the literals in a macro's unparsed tokens, including those of nested macros. Attributes
+    /// among the tokens are visited as such, so doc comments keep their kind.
+    fn visit_tokens(&mut self, tokens: TokenStream) {
+        let trees: Vec<TokenTree> = tokens.into_iter().collect();
+        let mut i = 0;
+        while i < trees.len() {
+            if let Some(len) = attribute_len(&trees[i..]) {
+                let attr_tokens: TokenStream = trees[i..i + len].iter().cloned().collect();
+                let parser = if len == 3 {
+                    Attribute::parse_inner
+                } else {
+                    Attribute::parse_outer
+                };
+                if let Ok(attrs) = parser.parse2(attr_tokens) {
+                    attrs.iter().for_each(|attr| self.visit_attribute(attr));
+                    i += len;
+                    continue;
+                }
+            }
+
+            match &trees[i] {
+                TokenTree::Group(group) => self.visit_tokens(group.stream()),
+                TokenTree::Literal(literal) => self.visit_lit(&Lit::new(literal.clone())),
+                TokenTree::Ident(_) | TokenTree::Punct(_) => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Collects the literals that initialize bindings named `name`, leaving every other literal alone
+struct BindingVisitor<'a> {
+    name: &'a str,
+    literals: StringVisitor,
+}
+
+impl BindingVisitor<'_> {
+    fn visit_binding(&mut self, ident: &Ident, expr: &Expr) {
+        if let Expr::Lit(ExprLit { lit, .. }) = expr {
+            if ident == self.name {
+                self.literals.visit_lit(lit);
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for BindingVisitor<'_> {
+    fn visit_local(&mut self, local: &'ast Local) {
+        // `let name = ...` and `let name: &str = ...`
+        let pat = match &local.pat {
+            Pat::Type(pat_type) => &*pat_type.pat,
+            pat => pat,
+        };
+        if let (Pat::Ident(pat_ident), Some(init)) = (pat, &local.init) {
+            self.visit_binding(&pat_ident.ident, &init.expr);
+        }
+        syn::visit::visit_local(self, local);
+    }
+
+    fn visit_item_const(&mut self, item: &'ast ItemConst) {
+        self.visit_binding(&item.ident, &item.expr);
+        syn::visit::visit_item_const(self, item);
+    }
+
+    fn visit_item_static(&mut self, item: &'ast ItemStatic) {
+        self.visit_binding(&item.ident, &item.expr);
+        syn::visit::visit_item_static(self, item);
+    }
+
+    fn visit_impl_item_const(&mut self, item: &'ast ImplItemConst) {
+        self.visit_binding(&item.ident, &item.expr);
+        syn::visit::visit_impl_item_const(self, item);
+    }
+
+    fn visit_field_value(&mut self, field: &'ast FieldValue) {
+        if let Member::Named(ident) = &field.member {
+            self.visit_binding(ident, &field.expr);
+        }
+        syn::visit::visit_field_value(self, field);
+    }
+}
+
+/// Number of tokens in the `#[...]` or `#![...]` attribute the trees start with, if they start with one
+fn attribute_len(trees: &[TokenTree]) -> Option<usize> {
+    let TokenTree::Punct(pound) = trees.first()? else {
+        return None;
+    };
+    let bang = matches!(trees.get(1), Some(TokenTree::Punct(punct)) if punct.as_char() == '!');
+    let len = if bang { 3 } else { 2 };
+    match trees.get(len - 1)? {
+        TokenTree::Group(group)
+            if pound.as_char() == '#' && group.delimiter() == Delimiter::Bracket =>
+        {
+            Some(len)
+        }
+        _ => None,
+    }
+}
+
+impl<'ast> Visit<'ast> for StringVisitor {
+    fn visit_file(&mut self, file: &'ast File) {
+        syn::visit::visit_file(self, file);
+        self.finish_doc_block();
+        // Whole-file listings leave char literals out
+        if self.target_line.is_some() {
+            self.fall_back_to_chars();
+        }
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast ItemFn) {
+        self.visit_fn(&item.attrs, &item.sig, Some(&item.block));
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast ImplItemFn) {
+        self.visit_fn(&item.attrs, &item.sig, Some(&item.block));
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast TraitItemFn) {
+        self.visit_fn(&item.attrs, &item.sig, item.default.as_ref());
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        match item {
+            // Tokens syn couldn't parse into an item, including the whole file from `parse_source_lenient`
+            Item::Verbatim(tokens) => self.visit_tokens(tokens.clone()),
+            _ => syn::visit::visit_item(self, item),
+        }
+    }
+
+    fn visit_attribute(&mut self, attr: &'ast Attribute) {
+        let lit_str = match &attr.meta {
+            Meta::NameValue(MetaNameValue {
+                path,
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }),
+                ..
+            }) if path.is_ident("doc") => lit_str,
+            Meta::List(list) => {
+                // Like macro arguments, `#[error("...")]` or `#[serde(rename = "...")]` are left as raw tokens
+                syn::visit::visit_attribute(self, attr);
+                return self.visit_tokens(list.tokens.clone());
+            }
+            _ => return syn::visit::visit_attribute(self, attr),
+        };
+
+        let span = lit_str.span();
+        let (start, end) = (span.start(), span.end());
+        let value = lit_str.value();
+        // A doc comment is desugared into tokens that all carry the comment's span, `#` included
+        if attr.pound_token.span.start() != start {
+            if self.covers_target(start, end) {
+                self.push(span, &lit_str.token(), LiteralKind::Doc, value, false);
+            }
+            return;
+        }
+
+        // The comment itself is not among the tokens, so its form is rebuilt from the span's width
+        let inner = matches!(attr.style, AttrStyle::Inner(_));
+        let line_comment =
+            start.line == end.line && end.column - start.column == value.chars().count() + 3;
+        let token = match (line_comment, inner) {
+            (true, false) => format!("///{}", value),
+            (true, true) => format!("//!{}", value),
+            (false, false) => format!("/**{}*/", value),
+            (false, true) => format!("/*!{}*/", value),
+        };
+        self.push_doc_comment(StringLiteralInfo {
+            value,
+            start_line: start.line,
+            start_column: start.column,
+            end_line: end.line,
+            end_column: end.column,
+            raw: true,
+            token,
+            kind: LiteralKind::Doc,
+            lossy: false,
+        });
+    }
+
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        // syn leaves macro arguments as raw tokens, so `println!("...")` needs its own walk
+        syn::visit::visit_macro(self, mac);
+        self.visit_tokens(mac.tokens.clone());
+    }
+
+    fn visit_lit_str(&mut self, lit_str: &'ast LitStr) {
+        let span = lit_str.span();
+
+        if self.covers_target(span.start(), span.end()) {
+            self.push(
+                span,
+                &lit_str.token(),
+                LiteralKind::Str,
+                lit_str.value(),
+                false,
+            );
+        }
+    }
+
+    fn visit_lit_byte_str(&mut self, lit_byte_str: &'ast LitByteStr) {
+        let span = lit_byte_str.span();
+
+        if self.covers_target(span.start(), span.end()) {
+            // Invalid sequences are replaced here; callers decide whether that is acceptable
+            let (value, lossy) = match String::from_utf8(lit_byte_str.value()) {
+                Ok(value) => (value, false),
+                Err(err) => (String::from_utf8_lossy(err.as_bytes()).into_owned(), true),
+            };
+            self.push(
+                span,
+                &lit_byte_str.token(),
+                LiteralKind::ByteStr,
+                value,
+                lossy,
+            );
+        }
+    }
+
+    fn visit_lit_char(&mut self, lit_char: &'ast LitChar) {
+        let span = lit_char.span();
+
+        if self.covers_target(span.start(), span.end()) {
+            let literal = Self::literal_info(
+                span,
+                &lit_char.token(),
+                LiteralKind::Char,
+                lit_char.value().to_string(),
+                false,
+            );
+            self.found_chars.push(literal);
+        }
+    }
+
+    fn visit_lit_cstr(&mut self, lit_cstr: &'ast LitCStr) {
+        let span = lit_cstr.span();
+
+        if self.covers_target(span.start(), span.end()) {
+            // `CString` already omits the trailing NUL, and syn rejects interior NULs while parsing
+            match lit_cstr.value().into_string() {
+                Ok(value) => self.push(span, &lit_cstr.token(), LiteralKind::CStr, value, false),
+                Err(_) => {
+                    self.error
+                        .get_or_insert(Error::InvalidCString(span.start().line));
+                }
+            }
+        }
+    }
+}
+
+/// String content to tokenize, with the file and line it came from when several sources are processed.
+/// Output is grouped by source, under [`Source::header`].
+///
+/// ```
+/// use rust_span_counter::{Source, SpanExtractor};
+///
+/// let source = Source::at_line(3, "hello world".to_string()).within(6..11);
+/// assert_eq!(source.header().as_deref(), Some("line 3:"));
+/// let (spans, _) = SpanExtractor::new().extract_source_truncated(&source).unwrap();
+/// assert_eq!((spans[0].1.start, spans[0].1.end), (6, 11));
+/// ```
+pub struct Source {
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    /// Zero-based index and 1-based starting column of the literal, when several share a line
+    pub literal: Option<(usize, usize)>,
+    /// 1-based starting column of the literal, for literals from a range of lines
+    pub column: Option<usize>,
+    pub content: String,
+    /// The Rust string literal the content was read from
+    pub origin: Option<StringLiteralInfo>,
+    /// Only this byte range of the content is tokenized; span offsets still count from the content's start
+    pub within: Option<Range<usize>>,
+    /// Byte range of the literal as written in its file, quotes included
+    pub file_bytes: Option<Range<usize>>,
+    /// The offset in the whole file of each byte offset of the content, set by [`Source::with_file_bytes`]
+    pub file_offsets: Option<Vec<usize>>,
+}
+
+impl Source {
+    /// Content with nothing to say where it came from
+    pub fn new(content: String) -> Self {
+        Self {
+            file: None,
+            line: None,
+            literal: None,
+            column: None,
+            content,
+            origin: None,
+            within: None,
+            file_bytes: None,
+            file_offsets: None,
+        }
+    }
+
+    /// Content from a 1-based line of the input
+    pub fn at_line(line: usize, content: String) -> Self {
+        Self {
+            file: None,
+            line: Some(line),
+            literal: None,
+            column: None,
+            content,
+            origin: None,
+            within: None,
+            file_bytes: None,
+            file_offsets: None,
+        }
+    }
+
+    /// Content from one of several files, optionally from a 1-based line of it
+    pub fn in_file(file: PathBuf, line: Option<usize>, content: String) -> Self {
+        Self {
+            file: Some(file),
+            line,
+            literal: None,
+            column: None,
+            content,
+            origin: None,
+            within: None,
+            file_bytes: None,
+            file_offsets: None,
+        }
+    }
+
+    /// One of several literals on a 1-based line, by its zero-based index and 1-based starting column
+    pub fn literal_on_line(line: usize, index: usize, column: usize, content: String) -> Self {
+        Self {
+            file: None,
+            line: Some(line),
+            literal: Some((index, column)),
+            column: None,
+            content,
+            origin: None,
+            within: None,
+            file_bytes: None,
+            file_offsets: None,
+        }
+    }
+
+    /// Replace the content with the literal's text as written, escapes included. The result no longer
+    /// maps back through the literal, so the origin is dropped.
+    pub fn with_written_text(self) -> Self {
+        match &self.origin {
+            Some(literal) => Self {
+                content: literal.source_text().to_string(),
+                origin: None,
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    /// Locate the literal in `content`, the file it was parsed from, and with `absolute` each byte of its
+    /// value, counted in `unit`
+    pub fn with_file_bytes(self, content: &str, absolute: bool, unit: OffsetUnit) -> Self {
+        let file_offsets = self
+            .origin
+            .as_ref()
+            .filter(|_| absolute)
+            .and_then(|literal| literal.file_offsets(content))
+            .map(|offsets| {
+                let units = unit_offsets(content, unit);
+                offsets.into_iter().map(|offset| units[offset]).collect()
+            });
+        Self {
+            file_bytes: self
+                .origin
+                .as_ref()
+                .and_then(|literal| literal.file_byte_range(content)),
+            file_offsets,
+            ..self
+        }
+    }
+
+    /// Name the starting column of the literal in the header, telling apart literals on the same line
+    pub fn at_column(self, column: usize) -> Self {
+        Self {
+            column: Some(column),
+            ..self
+        }
+    }
+
+    /// Tokenize only `range` of the content
+    pub fn within(self, range: Range<usize>) -> Self {
+        Self {
+            within: Some(range),
+            ..self
+        }
+    }
+
+    /// Record the literal this source's content came from
+    pub fn with_origin(self, origin: StringLiteralInfo) -> Self {
+        Self {
+            origin: Some(origin),
+            ..self
+        }
+    }
+
+    /// Whether this source's spans are reported as a group that says where they came from
+    pub fn is_grouped(&self) -> bool {
+        self.file.is_some() || self.line.is_some()
+    }
+
+    /// Keys identifying where this source's spans came from, for grouped JSON output
+    pub fn group_json(&self) -> serde_json::Value {
+        let mut group = serde_json::json!({ "line": self.line });
+        if let Some(file) = &self.file {
+            group["file"] = serde_json::json!(file.display().to_string());
+        }
+        if let Some(origin) = &self.origin {
+            group["kind"] = serde_json::json!(origin.kind.as_str());
+        }
+        if let Some((index, column)) = self.literal {
+            group["index"] = serde_json::json!(index);
+            group["column"] = serde_json::json!(column);
+        }
+        if let Some(column) = self.column {
+            group["column"] = serde_json::json!(column);
+        }
+        group
+    }
+
+    /// Header printed above this source's output, if it needs one
+    pub fn header(&self) -> Option<String> {
+        if let Some((index, column)) = self.literal {
+            return Some(format!("literal {} (column {}):", index, column));
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            return Some(format!("line {}, column {}:", line, column));
+        }
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => Some(format!("{}:{}:", file.display(), line)),
+            (Some(file), None) => Some(format!("{}:", file.display())),
+            (None, Some(line)) => Some(format!("line {}:", line)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Finished files from a scan checkpoint, mapped to the [`modification_stamp`] they were processed at.
+/// A missing checkpoint means nothing has been processed yet.
+pub fn read_checkpoint(checkpoint_path: &Path) -> Result<HashMap<PathBuf, String>, Error> {
+    let contents = match fs::read_to_string(checkpoint_path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(source) => {
+            return Err(Error::IoError {
+                path: checkpoint_path.to_path_buf(),
+                source,
+            })
+        }
+    };
+
+    Ok(contents
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(modified, path)| (PathBuf::from(path), modified.to_string()))
+        .collect())
+}
+
+/// A file's modification time as `seconds.nanoseconds` since the Unix epoch
+pub fn modification_stamp(file_path: &Path) -> Result<String, Error> {
+    let modified = fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|source| Error::IoError {
+            path: file_path.to_path_buf(),
+            source,
+        })?;
+    let since_epoch = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!(
+        "{}.{:09}",
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos()
+    ))
+}
+
+/// Record a finished file in a scan checkpoint, as a line [`read_checkpoint`] reads back
+pub fn write_checkpoint_entry(
+    checkpoint: &mut dyn Write,
+    modified: &str,
+    file_path: &Path,
+) -> io::Result<()> {
+    writeln!(checkpoint, "{}\t{}", modified, file_path.display())
+}
+
+/// SARIF results for the spans of one source, each a `note` for the rule `span`. With `file` each result
+/// names the file, and with `literal`, the Rust literal the spans were read from, their region in it,
+/// with columns counted in characters.
+pub fn sarif_span_results(
+    spans: &[IndexedSpan],
+    file: Option<&Path>,
+    literal: Option<&StringLiteralInfo>,
+) -> Vec<serde_json::Value> {
+    let positions = literal.map(StringLiteralInfo::source_positions);
+    spans
+        .iter()
+        .map(|(_, span)| {
+            let mut result = serde_json::json!({
+                "ruleId": "span",
+                "level": "note",
+                "message": { "text": format!("Span {}", span) },
+            });
+            if let Some(file) = file {
+                let mut location = serde_json::json!({
+                    "artifactLocation": { "uri": file.display().to_string().replace('\\', "/") },
+                });
+                if let Some(positions) = &positions {
+                    let ((start_line, start_column), (end_line, end_column)) =
+                        (positions.position(span.start), positions.position(span.end));
+                    location["region"] = serde_json::json!({
+                        "startLine": start_line,
+                        "startColumn": start_column,
+                        "endLine": end_line,
+                        "endColumn": end_column,
+                    });
+                }
+                result["locations"] = serde_json::json!([{ "physicalLocation": location }]);
+            }
+            result
+        })
+        .collect()
+}
+
+/// Wrap `results` in a single-run SARIF log whose tool declares the one rule they report
+pub fn sarif_document(
+    rule_id: &str,
+    description: &str,
+    results: Vec<serde_json::Value>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{ "id": rule_id, "shortDescription": { "text": description } }],
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+/// Write one digraph over all groups: a node per distinct word and an edge per adjacent word pair,
+/// weighted by how often the pair occurs. Adjacency never crosses from one group into the next.
+pub fn write_dot<'a>(
+    out: &mut dyn Write,
+    groups: impl IntoIterator<Item = &'a [IndexedSpan]>,
+) -> io::Result<()> {
+    let mut nodes: Vec<&str> = Vec::new();
+    let mut edges: Vec<((String, String), usize)> = Vec::new();
+    for indexed_spans in groups {
+        let mut ordered: Vec<&IndexedSpan> = indexed_spans.iter().collect();
+        ordered.sort_by_key(|(index, _)| *index);
+        for (_, span) in &ordered {
+            if !nodes.contains(&span.word.as_str()) {
+                nodes.push(&span.word);
+            }
+        }
+
+        let spans: Vec<WordSpan> = ordered.into_iter().map(|(_, span)| span.clone()).collect();
+        for (pair, count) in word_adjacency(&spans) {
+            match edges.iter_mut().find(|(existing, _)| *existing == pair) {
+                Some((_, total)) => *total += count,
+                None => edges.push((pair, count)),
+            }
+        }
+    }
+
+    writeln!(out, "digraph spans {{")?;
+    for node in nodes {
+        writeln!(out, "    {};", dot_id(node))?;
+    }
+    for ((from, to), count) in edges {
+        writeln!(
+            out,
+            "    {} -> {} [weight={}, label=\"{}\"];",
+            dot_id(&from),
+            dot_id(&to),
+            count,
+            count
+        )?;
+    }
+    writeln!(out, "}}")
+}
+
+/// Quote a word as a DOT identifier
+fn dot_id(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed(spans: Vec<WordSpan>) -> Vec<IndexedSpan> {
+        spans.into_iter().enumerate().collect()
+    }
+
+    fn unindexed(spans: Vec<IndexedSpan>) -> Vec<WordSpan> {
+        spans.into_iter().map(|(_, span)| span).collect()
+    }
+
+    fn read_test_file(path: &std::path::Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_basic_word_splitting() {
+        let content = "hello world";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 6,
+                    end: 11
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let content = "foo bar"; // This simulates the parsed content of "foo \"bar"
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "foo".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "bar".to_string(),
+                    start: 4,
+                    end: 7
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_word() {
+        let content = "hello";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_empty_string() {
+        let content = "";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(spans, vec![]);
+    }
+
+    #[test]
+    fn test_multiple_spaces() {
+        let content = "hello    world";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 9,
+                    end: 14
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leading_trailing_spaces() {
+        let content = "  hello world  ";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 2,
+                    end: 7
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 8,
+                    end: 13
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_extraction_from_line() {
+        let code = r#"
+        fn main() {
+            let s = "hello world";
+        }
+        "#;
+
+        let file = syn::parse_file(code).unwrap();
+        let result = find_strings_on_line(&file, 3, None);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "hello world");
+    }
+
+    #[test]
+    fn test_no_string_on_line() {
+        let code = r#"
+        fn main() {
+            let x = 42;
+        }
+        "#;
+
+        let file = syn::parse_file(code).unwrap();
+        let result = find_strings_on_line(&file, 3, None);
+
+        assert!(matches!(result, Err(Error::NoStringFound { .. })));
+    }
+
+    #[test]
+    fn test_multiple_strings_error() {
+        let code = r#"
+        fn main() {
+            let s = "hello"; let t = "world";
+        }
+        "#;
+
+        let file = syn::parse_file(code).unwrap();
+        let result = find_strings_on_line(&file, 3, None);
+
+        assert!(matches!(result, Err(Error::MultipleStringsFound)));
+    }
+
+    #[test]
+    fn test_raw_string() {
+        let code = r#"
+        fn main() {
+            let s = r"hello world";
+        }
+        "#;
+
+        let file = syn::parse_file(code).unwrap();
+        let result = find_strings_on_line(&file, 3, None);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "hello world");
+    }
+
+    #[test]
+    fn test_string_with_escapes() {
+        let code = r#"let s = "foo \"bar\" baz";"#;
+        let file = syn::parse_str::<syn::Stmt>(code).unwrap();
+
+        let mut visitor = StringVisitor::new(1);
+        visitor.visit_stmt(&file);
+
+        assert_eq!(visitor.found_strings.len(), 1);
+        assert_eq!(visitor.found_strings[0].start_line, 1);
+        assert_eq!(visitor.found_strings[0].value, "foo \"bar\" baz");
+    }
+
+    #[test]
+    fn test_complete_workflow() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("simple.rs");
+
+        let content = find_string_in_source(&read_test_file(&test_file_path), 2, None).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "test".to_string(),
+                    start: 12,
+                    end: 16
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complete_workflow_with_raw_string() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("raw_string.rs");
+
+        let content = find_string_in_source(&read_test_file(&test_file_path), 2, None).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "raw".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "string".to_string(),
+                    start: 4,
+                    end: 10
+                },
+                WordSpan {
+                    word: "content".to_string(),
+                    start: 11,
+                    end: 18
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complete_workflow_with_escaped_quotes() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("escaped.rs");
+
+        let content = find_string_in_source(&read_test_file(&test_file_path), 2, None).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "foo".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "\"".to_string(),
+                    start: 4,
+                    end: 5
+                },
+                WordSpan {
+                    word: "bar".to_string(),
+                    start: 5,
+                    end: 8
+                },
+                WordSpan {
+                    word: "\"".to_string(),
+                    start: 8,
+                    end: 9
+                },
+                WordSpan {
+                    word: "baz".to_string(),
+                    start: 10,
+                    end: 13
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complete_workflow_line_3() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("simple.rs");
+
+        let content = find_string_in_source(&read_test_file(&test_file_path), 3, None).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "foo".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "bar".to_string(),
+                    start: 4,
+                    end: 7
+                },
+                WordSpan {
+                    word: "baz".to_string(),
+                    start: 8,
+                    end: 11
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiline_string_multiple_lines() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("multiline.rs");
+
+        let expected_spans = vec![
+            WordSpan {
+                word: "this".to_string(),
+                start: 0,
+                end: 4,
+            },
+            WordSpan {
+                word: "is".to_string(),
+                start: 5,
+                end: 7,
+            },
+            WordSpan {
+                word: "a".to_string(),
+                start: 8,
+                end: 9,
+            },
+            WordSpan {
+                word: "multiline".to_string(),
+                start: 23,
+                end: 32,
+            },
+            WordSpan {
+                word: "string".to_string(),
+                start: 33,
+                end: 39,
+            },
+            WordSpan {
+                word: "with".to_string(),
+                start: 40,
+                end: 44,
+            },
+            WordSpan {
+                word: "multiple".to_string(),
+                start: 58,
+                end: 66,
+            },
+            WordSpan {
+                word: "words".to_string(),
+                start: 67,
+                end: 72,
+            },
+            WordSpan {
+                word: "per".to_string(),
+                start: 73,
+                end: 76,
+            },
+            WordSpan {
+                word: "line".to_string(),
+                start: 77,
+                end: 81,
+            },
+        ];
+
+        // Test that all lines covered by the multiline string return the same result
+        for line_number in [2, 3, 4] {
+            let content =
+                find_string_in_source(&read_test_file(&test_file_path), line_number, None).unwrap();
+            let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+            assert_eq!(spans, expected_spans, "Failed for line {}", line_number);
+        }
+    }
+
+    #[test]
+    fn test_multiline_raw_string_multiple_lines() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("multiline_raw.rs");
+
+        let expected_spans = vec![
+            WordSpan {
+                word: "this".to_string(),
+                start: 0,
+                end: 4,
+            },
+            WordSpan {
+                word: "is".to_string(),
+                start: 5,
+                end: 7,
+            },
+            WordSpan {
+                word: "a".to_string(),
+                start: 8,
+                end: 9,
+            },
+            WordSpan {
+                word: "raw".to_string(),
+                start: 10,
+                end: 13,
+            },
+            WordSpan {
+                word: "multiline".to_string(),
+                start: 29,
+                end: 38,
+            },
+            WordSpan {
+                word: "string".to_string(),
+                start: 39,
+                end: 45,
+            },
+            WordSpan {
+                word: "with".to_string(),
+                start: 46,
+                end: 50,
+            },
+            WordSpan {
+                word: "special".to_string(),
+                start: 66,
+                end: 73,
+            },
+            WordSpan {
+                word: "\"".to_string(),
+                start: 74,
+                end: 75,
+            },
+            WordSpan {
+                word: "quotes".to_string(),
+                start: 75,
+                end: 81,
+            },
+            WordSpan {
+                word: "\"".to_string(),
+                start: 81,
+                end: 82,
+            },
+            WordSpan {
+                word: "and".to_string(),
+                start: 83,
+                end: 86,
+            },
+            WordSpan {
+                word: "symbols".to_string(),
+                start: 87,
+                end: 94,
+            },
+        ];
+
+        // Test that all lines covered by the multiline raw string return the same result
+        for line_number in [2, 3, 4] {
+            let content =
+                find_string_in_source(&read_test_file(&test_file_path), line_number, None).unwrap();
+            let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+            assert_eq!(
+                spans, expected_spans,
+                "Failed for raw string line {}",
+                line_number
+            );
+        }
+    }
+
+    #[test]
+    fn test_single_line_on_multiline_file() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("multiline.rs");
+
+        let content = find_string_in_source(&read_test_file(&test_file_path), 5, None).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        // Should find the single line string on line 5
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "single".to_string(),
+                    start: 0,
+                    end: 6
+                },
+                WordSpan {
+                    word: "line".to_string(),
+                    start: 7,
+                    end: 11
+                },
+                WordSpan {
+                    word: "string".to_string(),
+                    start: 12,
+                    end: 18
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_string_on_multiline_boundary() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("multiline.rs");
+
+        let result = find_string_in_source(&read_test_file(&test_file_path), 1, None);
+
+        // Should return NoStringFound error for line 1 (fn main() line), listing the closest literal lines
+        assert!(
+            matches!(result, Err(Error::NoStringFound { line: 1, nearest_lines_with_strings }) if nearest_lines_with_strings == vec![2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn test_punctuation_tokenization() {
+        let content = "default(nextval(user_id_seq)),";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "default".to_string(),
+                    start: 0,
+                    end: 7
+                },
+                WordSpan {
+                    word: "(".to_string(),
+                    start: 7,
+                    end: 8
+                },
+                WordSpan {
+                    word: "nextval".to_string(),
+                    start: 8,
+                    end: 15
+                },
+                WordSpan {
+                    word: "(".to_string(),
+                    start: 15,
+                    end: 16
+                },
+                WordSpan {
+                    word: "user_id_seq".to_string(),
+                    start: 16,
+                    end: 27
+                },
+                WordSpan {
+                    word: ")".to_string(),
+                    start: 27,
+                    end: 28
+                },
+                WordSpan {
+                    word: ")".to_string(),
+                    start: 28,
+                    end: 29
+                },
+                WordSpan {
+                    word: ",".to_string(),
+                    start: 29,
+                    end: 30
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mixed_punctuation_and_whitespace() {
+        let content = "hello, world! how are you?";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: ",".to_string(),
+                    start: 5,
+                    end: 6
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 7,
+                    end: 12
+                },
+                WordSpan {
+                    word: "!".to_string(),
+                    start: 12,
+                    end: 13
+                },
+                WordSpan {
+                    word: "how".to_string(),
+                    start: 14,
+                    end: 17
+                },
+                WordSpan {
+                    word: "are".to_string(),
+                    start: 18,
+                    end: 21
+                },
+                WordSpan {
+                    word: "you".to_string(),
+                    start: 22,
+                    end: 25
+                },
+                WordSpan {
+                    word: "?".to_string(),
+                    start: 25,
+                    end: 26
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sql_like_expression() {
+        let content = "SELECT * FROM table WHERE id=42;";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "SELECT".to_string(),
+                    start: 0,
+                    end: 6
+                },
+                WordSpan {
+                    word: "*".to_string(),
+                    start: 7,
+                    end: 8
+                },
+                WordSpan {
+                    word: "FROM".to_string(),
+                    start: 9,
+                    end: 13
+                },
+                WordSpan {
+                    word: "table".to_string(),
+                    start: 14,
+                    end: 19
+                },
+                WordSpan {
+                    word: "WHERE".to_string(),
+                    start: 20,
+                    end: 25
+                },
+                WordSpan {
+                    word: "id".to_string(),
+                    start: 26,
+                    end: 28
+                },
+                WordSpan {
+                    word: "=".to_string(),
+                    start: 28,
+                    end: 29
+                },
+                WordSpan {
+                    word: "42".to_string(),
+                    start: 29,
+                    end: 31
+                },
+                WordSpan {
+                    word: ";".to_string(),
+                    start: 31,
+                    end: 32
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trim_punctuation_sql_and_mixed() {
+        let sql = get_word_spans(
+            "SELECT * FROM table WHERE id=42;",
+            false,
+            &Granularity::Word,
+        )
+        .unwrap();
+        let words: Vec<(String, usize, usize)> = trim_punctuation(sql)
+            .into_iter()
+            .map(|span| (span.word, span.start, span.end))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                ("SELECT".to_string(), 0, 6),
+                ("FROM".to_string(), 9, 13),
+                ("table".to_string(), 14, 19),
+                ("WHERE".to_string(), 20, 25),
+                ("id".to_string(), 26, 28),
+                ("42".to_string(), 29, 31),
+            ]
+        );
+
+        let mixed =
+            get_word_spans("hello, world! how are you?", false, &Granularity::Word).unwrap();
+        let words: Vec<String> = trim_punctuation(mixed)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(words, vec!["hello", "world", "how", "are", "you"]);
+
+        let calls =
+            get_word_spans("default(nextval(user_id_seq)),", false, &Granularity::Word).unwrap();
+        let words: Vec<String> = trim_punctuation(calls)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(words, vec!["default", "nextval", "user_id_seq"]);
+    }
+
+    #[test]
+    fn test_trim_punctuation_edges_of_merged_tokens() {
+        let content = "say don't --force (v1.2). and \"quoted text\"";
+        let spans = SpanExtractor::new()
+            .preserve_flags(true)
+            .protect_quotes(true)
+            .trim_punctuation(true)
+            .extract(content)
+            .unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "say".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "don't".to_string(),
+                    start: 4,
+                    end: 9
+                },
+                WordSpan {
+                    word: "force".to_string(),
+                    start: 12,
+                    end: 17
+                },
+                WordSpan {
+                    word: "v1.2".to_string(),
+                    start: 19,
+                    end: 23
+                },
+                WordSpan {
+                    word: "and".to_string(),
+                    start: 26,
+                    end: 29
+                },
+                WordSpan {
+                    word: "quoted text".to_string(),
+                    start: 31,
+                    end: 42
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_brackets_and_operators() {
+        let content = "array[index]+value*2";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "array".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "[".to_string(),
+                    start: 5,
+                    end: 6
+                },
+                WordSpan {
+                    word: "index".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "]".to_string(),
+                    start: 11,
+                    end: 12
+                },
+                WordSpan {
+                    word: "+".to_string(),
+                    start: 12,
+                    end: 13
+                },
+                WordSpan {
+                    word: "value".to_string(),
+                    start: 13,
+                    end: 18
+                },
+                WordSpan {
+                    word: "*".to_string(),
+                    start: 18,
+                    end: 19
+                },
+                WordSpan {
+                    word: "2".to_string(),
+                    start: 19,
+                    end: 20
+                },
+            ]
+        );
+    }
+
+    // Tests for the new strings-as-tokens functionality
+    #[test]
+    fn test_strings_as_tokens_double_quotes() {
+        let content = "hello \"world test\" end";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "\"world test\"".to_string(),
+                    start: 6,
+                    end: 18
+                },
+                WordSpan {
+                    word: "end".to_string(),
+                    start: 19,
+                    end: 22
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_single_quotes() {
+        let content = "hello 'world test' end";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "'world test'".to_string(),
+                    start: 6,
+                    end: 18
+                },
+                WordSpan {
+                    word: "end".to_string(),
+                    start: 19,
+                    end: 22
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_backticks() {
+        let content = "hello `world test` end";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "`world test`".to_string(),
+                    start: 6,
+                    end: 18
+                },
+                WordSpan {
+                    word: "end".to_string(),
+                    start: 19,
+                    end: 22
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_mixed_quotes() {
+        let content = "say \"hello\" and 'world' plus `test`";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "say".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "\"hello\"".to_string(),
+                    start: 4,
+                    end: 11
+                },
+                WordSpan {
+                    word: "and".to_string(),
+                    start: 12,
+                    end: 15
+                },
+                WordSpan {
+                    word: "'world'".to_string(),
+                    start: 16,
+                    end: 23
+                },
+                WordSpan {
+                    word: "plus".to_string(),
+                    start: 24,
+                    end: 28
+                },
+                WordSpan {
+                    word: "`test`".to_string(),
+                    start: 29,
+                    end: 35
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_escaped_quotes() {
+        let content = "before \"she said \\\"hello\\\" there\" after";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "before".to_string(),
+                    start: 0,
+                    end: 6
+                },
+                WordSpan {
+                    word: "\"she said \\\"hello\\\" there\"".to_string(),
+                    start: 7,
+                    end: 33
+                },
+                WordSpan {
+                    word: "after".to_string(),
+                    start: 34,
+                    end: 39
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_empty_quotes() {
+        let content = "before \"\" empty '' and `` after";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "before".to_string(),
+                    start: 0,
+                    end: 6
+                },
+                WordSpan {
+                    word: "\"\"".to_string(),
+                    start: 7,
+                    end: 9
+                },
+                WordSpan {
+                    word: "empty".to_string(),
+                    start: 10,
+                    end: 15
+                },
+                WordSpan {
+                    word: "''".to_string(),
+                    start: 16,
+                    end: 18
+                },
+                WordSpan {
+                    word: "and".to_string(),
+                    start: 19,
+                    end: 22
+                },
+                WordSpan {
+                    word: "``".to_string(),
+                    start: 23,
+                    end: 25
+                },
+                WordSpan {
+                    word: "after".to_string(),
+                    start: 26,
+                    end: 31
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_unclosed_quotes() {
+        let content = "hello \"unclosed quote and more";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        // Unclosed quotes should consume the rest of the string
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "\"unclosed quote and more".to_string(),
+                    start: 6,
+                    end: 30
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_vs_default_comparison() {
+        let content = "hello 'world test' end";
+
+        // Default behavior
+        let default_spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+        assert_eq!(
+            default_spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "'".to_string(),
+                    start: 6,
+                    end: 7
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 7,
+                    end: 12
+                },
+                WordSpan {
+                    word: "test".to_string(),
+                    start: 13,
+                    end: 17
+                },
+                WordSpan {
+                    word: "'".to_string(),
+                    start: 17,
+                    end: 18
+                },
+                WordSpan {
+                    word: "end".to_string(),
+                    start: 19,
+                    end: 22
+                }
+            ]
+        );
+
+        // Strings-as-tokens behavior
+        let token_spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+        assert_eq!(
+            token_spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "'world test'".to_string(),
+                    start: 6,
+                    end: 18
+                },
+                WordSpan {
+                    word: "end".to_string(),
+                    start: 19,
+                    end: 22
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_unquoted_punctuation() {
+        let content = "array[index] \"quoted text\" + value*2";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "array".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "[".to_string(),
+                    start: 5,
+                    end: 6
+                },
+                WordSpan {
+                    word: "index".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "]".to_string(),
+                    start: 11,
+                    end: 12
+                },
+                WordSpan {
+                    word: "\"quoted text\"".to_string(),
+                    start: 13,
+                    end: 26
+                },
+                WordSpan {
+                    word: "+".to_string(),
+                    start: 27,
+                    end: 28
+                },
+                WordSpan {
+                    word: "value".to_string(),
+                    start: 29,
+                    end: 34
+                },
+                WordSpan {
+                    word: "*".to_string(),
+                    start: 34,
+                    end: 35
+                },
+                WordSpan {
+                    word: "2".to_string(),
+                    start: 35,
+                    end: 36
+                }
+            ]
+        );
+    }
+
+    // Tests for filtering functionality
+    #[test]
+    fn test_filter_exact_match() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+            WordSpan {
+                word: "test".to_string(),
+                start: 12,
+                end: 16,
+            },
+        ];
+
+        let filters = vec!["world".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Exact, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11
+            }]
+        );
+    }
+
+    #[test]
+    fn test_filter_exact_match_multiple() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+            WordSpan {
+                word: "test".to_string(),
+                start: 12,
+                end: 16,
+            },
+        ];
+
+        let filters = vec!["hello".to_string(), "test".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Exact, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "test".to_string(),
+                    start: 12,
+                    end: 16
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_exact_match_case_sensitive() {
+        let spans = vec![
+            WordSpan {
+                word: "Hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "WORLD".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+
+        let filters = vec!["hello".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Exact, false).unwrap(),
+        );
+
+        assert_eq!(result, vec![]); // No matches because of case sensitivity
+    }
+
+    #[test]
+    fn test_filter_exact_match_case_insensitive() {
+        let spans = vec![
+            WordSpan {
+                word: "Hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "WORLD".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+
+        let filters = vec!["hello".to_string(), "world".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Exact, true).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "Hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "WORLD".to_string(),
+                    start: 6,
+                    end: 11
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_contains_mode() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+            WordSpan {
+                word: "wonderful".to_string(),
+                start: 12,
+                end: 21,
+            },
+        ];
+
+        let filters = vec!["orl".to_string(), "nde".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Contains, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "wonderful".to_string(),
+                    start: 12,
+                    end: 21
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_contains_case_insensitive() {
+        let spans = vec![
+            WordSpan {
+                word: "Hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "WORLD".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+
+        let filters = vec!["ell".to_string(), "orl".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Contains, true).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "Hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "WORLD".to_string(),
+                    start: 6,
+                    end: 11
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_regex_mode() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+            WordSpan {
+                word: "word".to_string(),
+                start: 12,
+                end: 16,
+            },
+            WordSpan {
+                word: "test123".to_string(),
+                start: 17,
+                end: 24,
+            },
+        ];
+
+        let filters = vec![r"wo.*d".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Regex, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "word".to_string(),
+                    start: 12,
+                    end: 16
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_regex_with_numbers() {
+        let spans = vec![
+            WordSpan {
+                word: "test123".to_string(),
+                start: 0,
+                end: 7,
+            },
+            WordSpan {
+                word: "hello".to_string(),
+                start: 8,
+                end: 13,
+            },
+            WordSpan {
+                word: "world456".to_string(),
+                start: 14,
+                end: 22,
+            },
+        ];
+
+        let filters = vec![r"\d+".to_string()]; // Match words containing digits
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Regex, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "test123".to_string(),
+                    start: 0,
+                    end: 7
+                },
+                WordSpan {
+                    word: "world456".to_string(),
+                    start: 14,
+                    end: 22
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_regex_case_insensitive() {
+        let spans = vec![
+            WordSpan {
+                word: "Hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "WORLD".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+
+        let filters = vec!["hello".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Regex, true).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![WordSpan {
+                word: "Hello".to_string(),
+                start: 0,
+                end: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_filter_invalid_regex() {
+        let spans = vec![WordSpan {
+            word: "hello".to_string(),
+            start: 0,
+            end: 5,
+        }];
+
+        let filters = vec!["[invalid".to_string()]; // Invalid regex
+        let result = filter_word_spans(indexed(spans), &filters, &FilterMode::Regex, false);
+
+        assert!(matches!(result, Err(Error::RegexError(_))));
+    }
+
+    #[test]
+    fn test_filter_empty_filters() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+
+        let filters = vec![];
+        let result = unindexed(
+            filter_word_spans(indexed(spans.clone()), &filters, &FilterMode::Exact, false).unwrap(),
+        );
+
+        assert_eq!(result, spans); // Should return all spans when no filters
+    }
+
+    #[test]
+    fn test_filter_no_matches() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+
+        let filters = vec!["nonexistent".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Exact, false).unwrap(),
+        );
+
+        assert_eq!(result, vec![]); // Should return empty vec when no matches
+    }
+
+    #[test]
+    fn test_filter_with_punctuation() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: ",".to_string(),
+                start: 5,
+                end: 6,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 7,
+                end: 12,
+            },
+            WordSpan {
+                word: "!".to_string(),
+                start: 12,
+                end: 13,
+            },
+        ];
+
+        let filters = vec![",".to_string(), "!".to_string()];
+        let result = unindexed(
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Exact, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: ",".to_string(),
+                    start: 5,
+                    end: 6
+                },
+                WordSpan {
+                    word: "!".to_string(),
+                    start: 12,
+                    end: 13
+                }
+            ]
+        );
+    }
+
+    // Tests for exclusion filtering
+    #[test]
+    fn test_exclude_exact_match() {
+        let spans = get_word_spans("the cat and a dog", false, &Granularity::Word).unwrap();
+
+        let excludes = vec!["the".to_string(), "a".to_string(), "and".to_string()];
+        let result = unindexed(
+            exclude_word_spans(indexed(spans), &excludes, &FilterMode::Exact, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "cat".to_string(),
+                    start: 4,
+                    end: 7
+                },
+                WordSpan {
+                    word: "dog".to_string(),
+                    start: 14,
+                    end: 17
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exclude_contains_case_insensitive() {
+        let spans = vec![
+            WordSpan {
+                word: "Hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "WORLD".to_string(),
+                start: 6,
+                end: 11,
+            },
+            WordSpan {
+                word: "test".to_string(),
+                start: 12,
+                end: 16,
+            },
+        ];
+
+        let excludes = vec!["orl".to_string()];
+        let result = unindexed(
+            exclude_word_spans(indexed(spans), &excludes, &FilterMode::Contains, true).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                WordSpan {
+                    word: "Hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "test".to_string(),
+                    start: 12,
+                    end: 16
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exclude_regex_mode() {
+        let spans = vec![
+            WordSpan {
+                word: "test123".to_string(),
+                start: 0,
+                end: 7,
+            },
+            WordSpan {
+                word: "hello".to_string(),
+                start: 8,
+                end: 13,
+            },
+            WordSpan {
+                word: "world456".to_string(),
+                start: 14,
+                end: 22,
+            },
+        ];
+
+        let excludes = vec![r"\d+".to_string()];
+        let result = unindexed(
+            exclude_word_spans(indexed(spans), &excludes, &FilterMode::Regex, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![WordSpan {
+                word: "hello".to_string(),
+                start: 8,
+                end: 13
+            }]
+        );
+    }
+
+    #[test]
+    fn test_filter_then_exclude() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+            WordSpan {
+                word: "wonderful".to_string(),
+                start: 12,
+                end: 21,
+            },
+        ];
+
+        let filters = vec!["wo".to_string()];
+        let excludes = vec!["der".to_string()];
+        let included =
+            filter_word_spans(indexed(spans), &filters, &FilterMode::Contains, false).unwrap();
+        let result = unindexed(
+            exclude_word_spans(included, &excludes, &FilterMode::Contains, false).unwrap(),
+        );
+
+        assert_eq!(
+            result,
+            vec![WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11
+            }]
+        );
+    }
+
+    // Tests for replacement output
+    #[test]
+    fn test_replace_with_shorter_value() {
+        let content = "hello world, hello again";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        let replacements = vec![("hello".to_string(), "hi".to_string())];
+        let result =
+            replace_spans(content, &spans, &replacements, &FilterMode::Exact, false).unwrap();
+
+        assert_eq!(result, "hi world, hi again");
+    }
+
+    #[test]
+    fn test_replace_with_longer_value() {
+        let content = "user=bob\tpass=secret";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        let replacements = vec![
+            ("bob".to_string(), "[REDACTED]".to_string()),
+            ("SECRET".to_string(), "[REDACTED]".to_string()),
+        ];
+        let result =
+            replace_spans(content, &spans, &replacements, &FilterMode::Exact, true).unwrap();
+
+        assert_eq!(result, "user=[REDACTED]\tpass=[REDACTED]");
+    }
+
+    #[test]
+    fn test_replace_only_within_given_spans() {
+        let content = "foo bar foo";
+        let spans = unindexed(
+            filter_word_spans(
+                indexed(get_word_spans(content, false, &Granularity::Word).unwrap()),
+                &["bar".to_string()],
+                &FilterMode::Exact,
+                false,
+            )
+            .unwrap(),
+        );
+
+        let replacements = vec![("^.*$".to_string(), "X".to_string())];
+        let result =
+            replace_spans(content, &spans, &replacements, &FilterMode::Regex, false).unwrap();
+
+        assert_eq!(result, "foo X foo");
+    }
+
+    // Tests for JSON input
+    #[test]
+    fn test_json_nested_string_value() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("nested.json");
+
+        let content =
+            find_json_string_in_source(&read_test_file(&test_file_path), "/messages/1/text")
+                .unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "foo".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "bar".to_string(),
+                    start: 4,
+                    end: 7
+                },
+                WordSpan {
+                    word: "baz".to_string(),
+                    start: 8,
+                    end: 11
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_offsets_relative_to_decoded_string() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("nested.json");
+
+        let content =
+            find_json_string_in_source(&read_test_file(&test_file_path), "/meta/title").unwrap();
+
+        assert_eq!(content, "nested \"quoted\" title");
+    }
+
+    #[test]
+    fn test_json_pointer_not_a_string() {
+        let document = serde_json::json!({ "messages": [{ "id": 1 }] });
+
+        assert!(matches!(
+            find_json_string(&document, "/messages/0/id"),
+            Err(Error::JsonPointerNotString(_))
+        ));
+        assert!(matches!(
+            find_json_string(&document, "/messages/0"),
+            Err(Error::JsonPointerNotString(_))
+        ));
+        assert!(matches!(
+            find_json_string(&document, "/missing"),
+            Err(Error::JsonPointerNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_string_pointers_resolve_back_to_their_values() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("nested.json");
+        let document: serde_json::Value =
+            serde_json::from_str(&read_test_file(&test_file_path)).unwrap();
+
+        let strings = json_string_pointers(&document);
+        assert_eq!(
+            strings,
+            vec![
+                (
+                    "/messages/0/text".to_string(),
+                    "hello world test".to_string()
+                ),
+                ("/messages/1/text".to_string(), "foo bar baz".to_string()),
+                (
+                    "/meta/title".to_string(),
+                    "nested \"quoted\" title".to_string()
+                ),
+            ]
+        );
+        for (pointer, value) in strings {
+            assert_eq!(find_json_string(&document, &pointer).unwrap(), value);
+        }
+
+        let document = serde_json::json!({ "odd~/key": "v", "n": [[null, "deep"]] });
+        let pointers: Vec<String> = json_string_pointers(&document)
+            .into_iter()
+            .map(|(pointer, _)| pointer)
+            .collect();
+        assert_eq!(pointers, vec!["/odd~0~1key", "/n/0/1"]);
+        assert_eq!(find_json_string(&document, "/odd~0~1key").unwrap(), "v");
+        assert_eq!(
+            json_string_pointers(&serde_json::json!("root")),
+            vec![(String::new(), "root".to_string())]
+        );
+    }
+
+    // Tests for TOML string values
+    fn read_toml_fixture() -> String {
+        read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("package.toml"),
+        )
+    }
+
+    #[test]
+    fn test_toml_strings_decode_every_string_form() {
+        let strings = toml_strings(&read_toml_fixture()).unwrap();
+
+        let found: Vec<(&str, &str, usize, usize)> = strings
+            .iter()
+            .map(|string| {
+                (
+                    string.key.as_str(),
+                    string.value.as_str(),
+                    string.start_line,
+                    string.end_line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("package.name", "demo-crate", 2, 2),
+                ("package.version", "0.1.0", 3, 3),
+                ("package.description", "A small demo crate", 4, 4),
+                ("package.authors.0", "Ada Lovelace", 5, 5),
+                ("package.authors.1", "Alan Turing", 5, 5),
+                ("package.license", "MIT OR Apache-2.0", 6, 6),
+                (
+                    "package.readme",
+                    "Literal multi-line text, with \\n kept as written\n",
+                    7,
+                    9
+                ),
+                (
+                    "package.metadata.notes",
+                    "First line continues here\nsecond line",
+                    12,
+                    15
+                ),
+            ]
+        );
+        assert_eq!(strings[3].start_column, 12);
+    }
+
+    #[test]
+    fn test_find_toml_string_by_key_or_line() {
+        let source = read_toml_fixture();
+
+        assert_eq!(
+            find_toml_string(&source, "package.description")
+                .unwrap()
+                .value,
+            "A small demo crate"
+        );
+        assert!(matches!(
+            find_toml_string(&source, "package.metadata.build"),
+            Err(Error::TomlKeyNotString(_))
+        ));
+        assert!(matches!(
+            find_toml_string(&source, "package.authors"),
+            Err(Error::TomlKeyNotString(_))
+        ));
+        assert!(matches!(
+            find_toml_string(&source, "package.missing"),
+            Err(Error::TomlKeyNotFound(_))
+        ));
+
+        assert_eq!(
+            find_toml_string_on_line(&source, 14).unwrap().key,
+            "package.metadata.notes"
+        );
+        assert!(matches!(
+            find_toml_string_on_line(&source, 5),
+            Err(Error::MultipleStringsFound)
+        ));
+        match find_toml_string_on_line(&source, 16) {
+            Err(Error::NoStringFound {
+                nearest_lines_with_strings,
+                ..
+            }) => assert_eq!(nearest_lines_with_strings, vec![13, 14, 15]),
+            other => panic!("expected NoStringFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_toml_keys_quote_segments_that_are_not_bare() {
+        let strings = toml_strings("[\"a.b\"]\n\"key with spaces\" = \"v\"\n").unwrap();
+
+        assert_eq!(strings[0].key, "\"a.b\".\"key with spaces\"");
+        assert_eq!(
+            find_toml_string("[\"a.b\"]\n\"key with spaces\" = \"v\"\n", &strings[0].key)
+                .unwrap()
+                .value,
+            "v"
+        );
+        assert!(matches!(toml_strings("key = "), Err(Error::TomlError(_))));
+    }
+
+    // Tests for Markdown code
+    fn read_markdown_fixture() -> String {
+        read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("docs.md"),
+        )
+    }
+
+    #[test]
+    fn test_markdown_code_blocks_handle_tildes_indents_and_nesting() {
+        let source = read_markdown_fixture();
+
+        let blocks = markdown_code_blocks(&source);
+        let blocks: Vec<(Option<&str>, usize, usize, &str)> = blocks
+            .iter()
+            .map(|block| {
+                (
+                    block.language.as_deref(),
+                    block.start_line,
+                    block.end_line,
+                    &source[block.content.clone()],
+                )
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (Some("rust"), 5, 7, "let greeting = \"hello world\";"),
+                (Some("sh"), 11, 13, "  cargo run -- string \"hi there\""),
+                (
+                    Some("markdown"),
+                    15,
+                    19,
+                    "```text\nnested fence stays inside\n```"
+                ),
+                (None, 23, 24, "last block"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_code_blocks_empty_and_crlf() {
+        let source = "```\r\n```\r\n~~~ toml extra\r\na = 1\r\n~~~\r\n";
+        let blocks = markdown_code_blocks(source);
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(&source[blocks[0].content.clone()], "");
+        assert_eq!(blocks[1].language.as_deref(), Some("toml"));
+        assert_eq!(&source[blocks[1].content.clone()], "a = 1");
+        // A backtick fence can't close a tilde block, and a closing fence takes no info string
+        assert_eq!(markdown_code_blocks("~~~\n```\n~~~ x\n").len(), 1);
+        assert_eq!(markdown_code_blocks("~~~\n```\n~~~ x\n")[0].end_line, 3);
+    }
+
+    #[test]
+    fn test_markdown_inline_code_skips_fenced_blocks() {
+        let source = read_markdown_fixture();
+
+        let spans = markdown_inline_code(&source);
+        let spans: Vec<(usize, &str)> = spans
+            .iter()
+            .map(|code| (code.start_line, &source[code.content.clone()]))
+            .collect();
+        assert_eq!(spans, vec![(3, "cargo build"), (3, "use `span` here")]);
+        assert_eq!(
+            markdown_inline_code("` a ` and `  ` and `unclosed")
+                .iter()
+                .map(|code| code.content.clone())
+                .collect::<Vec<_>>(),
+            vec![2..3, 11..13]
+        );
+    }
+
+    #[test]
+    fn test_find_markdown_code_by_line() {
+        let source = read_markdown_fixture();
+
+        assert_eq!(find_markdown_code(&source, 17).unwrap().start_line, 15);
+        assert_eq!(
+            find_markdown_code(&source, 15).unwrap().language.as_deref(),
+            Some("markdown")
+        );
+        assert!(matches!(
+            find_markdown_code(&source, 3),
+            Err(Error::MultipleStringsFound)
+        ));
+        assert!(find_markdown_code("see `x`\n", 1).unwrap().inline);
+        match find_markdown_code(&source, 1) {
+            Err(Error::NoStringFound {
+                nearest_lines_with_strings,
+                ..
+            }) => assert_eq!(nearest_lines_with_strings, vec![3, 5, 6]),
+            other => panic!("expected NoStringFound, got {:?}", other),
+        }
+    }
+
+    // Tests for sentence granularity
+    #[test]
+    fn test_sentence_granularity() {
+        let content = "Hello world. How are you? Fine!";
+        let spans = get_word_spans(content, false, &Granularity::Sentence).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "Hello world.".to_string(),
+                    start: 0,
+                    end: 12
+                },
+                WordSpan {
+                    word: "How are you?".to_string(),
+                    start: 13,
+                    end: 25
+                },
+                WordSpan {
+                    word: "Fine!".to_string(),
+                    start: 26,
+                    end: 31
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentence_granularity_trims_whitespace() {
+        let content = "  First one.   Second one.  \n";
+        let spans = get_word_spans(content, false, &Granularity::Sentence).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "First one.".to_string(),
+                    start: 2,
+                    end: 12
+                },
+                WordSpan {
+                    word: "Second one.".to_string(),
+                    start: 15,
+                    end: 26
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentence_granularity_without_terminal_punctuation() {
+        let content = "no punctuation here";
+        let spans = get_word_spans(content, false, &Granularity::Sentence).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![WordSpan {
+                word: "no punctuation here".to_string(),
+                start: 0,
+                end: 19
+            }]
+        );
+    }
+
+    // Tests for index-preserving filtering
+    #[test]
+    fn test_indexed_filtering_keeps_original_indices() {
+        let spans = get_word_spans("the quick brown fox jumps", false, &Granularity::Word).unwrap();
+        let indexed_spans = indexed(spans.clone());
+
+        let filters = vec!["fox".to_string(), "jumps".to_string()];
+        let result = filter_word_spans(indexed_spans, &filters, &FilterMode::Exact, false).unwrap();
+
+        assert_eq!(
+            result,
+            vec![
+                (
+                    3,
+                    WordSpan {
+                        word: "fox".to_string(),
+                        start: 16,
+                        end: 19
+                    }
+                ),
+                (
+                    4,
+                    WordSpan {
+                        word: "jumps".to_string(),
+                        start: 20,
+                        end: 25
+                    }
+                )
+            ]
+        );
+        for (index, span) in result {
+            assert_eq!(spans[index], span);
+        }
+    }
+
+    #[test]
+    fn test_indexed_exclusion_keeps_original_indices() {
+        let indexed_spans =
+            indexed(get_word_spans("a cat and a dog", false, &Granularity::Word).unwrap());
+
+        let excludes = vec!["a".to_string(), "and".to_string()];
+        let result =
+            exclude_word_spans(indexed_spans, &excludes, &FilterMode::Exact, false).unwrap();
+
+        let indices: Vec<usize> = result.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, vec![1, 4]);
+    }
+
+    // Tests for language detection
+    #[test]
+    fn test_detect_language_english_vs_french() {
+        let content = "The quick brown fox jumps over the lazy dog and runs away into the forest. \
+                       Le renard brun rapide saute par-dessus le chien paresseux et s'enfuit dans la forêt.";
+        let spans = get_word_spans(content, false, &Granularity::Sentence).unwrap();
+
+        let languages: Vec<Option<&str>> = spans
+            .iter()
+            .map(|span| detect_language(&span.word, 0))
+            .collect();
+        assert_eq!(languages, vec![Some("eng"), Some("fra")]);
+    }
+
+    #[test]
+    fn test_detect_language_skips_short_text() {
+        assert_eq!(detect_language("the", 4), None);
+        assert_eq!(
+            detect_language("The quick brown fox jumps over the lazy dog", 4),
+            Some("eng")
+        );
+    }
+
+    // Tests for line granularity
+    #[test]
+    fn test_line_granularity() {
+        let content = "first line\n  second line\nthird";
+        let spans = get_word_spans(content, false, &Granularity::Line).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "first line".to_string(),
+                    start: 0,
+                    end: 10
+                },
+                WordSpan {
+                    word: "  second line".to_string(),
+                    start: 11,
+                    end: 24
+                },
+                WordSpan {
+                    word: "third".to_string(),
+                    start: 25,
+                    end: 30
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_granularity_crlf_and_trailing_newline() {
+        let content = "one\r\ntwo\r\n";
+        let spans = get_word_spans(content, false, &Granularity::Line).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "one".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "two".to_string(),
+                    start: 5,
+                    end: 8
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_granularity_skips_blank_lines() {
+        let content = "alpha\n\n   \nbeta";
+        let spans = get_word_spans(content, false, &Granularity::Line).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "alpha".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "beta".to_string(),
+                    start: 11,
+                    end: 15
+                }
+            ]
+        );
+    }
+
+    // Tests for quote protection in default mode
+    #[test]
+    fn test_protect_quotes_compared_to_other_modes() {
+        let content = "don't split \"quoted text\" here";
+
+        assert_eq!(
+            get_word_spans(content, false, &Granularity::Word).unwrap(),
+            vec![
+                WordSpan {
+                    word: "don't".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "split".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "\"".to_string(),
+                    start: 12,
+                    end: 13
+                },
+                WordSpan {
+                    word: "quoted".to_string(),
+                    start: 13,
+                    end: 19
+                },
+                WordSpan {
+                    word: "text".to_string(),
+                    start: 20,
+                    end: 24
+                },
+                WordSpan {
+                    word: "\"".to_string(),
+                    start: 24,
+                    end: 25
+                },
+                WordSpan {
+                    word: "here".to_string(),
+                    start: 26,
+                    end: 30
+                }
+            ]
+        );
+
+        // strings-as-tokens also treats the apostrophe as an (unclosed) quote
+        assert_eq!(
+            get_word_spans(content, true, &Granularity::Word).unwrap(),
+            vec![
+                WordSpan {
+                    word: "don".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "'t split \"quoted text\" here".to_string(),
+                    start: 3,
+                    end: 30
+                }
+            ]
+        );
+
+        assert_eq!(
+            get_word_spans_protecting_quotes(content).unwrap(),
+            vec![
+                WordSpan {
+                    word: "don't".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "split".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "\"quoted text\"".to_string(),
+                    start: 12,
+                    end: 25
+                },
+                WordSpan {
+                    word: "here".to_string(),
+                    start: 26,
+                    end: 30
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_protect_quotes_escaped_and_unclosed() {
+        let content = "a(\"x \\\" y\") \"open é";
+        let spans = get_word_spans_protecting_quotes(content).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "a".to_string(),
+                    start: 0,
+                    end: 1
+                },
+                WordSpan {
+                    word: "(".to_string(),
+                    start: 1,
+                    end: 2
+                },
+                WordSpan {
+                    word: "\"x \\\" y\"".to_string(),
+                    start: 2,
+                    end: 10
+                },
+                WordSpan {
+                    word: ")".to_string(),
+                    start: 10,
+                    end: 11
+                },
+                WordSpan {
+                    word: "\"open é".to_string(),
+                    start: 12,
+                    end: 20
+                }
+            ]
+        );
+    }
+
+    // Tests for extracting every literal in a file
+    #[test]
+    fn test_find_all_strings_reports_starting_lines() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("multiline.rs");
+
+        let strings = find_all_strings_in_source(&read_test_file(&test_file_path)).unwrap();
+
+        assert_eq!(strings, vec![
+            (2, "this is a\n             multiline string with\n             multiple words per line".to_string()),
+            (5, "single line string".to_string())
+        ]);
+    }
+
+    #[test]
+    fn test_find_all_strings_same_line() {
+        let code = r#"
+        fn main() {
+            let s = "hello"; let t = "world";
+        }
+        "#;
+
+        let file = syn::parse_file(code).unwrap();
+
+        assert_eq!(
+            find_all_strings(&file).unwrap(),
+            vec![(3, "hello".to_string()), (3, "world".to_string())]
+        );
+    }
+
+    // Tests for densest window search
+    #[test]
+    fn test_densest_window_finds_cluster() {
+        let content = "x filler text here then x x x cluster and more filler x";
+        let spans = unindexed(
+            filter_word_spans(
+                indexed(get_word_spans(content, false, &Granularity::Word).unwrap()),
+                &["x".to_string()],
+                &FilterMode::Exact,
+                false,
+            )
+            .unwrap(),
+        );
+
+        let densest = find_densest_window(&spans, 5).unwrap();
+
+        assert_eq!(
+            densest,
+            DensestWindow {
+                start: 24,
+                end: 29,
+                count: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_densest_window_partial_spans_excluded() {
+        let spans = vec![
+            WordSpan {
+                word: "hello".to_string(),
+                start: 0,
+                end: 5,
+            },
+            WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11,
+            },
+        ];
+
+        assert_eq!(
+            find_densest_window(&spans, 10),
+            Some(DensestWindow {
+                start: 0,
+                end: 10,
+                count: 1
+            })
+        );
+        assert_eq!(
+            find_densest_window(&spans, 11),
+            Some(DensestWindow {
+                start: 0,
+                end: 11,
+                count: 2
+            })
+        );
+        assert_eq!(find_densest_window(&spans, 3), None);
+        assert_eq!(find_densest_window(&[], 3), None);
+    }
+
+    // Tests for column disambiguation
+    #[test]
+    fn test_column_selects_literal_on_shared_line() {
+        let code = r#"
+fn main() {
+    let s = "hello"; let t = "world";
+}
+"#;
+
+        let file = syn::parse_file(code).unwrap();
+
+        assert!(matches!(
+            find_strings_on_line(&file, 3, None),
+            Err(Error::MultipleStringsFound)
+        ));
+        assert_eq!(find_strings_on_line(&file, 3, Some(13)).unwrap(), "hello");
+        assert_eq!(find_strings_on_line(&file, 3, Some(19)).unwrap(), "hello");
+        assert_eq!(find_strings_on_line(&file, 3, Some(30)).unwrap(), "world");
+        assert!(matches!(
+            find_strings_on_line(&file, 3, Some(22)),
+            Err(Error::NoStringFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_column_on_multiline_literal() {
+        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-files")
+            .join("multiline.rs");
+
+        // The literal opens at column 13 of line 2 and continues through line 4
+        assert!(matches!(
+            find_string_in_source(&read_test_file(&test_file_path), 2, Some(5)),
+            Err(Error::NoStringFound { .. })
+        ));
+        assert!(find_string_in_source(&read_test_file(&test_file_path), 2, Some(13)).is_ok());
+        assert!(find_string_in_source(&read_test_file(&test_file_path), 3, Some(1)).is_ok());
+        assert!(find_string_in_source(&read_test_file(&test_file_path), 4, Some(37)).is_ok());
+        assert!(matches!(
+            find_string_in_source(&read_test_file(&test_file_path), 4, Some(38)),
+            Err(Error::NoStringFound { .. })
+        ));
+    }
+
+    // Tests for dotted identifier merging
+    #[test]
+    fn test_dotted_identifiers_with_trailing_dot() {
+        let content = "ping first.last and team.sub done.";
+        let spans =
+            merge_dotted_identifiers(get_word_spans(content, false, &Granularity::Word).unwrap());
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "ping".to_string(),
+                    start: 0,
+                    end: 4
+                },
+                WordSpan {
+                    word: "first.last".to_string(),
+                    start: 5,
+                    end: 15
+                },
+                WordSpan {
+                    word: "and".to_string(),
+                    start: 16,
+                    end: 19
+                },
+                WordSpan {
+                    word: "team.sub".to_string(),
+                    start: 20,
+                    end: 28
+                },
+                WordSpan {
+                    word: "done".to_string(),
+                    start: 29,
+                    end: 33
+                },
+                WordSpan {
+                    word: ".".to_string(),
+                    start: 33,
+                    end: 34
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dotted_identifiers_with_digits() {
+        let content = "ping host1.example.v2 and a.1b.";
+        let spans =
+            merge_dotted_identifiers(get_word_spans(content, false, &Granularity::Word).unwrap());
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "ping".to_string(),
+                    start: 0,
+                    end: 4
+                },
+                WordSpan {
+                    word: "host1.example.v2".to_string(),
+                    start: 5,
+                    end: 21
+                },
+                WordSpan {
+                    word: "and".to_string(),
+                    start: 22,
+                    end: 25
+                },
+                WordSpan {
+                    word: "a.1b".to_string(),
+                    start: 26,
+                    end: 30
+                },
+                WordSpan {
+                    word: ".".to_string(),
+                    start: 30,
+                    end: 31
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dotted_identifiers_not_across_spaces_or_double_dots() {
+        let content = "x1 . y foo..bar";
+        let spans =
+            merge_dotted_identifiers(get_word_spans(content, false, &Granularity::Word).unwrap());
+
+        let words: Vec<&str> = spans.iter().map(|span| span.word.as_str()).collect();
+        assert_eq!(words, vec!["x1", ".", "y", "foo", ".", ".", "bar"]);
+    }
+
+    // Tests for serde support
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_word_span_serde_field_names() {
+        let span = WordSpan {
+            word: "hello".to_string(),
+            start: 0,
+            end: 5,
+        };
+
+        let json = serde_json::to_string(&span).unwrap();
+
+        assert_eq!(json, r#"{"word":"hello","start":0,"end":5}"#);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_word_span_serde_round_trip() {
+        let spans = get_word_spans("héllo, \"world\"", false, &Granularity::Word).unwrap();
+
+        let json = serde_json::to_string(&spans).unwrap();
+        let decoded: Vec<WordSpan> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, spans);
+    }
+
+    // Tests for byte string literals
+    #[test]
+    fn test_byte_string_literal() {
+        let code = r#"
+        fn main() {
+            let s = b"foo bar";
+        }
+        "#;
+
+        let content = find_string_in_source(code, 3, None).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "foo".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "bar".to_string(),
+                    start: 4,
+                    end: 7
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_byte_string_invalid_utf8_is_lossy() {
+        let code = r#"
+        fn main() {
+            let s = b"ok \xff bytes";
+        }
+        "#;
+
+        let content = find_string_in_source(code, 3, None).unwrap();
+
+        assert_eq!(content, "ok \u{FFFD} bytes");
+    }
+
+    #[test]
+    fn test_byte_string_literal_kind_and_utf8_check() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("byte_string.rs"),
+        );
+        let literals = find_string_literals_in_source(&source).unwrap();
+
+        let found: Vec<(&str, LiteralKind, bool)> = literals
+            .iter()
+            .map(|literal| (literal.value.as_str(), literal.kind, literal.lossy))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("hello world", LiteralKind::ByteStr, false),
+                ("café ABC", LiteralKind::ByteStr, false),
+                ("bad \u{FFFD} byte", LiteralKind::ByteStr, true),
+                ("plain", LiteralKind::Str, false)
+            ]
+        );
+        assert!(literals[1].check_utf8().is_ok());
+        assert!(matches!(
+            literals[2].check_utf8(),
+            Err(Error::NonUtf8Literal(4))
+        ));
+    }
+
+    // Tests for C string literals
+    #[test]
+    fn test_c_string_literal() {
+        let code = r#"
+        fn main() {
+            let s = c"foo bar";
+        }
+        "#;
+
+        let content = find_string_in_source(code, 3, None).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(content, "foo bar");
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "foo".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "bar".to_string(),
+                    start: 4,
+                    end: 7
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_string_invalid_utf8_is_error() {
+        let code = r#"
+        fn main() {
+            let s = c"ok \xff bytes";
+        }
+        "#;
+
+        assert!(matches!(
+            find_string_in_source(code, 3, None),
+            Err(Error::InvalidCString(3))
+        ));
+        assert!(matches!(
+            find_all_strings_in_source(code),
+            Err(Error::InvalidCString(3))
+        ));
+    }
+
+    #[test]
+    fn test_c_string_interior_nul_is_parse_error() {
+        let code = r#"
+        fn main() {
+            let s = c"foo\0bar";
+        }
+        "#;
+
+        assert!(matches!(
+            find_string_in_source(code, 3, None),
+            Err(Error::ParseError(_))
+        ));
+    }
+
+    // Tests for batch line lookup
+    #[test]
+    fn test_find_strings_on_three_lines() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("multiline.rs"),
+        );
+
+        let strings = find_strings_on_lines_in_source(&source, &[5, 3, 2]).unwrap();
+
+        let multiline =
+            "this is a\n             multiline string with\n             multiple words per line"
+                .to_string();
+        assert_eq!(
+            strings,
+            vec![
+                (5, "single line string".to_string()),
+                (3, multiline.clone()),
+                (2, multiline)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_strings_on_lines_propagates_missing_line() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("simple.rs"),
+        );
+
+        assert!(matches!(
+            find_strings_on_lines_in_source(&source, &[2, 1]),
+            Err(Error::NoStringFound { .. })
+        ));
+    }
+
+    // Tests for the lazy span iterators
+    fn iterator_test_inputs() -> Vec<String> {
+        let mut inputs: Vec<String> = [
+            "",
+            "hello world test",
+            "  leading and trailing  ",
+            "She said \"hello world\" and 'goodbye' then `code here`",
+            "Text with \"escaped \\\" quote\" here",
+            "\"unclosed quote at end",
+            "café naïve résumé",
+            "team.sub host1.example",
+        ]
+        .iter()
+        .map(|input| input.to_string())
+        .collect();
+
+        for name in [
+            "simple.rs",
+            "escaped.rs",
+            "multiline.rs",
+            "multiline_raw.rs",
+            "raw_string.rs",
+        ] {
+            let source = read_test_file(
+                &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("test-files")
+                    .join(name),
+            );
+            inputs.extend(
+                find_all_strings_in_source(&source)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(_, content)| content),
+            );
+        }
+        inputs
+    }
+
+    #[test]
+    fn test_word_spans_iterator_matches_vec() {
+        for input in iterator_test_inputs() {
+            for strings_as_tokens in [false, true] {
+                let mut streamed = Vec::new();
+                for span in word_spans(&input, strings_as_tokens) {
+                    streamed.push(span);
+                }
+
+                assert_eq!(
+                    streamed,
+                    get_word_spans(&input, strings_as_tokens, &Granularity::Word).unwrap(),
+                    "input: {:?}",
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_word_spans_iterator_stops_early() {
+        let first = word_spans("hello world test", false).find(|span| span.word.starts_with('w'));
+
+        assert_eq!(
+            first,
+            Some(WordSpan {
+                word: "world".to_string(),
+                start: 6,
+                end: 11
+            })
+        );
+    }
+
+    #[test]
+    fn test_filter_adapters_match_vec() {
+        let filters = vec!["hello".to_string(), "line".to_string()];
+        for input in iterator_test_inputs() {
+            let spans = get_word_spans(&input, false, &Granularity::Word).unwrap();
+
+            let streamed: Vec<IndexedSpan> = filter_spans(
+                word_spans(&input, false).enumerate(),
+                &filters,
+                &FilterMode::Contains,
+                true,
+            )
+            .unwrap()
+            .collect();
+            assert_eq!(
+                streamed,
+                filter_word_spans(
+                    indexed(spans.clone()),
+                    &filters,
+                    &FilterMode::Contains,
+                    true
+                )
+                .unwrap()
+            );
+
+            let streamed: Vec<IndexedSpan> = exclude_spans(
+                word_spans(&input, false).enumerate(),
+                &filters,
+                &FilterMode::Contains,
+                true,
+            )
+            .unwrap()
+            .collect();
+            assert_eq!(
+                streamed,
+                exclude_word_spans(indexed(spans), &filters, &FilterMode::Contains, true).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_spans_reports_invalid_regex() {
+        let result = filter_spans(
+            word_spans("hello", false).enumerate(),
+            &["[".to_string()],
+            &FilterMode::Regex,
+            false,
+        );
+
+        assert!(matches!(result, Err(Error::RegexError(_))));
+    }
+
+    // Tests for span clustering
+    #[test]
+    fn test_cluster_accented_case_variants() {
+        let spans = get_word_spans("Café cafe CAFÉ tea", false, &Granularity::Word).unwrap();
+        let all_keys = [
+            ClusterKey::Lowercase,
+            ClusterKey::StripAccents,
+            ClusterKey::TrimPunctuation,
+        ];
+
+        let clusters = cluster_spans(indexed(spans), &all_keys);
+
+        assert_eq!(
+            clusters,
+            vec![
+                (
+                    "cafe".to_string(),
+                    vec![
+                        (
+                            0,
+                            WordSpan {
+                                word: "Café".to_string(),
+                                start: 0,
+                                end: 5
+                            }
+                        ),
+                        (
+                            1,
+                            WordSpan {
+                                word: "cafe".to_string(),
+                                start: 6,
+                                end: 10
+                            }
+                        ),
+                        (
+                            2,
+                            WordSpan {
+                                word: "CAFÉ".to_string(),
+                                start: 11,
+                                end: 16
+                            }
+                        )
+                    ]
+                ),
+                (
+                    "tea".to_string(),
+                    vec![(
+                        3,
+                        WordSpan {
+                            word: "tea".to_string(),
+                            start: 17,
+                            end: 20
+                        }
+                    )]
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_word_applies_only_selected_keys() {
+        assert_eq!(
+            normalize_word("\"Café!\"", &[ClusterKey::TrimPunctuation]),
+            "Café"
+        );
+        assert_eq!(normalize_word("Café", &[ClusterKey::StripAccents]), "Cafe");
+        assert_eq!(normalize_word("Café", &[ClusterKey::Lowercase]), "café");
+        assert_eq!(normalize_word("!", &[ClusterKey::TrimPunctuation]), "!");
+    }
+
+    // Tests for span summaries
+    #[test]
+    fn test_summarize_spans() {
+        let spans = get_word_spans("the café and the tea", false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            summarize_spans(&spans),
+            SpanSummary {
+                spans: 5,
+                unique_words: 4,
+                covered_chars: 16
+            }
+        );
+    }
+
+    #[test]
+    fn test_summarize_quoted_tokens() {
+        let spans =
+            get_word_spans(r#"say "hi there" and "bye""#, true, &Granularity::Word).unwrap();
+        let filtered = filter_word_spans(
+            indexed(spans),
+            &["^\"".to_string()],
+            &FilterMode::Regex,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            summarize_spans(&unindexed(filtered)),
+            SpanSummary {
+                spans: 2,
+                unique_words: 2,
+                covered_chars: 15
+            }
+        );
+    }
+
+    // Tests for the SpanExtractor builder
+    #[test]
+    fn test_span_extractor_defaults_match_get_word_spans() {
+        let content = "Hello world, this is a test.";
+
+        assert_eq!(
+            SpanExtractor::new().extract(content).unwrap(),
+            get_word_spans(content, false, &Granularity::Word).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_span_extractor_combines_options() {
+        let extractor = SpanExtractor::new()
+            .strings_as_tokens(true)
+            .filters(vec!["\"".to_string(), "said".to_string()])
+            .filter_mode(FilterMode::Contains)
+            .excludes(vec!["\"bye\"".to_string()])
+            .ignore_case(true);
+
+        let spans = extractor
+            .extract_indexed(r#"She SAID "hello world" and "bye""#)
+            .unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                (
+                    1,
+                    WordSpan {
+                        word: "SAID".to_string(),
+                        start: 4,
+                        end: 8
+                    }
+                ),
+                (
+                    2,
+                    WordSpan {
+                        word: "\"hello world\"".to_string(),
+                        start: 9,
+                        end: 22
+                    }
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_span_extractor_reports_invalid_regex() {
+        let extractor = SpanExtractor::new()
+            .filters(vec!["[".to_string()])
+            .filter_mode(FilterMode::Regex);
+
+        assert!(matches!(
+            extractor.extract("hello"),
+            Err(Error::RegexError(_))
+        ));
+    }
+
+    // Tests for line and column positions
+    #[test]
+    fn test_line_index_positions_per_line() {
+        let content = "a\nb\nc";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+        let index = LineIndex::new(content);
+
+        let positions: Vec<(usize, usize)> = spans
+            .iter()
+            .map(|span| index.position(span.start))
+            .collect();
+
+        assert_eq!(positions, vec![(1, 1), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn test_line_index_counts_characters() {
+        let content = "café au lait\r\n  naïve";
+        let index = LineIndex::new(content);
+
+        assert_eq!(index.position(content.find("au").unwrap()), (1, 6));
+        assert_eq!(index.position(content.find("naïve").unwrap()), (2, 3));
+    }
+
+    #[test]
+    fn test_line_index_line_ranges() {
+        let content = "one\r\n\ntwo words\n";
+        let index = LineIndex::new(content);
+
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(
+            index.line_range(1).map(|range| &content[range]),
+            Some("one")
+        );
+        assert_eq!(index.line_range(2).map(|range| &content[range]), Some(""));
+        assert_eq!(index.line_range(3), Some(6..15));
+        assert_eq!(index.line_range(0), None);
+        assert_eq!(index.line_range(4), None);
+    }
+
+    // Tests for word frequencies
+    #[test]
+    fn test_word_frequencies_sorted_by_count_then_word() {
+        let spans = get_word_spans("b a c b a b", false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            word_frequencies(&spans, false),
+            vec![
+                ("b".to_string(), 3),
+                ("a".to_string(), 2),
+                ("c".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_word_frequencies_fold_case() {
+        let spans = get_word_spans("Hello hello World", false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            word_frequencies(&spans, false),
+            vec![
+                ("Hello".to_string(), 1),
+                ("World".to_string(), 1),
+                ("hello".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            word_frequencies(&spans, true),
+            vec![("hello".to_string(), 2), ("world".to_string(), 1)]
+        );
+    }
+
+    // Tests for string literal locations
+    #[test]
+    fn test_find_string_literals_with_locations() {
+        let code = "fn main() {\n    let a = \"hello\";\n    let b = r#\"raw\n text\"#;\n}\n";
+        let file = syn::parse_file(code).unwrap();
+
+        let literals = find_string_literals(&file).unwrap();
+
+        assert_eq!(
+            literals,
+            vec![
+                StringLiteralInfo {
+                    value: "hello".to_string(),
+                    start_line: 2,
+                    start_column: 12,
+                    end_line: 2,
+                    end_column: 19,
+                    raw: false,
+                    token: "\"hello\"".to_string(),
+                    kind: LiteralKind::Str,
+                    lossy: false
+                },
+                StringLiteralInfo {
+                    value: "raw\n text".to_string(),
+                    start_line: 3,
+                    start_column: 12,
+                    end_line: 4,
+                    end_column: 7,
+                    raw: true,
+                    token: "r#\"raw\n text\"#".to_string(),
+                    kind: LiteralKind::Str,
+                    lossy: false
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_on_line_includes_multiline_literals() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("multiline.rs"),
+        );
+        let literals = find_string_literals(&syn::parse_file(&source).unwrap()).unwrap();
+
+        let on_line_3: Vec<usize> = find_on_line(&literals, 3)
+            .iter()
+            .map(|literal| literal.start_line)
+            .collect();
+        let on_line_5: Vec<&str> = find_on_line(&literals, 5)
+            .iter()
+            .map(|literal| literal.value.as_str())
+            .collect();
+
+        assert_eq!(on_line_3, vec![2]);
+        assert_eq!(on_line_5, vec!["single line string"]);
+        assert!(find_on_line(&literals, 1).is_empty());
+    }
+
+    // Tests for JSON-aware tokenization
+    #[test]
+    fn test_json_aware_keeps_strings_whole() {
+        let spans = get_json_aware_spans(r#"{"a": "b c", "n": 5}"#, false);
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "{".to_string(),
+                    start: 0,
+                    end: 1
+                },
+                WordSpan {
+                    word: "\"a\"".to_string(),
+                    start: 1,
+                    end: 4
+                },
+                WordSpan {
+                    word: ":".to_string(),
+                    start: 4,
+                    end: 5
+                },
+                WordSpan {
+                    word: "\"b c\"".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: ",".to_string(),
+                    start: 11,
+                    end: 12
+                },
+                WordSpan {
+                    word: "\"n\"".to_string(),
+                    start: 13,
+                    end: 16
+                },
+                WordSpan {
+                    word: ":".to_string(),
+                    start: 16,
+                    end: 17
+                },
+                WordSpan {
+                    word: "5".to_string(),
+                    start: 18,
+                    end: 19
+                },
+                WordSpan {
+                    word: "}".to_string(),
+                    start: 19,
+                    end: 20
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_aware_strip_quotes() {
+        let spans = get_json_aware_spans(r#"{"a": "b c", "n": 5}"#, true);
+        let strings: Vec<(&str, usize, usize)> = spans
+            .iter()
+            .map(|span| (span.word.as_str(), span.start, span.end))
+            .collect();
+
+        assert_eq!(
+            strings,
+            vec![
+                ("{", 0, 1),
+                ("a", 2, 3),
+                (":", 4, 5),
+                ("b c", 7, 10),
+                (",", 11, 12),
+                ("n", 14, 15),
+                (":", 16, 17),
+                ("5", 18, 19),
+                ("}", 19, 20)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_aware_literals_and_escapes() {
+        let words: Vec<String> = get_json_aware_spans(r#"[true, null, -1.5e3, "q\"x"]"#, false)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+
+        assert_eq!(
+            words,
+            vec![
+                "[",
+                "true",
+                ",",
+                "null",
+                ",",
+                "-1.5e3",
+                ",",
+                "\"q\\\"x\"",
+                "]"
+            ]
+        );
+    }
+
+    // Tests for the Tokenizer trait
+    struct CommaTokenizer;
+
+    impl Tokenizer for CommaTokenizer {
+        fn tokenize(&self, content: &str) -> Vec<WordSpan> {
+            let mut spans = Vec::new();
+            let mut start = 0;
+            for field in content.split(',') {
+                spans.push(WordSpan {
+                    word: field.to_string(),
+                    start,
+                    end: start + field.len(),
+                });
+                start += field.len() + 1;
+            }
+            spans
+        }
+    }
+
+    fn tokenize_with(tokenizer: &dyn Tokenizer, content: &str) -> Vec<WordSpan> {
+        tokenizer.tokenize(content)
+    }
+
+    #[test]
+    fn test_custom_tokenizer_through_trait_object() {
+        let spans = tokenize_with(&CommaTokenizer, "id,name,email");
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "id".to_string(),
+                    start: 0,
+                    end: 2
+                },
+                WordSpan {
+                    word: "name".to_string(),
+                    start: 3,
+                    end: 7
+                },
+                WordSpan {
+                    word: "email".to_string(),
+                    start: 8,
+                    end: 13
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_tokenizer_output_filters() {
+        let spans = CommaTokenizer.tokenize("id,name,email");
+
+        let filtered = filter_word_spans(
+            indexed(spans),
+            &["name".to_string()],
+            &FilterMode::Exact,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            filtered,
+            vec![(
+                1,
+                WordSpan {
+                    word: "name".to_string(),
+                    start: 3,
+                    end: 7
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_builtin_tokenizers_match_get_word_spans() {
+        let content = r#"She said "hello world" today."#;
+
+        assert_eq!(
+            WordBoundTokenizer.tokenize(content),
+            get_word_spans(content, false, &Granularity::Word).unwrap()
+        );
+        assert_eq!(
+            QuotedStringsTokenizer.tokenize(content),
+            get_word_spans(content, true, &Granularity::Word).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_span_extractor_without_filters_matches_get_word_spans() {
+        let content = "She said \"hello world\" today. Then left.\nBye";
+
+        for granularity in [Granularity::Word, Granularity::Sentence, Granularity::Line] {
+            for strings_as_tokens in [false, true] {
+                let extractor = SpanExtractor::new()
+                    .strings_as_tokens(strings_as_tokens)
+                    .granularity(granularity.clone());
+                assert_eq!(
+                    extractor.extract(content).unwrap(),
+                    get_word_spans(content, strings_as_tokens, &granularity).unwrap(),
+                    "{:?} with strings_as_tokens {}",
+                    granularity,
+                    strings_as_tokens
+                );
+            }
+        }
+    }
+
+    // Tests for span sorting
+    #[test]
+    fn test_sort_spans_by_word_folds_case_when_requested() {
+        let spans = get_word_spans("banana Apple apple cherry", false, &Granularity::Word).unwrap();
+
+        let mut sorted = indexed(spans.clone());
+        sort_spans(&mut sorted, &SortOrder::Word, false);
+        let words: Vec<String> = unindexed(sorted)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(words, vec!["Apple", "apple", "banana", "cherry"]);
+
+        let mut sorted = indexed(spans);
+        sort_spans(&mut sorted, &SortOrder::WordDesc, true);
+        let words: Vec<(String, usize)> = unindexed(sorted)
+            .into_iter()
+            .map(|span| (span.word, span.start))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                ("cherry".to_string(), 19),
+                ("banana".to_string(), 0),
+                ("Apple".to_string(), 7),
+                ("apple".to_string(), 13)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_spans_by_length_ties_on_start() {
+        let mut spans = indexed(get_word_spans("ccc a bb d", false, &Granularity::Word).unwrap());
+
+        sort_spans(&mut spans, &SortOrder::Length, false);
+
+        let order: Vec<usize> = spans.iter().map(|(index, _)| *index).collect();
+        assert_eq!(order, vec![1, 3, 2, 0]);
+
+        sort_spans(&mut spans, &SortOrder::Position, false);
+        let order: Vec<usize> = spans.iter().map(|(index, _)| *index).collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+    }
+
+    // Tests for length percentile filtering
+    #[test]
+    fn test_length_top_percentile() {
+        let spans = get_word_spans(
+            "a bb ccc dddd eeeee ffffff gggggggggg hh i jjj",
+            false,
+            &Granularity::Word,
+        )
+        .unwrap();
+
+        let kept = filter_by_length_percentile(indexed(spans), Some(20.0), None);
+
+        let words: Vec<String> = unindexed(kept).into_iter().map(|span| span.word).collect();
+        assert_eq!(words, vec!["ffffff", "gggggggggg"]);
+    }
+
+    #[test]
+    fn test_length_bottom_percentile_keeps_ties_and_counts_graphemes() {
+        let spans = get_word_spans("naïve a b longer e\u{301}", false, &Granularity::Word).unwrap();
+
+        let kept = filter_by_length_percentile(indexed(spans), None, Some(40.0));
+
+        let words: Vec<String> = unindexed(kept).into_iter().map(|span| span.word).collect();
+        assert_eq!(words, vec!["a", "b", "e\u{301}"]);
+    }
+
+    #[test]
+    fn test_length_percentile_zero_and_both_bands() {
+        let spans = indexed(get_word_spans("a bb ccc dddd", false, &Granularity::Word).unwrap());
+
+        assert!(filter_by_length_percentile(spans.clone(), Some(0.0), None).is_empty());
+
+        let words: Vec<String> =
+            unindexed(filter_by_length_percentile(spans, Some(25.0), Some(25.0)))
+                .into_iter()
+                .map(|span| span.word)
+                .collect();
+        assert_eq!(words, vec!["a", "dddd"]);
+    }
+
+    // Tests for offset range filtering
+    #[test]
+    fn test_offset_range_keeps_contained_spans() {
+        // "alpha" 0-5, "beta" 6-10, "gamma" 11-16, "delta" 17-22
+        let spans =
+            indexed(get_word_spans("alpha beta gamma delta", false, &Granularity::Word).unwrap());
+
+        let words = |kept: Vec<IndexedSpan>| -> Vec<String> {
+            unindexed(kept).into_iter().map(|span| span.word).collect()
+        };
+        assert_eq!(
+            words(filter_by_offset_range(
+                spans.clone(),
+                Some(6),
+                Some(16),
+                false
+            )),
+            vec!["beta", "gamma"]
+        );
+        assert_eq!(
+            words(filter_by_offset_range(
+                spans.clone(),
+                Some(8),
+                Some(13),
+                false
+            )),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            words(filter_by_offset_range(
+                spans.clone(),
+                Some(8),
+                Some(13),
+                true
+            )),
+            vec!["beta", "gamma"]
+        );
+        assert_eq!(
+            words(filter_by_offset_range(spans.clone(), Some(11), None, false)),
+            vec!["gamma", "delta"]
+        );
+        assert_eq!(
+            words(filter_by_offset_range(spans, None, Some(5), true)),
+            vec!["alpha"]
+        );
+    }
+
+    // Tests for deduplication
+    #[test]
+    fn test_dedup_keeps_first_occurrence_in_order() {
+        let spans =
+            get_word_spans("the cat and the hat and The end", false, &Granularity::Word).unwrap();
+
+        let deduped = dedup_spans(indexed(spans.clone()), false);
+        let words: Vec<(usize, String)> = deduped
+            .into_iter()
+            .map(|(index, span)| (index, span.word))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                (0, "the".to_string()),
+                (1, "cat".to_string()),
+                (2, "and".to_string()),
+                (4, "hat".to_string()),
+                (6, "The".to_string()),
+                (7, "end".to_string())
+            ]
+        );
+
+        let deduped = dedup_spans(indexed(spans), true);
+        let words: Vec<String> = unindexed(deduped)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(words, vec!["the", "cat", "and", "hat", "end"]);
+    }
+
+    // Tests for error context
+    #[test]
+    fn test_no_string_found_lists_nearest_lines() {
+        let code = "fn main() {\n    let a = \"one\";\n\n\n\n\n    let b = \"two\";\n}\n";
+
+        let err = find_string_in_source(code, 4, None).unwrap_err();
+
+        assert!(
+            matches!(&err, Error::NoStringFound { line: 4, nearest_lines_with_strings } if *nearest_lines_with_strings == vec![2, 7])
+        );
+        assert_eq!(
+            err.to_string(),
+            "No string found on line 4 (nearest lines with strings: 2, 7)"
+        );
+    }
+
+    #[test]
+    fn test_error_source_chains_underlying_error() {
+        use std::error::Error as _;
+
+        let err = Error::IoError {
+            path: PathBuf::from("missing.rs"),
+            source: std::io::Error::new(std::io::ErrorKind::NotFound, "not found"),
+        };
+
+        assert_eq!(err.to_string(), "File error: missing.rs: not found");
+        assert_eq!(err.source().unwrap().to_string(), "not found");
+        assert!(Error::MultipleStringsFound.source().is_none());
+    }
+
+    // Tests for word adjacency
+    #[test]
+    fn test_word_adjacency_counts_repeated_bigrams() {
+        let spans = get_word_spans("the cat saw the cat", false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            word_adjacency(&spans),
+            vec![
+                (("the".to_string(), "cat".to_string()), 2),
+                (("cat".to_string(), "saw".to_string()), 1),
+                (("saw".to_string(), "the".to_string()), 1)
+            ]
+        );
+    }
+
+    // Tests for WordSpan helpers
+    #[test]
+    fn test_slice_matches_word_for_every_tokenization() {
+        let mut inputs = iterator_test_inputs();
+        inputs.push("First sentence. Second one!\r\nThird line\n\n  last".to_string());
+
+        for input in &inputs {
+            for granularity in [Granularity::Word, Granularity::Sentence, Granularity::Line] {
+                for strings_as_tokens in [false, true] {
+                    for span in get_word_spans(input, strings_as_tokens, &granularity).unwrap() {
+                        assert_eq!(
+                            span.slice(input),
+                            Some(span.word.as_str()),
+                            "input: {:?}, span: {:?}",
+                            input,
+                            span
+                        );
+                        assert_eq!(span.len(), span.word.len());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_slice_rejects_out_of_range_and_mid_char() {
+        let content = "café";
+
+        assert_eq!(
+            WordSpan {
+                word: "é".to_string(),
+                start: 3,
+                end: 5
+            }
+            .slice(content),
+            Some("é")
+        );
+        assert_eq!(
+            WordSpan {
+                word: "?".to_string(),
+                start: 4,
+                end: 5
+            }
+            .slice(content),
+            None
+        );
+        assert_eq!(
+            WordSpan {
+                word: "?".to_string(),
+                start: 3,
+                end: 9
+            }
+            .slice(content),
+            None
+        );
+    }
+
+    #[test]
+    fn test_contains_and_overlaps() {
+        let hello = WordSpan {
+            word: "hello".to_string(),
+            start: 0,
+            end: 5,
+        };
+        let world = WordSpan {
+            word: "world".to_string(),
+            start: 6,
+            end: 11,
+        };
+        let lo_w = WordSpan {
+            word: "lo w".to_string(),
+            start: 3,
+            end: 7,
+        };
+
+        assert!(hello.contains(0) && hello.contains(4) && !hello.contains(5));
+        assert!(!hello.overlaps(&world));
+        assert!(lo_w.overlaps(&hello) && lo_w.overlaps(&world));
+        assert!(!hello.is_empty());
+    }
+
+    #[test]
+    fn test_word_spans_order_and_hash() {
+        let mut spans = get_word_spans("b a b", false, &Granularity::Word).unwrap();
+        spans.reverse();
+        spans.sort();
+
+        let starts: Vec<usize> = spans.iter().map(|span| span.start).collect();
+        assert_eq!(starts, vec![0, 2, 4]);
+
+        let unique: HashSet<WordSpan> =
+            spans.iter().cloned().chain(spans.iter().cloned()).collect();
+        assert_eq!(unique.len(), 3);
+    }
+
+    // Tests for percentage merging
+    #[test]
+    fn test_merge_percentages() {
+        let spans = get_word_spans("up 50% and 3.5% down", false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            merge_percentages(spans),
+            vec![
+                WordSpan {
+                    word: "up".to_string(),
+                    start: 0,
+                    end: 2
+                },
+                WordSpan {
+                    word: "50%".to_string(),
+                    start: 3,
+                    end: 6
+                },
+                WordSpan {
+                    word: "and".to_string(),
+                    start: 7,
+                    end: 10
+                },
+                WordSpan {
+                    word: "3.5%".to_string(),
+                    start: 11,
+                    end: 15
+                },
+                WordSpan {
+                    word: "down".to_string(),
+                    start: 16,
+                    end: 20
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_percentages_per_mille_and_detached_sign() {
+        let spans = get_word_spans("2‰ vs 7 % and x%", false, &Granularity::Word).unwrap();
+        let words: Vec<String> = merge_percentages(spans)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+
+        assert_eq!(words, vec!["2‰", "vs", "7", "%", "and", "x", "%"]);
+    }
+
+    // Tests for number merging
+    #[test]
+    fn test_merge_numbers_leading_decimals_and_exponents() {
+        let content = "take .5 of 3.14e-10 or 6E+23, then end.5 and 2. Next .5e-3";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+        let words: Vec<String> = merge_numbers(content, spans, false)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+
+        assert_eq!(
+            words,
+            vec![
+                "take", ".5", "of", "3.14e-10", "or", "6E+23", ",", "then", "end", ".", "5", "and",
+                "2", ".", "Next", ".5e-3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_numbers_thousands_separators() {
+        let content = "pay 1 000 000 or 12\u{202f}500, not 5 10 or 1234 567";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        let merged = merge_numbers(content, spans.clone(), true);
+        let words: Vec<&str> = merged.iter().map(|span| span.word.as_str()).collect();
+        assert_eq!(
+            words,
+            vec![
+                "pay",
+                "1 000 000",
+                "or",
+                "12\u{202f}500",
+                ",",
+                "not",
+                "5",
+                "10",
+                "or",
+                "1234",
+                "567"
+            ]
+        );
+        assert_eq!((merged[1].start, merged[1].end), (4, 13));
+
+        let words: Vec<String> = merge_numbers(content, spans, false)
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(words[1..4], ["1", "000", "000"]);
+    }
+
+    #[test]
+    fn test_span_extractor_preserve_percentages() {
+        let spans = SpanExtractor::new()
+            .preserve_percentages(true)
+            .extract("up 50% and 3.5% down")
+            .unwrap();
+
+        let words: Vec<String> = spans.into_iter().map(|span| span.word).collect();
+        assert_eq!(words, vec!["up", "50%", "and", "3.5%", "down"]);
+    }
+
+    #[test]
+    fn test_merge_command_flags() {
+        let content = "run --verbose -rf /tmp --dry-run a-b 5 - 3 (-x) --";
+        let spans =
+            merge_command_flags(get_word_spans(content, false, &Granularity::Word).unwrap());
+
+        assert_eq!(
+            spans[..4],
+            [
+                WordSpan {
+                    word: "run".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "--verbose".to_string(),
+                    start: 4,
+                    end: 13
+                },
+                WordSpan {
+                    word: "-rf".to_string(),
+                    start: 14,
+                    end: 17
+                },
+                WordSpan {
+                    word: "/".to_string(),
+                    start: 18,
+                    end: 19
+                }
+            ]
+        );
+        let words: Vec<&str> = spans[5..].iter().map(|span| span.word.as_str()).collect();
+        assert_eq!(
+            words,
+            vec![
+                "--dry-run",
+                "a",
+                "-",
+                "b",
+                "5",
+                "-",
+                "3",
+                "(",
+                "-",
+                "x",
+                ")",
+                "-",
+                "-"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_span_extractor_preserve_flags() {
+        let spans = SpanExtractor::new()
+            .preserve_flags(true)
+            .extract("run --verbose -rf /tmp")
+            .unwrap();
+
+        let words: Vec<String> = spans.into_iter().map(|span| span.word).collect();
+        assert_eq!(words, vec!["run", "--verbose", "-rf", "/", "tmp"]);
+    }
+
+    // Tests for nearest-column selection
+    #[test]
+    fn test_find_string_near_column_with_three_literals() {
+        // Literals cover 1-based columns 9-15, 26-33, and 44-50
+        let code = r#"let a = "first"; let b = "second"; let c = "third";"#;
+        let file = syn::parse_file(&format!("fn main() {{\n{}\n}}\n", code)).unwrap();
+
+        for (column, expected) in [
+            (9, "first"),
+            (15, "first"),
+            (26, "second"),
+            (33, "second"),
+            (44, "third"),
+            (50, "third"),
+        ] {
+            assert_eq!(
+                find_string_near_column(&file, 2, column).unwrap(),
+                expected,
+                "column {}",
+                column
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_string_near_column_picks_next_literal_after_gap() {
+        let code = r#"let a = "first"; let b = "second"; let c = "third";"#;
+        let file = syn::parse_file(&format!("fn main() {{\n{}\n}}\n", code)).unwrap();
+
+        assert_eq!(find_string_near_column(&file, 2, 1).unwrap(), "first");
+        assert_eq!(find_string_near_column(&file, 2, 17).unwrap(), "second");
+        assert_eq!(find_string_near_column(&file, 2, 34).unwrap(), "third");
+        assert!(matches!(
+            find_string_near_column(&file, 2, 51),
+            Err(Error::NoStringFound { .. })
+        ));
+    }
+
+    // Tests for literal selection by index
+    #[test]
+    fn test_find_string_by_index() {
+        let file = syn::parse_file("fn main() {\n    let a = (\"one\", \"two\", \"three\");\n}\n")
+            .unwrap();
+
+        assert_eq!(find_string_by_index(&file, 2, 0).unwrap(), "one");
+        assert_eq!(find_string_by_index(&file, 2, 1).unwrap(), "two");
+        assert_eq!(find_string_by_index(&file, 2, 2).unwrap(), "three");
+    }
+
+    #[test]
+    fn test_find_string_by_index_out_of_range() {
+        let file = syn::parse_file("fn main() {\n    let a = (\"one\", \"two\");\n}\n").unwrap();
+
+        let err = find_string_by_index(&file, 2, 5).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::LiteralIndexOutOfRange {
+                line: 2,
+                index: 5,
+                found: 2
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "No string literal at index 5 on line 2; found 2"
+        );
+    }
+
+    // Tests for boundary-checked slicing
+    #[test]
+    fn test_safe_slice_rejects_non_boundary_ranges() {
+        let content = "naïve café";
+
+        assert_eq!(safe_slice(content, 0, 2).unwrap(), "na");
+        assert!(matches!(
+            safe_slice(content, 0, 3),
+            Err(Error::InvalidSpanBoundary { start: 0, end: 3 })
+        ));
+        assert!(matches!(
+            safe_slice(content, 5, 40),
+            Err(Error::InvalidSpanBoundary { start: 5, end: 40 })
+        ));
+        assert!(matches!(
+            safe_slice(content, 4, 2),
+            Err(Error::InvalidSpanBoundary { start: 4, end: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_replace_spans_skips_non_boundary_span() {
+        let spans = vec![
+            WordSpan {
+                word: "ï".to_string(),
+                start: 3,
+                end: 4,
+            },
+            WordSpan {
+                word: "café".to_string(),
+                start: 7,
+                end: 12,
+            },
+        ];
+
+        let rebuilt = replace_spans(
+            "naïve café",
+            &spans,
+            &[(".*".to_string(), "X".to_string())],
+            &FilterMode::Regex,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(rebuilt, "naïve X");
+    }
+
+    // Tests for raw strings in strings_as_tokens mode
+    #[test]
+    fn test_strings_as_tokens_raw_string_with_hashes() {
+        let content = r##"say r#"he said "hi""# now"##;
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "say".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: r##"r#"he said "hi""#"##.to_string(),
+                    start: 4,
+                    end: 21
+                },
+                WordSpan {
+                    word: "now".to_string(),
+                    start: 22,
+                    end: 25
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_raw_string_hash_counts() {
+        let content = r###"r"a b" (r##"x"# y"##) for"z""###;
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: r#"r"a b""#.to_string(),
+                    start: 0,
+                    end: 6
+                },
+                WordSpan {
+                    word: "(".to_string(),
+                    start: 7,
+                    end: 8
+                },
+                WordSpan {
+                    word: r###"r##"x"# y"##"###.to_string(),
+                    start: 8,
+                    end: 20
+                },
+                WordSpan {
+                    word: ")".to_string(),
+                    start: 20,
+                    end: 21
+                },
+                WordSpan {
+                    word: "for".to_string(),
+                    start: 22,
+                    end: 25
+                },
+                WordSpan {
+                    word: "\"z\"".to_string(),
+                    start: 25,
+                    end: 28
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_as_tokens_unclosed_raw_string() {
+        let content = r##"a r#"open "end"##;
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "a".to_string(),
+                    start: 0,
+                    end: 1
+                },
+                WordSpan {
+                    word: r##"r#"open "end"##.to_string(),
+                    start: 2,
+                    end: content.len()
+                }
+            ]
+        );
+    }
+
+    // Tests for every literal on a line
+    #[test]
+    fn test_find_strings_on_line_all() {
+        let source = "fn main() {\n    let a = (\"first one\", \"second\");\n}\n";
+
+        let literals = find_strings_on_line_all_in_source(source, 2).unwrap();
+        let found: Vec<(&str, usize)> = literals
+            .iter()
+            .map(|literal| (literal.value.as_str(), literal.start_column))
+            .collect();
+        assert_eq!(found, vec![("first one", 13), ("second", 26)]);
+
+        assert!(matches!(
+            find_strings_on_line_all_in_source(source, 1),
+            Err(Error::NoStringFound { line: 1, .. })
+        ));
+    }
+
+    // Tests for span neighbors
+    #[test]
+    fn test_span_neighbors_for_repeated_word() {
+        let content = "the quick fox jumps over a lazy fox";
+        let stream = get_word_spans(content, false, &Granularity::Word).unwrap();
+        let matched = filter_spans(
+            stream.clone().into_iter().enumerate(),
+            &["fox".to_string()],
+            &FilterMode::Exact,
+            false,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+
+        let neighbors = span_neighbors(&stream, &matched);
+
+        assert_eq!(
+            neighbors,
+            vec![
+                SpanNeighbors {
+                    span: WordSpan {
+                        word: "fox".to_string(),
+                        start: 10,
+                        end: 13
+                    },
+                    before: Some("quick".to_string()),
+                    after: Some("jumps".to_string())
+                },
+                SpanNeighbors {
+                    span: WordSpan {
+                        word: "fox".to_string(),
+                        start: 32,
+                        end: 35
+                    },
+                    before: Some("lazy".to_string()),
+                    after: None
+                }
+            ]
+        );
+    }
+
+    // Tests for context windows
+    #[test]
+    fn test_context_windows_merge_and_stop_at_stream_edges() {
+        let content = "one two three four five six seven eight";
+        let stream = get_word_spans(content, false, &Granularity::Word).unwrap();
+        let matched = filter_word_spans(
+            indexed(stream.clone()),
+            &[
+                "one".to_string(),
+                "four".to_string(),
+                "five".to_string(),
+                "eight".to_string(),
+            ],
+            &FilterMode::Exact,
+            false,
+        )
+        .unwrap();
+
+        let with_context = spans_with_context(&stream, &matched, 1);
+
+        let indices: Vec<usize> = with_context.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(
+            spans_with_context(&stream, &matched[1..2], 2),
+            indexed(stream.clone())
+                .into_iter()
+                .skip(1)
+                .take(5)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(spans_with_context(&stream, &matched, 0), matched);
+    }
+
+    // Tests for configurable quote pairs
+    #[test]
+    fn test_quote_pairs_match_designated_closer() {
+        let content = "a “b ‹c” d› e";
+        let spans: Vec<WordSpan> =
+            word_spans_with_quotes(content, &[('“', '”'), ('‹', '›')]).collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "a".to_string(),
+                    start: 0,
+                    end: 1
+                },
+                WordSpan {
+                    word: "“b ‹c”".to_string(),
+                    start: 2,
+                    end: 14
+                },
+                WordSpan {
+                    word: "d".to_string(),
+                    start: 15,
+                    end: 16
+                },
+                WordSpan {
+                    word: "›".to_string(),
+                    start: 16,
+                    end: 19
+                },
+                WordSpan {
+                    word: "e".to_string(),
+                    start: 20,
+                    end: 21
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quote_pairs_unmatched_opener_consumes_rest() {
+        let content = "x ‹open \"quoted\" tail";
+        let spans = SpanExtractor::new()
+            .strings_as_tokens(true)
+            .quote_pairs(vec![('‹', '›')])
+            .extract(content)
+            .unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "x".to_string(),
+                    start: 0,
+                    end: 1
+                },
+                WordSpan {
+                    word: "‹open \"quoted\" tail".to_string(),
+                    start: 2,
+                    end: content.len()
+                }
+            ]
+        );
+    }
+
+    // Tests for mapping value offsets back to source positions
+    #[test]
+    fn test_source_positions_multiline_raw_string() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("multiline_raw.rs"),
+        );
+        let literal = find_literal_in_source(&source, 3, None).unwrap();
+        let positions = literal.source_positions();
+
+        assert_eq!(positions.position(0), (2, 16));
+        let multiline = literal.value.find("multiline").unwrap();
+        assert_eq!(positions.position(multiline), (3, 16));
+        let quotes = literal.value.find("\"quotes\"").unwrap();
+        assert_eq!(positions.position(quotes), (4, 24));
+        assert_eq!(positions.position(literal.value.len()), (4, 44));
+    }
+
+    #[test]
+    fn test_source_positions_escapes_and_continuations() {
+        let source = "fn main() {\n    let s = \"a\\u{e9}b\\\n        c\";\n}\n";
+        let literal = find_literal_in_source(source, 2, None).unwrap();
+        assert_eq!(literal.value, "aébc");
+        let positions = literal.source_positions();
+
+        // `a` is at column 14, `\u{e9}` starts at 15 and decodes to two bytes, `b` follows at 21
+        assert_eq!(
+            [0, 1, 2, 3].map(|offset| positions.position(offset)),
+            [(2, 14), (2, 15), (2, 15), (2, 21)]
+        );
+        // The continuation skips the line break and indentation, so `c` maps to the next line
+        assert_eq!(positions.position(4), (3, 9));
+    }
+
+    #[test]
+    fn test_source_positions_escaped_quotes_and_newlines() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("escaped.rs"),
+        );
+
+        let quoted = find_literal_in_source(&source, 2, None).unwrap();
+        assert_eq!(quoted.value, "foo \"bar\" baz");
+        let positions = quoted.source_positions();
+        // Each `\"` is two source characters for one byte of the value
+        assert_eq!(
+            [0, 4, 5, 8, 9, 11].map(|offset| positions.position(offset)),
+            [(2, 14), (2, 18), (2, 20), (2, 23), (2, 25), (2, 27)]
+        );
+
+        let escaped = find_literal_in_source(&source, 3, None).unwrap();
+        assert_eq!(escaped.value, "hello\nworld\ttab");
+        let positions = escaped.source_positions();
+        // A `\n` escape decodes to a line break in the value but stays on the literal's line in the file
+        assert_eq!(
+            [5, 6, 11, 12, 15].map(|offset| positions.position(offset)),
+            [(3, 19), (3, 21), (3, 26), (3, 28), (3, 31)]
+        );
+    }
+
+    #[test]
+    fn test_source_positions_lossy_byte_string() {
+        let literal = find_literal_in_source("const B: &[u8] = b\"x\\xFFy\";", 1, None).unwrap();
+        assert_eq!(literal.value, "x\u{FFFD}y");
+        let positions = literal.source_positions();
+
+        // The replacement character stands for the `\xFF` escape
+        assert_eq!(
+            [0, 1, 3, 4].map(|offset| positions.position(offset)),
+            [(1, 20), (1, 21), (1, 21), (1, 25)]
+        );
+    }
+
+    // Tests for char literals
+    #[test]
+    fn test_char_and_c_string_literal_kinds() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("c_string_and_char.rs"),
+        );
+        let literals = find_string_literals_in_source(&source).unwrap();
+
+        let found: Vec<(&str, LiteralKind)> = literals
+            .iter()
+            .map(|literal| (literal.value.as_str(), literal.kind))
+            .collect();
+        // Whole-file listings leave char literals out; a line holding only a char still finds it
+        assert_eq!(found, vec![("hello c world", LiteralKind::CStr)]);
+        let accent = find_literal_in_source(&source, 5, None).unwrap();
+        assert_eq!(
+            (accent.value.as_str(), accent.kind),
+            ("é", LiteralKind::Char)
+        );
+        assert_eq!(accent.source_positions().position(0), (5, 19));
+    }
+
+    #[test]
+    fn test_char_literal_only_used_when_no_string_matches() {
+        let source = "fn f() {\n    let v: Vec<&str> = \"hello world\".split(' ').collect();\n}\n";
+
+        assert_eq!(
+            find_literal_in_source(source, 2, None).unwrap().value,
+            "hello world"
+        );
+        let on_line: Vec<String> = find_strings_on_line_all_in_source(source, 2)
+            .unwrap()
+            .into_iter()
+            .map(|literal| literal.value)
+            .collect();
+        assert_eq!(on_line, vec!["hello world"]);
+        assert_eq!(
+            find_strings_in_range_in_source(source, 1, 3).unwrap().len(),
+            1
+        );
+
+        // A column on the char selects it, since no string covers that column
+        let space = find_literal_in_source(source, 2, Some(45)).unwrap();
+        assert_eq!((space.value.as_str(), space.kind), (" ", LiteralKind::Char));
+    }
+
+    #[test]
+    fn test_extract_literal_keeps_char_as_single_span() {
+        let source = "fn main() {\n    let space = ' ';\n}\n";
+        let literal = find_literal_in_source(source, 2, None).unwrap();
+
+        assert_eq!(
+            SpanExtractor::new().extract_literal(&literal).unwrap(),
+            vec![(
+                0,
+                WordSpan {
+                    word: " ".to_string(),
+                    start: 0,
+                    end: 1
+                }
+            )]
+        );
+        assert!(SpanExtractor::new()
+            .filters(vec!["x".to_string()])
+            .extract_literal(&literal)
+            .unwrap()
+            .is_empty());
+    }
+
+    // Tests for semantic filtering
+    #[test]
+    fn test_embeddings_parse_skips_header_and_rejects_bad_lines() {
+        let embeddings = Embeddings::parse("2 2\ncat 1 0\n\nDog 0.6 0.8\n").unwrap();
+
+        assert_eq!(embeddings.len(), 2);
+        assert!((embeddings.similarity("cat", "Dog").unwrap() - 0.6).abs() < 1e-6);
+        assert_eq!(embeddings.similarity("cat", "dog"), None);
+        assert!(matches!(
+            Embeddings::parse("cat 1 0\ndog 1\n"),
+            Err(Error::InvalidEmbeddings(2))
+        ));
+        assert!(matches!(
+            Embeddings::parse("cat one two\n"),
+            Err(Error::InvalidEmbeddings(1))
+        ));
+    }
+
+    #[test]
+    fn test_semantic_filter_keeps_similar_words() {
+        let text = std::fs::read_to_string(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("embeddings.txt"),
+        )
+        .unwrap();
+        let extractor = SpanExtractor::new()
+            .filters(vec!["cat".to_string()])
+            .filter_mode(FilterMode::Semantic)
+            .embeddings(Embeddings::parse(&text).unwrap());
+
+        let words: Vec<String> = extractor
+            .extract("the Dog and the xylophone")
+            .unwrap()
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(words, vec!["Dog"]);
+
+        let strict: Vec<String> = extractor
+            .similarity_threshold(0.999)
+            .extract("dog kitten cat")
+            .unwrap()
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(strict, vec!["cat"]);
+    }
+
+    #[test]
+    fn test_semantic_filter_without_embeddings_fails() {
+        let extractor = SpanExtractor::new()
+            .filters(vec!["cat".to_string()])
+            .filter_mode(FilterMode::Semantic);
+
+        assert!(matches!(
+            extractor.extract("dog"),
+            Err(Error::MissingEmbeddings)
+        ));
+    }
+
+    // Tests for literals inside macro invocations
+    #[test]
+    fn test_finds_literals_inside_macros() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("macros.rs"),
+        );
+
+        assert_eq!(
+            find_literal_in_source(&source, 3, None).unwrap().value,
+            "hello {} world"
+        );
+        assert_eq!(
+            find_literal_in_source(&source, 8, None).unwrap().value,
+            "custom macro text"
+        );
+
+        let values = |line| -> Vec<String> {
+            find_strings_on_line_all_in_source(&source, line)
+                .unwrap()
+                .into_iter()
+                .map(|literal| literal.value)
+                .collect()
+        };
+        assert_eq!(values(4), vec!["alpha", "beta"]);
+        // Literals of nested macros are found too
+        assert_eq!(values(5), vec!["outer {}", "inner {}", "deep"]);
+    }
+
+    #[test]
+    fn test_multiline_literal_inside_macro_covers_each_line() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("macros.rs"),
+        );
+
+        for line in [6, 7] {
+            let literal = find_literal_in_source(&source, line, None).unwrap();
+            assert_eq!(literal.value, "expected two\n        words in the list");
+            assert_eq!((literal.start_line, literal.end_line), (6, 7));
+        }
+    }
+
+    // Tests for quoted-only extraction
+    #[test]
+    fn test_quoted_spans_strip_quotes_and_point_at_inner_text() {
+        let content = r##"msg="it\"s" 'one' r#"raw "x""# `open"##;
+        let spans = get_quoted_spans(content, DEFAULT_QUOTE_PAIRS, false);
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: r#"it\"s"#.to_string(),
+                    start: 5,
+                    end: 10
+                },
+                WordSpan {
+                    word: "one".to_string(),
+                    start: 13,
+                    end: 16
+                },
+                WordSpan {
+                    word: r#"raw "x""#.to_string(),
+                    start: 21,
+                    end: 28
+                },
+                WordSpan {
+                    word: "open".to_string(),
+                    start: 32,
+                    end: 36
+                }
+            ]
+        );
+        for span in &spans {
+            assert_eq!(span.slice(content).unwrap(), span.word);
+        }
+    }
+
+    #[test]
+    fn test_quoted_spans_empty_quotes() {
+        assert_eq!(
+            get_quoted_spans(r#"a "" b"#, DEFAULT_QUOTE_PAIRS, false),
+            vec![WordSpan {
+                word: String::new(),
+                start: 3,
+                end: 3
+            }]
+        );
+        assert!(get_quoted_spans(r#"a "" b"#, DEFAULT_QUOTE_PAIRS, true).is_empty());
+    }
+
+    #[test]
+    fn test_span_extractor_quoted_only_uses_quote_pairs() {
+        let extractor = SpanExtractor::new()
+            .quoted_only(true)
+            .quote_pairs(vec![('«', '»')]);
+
+        assert_eq!(
+            extractor.extract(r#"«ja» "nein""#).unwrap(),
+            vec![WordSpan {
+                word: "ja".to_string(),
+                start: 2,
+                end: 4
+            }]
+        );
+    }
+
+    // Tests for doc comments
+    #[test]
+    fn test_doc_comment_blocks_combine_consecutive_lines() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("doc_comments.rs"),
+        );
+        let literals = find_string_literals_in_source(&source).unwrap();
+
+        let found: Vec<(&str, usize, usize)> = literals
+            .iter()
+            .map(|literal| (literal.value.as_str(), literal.start_line, literal.end_line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (" Inner crate docs\n on two lines", 1, 2),
+                (" Adds two numbers\n   and returns the sum", 4, 5),
+                ("explicit doc text", 6, 6),
+                (" Block docs\n    over two lines ", 11, 12)
+            ]
+        );
+        assert!(literals
+            .iter()
+            .all(|literal| literal.kind == LiteralKind::Doc));
+        assert_eq!(
+            find_literal_in_source(&source, 5, None).unwrap(),
+            literals[1]
+        );
+    }
+
+    #[test]
+    fn test_doc_comment_positions_skip_prefixes() {
+        let source = "mod m {\n    /// one\n    ///  two\n    fn f() {}\n}\n";
+        let literal = find_literal_in_source(source, 3, None).unwrap();
+
+        let positions = literal.source_positions();
+        assert_eq!(
+            [1, 4, 7].map(|offset| positions.position(offset)),
+            [(2, 9), (2, 12), (3, 10)]
+        );
+
+        let line = literal.doc_comment_line(3).unwrap();
+        assert_eq!(
+            (
+                line.value.as_str(),
+                line.start_line,
+                line.start_column,
+                line.end_column
+            ),
+            ("  two", 3, 4, 12)
+        );
+        assert_eq!(line.source_positions().position(2), (3, 10));
+        assert_eq!(literal.doc_comment_line(4), None);
+    }
+
+    // Tests for literals inside attributes
+    #[test]
+    fn test_finds_literals_in_attribute_arguments() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("attributes.rs"),
+        );
+
+        let error = find_literal_in_source(&source, 3, None).unwrap();
+        assert_eq!(
+            (error.value.as_str(), error.kind, error.start_column),
+            ("invalid value {0} for field", LiteralKind::Str, 12)
+        );
+        assert_eq!(
+            find_literal_in_source(&source, 12, None).unwrap().value,
+            "userName"
+        );
+
+        let values: Vec<String> = find_strings_on_line_all_in_source(&source, 5)
+            .unwrap()
+            .into_iter()
+            .map(|literal| literal.value)
+            .collect();
+        assert_eq!(values, vec!["missing section `{section}`", "defaults"]);
+    }
+
+    // Tests for line ranges
+    #[test]
+    fn test_find_strings_in_range_across_functions() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("functions.rs"),
+        );
+
+        let found: Vec<(usize, String)> = find_strings_in_range_in_source(&source, 3, 8)
+            .unwrap()
+            .into_iter()
+            .map(|literal| (literal.start_line, literal.value))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (3, "{} friend".to_string()),
+                (7, "see you".to_string()),
+                (7, "take care".to_string()),
+                (8, "until\n        next time".to_string())
+            ]
+        );
+        // A multiline literal counts when the range only reaches its later lines
+        assert_eq!(
+            find_strings_in_range_in_source(&source, 9, 13).unwrap()[0].start_line,
+            8
+        );
+    }
+
+    #[test]
+    fn test_find_strings_in_range_errors() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("functions.rs"),
+        );
+
+        assert!(matches!(
+            find_strings_in_range_in_source(&source, 12, 14),
+            Err(Error::NoStringFound { line: 12, .. })
+        ));
+        assert!(
+            matches!(find_strings_in_range_in_source(&source, 9, 4), Err(Error::InvalidLineNumber(range)) if range == "9-4")
+        );
+    }
+
+    // Tests for literal source text
+    #[test]
+    fn test_source_text_keeps_escapes() {
+        let source = "const A: &str = \"foo \\\"bar\\\" baz\";\nconst B: &[u8] = br##\"a \"#\" b\"##;\nconst C: char = '\\n';\n";
+        let texts: Vec<String> = find_string_literals_in_source(source)
+            .unwrap()
+            .iter()
+            .map(|literal| literal.source_text().to_string())
+            .collect();
+
+        assert_eq!(texts, vec!["foo \\\"bar\\\" baz", "a \"#\" b"]);
+        let char_literal = find_literal_in_source(source, 3, None).unwrap();
+        assert_eq!(char_literal.source_text(), "\\n");
+    }
+
+    // Tests for delimiter splitting
+    #[test]
+    fn test_delimited_spans_skip_empty_segments() {
+        let spans = get_delimited_spans("||a b|| c;é|", &['|', ';']);
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "a b".to_string(),
+                    start: 2,
+                    end: 5
+                },
+                WordSpan {
+                    word: " c".to_string(),
+                    start: 7,
+                    end: 9
+                },
+                WordSpan {
+                    word: "é".to_string(),
+                    start: 10,
+                    end: 12
+                },
+            ]
+        );
+        assert!(get_delimited_spans("|| | ", &['|']).is_empty());
+    }
+
+    #[test]
+    fn test_delimited_spans_multibyte_delimiter() {
+        let spans = get_delimited_spans("x→yz→", &['→']);
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "x".to_string(),
+                    start: 0,
+                    end: 1
+                },
+                WordSpan {
+                    word: "yz".to_string(),
+                    start: 4,
+                    end: 6
+                }
+            ]
+        );
+    }
+
+    // Tests for lenient parsing
+    #[test]
+    fn test_parse_source_lenient_finds_literals_around_syntax_error() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("syntax_error.rs"),
+        );
+        assert!(syn::parse_file(&source).is_err());
+
+        let (file, lexed) = parse_source_lenient(&source).unwrap();
+        assert!(lexed);
+        let found: Vec<(usize, String, String)> = find_string_literals(&file)
+            .unwrap()
+            .into_iter()
+            .map(|literal| (literal.start_line, literal.kind_label(), literal.value))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, "doc".to_string(), " Greets the caller".to_string()),
+                (3, "raw_str".to_string(), "say \"hi\"".to_string()),
+                (4, "byte_str".to_string(), "bytes here".to_string()),
+                (5, "str".to_string(), "hello {}".to_string()),
+                (10, "str".to_string(), "still found".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_source_lenient_keeps_valid_source_and_rejects_unbalanced() {
+        let (file, lexed) = parse_source_lenient("const S: &str = \"ok\";").unwrap();
+        assert!(!lexed);
+        assert!(matches!(file.items[0], Item::Const(_)));
+
+        assert!(matches!(
+            parse_source_lenient("fn broken( { \"text\""),
+            Err(Error::ParseError(_))
+        ));
+    }
+
+    // Tests for selecting literals by binding name
+    #[test]
+    fn test_find_literal_by_name_in_each_binding_position() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("bindings.rs"),
+        );
+        let file = syn::parse_file(&source).unwrap();
+
+        let found: Vec<(usize, String)> = ["GREETING", "BANNER", "label", "title"]
+            .iter()
+            .map(|name| find_literal_by_name(&file, name, None).unwrap())
+            .map(|literal| (literal.start_line, literal.value))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, "hello from a const".to_string()),
+                (2, "static banner text".to_string()),
+                (9, "typed let binding".to_string()),
+                (12, "field initializer".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_literal_by_name_reports_ambiguous_and_missing_names() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("bindings.rs"),
+        );
+        let file = syn::parse_file(&source).unwrap();
+
+        match find_literal_by_name(&file, "message", None) {
+            Err(Error::AmbiguousName { name, lines }) => {
+                assert_eq!(name, "message");
+                assert_eq!(lines, vec![10, 11]);
+            }
+            other => panic!("expected AmbiguousName, got {:?}", other),
+        }
+        assert_eq!(
+            find_literal_by_name(&file, "message", Some(11))
+                .unwrap()
+                .value,
+            "shadowed message"
+        );
+
+        // Both the name and the line have to match
+        assert!(matches!(
+            find_literal_by_name(&file, "message", Some(9)),
+            Err(Error::NameNotFound { line: Some(9), .. })
+        ));
+        assert!(matches!(
+            find_literal_by_name(&file, "Config", None),
+            Err(Error::NameNotFound { line: None, .. })
+        ));
+    }
+
+    // Tests for selecting literals by enclosing function
+    #[test]
+    fn test_find_literals_in_fn_distinguishes_identical_literals() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("enclosing.rs"),
+        );
+        let file = syn::parse_file(&source).unwrap();
+
+        // The doc comment belongs to the item, not the function body
+        let greet = find_string_literals_in_fn(&file, "greet").unwrap();
+        assert_eq!(
+            greet
+                .iter()
+                .map(|literal| (literal.start_line, literal.value.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(3, "hello")]
+        );
+
+        // Line 6 holds the same literal in two functions
+        assert!(matches!(
+            find_literal_on_line(&file, 6, None),
+            Err(Error::MultipleStringsFound)
+        ));
+        assert_eq!(
+            find_literal_on_line_in_fn(&file, 6, None, "shout")
+                .unwrap()
+                .start_column,
+            29
+        );
+        assert_eq!(
+            find_literal_on_line_in_fn(&file, 6, None, "whisper")
+                .unwrap()
+                .start_column,
+            70
+        );
+
+        // Methods with the same name are all searched, closures included
+        let ring = find_string_literals_in_fn(&file, "ring").unwrap();
+        assert_eq!(
+            ring.iter()
+                .map(|literal| (literal.start_line, literal.value.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(12, "ding"), (13, "hello"), (20, "dong"),]
+        );
+    }
+
+    #[test]
+    fn test_find_literal_in_fn_reports_missing_function_and_literal() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("enclosing.rs"),
+        );
+        let file = syn::parse_file(&source).unwrap();
+
+        assert!(
+            matches!(find_string_literals_in_fn(&file, "missing"), Err(Error::FunctionNotFound(name)) if name == "missing")
+        );
+        match find_literal_on_line_in_fn(&file, 6, None, "greet") {
+            Err(Error::NoStringFound {
+                line,
+                nearest_lines_with_strings,
+            }) => {
+                assert_eq!(line, 6);
+                assert_eq!(nearest_lines_with_strings, vec![3]);
+            }
+            other => panic!("expected NoStringFound, got {:?}", other),
+        }
+    }
+
+    // Tests for the span limit
+    #[test]
+    fn test_max_spans_stops_tokenizing_and_reports_truncation() {
+        let content = "one two three four";
+
+        for extractor in [
+            SpanExtractor::new(),
+            SpanExtractor::new().strings_as_tokens(true),
+            SpanExtractor::new().granularity(Granularity::Line),
+        ] {
+            let (spans, truncated) = extractor
+                .clone()
+                .max_spans(4)
+                .extract_indexed_truncated(content)
+                .unwrap();
+            assert!(!truncated);
+            assert_eq!(spans.len(), extractor.extract(content).unwrap().len());
+        }
+
+        let (spans, truncated) = SpanExtractor::new()
+            .max_spans(2)
+            .extract_indexed_truncated(content)
+            .unwrap();
+        assert!(truncated);
+        assert_eq!(
+            unindexed(spans),
+            vec![
+                WordSpan {
+                    word: "one".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "two".to_string(),
+                    start: 4,
+                    end: 7
+                },
+            ]
+        );
+
+        // The limit counts tokens before filtering
+        let extractor = SpanExtractor::new()
+            .max_spans(3)
+            .filters(vec!["four".to_string()]);
+        assert_eq!(
+            extractor.extract_indexed_truncated(content).unwrap(),
+            (vec![], true)
+        );
+    }
+
+    #[test]
+    fn test_max_spans_applies_to_every_tokenizer() {
+        for (extractor, content) in [
+            (SpanExtractor::new().protect_quotes(true), r#"a "b c" d"#),
+            (SpanExtractor::new().json_aware(true), r#"{"a": 1}"#),
+            (SpanExtractor::new().quoted_only(true), r#""a" x 'b' y"#),
+            (SpanExtractor::new().delimiters(vec!['|']), "a|b|c"),
+            (
+                SpanExtractor::new().granularity(Granularity::Sentence),
+                "One. Two. Three.",
+            ),
+            (
+                SpanExtractor::new().granularity(Granularity::Line),
+                "one\ntwo\nthree",
+            ),
+        ] {
+            let all = extractor.extract_indexed(content).unwrap();
+            let (spans, truncated) = extractor
+                .max_spans(1)
+                .extract_indexed_truncated(content)
+                .unwrap();
+            assert!(truncated, "{}", content);
+            assert_eq!(spans, all[..1], "{}", content);
+        }
+    }
+
+    // Tests for inverted filters
+    #[test]
+    fn test_invert_match_keeps_spans_matching_no_filter() {
+        let extractor = SpanExtractor::new()
+            .filters(vec!["^b".to_string(), "^c".to_string()])
+            .filter_mode(FilterMode::Regex)
+            .invert_match(true);
+
+        let spans = unindexed(
+            extractor
+                .clone()
+                .extract_indexed("apple banana cherry date")
+                .unwrap(),
+        );
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "apple".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "date".to_string(),
+                    start: 20,
+                    end: 24
+                },
+            ]
+        );
+
+        // Excludes still apply on top, and the original indices are kept
+        let spans = extractor
+            .excludes(vec!["date".to_string()])
+            .extract_indexed("apple banana cherry date")
+            .unwrap();
+        assert_eq!(
+            spans,
+            vec![(
+                0,
+                WordSpan {
+                    word: "apple".to_string(),
+                    start: 0,
+                    end: 5
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_invert_match_without_filters_keeps_nothing() {
+        assert_eq!(
+            SpanExtractor::new()
+                .invert_match(true)
+                .extract("hello world")
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    // Tests for word normalization
+    #[test]
+    fn test_normalize_spans_keeps_original_offsets() {
+        let content = "Cafe\u{301} ﬁle";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        let words = |normalization| -> Vec<(String, usize, usize)> {
+            normalize_spans(spans.clone(), &normalization)
+                .into_iter()
+                .map(|span| (span.word, span.start, span.end))
+                .collect()
+        };
+        assert_eq!(
+            words(WordNormalization::Lowercase),
+            vec![
+                ("cafe\u{301}".to_string(), 0, 6),
+                ("ﬁle".to_string(), 7, 12)
+            ]
+        );
+        assert_eq!(
+            words(WordNormalization::Uppercase),
+            vec![
+                ("CAFE\u{301}".to_string(), 0, 6),
+                ("FILE".to_string(), 7, 12)
+            ]
+        );
+        assert_eq!(
+            words(WordNormalization::Nfc),
+            vec![("Café".to_string(), 0, 6), ("ﬁle".to_string(), 7, 12)]
+        );
+        assert_eq!(
+            words(WordNormalization::Nfkc),
+            vec![("Café".to_string(), 0, 6), ("file".to_string(), 7, 12)]
+        );
+        assert_eq!(
+            normalize_spans(spans.clone(), &WordNormalization::None),
+            spans
+        );
+    }
+
+    #[test]
+    fn test_span_extractor_filters_original_or_normalized_words() {
+        let content = "cafe\u{301} café";
+        let extractor = SpanExtractor::new()
+            .normalization(WordNormalization::Nfc)
+            .filters(vec!["café".to_string()]);
+
+        let spans = extractor.clone().extract(content).unwrap();
+        assert_eq!(
+            spans,
+            vec![WordSpan {
+                word: "café".to_string(),
+                start: 7,
+                end: 12
+            }]
+        );
+
+        let spans = extractor.filter_normalized(true).extract(content).unwrap();
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "café".to_string(),
+                    start: 0,
+                    end: 6
+                },
+                WordSpan {
+                    word: "café".to_string(),
+                    start: 7,
+                    end: 12
+                },
+            ]
+        );
+    }
+
+    // Tests for literal kind labels
+    #[test]
+    fn test_kind_label_marks_raw_literals() {
+        let source = "const A: &str = \"a\";\nconst B: &str = r#\"b\"#;\nconst C: &[u8] = br\"c\";\nconst D: &std::ffi::CStr = c\"d\";\n/// e\nconst E: char = 'e';\n";
+        let labels: Vec<String> = find_string_literals_in_source(source)
+            .unwrap()
+            .iter()
+            .map(StringLiteralInfo::kind_label)
+            .collect();
+
+        assert_eq!(
+            labels,
+            vec!["str", "raw_str", "raw_byte_str", "c_str", "doc"]
+        );
+        assert_eq!(
+            find_literal_in_source(source, 6, None)
+                .unwrap()
+                .kind_label(),
+            "char"
+        );
+    }
+
+    // Tests for whole-content filtering
+    #[test]
+    fn test_keeps_whole_matches_anywhere_in_content() {
+        let extractor = SpanExtractor::new()
+            .filters(vec!["world".to_string()])
+            .filter_mode(FilterMode::Contains);
+        assert!(extractor.keeps_whole("hello world, again").unwrap());
+        assert!(!extractor.keeps_whole("hello there").unwrap());
+
+        let extractor = SpanExtractor::new()
+            .excludes(vec!["hello".to_string()])
+            .filter_mode(FilterMode::Contains);
+        assert!(!extractor.keeps_whole("hello world").unwrap());
+        assert!(SpanExtractor::new().keeps_whole("anything").unwrap());
+    }
+
+    // Tests for capture group extraction
+    #[test]
+    fn test_capture_spans_offsets_groups_into_content() {
+        let spans = vec![
+            (
+                0,
+                WordSpan {
+                    word: "key=value".to_string(),
+                    start: 4,
+                    end: 13,
+                },
+            ),
+            (
+                1,
+                WordSpan {
+                    word: "plain".to_string(),
+                    start: 14,
+                    end: 19,
+                },
+            ),
+        ];
+        let patterns = vec![r"(\w+)=(\w+)".to_string()];
+
+        let captured = capture_spans(spans.clone(), &patterns, false, false).unwrap();
+        assert_eq!(
+            captured,
+            vec![
+                (
+                    0,
+                    WordSpan {
+                        word: "key=value".to_string(),
+                        start: 4,
+                        end: 13
+                    }
+                ),
+                (
+                    0,
+                    WordSpan {
+                        word: "key".to_string(),
+                        start: 4,
+                        end: 7
+                    }
+                ),
+                (
+                    0,
+                    WordSpan {
+                        word: "value".to_string(),
+                        start: 8,
+                        end: 13
+                    }
+                ),
+                (
+                    1,
+                    WordSpan {
+                        word: "plain".to_string(),
+                        start: 14,
+                        end: 19
+                    }
+                ),
+            ]
+        );
+
+        let captured = capture_spans(spans, &patterns, false, true).unwrap();
+        assert_eq!(
+            unindexed(captured),
+            vec![
+                WordSpan {
+                    word: "key".to_string(),
+                    start: 4,
+                    end: 7
+                },
+                WordSpan {
+                    word: "value".to_string(),
+                    start: 8,
+                    end: 13
+                },
+                WordSpan {
+                    word: "plain".to_string(),
+                    start: 14,
+                    end: 19
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extractor_extract_captures_only_in_regex_mode() {
+        let extractor = SpanExtractor::new()
+            .granularity(Granularity::Line)
+            .filters(vec![r"v(\d+)\.(\d+)".to_string()])
+            .filter_mode(FilterMode::Regex)
+            .extract_captures(true)
+            .captures_only(true);
+        let spans = extractor
+            .extract("release v1.2 and v3.45\nnothing here")
+            .unwrap();
+        assert_eq!(
+            spans
+                .iter()
+                .map(|span| (span.word.as_str(), span.start))
+                .collect::<Vec<_>>(),
+            vec![("1", 9), ("2", 11), ("3", 18), ("45", 20)]
+        );
+
+        let spans = SpanExtractor::new()
+            .filters(vec!["b".to_string()])
+            .filter_mode(FilterMode::Contains)
+            .extract_captures(true)
+            .extract("abc b")
+            .unwrap();
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "abc".to_string(),
+                    start: 0,
+                    end: 3
+                },
+                WordSpan {
+                    word: "b".to_string(),
+                    start: 4,
+                    end: 5
+                }
+            ]
+        );
+    }
+
+    // Tests for whitespace spans
+    #[test]
+    fn test_include_whitespace_spans_concatenate_to_input() {
+        let content = "  Hello,\t\"quoted text\"  world.\r\nSecond line here.\n\n";
+
+        for extractor in [
+            SpanExtractor::new(),
+            SpanExtractor::new().strings_as_tokens(true),
+            SpanExtractor::new().trim_punctuation(true),
+            SpanExtractor::new().granularity(Granularity::Line),
+            SpanExtractor::new().granularity(Granularity::Sentence),
+        ] {
+            let spans = extractor.include_whitespace(true).extract(content).unwrap();
+            let rebuilt: String = spans.iter().map(|span| span.word.as_str()).collect();
+            assert_eq!(rebuilt, content);
+            assert!(spans.windows(2).all(|pair| pair[0].end == pair[1].start));
+        }
+
+        let spans = SpanExtractor::new()
+            .include_whitespace(true)
+            .extract("a \t b")
+            .unwrap();
+        assert_eq!(
+            spans[1],
+            WordSpan {
+                word: " \t ".to_string(),
+                start: 1,
+                end: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_spans_counts_whitespace_spans() {
+        let extractor = SpanExtractor::new().include_whitespace(true).max_spans(2);
+        let (spans, truncated) = extractor.extract_indexed_truncated("a b c d").unwrap();
+        assert!(truncated);
+        assert_eq!(
+            unindexed(spans),
+            vec![
+                WordSpan {
+                    word: "a".to_string(),
+                    start: 0,
+                    end: 1
+                },
+                WordSpan {
+                    word: " ".to_string(),
+                    start: 1,
+                    end: 2
+                },
+            ]
+        );
+
+        // Three spans fit exactly, so nothing is cut
+        let extractor = SpanExtractor::new().include_whitespace(true).max_spans(3);
+        let (spans, truncated) = extractor.extract_indexed_truncated("a b").unwrap();
+        assert_eq!((spans.len(), truncated), (3, false));
+    }
+
+    #[test]
+    fn test_fill_gaps_keeps_whitespace_spans_whitespace_only() {
+        let content = "(a) b";
+        let extractor = SpanExtractor::new()
+            .include_whitespace(true)
+            .trim_punctuation(true);
+        let words: Vec<String> = extractor
+            .extract(content)
+            .unwrap()
+            .into_iter()
+            .map(|span| span.word)
+            .collect();
+        assert_eq!(words, vec!["(", "a", ")", " ", "b"]);
+    }
+
+    // Tests for test code selection
+    #[test]
+    fn test_retain_test_code_skips_or_keeps_test_items() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("test_code.rs"),
+        );
+        let lines = |tests: TestCode| -> Vec<usize> {
+            let mut file = syn::parse_file(&source).unwrap();
+            retain_test_code(&mut file, tests);
+            find_string_literals(&file)
+                .unwrap()
+                .iter()
+                .map(|literal| literal.start_line)
+                .collect()
+        };
+
+        // Line 16 is `feature = "slow"` in the module's `cfg`
+        assert_eq!(lines(TestCode::Include), vec![1, 3, 12, 16, 18, 23, 27]);
+        // `cfg(not(test))` is regular code; `cfg(all(test, ...))` and `#[tokio::test]` are test code
+        assert_eq!(lines(TestCode::Skip), vec![1, 3, 27]);
+        assert_eq!(lines(TestCode::Only), vec![12, 16, 18, 23]);
+    }
+
+    #[test]
+    fn test_is_test_code_reads_cfg_predicates() {
+        let attrs = |source: &str| {
+            syn::parse_str::<ItemFn>(&format!("{} fn f() {{}}", source))
+                .unwrap()
+                .attrs
+        };
+
+        assert!(is_test_code(&attrs("#[test]")));
+        assert!(is_test_code(&attrs("#[cfg(any(test, all(test, unix)))]")));
+        assert!(!is_test_code(&attrs(
+            "#[cfg(any(test, feature = \"mock\"))]"
+        )));
+        assert!(!is_test_code(&attrs("#[cfg(not(test))]")));
+        assert!(!is_test_code(&attrs("#[inline]")));
+    }
+
+    // Tests for byte order marks and shebangs
+    #[test]
+    fn test_bom_prefixed_source_keeps_line_and_column_numbers() {
+        let source = read_test_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("test-files")
+                .join("bom.rs"),
+        );
+        assert!(source.starts_with('\u{feff}'));
+
+        let file = syn::parse_file(&source).unwrap();
+        let first = find_literal_on_line(&file, 2, None).unwrap();
+        assert_eq!(
+            (first.value.as_str(), first.start_column),
+            ("first line", 20)
+        );
+        assert_eq!(
+            find_literal_on_line(&file, 5, None).unwrap().value,
+            "fourth line"
+        );
+
+        // The lexing fallback skips the BOM too, so columns on the first line don't shift
+        let broken = format!("{}\nlet = ;\n", source);
+        let (file, lexed) = parse_source_lenient(&broken).unwrap();
+        assert!(lexed);
+        let found: Vec<(usize, usize)> = find_string_literals(&file)
+            .unwrap()
+            .iter()
+            .map(|literal| (literal.start_line, literal.start_column))
+            .collect();
+        assert_eq!(found, vec![(1, 0), (2, 20), (5, 16)]);
+    }
+
+    #[test]
+    fn test_parse_source_lenient_skips_shebang_line() {
+        let (file, lexed) =
+            parse_source_lenient("#!/usr/bin/env run-script\nconst S: &str = \"kept\"; let\n")
+                .unwrap();
+        assert!(lexed);
+        assert_eq!(file.shebang.as_deref(), Some("#!/usr/bin/env run-script"));
+        let literal = find_literal_on_line(&file, 2, None).unwrap();
+        assert_eq!((literal.value.as_str(), literal.start_column), ("kept", 16));
+    }
+
+    // Tests for fuzzy filtering
+    #[test]
+    fn test_fuzzy_filter_distances() {
+        let content = "color colour colours cooler kolours";
+        let fuzzy = |distance: usize| -> Vec<String> {
+            SpanExtractor::new()
+                .filters(vec!["colour".to_string()])
+                .filter_mode(FilterMode::Fuzzy)
+                .fuzzy_distance(distance)
+                .extract(content)
+                .unwrap()
+                .into_iter()
+                .map(|span| span.word)
+                .collect()
+        };
+
+        assert_eq!(fuzzy(0), vec!["colour"]);
+        assert_eq!(fuzzy(1), vec!["color", "colour", "colours"]);
+        assert_eq!(fuzzy(2), vec!["color", "colour", "colours", "kolours"]);
+
+        // The default distance is 1
+        let spans = indexed(get_word_spans(content, false, &Granularity::Word).unwrap());
+        assert_eq!(
+            filter_word_spans(spans, &["colour".to_string()], &FilterMode::Fuzzy, false)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_filter_folds_case_and_caps_word_length() {
+        let spans = indexed(get_word_spans("Colour COLOR", false, &Granularity::Word).unwrap());
+        assert_eq!(
+            filter_word_spans(
+                spans.clone(),
+                &["colour".to_string()],
+                &FilterMode::Fuzzy,
+                true
+            )
+            .unwrap()
+            .len(),
+            2
+        );
+        assert_eq!(
+            filter_word_spans(spans, &["colour".to_string()], &FilterMode::Fuzzy, false)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Past the length cap, one differing character is no longer a match
+        let at_cap = vec!['a'; MAX_FUZZY_WORD_LEN];
+        let mut at_cap_near = at_cap.clone();
+        at_cap_near[0] = 'b';
+        assert!(within_edit_distance(&at_cap, &at_cap_near, 1));
+
+        let long = vec!['a'; MAX_FUZZY_WORD_LEN + 1];
+        let mut long_near = long.clone();
+        long_near[0] = 'b';
+        assert!(!within_edit_distance(&long, &long_near, 1));
+        assert!(within_edit_distance(&long, &long, 0));
+    }
+
+    // Tests for glob filtering
+    #[test]
+    fn test_glob_filter_wildcards() {
+        let spans = indexed(
+            get_word_spans(
+                "test tests testing contest tent text",
+                false,
+                &Granularity::Word,
+            )
+            .unwrap(),
+        );
+        let glob = |pattern: &str| -> Vec<String> {
+            filter_word_spans(
+                spans.clone(),
+                &[pattern.to_string()],
+                &FilterMode::Glob,
+                false,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| span.word)
+            .collect()
+        };
+
+        // `*` matches an empty run too, and globs are anchored to the whole word
+        assert_eq!(glob("test*"), vec!["test", "tests", "testing"]);
+        assert_eq!(glob("*test"), vec!["test", "contest"]);
+        // `?` matches exactly one character
+        assert_eq!(glob("test?"), vec!["tests"]);
+        assert_eq!(glob("te?t"), vec!["test", "tent", "text"]);
+        assert_eq!(glob("te[nx]t"), vec!["tent", "text"]);
+        assert_eq!(glob("te[!nx]t"), vec!["test"]);
+        assert_eq!(glob("te[a-n]t"), vec!["tent"]);
+    }
+
+    #[test]
+    fn test_glob_filter_case_and_literals() {
+        let spans = indexed(get_word_spans("Test TEST test", false, &Granularity::Word).unwrap());
+        let count = |pattern: &str, ignore_case: bool| {
+            filter_word_spans(
+                spans.clone(),
+                &[pattern.to_string()],
+                &FilterMode::Glob,
+                ignore_case,
+            )
+            .unwrap()
+            .len()
+        };
+        assert_eq!(count("t*", false), 1);
+        assert_eq!(count("t*", true), 3);
+
+        // Regex metacharacters and unclosed brackets match literally
+        assert_eq!(glob_to_regex("a.b"), r"^a\.b$");
+        assert_eq!(glob_to_regex("a[b"), r"^a\[b$");
+        assert_eq!(glob_to_regex("[]a]"), r"^[\]a]$");
+        assert_eq!(glob_to_regex("[a-]"), r"^[a\-]$");
+    }
+
+    // Tests for literal byte offsets in the file
+    #[test]
+    fn test_file_byte_range_after_multibyte_characters() {
+        let content =
+            "fn f() {\n    let café = (\"日本\", \"naïve\");\n    let s = r#\"a\nb\"#;\n}\n";
+        let file = syn::parse_file(content).unwrap();
+        let literals = find_string_literals(&file).unwrap();
+        let written: Vec<&str> = literals
+            .iter()
+            .map(|literal| &content[literal.file_byte_range(content).unwrap()])
+            .collect();
+        assert_eq!(written, vec!["\"日本\"", "\"naïve\"", "r#\"a\nb\"#"]);
+
+        // The second literal's column counts characters; its byte offset is larger
+        let naive = &literals[1];
+        assert_eq!(naive.start_column, 22);
+        assert_eq!(naive.file_byte_range(content).unwrap(), 36..44);
+
+        // A byte order mark shifts every offset, though syn's columns don't count it
+        let with_bom = format!("\u{feff}{}", content);
+        let literals = find_string_literals(&syn::parse_file(&with_bom).unwrap()).unwrap();
+        assert_eq!(literals[1].file_byte_range(&with_bom).unwrap(), 39..47);
+
+        assert_eq!(literals[0].file_byte_range("fn f() {}"), None);
+    }
+
+    #[test]
+    fn test_file_offsets_follow_escapes_and_lines() {
+        let content = "fn f() {\n    let s = \"é\\u{e9}\\\n        x\ny\";\n}\n";
+        let literal = &find_string_literals(&syn::parse_file(content).unwrap()).unwrap()[0];
+        assert_eq!(literal.value, "ééx\ny");
+
+        let offsets = literal.file_offsets(content).unwrap();
+        // Both bytes of `é` and of the `\u{e9}` escape map to where they start
+        assert_eq!(&offsets[..4], &[22, 22, 24, 24]);
+        // The continuation and indentation are skipped; the `\n` byte is the raw line break after `x`
+        assert_eq!(&content[offsets[4]..offsets[4] + 1], "x");
+        assert_eq!(&content[offsets[5]..offsets[5] + 1], "\n");
+        assert_eq!(&content[offsets[6]..offsets[6] + 1], "y");
+        // The end of the value maps to the closing quote
+        assert_eq!(&content[offsets[7]..offsets[7] + 1], "\"");
+        assert_eq!(offsets.len(), literal.value.len() + 1);
+    }
+
+    // Tests for offset units
+    #[test]
+    fn test_unit_offsets_disagree_on_emoji_cjk_and_combining_marks() {
+        // `é` is `e` plus a combining acute accent: two chars but one grapheme
+        let content = "a😀 日本 e\u{301}x";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+        let in_unit = |unit: OffsetUnit| -> Vec<(usize, usize)> {
+            let table = unit_offsets(content, unit);
+            spans
+                .iter()
+                .map(|span| (table[span.start], table[span.end]))
+                .collect()
+        };
+
+        let words: Vec<&str> = spans.iter().map(|span| span.word.as_str()).collect();
+        assert_eq!(words, vec!["a", "😀", "日", "本", "e\u{301}x"]);
+        assert_eq!(
+            in_unit(OffsetUnit::Bytes),
+            vec![(0, 1), (1, 5), (6, 9), (9, 12), (13, 17)]
+        );
+        assert_eq!(
+            in_unit(OffsetUnit::Chars),
+            vec![(0, 1), (1, 2), (3, 4), (4, 5), (6, 9)]
+        );
+        assert_eq!(
+            in_unit(OffsetUnit::Graphemes),
+            vec![(0, 1), (1, 2), (3, 4), (4, 5), (6, 8)]
+        );
+        assert_eq!(
+            in_unit(OffsetUnit::Utf16),
+            vec![(0, 1), (1, 3), (4, 5), (5, 6), (7, 10)]
+        );
+
+        // Offsets inside a character map to where it starts
+        assert_eq!(unit_offsets("😀", OffsetUnit::Chars), vec![0, 0, 0, 0, 1]);
+        assert_eq!(unit_offsets("", OffsetUnit::Graphemes), vec![0]);
+    }
+
+    #[test]
+    fn test_span_extractor_offset_unit() {
+        let offsets = |unit, content: &str| -> Vec<(usize, usize)> {
+            SpanExtractor::new()
+                .offset_unit(unit)
+                .extract(content)
+                .unwrap()
+                .iter()
+                .map(|span| (span.start, span.end))
+                .collect()
+        };
+        assert_eq!(
+            offsets(OffsetUnit::Bytes, "é 😀a"),
+            vec![(0, 2), (3, 7), (7, 8)]
+        );
+        assert_eq!(
+            offsets(OffsetUnit::Chars, "é 😀a"),
+            vec![(0, 1), (2, 3), (3, 4)]
+        );
+        assert_eq!(
+            offsets(OffsetUnit::Utf16, "é 😀a"),
+            vec![(0, 1), (2, 4), (4, 5)]
+        );
+
+        // A source range is tokenized alone but counted from the start of the content
+        let source = Source::new("日本 語".to_string()).within(7..10);
+        let extractor = SpanExtractor::new().offset_unit(OffsetUnit::Chars);
+        let (spans, _) = extractor.extract_source_truncated(&source).unwrap();
+        assert_eq!((spans[0].1.start, spans[0].1.end), (3, 4));
+    }
+
+    // Tests for offset styles
+    #[test]
+    fn test_offset_style_combines_one_based_and_end_inclusive() {
+        let style = |one_based, end_inclusive| OffsetStyle {
+            one_based,
+            end_inclusive,
+        };
+        assert_eq!(style(false, false).report(0, 1, None), (0, 1));
+        assert_eq!(style(true, false).report(0, 1, None), (1, 2));
+        assert_eq!(style(false, true).report(0, 1, None), (0, 0));
+        assert_eq!(style(true, true).report(0, 1, None), (1, 1));
+        // An empty range keeps its end at its start
+        assert_eq!(style(true, true).report(2, 2, None), (3, 3));
+
+        // Offsets past the table count as its last entry
+        let units = unit_offsets("日a", OffsetUnit::Chars);
+        assert_eq!(style(false, true).report(0, 4, Some(&units)), (0, 1));
+        assert_eq!(style(false, false).report(3, 9, Some(&units)), (1, 2));
+    }
+
+    // Tests for sources
+    #[test]
+    fn test_source_group_json_names_file_line_and_column() {
+        let source = Source::new("a".to_string());
+        assert!(!source.is_grouped());
+        assert_eq!(source.header(), None);
+
+        let source = Source::in_file(PathBuf::from("src/a.rs"), Some(4), "a".to_string());
+        assert!(source.is_grouped());
+        assert_eq!(source.header().as_deref(), Some("src/a.rs:4:"));
+        assert_eq!(
+            source.group_json(),
+            serde_json::json!({ "line": 4, "file": "src/a.rs" })
+        );
+
+        let source = Source::at_line(2, "a".to_string()).at_column(7);
+        assert_eq!(source.header().as_deref(), Some("line 2, column 7:"));
+        assert_eq!(
+            source.group_json(),
+            serde_json::json!({ "line": 2, "column": 7 })
+        );
+    }
+
+    // Tests for scan checkpoints
+    #[test]
+    fn test_checkpoint_entries_read_back() {
+        let dir =
+            std::env::temp_dir().join(format!("span-counter-checkpoint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let checkpoint_path = dir.join("checkpoint");
+        assert!(read_checkpoint(&checkpoint_path).unwrap().is_empty());
+
+        let file_path = dir.join("a b.rs");
+        fs::write(&file_path, "").unwrap();
+        let modified = modification_stamp(&file_path).unwrap();
+        let mut checkpoint = Vec::new();
+        write_checkpoint_entry(&mut checkpoint, &modified, &file_path).unwrap();
+        fs::write(&checkpoint_path, checkpoint).unwrap();
+
+        let finished = read_checkpoint(&checkpoint_path).unwrap();
+        assert_eq!(finished.get(&file_path), Some(&modified));
+        assert!(matches!(
+            modification_stamp(&dir.join("missing.rs")),
+            Err(Error::IoError { .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Tests for SARIF and DOT output
+    #[test]
+    fn test_sarif_span_results_locate_spans_in_the_literal() {
+        let literals = find_string_literals_in_source("fn f() {\n    \"a bc\";\n}\n").unwrap();
+        let spans = SpanExtractor::new().extract_literal(&literals[0]).unwrap();
+
+        let results = sarif_span_results(&spans, Some(Path::new("src\\f.rs")), Some(&literals[0]));
+        assert_eq!(results.len(), 2);
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/f.rs");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 8);
+        assert_eq!(location["region"]["endColumn"], 10);
+
+        let results = sarif_span_results(&spans, None, None);
+        assert!(results[0].get("locations").is_none());
+        let document = sarif_document("span", "A span", results);
+        assert_eq!(document["runs"][0]["results"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_write_dot_keeps_adjacency_within_groups() {
+        let extractor = SpanExtractor::new();
+        let first = extractor.extract_indexed("a b").unwrap();
+        let second = extractor.extract_indexed("b \"").unwrap();
+
+        let mut out = Vec::new();
+        write_dot(&mut out, [first.as_slice(), second.as_slice()]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "digraph spans {\n    \"a\";\n    \"b\";\n    \"\\\"\";\n    \"a\" -> \"b\" [weight=1, label=\"1\"];\n    \"b\" -> \"\\\"\" [weight=1, label=\"1\"];\n}\n"
+        );
+    }
+}
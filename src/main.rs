@@ -1,48 +1,583 @@
-use clap::{Parser, Subcommand, ValueEnum};
-use regex::Regex;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use rayon::prelude::*;
+use rust_span_counter::{
+    cluster_spans, dedup_spans, detect_language, filter_by_length_percentile,
+    filter_by_offset_range, find_densest_window, find_json_string_in_source, find_literal_by_index,
+    find_literal_by_name, find_literal_near_column, find_literal_on_line,
+    find_literal_on_line_in_fn, find_literals_on_lines, find_markdown_code, find_string_literals,
+    find_string_literals_in_fn, find_strings_in_range, find_strings_on_line_all, find_toml_string,
+    find_toml_string_on_line, json_string_pointers, markdown_code_blocks, modification_stamp,
+    parse_source_lenient, read_checkpoint, retain_test_code, safe_slice, sarif_document,
+    sarif_span_results, sort_spans, span_neighbors, spans_with_context, summarize_spans,
+    toml_strings, unit_offsets, word_frequencies, write_checkpoint_entry, write_dot, ClusterKey,
+    DensestWindow, Embeddings, Error, FilterMode, Granularity, IndexedSpan, LineIndex, OffsetStyle,
+    OffsetUnit, SortOrder, Source, SourcePositions, SpanExtractor, SpanNeighbors, SpanSummary,
+    StringLiteralInfo, TestCode, TomlString, WordNormalization, WordSpan, DEFAULT_FUZZY_DISTANCE,
+    DEFAULT_SIMILARITY_THRESHOLD,
+};
 use std::fs;
-use std::io::{self, Read};
-use std::path::PathBuf;
-use syn::{visit::Visit, File, LitStr};
-use unicode_segmentation::UnicodeSegmentation;
-
-#[derive(Clone, Debug, ValueEnum)]
-enum FilterMode {
-    /// Exact word match
-    Exact,
-    /// Word contains the filter string
-    Contains,
-    /// Word matches the regex pattern
-    Regex,
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::OnceLock;
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum ColorChoice {
+    /// Use colors when writing to a terminal
+    #[default]
+    Auto,
+    /// Always use colors
+    Always,
+    /// Never use colors
+    Never,
 }
 
-impl Default for FilterMode {
-    fn default() -> Self {
-        FilterMode::Exact
+impl ColorChoice {
+    fn use_color(&self, output_is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => output_is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
     }
 }
 
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum OutputFormat {
+    /// One `"word" | start-end` line per span
+    #[default]
+    Text,
+    /// A JSON array of span objects
+    Json,
+    /// One compact JSON span object per line, written as soon as it is produced
+    Jsonl,
+    /// A Graphviz `digraph` of word adjacency, with edges weighted by bigram count
+    Dot,
+    /// A SARIF 2.1.0 log with one result per span, located in the Rust file for file input
+    Sarif,
+}
+
+/// What `file` does when the requested line holds several string literals
+#[derive(Clone, Debug, Default, ValueEnum)]
+enum OnConflict {
+    /// Fail with "Multiple strings found"
+    #[default]
+    Error,
+    /// Use the literal that starts first on the line
+    First,
+    /// Use the literal that starts last on the line
+    Last,
+    /// Join the values with a single space and process them as one content
+    Concat,
+}
+
 /// Extract word-by-word character spans from string literals
 #[derive(Parser)]
 #[command(name = "rust-span-counter")]
 #[command(about = "Extracts strings and provides word-by-word character spans")]
+#[command(after_help = "Exit codes:
+  0  success
+  1  no span matched (--quiet)
+  2  invalid config file, the Rust source, JSON, or TOML document failed to parse, or --filter-mode
+     semantic without --embeddings
+  3  no string found on the requested line, index, name, function, JSON pointer, or TOML key
+  4  multiple strings found on the requested line, or bound to the requested name
+  5  reading input or writing output failed
+  6  invalid pattern or input value (regex, line list, non-UTF-8 input or literal, span offsets, embeddings)
+  7  invalid command line: unknown option, bad value, or conflicting or missing arguments")]
 struct Args {
     /// Treat quoted strings as single tokens (preserving quote boundaries)
-    #[arg(long, help = "Treat quoted content (\"...\", '...', `...`) as single tokens")]
+    #[arg(
+        long,
+        group = "quoting",
+        help = "Treat quoted content (\"...\", '...', `...`, r#\"...\"#) as single tokens"
+    )]
     strings_as_tokens: bool,
 
+    /// Emit only the quoted regions, with the quotes stripped and offsets covering the inner text
+    #[arg(
+        long,
+        group = "quoting",
+        conflicts_with = "granularity",
+        help = "Only emit quoted content, without its quotes"
+    )]
+    quoted_only: bool,
+
+    /// Leave out the empty spans that `""` produces in --quoted-only mode
+    #[arg(
+        long,
+        requires = "quoted_only",
+        help = "Skip empty quotes with --quoted-only"
+    )]
+    skip_empty_quotes: bool,
+
+    /// Quote pairs for --strings-as-tokens or --quoted-only, each written OPEN:CLOSE. Quoted tokens only
+    /// exist at word granularity, so --granularity is rejected alongside them
+    #[arg(
+        long,
+        value_name = "PAIRS",
+        value_delimiter = ',',
+        value_parser = parse_quote_pair,
+        requires = "quoting",
+        conflicts_with = "granularity",
+        help = "Comma-separated OPEN:CLOSE quote pairs for --strings-as-tokens or --quoted-only, e.g. “:”,‹:› (default \":\",':',`:`)"
+    )]
+    quote_pairs: Vec<(char, char)>,
+
+    /// Split only on runs of these characters instead of Unicode word bounds
+    #[arg(
+        long,
+        value_name = "CHARS",
+        conflicts_with_all = ["quoting", "granularity"],
+        help = "Split on any of these characters (e.g. '|' or ',;') instead of word bounds"
+    )]
+    delimiters: Option<String>,
+
+    /// Keep double-quoted regions whole while using normal word bounds elsewhere
+    #[arg(long, conflicts_with_all = ["quoting", "granularity", "delimiters"], help = "Keep \"...\" regions as single tokens in default word mode")]
+    protect_quotes: bool,
+
+    /// Scan the content as loose JSON: strings stay whole, structural characters are separate tokens
+    #[arg(long, conflicts_with_all = ["quoting", "protect_quotes", "granularity", "delimiters"], help = "Tokenize as JSON: string literals whole, { } [ ] : , separate")]
+    json_aware: bool,
+
+    /// Drop the surrounding quotes from string spans in --json-aware mode
+    #[arg(
+        long,
+        requires = "json_aware",
+        help = "Exclude the quotes from string spans with --json-aware"
+    )]
+    json_strip_quotes: bool,
+
+    /// Keep dotted identifier runs like `first.last` or `a.b1.c` as single tokens
+    #[arg(
+        long,
+        help = "Keep dotted identifiers (word.word.word) as single tokens"
+    )]
+    dotted_identifiers: bool,
+
+    /// Strip punctuation from the edges of spans and drop spans that are only punctuation
+    #[arg(
+        long,
+        help = "Trim punctuation from span edges and drop punctuation-only spans"
+    )]
+    trim_punctuation: bool,
+
+    /// Rejoin numbers that word bounds split apart, such as `.5` and `3.14e-10`
+    #[arg(long, help = "Keep numbers like .5 and 3.14e-10 as single tokens")]
+    keep_numbers: bool,
+
+    /// With --keep-numbers, also join digit groups separated by a space, like `1 000 000`
+    #[arg(
+        long,
+        requires = "keep_numbers",
+        help = "Join space-separated thousands groups with --keep-numbers"
+    )]
+    thousands_separators: bool,
+
+    /// Keep a number and a directly following `%` or `‰` as one token
+    #[arg(long, help = "Keep percentages like 50% or 3.5% as single tokens")]
+    preserve_percentages: bool,
+
+    /// Keep command-line flags like `-x`, `-rf`, and `--long-flag` as one token including the dashes
+    #[arg(
+        long,
+        help = "Keep command-line flags like --verbose or -rf as single tokens"
+    )]
+    preserve_flags: bool,
+
+    /// Also emit the whitespace skipped between words as spans, with the exact whitespace as the word,
+    /// so the spans tile the input and their words concatenate back to it. Other skipped text, such
+    /// as punctuation removed by --trim-punctuation, gets spans of its own
+    #[arg(
+        long,
+        help = "Emit whitespace runs between words as spans too, so spans cover the whole input"
+    )]
+    include_whitespace: bool,
+
+    /// Size of the produced spans
+    #[arg(long, value_enum, default_value_t = Granularity::Word, help = "Span granularity: word, sentence, or line")]
+    granularity: Granularity,
+
+    /// Stop tokenizing each source after N spans, before filtering, with a note on stderr when it cuts
+    /// the output short. Word tokenizing stops early rather than splitting the rest of the content
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Stop after N spans per source (before filtering), noting partial output on stderr"
+    )]
+    max_spans: Option<usize>,
+
+    /// Rewrite each span's word after tokenizing; offsets still point at the original text
+    #[arg(long, value_enum, default_value_t = WordNormalization::None, help = "Normalize span words: none, lowercase, uppercase, nfc, or nfkc")]
+    normalize: WordNormalization,
+
+    /// Match filters and excludes against the normalized words instead of the original ones
+    #[arg(
+        long,
+        help = "Apply filters and excludes to the --normalize output instead of the original words"
+    )]
+    filter_normalized: bool,
+
     /// Filter output to include only specified words/tokens (can be used multiple times)
-    #[arg(long = "filter", short = 'f', help = "Filter to include only specified words (can be used multiple times)")]
+    #[arg(
+        long = "filter",
+        short = 'f',
+        help = "Filter to include only specified words (can be used multiple times)"
+    )]
     filters: Vec<String>,
 
-    /// Filter mode: exact, contains, or regex
-    #[arg(long, value_enum, default_value_t = FilterMode::Exact, help = "Filter mode: exact match, contains, or regex pattern")]
+    /// Exclude words/tokens matching these patterns (can be used multiple times)
+    #[arg(
+        long = "exclude",
+        short = 'x',
+        help = "Exclude specified words (can be used multiple times)"
+    )]
+    excludes: Vec<String>,
+
+    /// Keep the spans that match none of the filters; without filters nothing is kept
+    #[arg(
+        long,
+        short = 'v',
+        conflicts_with = "extract_captures",
+        help = "Invert --filter: keep spans matching no filter, like grep -v"
+    )]
+    invert_match: bool,
+
+    /// Filter mode: exact, contains, regex, semantic, fuzzy, or glob
+    #[arg(long, value_enum, default_value_t = FilterMode::Exact, help = "Filter mode: exact match, contains, regex pattern, semantic similarity, fuzzy (edit distance), or glob (*, ?, [...])")]
     filter_mode: FilterMode,
 
+    /// Word vectors for `--filter-mode semantic`, one `word v1 v2 ...` line per word
+    #[arg(
+        long,
+        value_name = "PATH",
+        required_if_eq("filter_mode", "semantic"),
+        help = "Word embeddings file used by --filter-mode semantic"
+    )]
+    embeddings: Option<PathBuf>,
+
+    /// Cosine similarity a word must exceed to match a filter in semantic mode
+    #[arg(long, value_name = "S", default_value_t = DEFAULT_SIMILARITY_THRESHOLD, help = "Minimum cosine similarity for --filter-mode semantic")]
+    similarity_threshold: f32,
+
+    /// Most single-character insertions, deletions, or substitutions between a word and a filter in
+    /// fuzzy mode. Words over 64 characters only match exactly
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_FUZZY_DISTANCE, help = "Maximum Levenshtein distance for --filter-mode fuzzy")]
+    fuzzy_distance: usize,
+
     /// Case-insensitive filtering
     #[arg(long, help = "Case-insensitive filtering")]
     ignore_case: bool,
 
+    /// In regex filter mode, replace each matching span with spans for the whole match and each capture group
+    #[arg(
+        long,
+        help = "With --filter-mode regex, emit the text of each capture group instead of the whole span"
+    )]
+    extract_captures: bool,
+
+    /// With --extract-captures, leave out the whole match (group 0)
+    #[arg(
+        long,
+        requires = "extract_captures",
+        help = "Only emit capture groups, not the whole match"
+    )]
+    captures_only: bool,
+
+    /// Keep only the longest P percent of spans by grapheme length
+    #[arg(long, value_name = "P", value_parser = parse_percentile, help = "Keep spans in the top P percent by length (graphemes)")]
+    len_top_percentile: Option<f64>,
+
+    /// Keep only the shortest P percent of spans by grapheme length
+    #[arg(long, value_name = "P", value_parser = parse_percentile, help = "Keep spans in the bottom P percent by length (graphemes)")]
+    len_bottom_percentile: Option<f64>,
+
+    /// Keep only spans starting at or after this offset, counted like the printed offsets: 0-based, in
+    /// --offset-unit, and into the file with `file --absolute-offsets`
+    #[arg(
+        long,
+        value_name = "OFFSET",
+        help = "Keep spans that start at or after this offset (in --offset-unit)"
+    )]
+    offset_start: Option<usize>,
+
+    /// Keep only spans ending at or before this offset (exclusive end), counted like --offset-start
+    #[arg(
+        long,
+        value_name = "OFFSET",
+        help = "Keep spans that end at or before this offset (in --offset-unit)"
+    )]
+    offset_end: Option<usize>,
+
+    /// With --offset-start/--offset-end, also keep spans that only partly overlap the range
+    #[arg(long, help = "Also keep spans partly overlapping the offset range")]
+    overlap: bool,
+
+    /// Also keep the N unfiltered spans before and after each match, merging overlapping windows
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Include N spans of context before and after each matching span"
+    )]
+    context: Option<usize>,
+
+    /// Keep only the first occurrence of each distinct word
+    #[arg(
+        long,
+        help = "Drop repeated words, keeping each word's first occurrence"
+    )]
+    dedup: bool,
+
+    /// Order of the printed spans
+    #[arg(long, value_enum, default_value_t = SortOrder::Position, conflicts_with = "replace", help = "Sort spans by position, word, word-desc, length, or start")]
+    sort: SortOrder,
+
+    /// Prefix each span with its index in the unfiltered token stream
+    #[arg(
+        long,
+        help = "Prefix each span with its token index (stable under filtering)"
+    )]
+    numbered: bool,
+
+    /// Start token numbering at 1 instead of 0
+    #[arg(
+        long,
+        requires = "numbered",
+        help = "Use one-based token indices with --numbered"
+    )]
+    one_based_index: bool,
+
+    /// Add 1 to each printed span start and end, so "a" is 1-2. Combined with --end-inclusive the
+    /// range is 1-based and inclusive ("a" is 1-1). Only the printed start and end change; offset
+    /// options like --offset-start still take 0-based offsets
+    #[arg(long, help = "Report span start and end counted from 1 instead of 0")]
+    one_based: bool,
+
+    /// Print each span's end as the offset of its last byte (or --offset-unit) instead of one past it, so
+    /// "a" is 0-0. An empty span has no last unit, so its end stays at its start. Applied before
+    /// --one-based, so both together give "a" as 1-1. Only printed offsets change; offset options
+    /// like --offset-start are unchanged
+    #[arg(
+        long,
+        help = "Report span end as the offset of its last unit instead of one past it"
+    )]
+    end_inclusive: bool,
+
+    /// Print span offsets as 1-based inclusive ranges, so a lone "a" is 1-1 instead of 0-1; the same as
+    /// --one-based --end-inclusive. Only the printed start and end change; offset options like
+    /// --offset-start still take 0-based offsets
+    #[arg(long, help = "Report span start and end as a 1-based inclusive range")]
+    one_based_offsets: bool,
+
+    /// Count printed span offsets, and the --offset-start/--offset-end range, in this unit instead of
+    /// bytes. Tokenization is unchanged
+    #[arg(long, value_enum, default_value_t = OffsetUnit::Bytes, help = "Unit of span start and end: bytes, chars, graphemes, or utf16")]
+    offset_unit: OffsetUnit,
+
+    /// Add each span's line and column, relative to the start of the processed content
+    #[arg(
+        long,
+        help = "Include the 1-based line and column of each span within the content"
+    )]
+    with_position: bool,
+
+    /// Decode input files and byte string literals that aren't valid UTF-8 by replacing invalid
+    /// sequences, with a warning. Offsets into a file then count the replacement characters, not the bytes on disk
+    #[arg(
+        long,
+        help = "Replace invalid UTF-8 in input files and byte string literals with U+FFFD instead of failing"
+    )]
+    lossy: bool,
+
+    /// Leave out literals in test code: `#[test]` functions (also `#[tokio::test]` and the like) and
+    /// items under a `#[cfg(...)]` that requires `test`, such as `#[cfg(test)] mod tests`
+    #[arg(
+        long,
+        help = "Skip literals inside #[cfg(test)] items and #[test] functions"
+    )]
+    skip_tests: bool,
+
+    /// Only use literals in test code, as recognized by --skip-tests
+    #[arg(
+        long,
+        conflicts_with = "skip_tests",
+        help = "Only use literals inside #[cfg(test)] items and #[test] functions"
+    )]
+    only_tests: bool,
+
+    /// Add the kind of literal each span came from: str, raw_str, byte_str, raw_byte_str, c_str, raw_c_str,
+    /// char, doc, or plain for input that isn't a Rust literal
+    #[arg(
+        long,
+        help = "Include the kind of string literal each span came from (plain for non-Rust input)"
+    )]
+    show_kind: bool,
+
+    /// Add each span's line:column range in the Rust file its string literal came from
+    #[arg(
+        long,
+        help = "For Rust file input, include each span's 1-based line:column range in the source file"
+    )]
+    source_positions: bool,
+
+    /// Describe each matched Rust literal on stderr before its spans: kind, line:column range, and byte
+    /// length. JSON output carries the same record under a `literal` key next to the `spans`
+    #[arg(
+        long,
+        conflicts_with = "quiet",
+        help = "Print the matched literal's kind, position, and byte length to stderr"
+    )]
+    verbose: bool,
+
+    /// Output format for the span list
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, help = "Output format: text, json, jsonl, dot, or sarif")]
+    format: OutputFormat,
+
+    /// Tag each span with its detected language
+    #[arg(
+        long,
+        help = "Detect the language of each span (use --granularity sentence for per-sentence detection)"
+    )]
+    detect_language: bool,
+
+    /// Skip language detection for spans shorter than this many characters
+    #[arg(
+        long,
+        value_name = "LEN",
+        default_value_t = 0,
+        requires = "detect_language",
+        help = "Minimum span length in characters for language detection"
+    )]
+    min_detect_len: usize,
+
+    /// Print only the number of spans left after filtering
+    #[arg(long, help = "Print only the number of matching spans")]
+    count: bool,
+
+    /// Print the span count, unique word count, and covered characters
+    #[arg(
+        long,
+        conflicts_with = "count",
+        help = "Print a summary table: spans, unique words, and covered characters"
+    )]
+    summary: bool,
+
+    /// Print how often each distinct word occurs, most frequent first
+    #[arg(long, conflicts_with_all = ["count", "summary"], help = "Print `count<TAB>word` per distinct word, most frequent first")]
+    frequency: bool,
+
+    /// Print the words directly before and after each matching span in the unfiltered token stream
+    #[arg(
+        long,
+        conflicts_with_all = ["count", "summary", "frequency", "cluster"],
+        help = "Show the unfiltered words before and after each span; JSON aggregates neighbor counts per word"
+    )]
+    neighbors: bool,
+
+    /// Only report the N most frequent words
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "frequency",
+        help = "Limit --frequency output to the N most frequent words"
+    )]
+    top: Option<usize>,
+
+    /// End each printed span (and group header) with a NUL byte instead of a newline, for `xargs -0`
+    #[arg(
+        long,
+        help = "Terminate each span with NUL instead of newline (text and jsonl formats)"
+    )]
+    print0: bool,
+
+    /// Print nothing; exit with 0 if any span matched and 1 otherwise
+    #[arg(
+        long,
+        short = 'q',
+        help = "Print nothing; exit status 0 if any span matched, 1 otherwise"
+    )]
+    quiet: bool,
+
+    /// Fail if any span's offsets don't land on character boundaries of its content
+    #[arg(
+        long,
+        help = "Exit with an error instead of skipping spans whose offsets split a character"
+    )]
+    strict_offsets: bool,
+
+    /// Print the string content with carets marking each span
+    #[arg(
+        long,
+        help = "Show the string content with ^^^ markers under each span"
+    )]
+    annotate: bool,
+
+    /// Replace matching words with a placeholder and print the rebuilt string (can be used multiple times)
+    #[arg(long, num_args = 2, value_names = ["FROM", "TO"], action = clap::ArgAction::Append, help = "Replace words matching FROM with TO and print the rebuilt string (can be used multiple times)")]
+    replace: Vec<String>,
+
+    /// Write results to this file instead of stdout
+    #[arg(
+        long,
+        short = 'o',
+        value_name = "PATH",
+        help = "Write results to a file instead of stdout"
+    )]
+    output: Option<PathBuf>,
+
+    /// Read default options from this TOML file instead of `.span-counter.toml` in the working directory.
+    /// It may set `filter-mode`, `ignore-case`, `strings-as-tokens`, and `format`; flags given on the
+    /// command line take precedence, and a config value is dropped when a given flag conflicts with it
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read default options from this TOML file (default: ./.span-counter.toml if present)"
+    )]
+    config: Option<PathBuf>,
+
+    /// Create missing parent directories of the --output path
+    #[arg(
+        long,
+        requires = "output",
+        help = "Create parent directories for --output if missing"
+    )]
+    create_dirs: bool,
+
+    /// Report the N-byte window containing the most spans
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Report the N-byte window containing the most matching spans"
+    )]
+    densest_window: Option<usize>,
+
+    /// Group spans whose words share a normalized form
+    #[arg(
+        long,
+        help = "Group spans into clusters by a normalized form of their word"
+    )]
+    cluster: bool,
+
+    /// Normalizations that make up the cluster key
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        requires = "cluster",
+        default_values_t = [ClusterKey::Lowercase, ClusterKey::StripAccents, ClusterKey::TrimPunctuation],
+        help = "Normalizations for --cluster keys: lowercase, strip-accents, trim-punctuation (comma-separated)"
+    )]
+    cluster_key: Vec<ClusterKey>,
+
+    /// When to use colors in annotated output
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, help = "Colorize annotated output: auto, always, or never")]
+    color: ColorChoice,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -51,1042 +586,2884 @@ struct Args {
 enum Commands {
     /// Extract spans from a string literal in a Rust source file
     File {
-        /// Path to the Rust source file (.rs)
+        /// Path to the Rust source file (.rs), or `-` to read the source from stdin
         #[arg(value_name = "FILE")]
         file_path: PathBuf,
-        
+
         /// Line number containing the string literal (1-based)
+        #[arg(value_name = "LINE_NUM", required_unless_present_any = ["all", "read_lines_from", "name", "in_fn"])]
+        line_number: Option<usize>,
+
+        /// Last line (inclusive) of a range starting at LINE_NUM; every literal in the range is processed
+        /// under a header with its starting line
+        #[arg(long, value_name = "N", requires = "line_number", conflicts_with_all = ["column", "near_column", "index", "all"])]
+        end_line: Option<usize>,
+
+        /// Column (1-based) inside the wanted literal, to pick one of several on the same line; it's an
+        /// error if no literal covers it. Use --near-column to fall back to the next literal instead
+        #[arg(value_name = "COLUMN", requires = "line_number")]
+        column: Option<usize>,
+
+        /// Column (1-based) selecting the literal that covers it, or else the next one starting after it.
+        /// Can't be combined with COLUMN, which only accepts a literal covering the column
+        #[arg(
+            long,
+            value_name = "N",
+            requires = "line_number",
+            conflicts_with = "column"
+        )]
+        near_column: Option<usize>,
+
+        /// Zero-based index of the literal to use when the line holds several, ordered by start column
+        #[arg(long, value_name = "N", requires = "line_number", conflicts_with_all = ["column", "near_column"])]
+        index: Option<usize>,
+
+        /// Process every string literal in the file, each under a header with its starting line.
+        /// With LINE_NUM, process every literal on that line, each under its index and starting column
+        #[arg(long, conflicts_with_all = ["column", "near_column", "index"])]
+        all: bool,
+
+        /// Read line numbers (one per line) from this file and process each against the same source
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["line_number", "all"])]
+        read_lines_from: Option<PathBuf>,
+
+        /// Use only the requested line's text of a `///` or `//!` doc comment block, instead of the whole block.
+        /// With `--all` and no LINE_NUM, each line of every block is processed on its own
+        #[arg(long)]
+        doc_lines: bool,
+
+        /// When LINE_NUM holds several literals: error, first, last, or concat. Concatenated values have no
+        /// single source literal, so --source-positions and --show-kind don't apply to them
+        #[arg(long, value_enum, default_value_t = OnConflict::Error, requires = "line_number", conflicts_with_all = ["column", "near_column", "index", "all", "end_line"])]
+        on_conflict: OnConflict,
+
+        /// Tokenize the literal's text as written between its quotes, escapes included, instead of its
+        /// decoded value. Offsets then match the source text, so --source-positions and --show-kind don't apply
+        #[arg(long)]
+        raw_literal: bool,
+
+        /// Report span offsets as byte offsets into the file instead of into the literal's value, following
+        /// escapes and line breaks. An escape's bytes all start where the escape does; a span ending at the
+        /// value's end ends at the closing quote
+        #[arg(long, conflicts_with = "raw_literal")]
+        absolute_offsets: bool,
+
+        /// If the file doesn't parse, find literals by lexing it instead, so a syntax error elsewhere in
+        /// the file doesn't stop the lookup. The source must still lex: delimiters have to be balanced
+        #[arg(long)]
+        lenient: bool,
+
+        /// Use the string literal bound to this name by `let`, `const`, `static`, or a struct field
+        /// initializer. With LINE_NUM, the literal must also be on that line
+        #[arg(long, value_name = "IDENT", conflicts_with_all = ["end_line", "column", "near_column", "index", "all", "read_lines_from"])]
+        name: Option<String>,
+
+        /// Only consider literals inside the function, method, or trait method with this name. With
+        /// LINE_NUM, pick the literal on that line; without it, process every literal in the function
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["end_line", "near_column", "index", "read_lines_from", "name", "on_conflict"])]
+        in_fn: Option<String>,
+    },
+    /// Extract spans from the string literal on the same line of several Rust source files
+    Files {
+        /// Line number containing the string literal in every file (1-based)
         #[arg(value_name = "LINE_NUM")]
         line_number: usize,
+
+        /// Paths to the Rust source files; files that fail are reported and skipped
+        #[arg(value_name = "FILES", required = true)]
+        file_paths: Vec<PathBuf>,
+    },
+    /// Extract spans from every string literal in every .rs file under a directory
+    Scan {
+        /// Directory to walk
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+
+        /// Also descend into hidden files and directories (names starting with `.`)
+        #[arg(long)]
+        include_hidden: bool,
+
+        /// Skip directories with this name (can be used multiple times); giving any replaces the default
+        #[arg(long = "exclude-dir", value_name = "NAME", default_value = "target")]
+        exclude_dirs: Vec<String>,
+
+        /// Record each finished file (path and modification time) here, and skip files it already lists.
+        /// Results are written file by file, appending to --output when resuming, so --format must be
+        /// text or jsonl: a JSON, SARIF, or DOT document can't be continued.
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+
+        /// Parse and tokenize files on at most this many threads instead of one per CPU. Output is in
+        /// the same order either way; --checkpoint scans still go file by file
+        #[arg(long, value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+    },
+    /// List every string literal in a Rust file, or in every .rs file under a directory, without
+    /// splitting it into spans. Filters and excludes are matched against each literal's whole content
+    List {
+        /// Rust source file (.rs), directory to walk, or `-` to read the source from stdin
+        path: PathBuf,
+
+        /// When walking a directory, also descend into hidden files and directories
+        #[arg(long)]
+        include_hidden: bool,
+
+        /// When walking a directory, skip directories with this name (can be used multiple times);
+        /// giving any replaces the default
+        #[arg(long = "exclude-dir", value_name = "NAME", default_value = "target")]
+        exclude_dirs: Vec<String>,
     },
     /// Extract spans from raw string content
     String {
         /// String content to process, or use "--" to read from stdin
         #[arg(value_name = "CONTENT")]
         content: Option<String>,
+
+        /// Read stdin line by line and tokenize each line on its own as it arrives, printing every span
+        /// with its line number and offsets within that line. JSON output is written as JSON Lines
+        #[arg(long, conflicts_with = "content")]
+        stream: bool,
+    },
+    /// Extract spans from a plain-text file read verbatim, with offsets into the file
+    Text {
+        /// Path to the file, or `-` to read from stdin
+        #[arg(value_name = "FILE")]
+        file_path: PathBuf,
+
+        /// Only process this 1-based line, without its line break (`\n` or `\r\n`)
+        #[arg(long, value_name = "N")]
+        line: Option<usize>,
+
+        /// With --line, report offsets from the start of the line instead of the start of the file
+        #[arg(long, requires = "line")]
+        relative: bool,
+    },
+    /// Extract spans from a string value in a JSON document
+    Json {
+        /// Path to the JSON file, or `-` to read the document from stdin
+        #[arg(value_name = "FILE")]
+        file_path: PathBuf,
+
+        /// JSON Pointer (RFC 6901) to the string value, e.g. /messages/0/text. Without one, every
+        /// string value that passes the filters is listed with its pointer instead
+        #[arg(long, value_name = "POINTER")]
+        json_pointer: Option<String>,
+    },
+    /// Extract spans from a fenced code block or inline code span in a Markdown document
+    Markdown {
+        /// Path to the Markdown file, or `-` to read it from stdin
+        #[arg(value_name = "FILE")]
+        file_path: PathBuf,
+
+        /// Use the fenced block covering this 1-based line, or else the single inline code span on it.
+        /// Without a line, every fenced block is listed with its line range and language
+        #[arg(value_name = "LINE_NUM")]
+        line_number: Option<usize>,
+
+        /// Report offsets from the start of the code instead of the start of the file
+        #[arg(long, requires = "line_number")]
+        relative: bool,
+    },
+    /// Extract spans from a string value in a TOML document
+    Toml {
+        /// Path to the TOML file, or `-` to read the document from stdin
+        #[arg(value_name = "FILE")]
+        file_path: PathBuf,
+
+        /// Dotted key of the string value, e.g. package.description; array elements are their index, as
+        /// in package.authors.0. Without --key or --line, every string value is listed with its key and line
+        #[arg(long, value_name = "KEY")]
+        key: Option<String>,
+
+        /// Use the single string value on this 1-based line instead of a key
+        #[arg(long, value_name = "N", conflicts_with = "key")]
+        line: Option<usize>,
     },
 }
 
-#[derive(Debug)]
-enum Error {
-    IoError(std::io::Error),
-    ParseError(syn::Error),
-    NoStringFound,
-    MultipleStringsFound,
-    RegexError(regex::Error),
+/// Config file read from the working directory when `--config` isn't given
+const DEFAULT_CONFIG_FILE: &str = ".span-counter.toml";
+
+/// Exit code for a command line clap rejects, kept apart from the codes of [`exit_code`]
+const USAGE_EXIT_CODE: u8 = 7;
+
+fn main() -> ExitCode {
+    let parsed = Args::command()
+        .try_get_matches()
+        .and_then(|matches| Ok((Args::from_arg_matches(&matches)?, matches)));
+    let (mut args, matches) = match parsed {
+        Ok(parsed) => parsed,
+        // --help and --version are reported as errors too, but print to stdout and exit with 0
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => {
+            let _ = err.print();
+            return ExitCode::from(USAGE_EXIT_CODE);
+        }
+    };
+
+    if let Err(err) = apply_config(&mut args, &matches) {
+        eprintln!("Error: {}", err);
+        return ExitCode::from(exit_code(&err));
+    }
+    // Checked after the config file is applied, since it may set --format
+    if let Err(err) = check_usage(&args) {
+        let _ = err.print();
+        return ExitCode::from(USAGE_EXIT_CODE);
+    }
+
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(exit_code(&err))
+        }
+    }
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::IoError(err) => write!(f, "File error: {}", err),
-            Error::ParseError(err) => write!(f, "Parse error: {}", err),
-            Error::NoStringFound => write!(f, "No string found on the specified line"),
-            Error::MultipleStringsFound => write!(f, "Multiple strings found on the same line"),
-            Error::RegexError(err) => write!(f, "Regex error: {}", err),
+/// Reject option combinations clap can't express, as a usage error like clap's own
+fn check_usage(args: &Args) -> Result<(), clap::Error> {
+    // A checkpointed scan writes each file's results as it finishes, and a resumed one appends to the
+    // earlier output, so only formats whose records stand alone line by line can be resumed
+    if let Commands::Scan {
+        checkpoint: Some(_),
+        ..
+    } = &args.command
+    {
+        if !matches!(args.format, OutputFormat::Text | OutputFormat::Jsonl) {
+            return Err(Args::command().error(
+                ErrorKind::ArgumentConflict,
+                "--checkpoint can't be used with --format json, sarif, or dot; use text or jsonl",
+            ));
         }
     }
+    Ok(())
 }
 
-impl std::error::Error for Error {}
+/// Fill in options that weren't given on the command line from the `--config` file, or from
+/// `.span-counter.toml` in the working directory if there is one
+fn apply_config(args: &mut Args, matches: &ArgMatches) -> Result<(), Error> {
+    let path = args
+        .config
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        // Only a config file asked for by name has to exist
+        Err(err) if err.kind() == io::ErrorKind::NotFound && args.config.is_none() => return Ok(()),
+        Err(err) => return Err(path_error(&path)(err)),
+    };
 
-fn main() -> Result<(), Error> {
-    let args = Args::parse();
-    
-    let string_content = match &args.command {
-        Commands::File { file_path, line_number } => {
-            handle_file_command(file_path, *line_number)?
+    let invalid = |message: String| Error::InvalidConfig {
+        path: path.clone(),
+        message,
+    };
+    let table = toml::de::DeTable::parse(&source)
+        .map_err(|err| invalid(err.to_string().trim_end().to_string()))?;
+    // A config value also gives way to a command-line option it can't be combined with, so an explicit
+    // --quoted-only turns off `strings-as-tokens = true` rather than silently running both modes
+    let mut command = Args::command();
+    command.build();
+    let given = |id: &str| {
+        matches.value_source(id) == Some(ValueSource::CommandLine)
+            || conflicts_with_given(&command, matches, id)
+    };
+    for (key, value) in table.get_ref() {
+        let key = key.get_ref().as_ref();
+        match (key, value.get_ref()) {
+            ("filter-mode", toml::de::DeValue::String(mode)) if !given("filter_mode") => {
+                args.filter_mode = FilterMode::from_str(mode, false)
+                    .map_err(|err| invalid(format!("filter-mode: {}", err)))?;
+            }
+            ("format", toml::de::DeValue::String(format)) if !given("format") => {
+                args.format = OutputFormat::from_str(format, false)
+                    .map_err(|err| invalid(format!("format: {}", err)))?;
+            }
+            ("ignore-case", toml::de::DeValue::Boolean(ignore_case)) if !given("ignore_case") => {
+                args.ignore_case = *ignore_case
+            }
+            ("strings-as-tokens", toml::de::DeValue::Boolean(strings_as_tokens))
+                if !given("strings_as_tokens") =>
+            {
+                args.strings_as_tokens = *strings_as_tokens;
+            }
+            ("filter-mode" | "format", toml::de::DeValue::String(_))
+            | ("ignore-case" | "strings-as-tokens", toml::de::DeValue::Boolean(_)) => {}
+            ("filter-mode" | "format", _) => {
+                return Err(invalid(format!("{} must be a string", key)))
+            }
+            ("ignore-case" | "strings-as-tokens", _) => {
+                return Err(invalid(format!("{} must be true or false", key)))
+            }
+            _ => return Err(invalid(format!("unknown option {}", key))),
         }
-        Commands::String { content } => {
-            handle_string_command(content.as_deref())?
+    }
+
+    Ok(())
+}
+
+/// Whether an option given on the command line conflicts with the option `id`, either directly or by
+/// sharing an argument group that allows only one of its members
+fn conflicts_with_given(command: &clap::Command, matches: &ArgMatches, id: &str) -> bool {
+    let Some(arg) = command.get_arguments().find(|arg| arg.get_id() == id) else {
+        return false;
+    };
+    let conflicts = command.get_arg_conflicts_with(arg);
+    command
+        .get_arguments()
+        .filter(|other| {
+            other.get_id() != id
+                && matches.value_source(other.get_id().as_str()) == Some(ValueSource::CommandLine)
+        })
+        .any(|other| {
+            conflicts
+                .iter()
+                .any(|conflict| conflict.get_id() == other.get_id())
+                || command
+                    .get_arg_conflicts_with(other)
+                    .iter()
+                    .any(|conflict| conflict.get_id() == id)
+                || command.get_groups().any(|group| {
+                    !group.clone().is_multiple()
+                        && group.get_args().any(|member| member == id)
+                        && group.get_args().any(|member| member == other.get_id())
+                })
+        })
+}
+
+/// Process exit code for an error, as listed in the `--help` text
+fn exit_code(err: &Error) -> u8 {
+    match err {
+        Error::ParseError(_)
+        | Error::JsonError(_)
+        | Error::TomlError(_)
+        | Error::MissingEmbeddings
+        | Error::InvalidConfig { .. } => 2,
+        Error::NoStringFound { .. }
+        | Error::LiteralIndexOutOfRange { .. }
+        | Error::JsonPointerNotFound(_)
+        | Error::JsonPointerNotString(_)
+        | Error::TomlKeyNotFound(_)
+        | Error::TomlKeyNotString(_)
+        | Error::NameNotFound { .. }
+        | Error::FunctionNotFound(_) => 3,
+        Error::MultipleStringsFound | Error::AmbiguousName { .. } => 4,
+        Error::IoError { .. } => 5,
+        Error::RegexError(_)
+        | Error::InvalidLineNumber(_)
+        | Error::InvalidCString(_)
+        | Error::NonUtf8Literal(_)
+        | Error::InvalidEncoding { .. }
+        | Error::InvalidSpanBoundary { .. }
+        | Error::InvalidEmbeddings(_) => 6,
+    }
+}
+
+fn run(args: &Args) -> Result<ExitCode, Error> {
+    let extractor = filtering_extractor(args)?;
+
+    if let Commands::Scan {
+        dir,
+        include_hidden,
+        exclude_dirs,
+        checkpoint: Some(checkpoint),
+        ..
+    } = &args.command
+    {
+        return run_checkpointed_scan(
+            dir,
+            *include_hidden,
+            exclude_dirs,
+            checkpoint,
+            &extractor,
+            args,
+        );
+    }
+    if let Commands::List {
+        path,
+        include_hidden,
+        exclude_dirs,
+    } = &args.command
+    {
+        return run_list(path, *include_hidden, exclude_dirs, &extractor, args);
+    }
+    if let Commands::Json {
+        file_path,
+        json_pointer: None,
+    } = &args.command
+    {
+        return run_json_list(file_path, &extractor, args);
+    }
+    if let Commands::Toml {
+        file_path,
+        key: None,
+        line: None,
+    } = &args.command
+    {
+        return run_toml_list(file_path, &extractor, args);
+    }
+    if let Commands::Markdown {
+        file_path,
+        line_number: None,
+        ..
+    } = &args.command
+    {
+        return run_markdown_list(file_path, &extractor, args);
+    }
+    if let Commands::String { stream: true, .. } = &args.command {
+        return run_stream(&extractor, args);
+    }
+
+    // Per-file failures from `scan`, reported once the results are written
+    let mut scan_failures = Vec::new();
+
+    let sources = match &args.command {
+        Commands::File {
+            file_path,
+            line_number,
+            name: Some(name),
+            lenient,
+            ..
+        } => {
+            let literal = handle_file_name_command(
+                file_path,
+                name,
+                *line_number,
+                ParseOptions::new(args, *lenient),
+            )?;
+            vec![Source::new(literal.value.clone()).with_origin(literal)]
+        }
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            column,
+            all: false,
+            in_fn: Some(in_fn),
+            doc_lines,
+            lenient,
+            ..
+        } => {
+            let literal = handle_file_in_fn_command(
+                file_path,
+                *line_number,
+                *column,
+                in_fn,
+                ParseOptions::new(args, *lenient),
+            )?;
+            let literal = select_doc_line(literal, *line_number, *doc_lines);
+            vec![Source::new(literal.value.clone()).with_origin(literal)]
+        }
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            all: true,
+            in_fn: Some(in_fn),
+            doc_lines,
+            lenient,
+            ..
+        } => handle_file_in_fn_all_command(file_path, in_fn, ParseOptions::new(args, *lenient))?
+            .into_iter()
+            .filter(|literal| (literal.start_line..=literal.end_line).contains(line_number))
+            .map(|literal| select_doc_line(literal, *line_number, *doc_lines))
+            .enumerate()
+            .map(|(index, literal)| {
+                Source::literal_on_line(
+                    *line_number,
+                    index,
+                    literal.start_column + 1,
+                    literal.value.clone(),
+                )
+                .with_origin(literal)
+            })
+            .collect(),
+        Commands::File {
+            file_path,
+            line_number: None,
+            in_fn: Some(in_fn),
+            lenient,
+            ..
+        } => handle_file_in_fn_all_command(file_path, in_fn, ParseOptions::new(args, *lenient))?
+            .into_iter()
+            .map(|literal| {
+                Source::at_line(literal.start_line, literal.value.clone()).with_origin(literal)
+            })
+            .collect(),
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            end_line: Some(end_line),
+            lenient,
+            ..
+        } => handle_file_range_command(
+            file_path,
+            *line_number,
+            *end_line,
+            ParseOptions::new(args, *lenient),
+        )?
+        .into_iter()
+        .map(|literal| {
+            Source::at_line(literal.start_line, literal.value.clone())
+                .at_column(literal.start_column + 1)
+                .with_origin(literal)
+        })
+        .collect(),
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            on_conflict: OnConflict::Concat,
+            lenient,
+            ..
+        } => {
+            let literals = handle_file_line_all_command(
+                file_path,
+                *line_number,
+                ParseOptions::new(args, *lenient),
+            )?;
+            let values: Vec<&str> = literals
+                .iter()
+                .map(|literal| literal.value.as_str())
+                .collect();
+            vec![Source::new(values.join(" "))]
+        }
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            on_conflict: on_conflict @ (OnConflict::First | OnConflict::Last),
+            doc_lines,
+            lenient,
+            ..
+        } => {
+            let literals = handle_file_line_all_command(
+                file_path,
+                *line_number,
+                ParseOptions::new(args, *lenient),
+            )?;
+            // There is always at least one; no literal on the line is a NoStringFound error
+            let literal = match on_conflict {
+                OnConflict::First => literals.into_iter().next(),
+                _ => literals.into_iter().last(),
+            }
+            .expect("literals on the line");
+            let literal = select_doc_line(literal, *line_number, *doc_lines);
+            vec![Source::new(literal.value.clone()).with_origin(literal)]
+        }
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            near_column: Some(near_column),
+            all: false,
+            doc_lines,
+            lenient,
+            ..
+        } => {
+            let literal = handle_file_near_column_command(
+                file_path,
+                *line_number,
+                *near_column,
+                ParseOptions::new(args, *lenient),
+            )?;
+            let literal = select_doc_line(literal, *line_number, *doc_lines);
+            vec![Source::new(literal.value.clone()).with_origin(literal)]
+        }
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            index: Some(index),
+            all: false,
+            doc_lines,
+            lenient,
+            ..
+        } => {
+            let literal = handle_file_index_command(
+                file_path,
+                *line_number,
+                *index,
+                ParseOptions::new(args, *lenient),
+            )?;
+            let literal = select_doc_line(literal, *line_number, *doc_lines);
+            vec![Source::new(literal.value.clone()).with_origin(literal)]
+        }
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            column,
+            all: false,
+            doc_lines,
+            lenient,
+            ..
+        } => {
+            let literal = handle_file_command(
+                file_path,
+                *line_number,
+                *column,
+                ParseOptions::new(args, *lenient),
+            )?;
+            let literal = select_doc_line(literal, *line_number, *doc_lines);
+            vec![Source::new(literal.value.clone()).with_origin(literal)]
+        }
+        Commands::File {
+            file_path,
+            line_number: Some(line_number),
+            all: true,
+            doc_lines,
+            lenient,
+            ..
+        } => handle_file_line_all_command(
+            file_path,
+            *line_number,
+            ParseOptions::new(args, *lenient),
+        )?
+        .into_iter()
+        .map(|literal| select_doc_line(literal, *line_number, *doc_lines))
+        .enumerate()
+        .map(|(index, literal)| {
+            Source::literal_on_line(
+                *line_number,
+                index,
+                literal.start_column + 1,
+                literal.value.clone(),
+            )
+            .with_origin(literal)
+        })
+        .collect(),
+        Commands::File {
+            file_path,
+            read_lines_from: Some(lines_path),
+            doc_lines,
+            lenient,
+            ..
+        } => handle_file_lines_command(file_path, lines_path, ParseOptions::new(args, *lenient))?
+            .into_iter()
+            .map(|(line, literal)| (line, select_doc_line(literal, line, *doc_lines)))
+            .map(|(line, literal)| {
+                Source::at_line(line, literal.value.clone()).with_origin(literal)
+            })
+            .collect(),
+        Commands::File {
+            file_path,
+            doc_lines,
+            lenient,
+            ..
+        } => handle_file_all_command(file_path, ParseOptions::new(args, *lenient))?
+            .into_iter()
+            .flat_map(|literal| {
+                if *doc_lines && literal.doc_comment_line(literal.start_line).is_some() {
+                    (literal.start_line..=literal.end_line)
+                        .filter_map(|line| literal.doc_comment_line(line))
+                        .collect()
+                } else {
+                    vec![literal]
+                }
+            })
+            .map(|literal| {
+                Source::at_line(literal.start_line, literal.value.clone()).with_origin(literal)
+            })
+            .collect(),
+        Commands::Files {
+            line_number,
+            file_paths,
+        } => handle_files_command(file_paths, *line_number, ParseOptions::new(args, false))?,
+        Commands::Scan {
+            dir,
+            include_hidden,
+            exclude_dirs,
+            jobs,
+            ..
+        } => {
+            if let Some(jobs) = jobs {
+                // Building fails only once the global pool has started, and nothing starts it before this
+                let _ = rayon::ThreadPoolBuilder::new()
+                    .num_threads(jobs.get())
+                    .build_global();
+            }
+            let (sources, failures) = handle_scan_command(
+                dir,
+                *include_hidden,
+                exclude_dirs,
+                ParseOptions::new(args, false),
+            );
+            scan_failures = failures;
+            sources
+        }
+        Commands::List { .. } => unreachable!("list is handled by run_list"),
+        Commands::String { stream: true, .. } => unreachable!("streaming is handled by run_stream"),
+        Commands::String { content, .. } => {
+            vec![Source::new(handle_string_command(
+                content.as_deref(),
+                args.lossy,
+            )?)]
+        }
+        Commands::Text {
+            file_path,
+            line,
+            relative,
+        } => vec![handle_text_command(
+            file_path, *line, *relative, args.lossy,
+        )?],
+        Commands::Json {
+            file_path,
+            json_pointer: Some(json_pointer),
+        } => {
+            vec![Source::new(handle_json_command(
+                file_path,
+                json_pointer,
+                args.lossy,
+            )?)]
+        }
+        Commands::Json {
+            json_pointer: None, ..
+        } => unreachable!("listing JSON strings is handled by run_json_list"),
+        Commands::Markdown {
+            file_path,
+            line_number: Some(line_number),
+            relative,
+        } => {
+            vec![handle_markdown_command(
+                file_path,
+                *line_number,
+                *relative,
+                args.lossy,
+            )?]
+        }
+        Commands::Markdown {
+            line_number: None, ..
+        } => unreachable!("listing code blocks is handled by run_markdown_list"),
+        Commands::Toml {
+            file_path,
+            key,
+            line,
+        } => {
+            let string = handle_toml_command(file_path, key.as_deref(), *line, args.lossy)?;
+            vec![Source::new(string.value)]
         }
     };
-    
-    let spans = get_word_spans(&string_content, args.strings_as_tokens)?;
-    let filtered_spans = filter_word_spans(spans, &args.filters, &args.filter_mode, args.ignore_case)?;
-    
+
+    // Verbose and structured output say where each literal sits in the file, in bytes
+    let sources = match &args.command {
+        Commands::File {
+            file_path,
+            absolute_offsets,
+            ..
+        } if (args.verbose
+            || *absolute_offsets
+            || matches!(args.format, OutputFormat::Json | OutputFormat::Jsonl))
+            && sources.iter().any(|source| source.origin.is_some()) =>
+        {
+            let content = reread_input(file_path)?;
+            sources
+                .into_iter()
+                .map(|source| source.with_file_bytes(&content, *absolute_offsets, args.offset_unit))
+                .collect()
+        }
+        _ => sources,
+    };
+    let sources = match &args.command {
+        Commands::File {
+            raw_literal: true, ..
+        } => sources.into_iter().map(Source::with_written_text).collect(),
+        _ => sources,
+    };
+    let color = args
+        .color
+        .use_color(args.output.is_none() && io::stdout().is_terminal());
+    if matches!(args.format, OutputFormat::Jsonl) && lists_spans(args) && !args.quiet {
+        return stream_span_lines(sources, &extractor, args, color, &scan_failures);
+    }
+
+    let groups = extract_groups(sources, &extractor, args)?;
+    describe_literals(&groups, args);
+
+    // With --quiet, success is reported purely through the exit code
+    if args.quiet {
+        let matched = groups
+            .iter()
+            .any(|(_, indexed_spans)| !indexed_spans.is_empty());
+        return Ok(if matched {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    }
+
     // Print the results
-    for span in filtered_spans {
-        println!("{}", span);
+    match &args.output {
+        Some(path) => {
+            let mut file = create_output_file(path, args.create_dirs, false)?;
+            write_output(&mut file, &groups, args, color)?;
+            file.flush().map_err(output_error(args))?;
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            write_output(&mut stdout, &groups, args, color)?;
+            stdout.flush().map_err(output_error(args))?;
+        }
+    }
+
+    Ok(report_scan_failures(&scan_failures))
+}
+
+/// With --verbose, describe each literal the spans came from on stderr
+fn describe_literals(groups: &[(Source, Vec<IndexedSpan>)], args: &Args) {
+    if !args.verbose {
+        return;
+    }
+    for (source, _) in groups {
+        if let Some(literal) = &source.origin {
+            eprintln!(
+                "{}",
+                describe_literal(source.file.as_deref(), literal, source.file_bytes.as_ref())
+            );
+        }
     }
-    
-    Ok(())
 }
 
-fn filter_word_spans(spans: Vec<WordSpan>, filters: &[String], filter_mode: &FilterMode, ignore_case: bool) -> Result<Vec<WordSpan>, Error> {
-    if filters.is_empty() {
-        return Ok(spans);
+/// Write JSONL span records batch by batch as the sources are tokenized, so the first records of a
+/// long run don't wait for the last source; each batch is one source per thread
+fn stream_span_lines(
+    mut sources: Vec<Source>,
+    extractor: &SpanExtractor,
+    args: &Args,
+    color: bool,
+    scan_failures: &[(PathBuf, Error)],
+) -> Result<ExitCode, Error> {
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(create_output_file(path, args.create_dirs, false)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let batch_size = rayon::current_num_threads().max(1);
+    while !sources.is_empty() {
+        let rest = sources.split_off(sources.len().min(batch_size));
+        let groups = extract_groups(std::mem::replace(&mut sources, rest), extractor, args)?;
+        describe_literals(&groups, args);
+        write_output(&mut out, &groups, args, color)?;
     }
+    out.flush().map_err(output_error(args))?;
 
-    match filter_mode {
-        FilterMode::Exact => {
-            let filtered = spans.into_iter()
-                .filter(|span| {
-                    filters.iter().any(|filter| {
-                        if ignore_case {
-                            span.word.to_lowercase() == filter.to_lowercase()
-                        } else {
-                            span.word == *filter
-                        }
-                    })
-                })
-                .collect();
-            Ok(filtered)
-        }
-        FilterMode::Contains => {
-            let filtered = spans.into_iter()
-                .filter(|span| {
-                    filters.iter().any(|filter| {
-                        if ignore_case {
-                            span.word.to_lowercase().contains(&filter.to_lowercase())
-                        } else {
-                            span.word.contains(filter)
-                        }
-                    })
+    Ok(report_scan_failures(scan_failures))
+}
+
+/// Tokenize, filter, and order the spans of each source
+fn extract_groups(
+    sources: Vec<Source>,
+    extractor: &SpanExtractor,
+    args: &Args,
+) -> Result<Vec<(Source, Vec<IndexedSpan>)>, Error> {
+    for literal in sources
+        .iter()
+        .filter_map(|source| source.origin.as_ref())
+        .filter(|literal| literal.lossy)
+    {
+        if !args.lossy {
+            literal.check_utf8()?;
+        }
+        eprintln!(
+            "warning: byte string on line {} is not valid UTF-8; invalid sequences were replaced",
+            literal.start_line
+        );
+    }
+
+    // Sources are tokenized in parallel; the results keep their order, and notes are printed after
+    let extracted: Vec<_> = sources
+        .par_iter()
+        .map(|source| extractor.extract_source_truncated(source))
+        .collect();
+    let mut groups = Vec::new();
+    for (source, extracted) in sources.into_iter().zip(extracted) {
+        let (indexed_spans, truncated) = extracted?;
+        if truncated {
+            let max_spans = args.max_spans.unwrap_or_default();
+            match source.header() {
+                Some(header) => eprintln!(
+                    "note: {} stopped after {} spans (--max-spans); output is partial",
+                    header, max_spans
+                ),
+                None => eprintln!(
+                    "note: stopped after {} spans (--max-spans); output is partial",
+                    max_spans
+                ),
+            }
+        }
+        let (offset_start, offset_end) = if args.offset_start.is_some() || args.offset_end.is_some()
+        {
+            // The range is given in printed offsets; the spans it is compared with are in content bytes
+            let positions = SpanPositions::new(&source, args);
+            let offsets = positions.file_offsets.or(positions.units.as_deref());
+            let to_byte = |offset: usize| {
+                offsets.map_or(offset, |offsets| {
+                    offsets.partition_point(|&unit| unit < offset)
                 })
+            };
+            (args.offset_start.map(to_byte), args.offset_end.map(to_byte))
+        } else {
+            (None, None)
+        };
+        let indexed_spans =
+            filter_by_offset_range(indexed_spans, offset_start, offset_end, args.overlap);
+        let indexed_spans = filter_by_length_percentile(
+            indexed_spans,
+            args.len_top_percentile,
+            args.len_bottom_percentile,
+        );
+        // The DOT graph needs every repeated pair, so it is built from the spans before deduplication
+        let dedup = args.dedup && !matches!(args.format, OutputFormat::Dot);
+        let indexed_spans = if dedup {
+            dedup_spans(indexed_spans, args.ignore_case)
+        } else {
+            indexed_spans
+        };
+        let mut indexed_spans = match args.context {
+            Some(context) => {
+                // Context comes from the token stream before filters and excludes were applied
+                let unfiltered = extractor
+                    .clone()
+                    .filters(Vec::new())
+                    .excludes(Vec::new())
+                    .invert_match(false);
+                let stream: Vec<WordSpan> = unfiltered
+                    .extract_source_truncated(&source)?
+                    .0
+                    .into_iter()
+                    .map(|(_, span)| span)
+                    .collect();
+                spans_with_context(&stream, &indexed_spans, context)
+            }
+            None => indexed_spans,
+        };
+        sort_spans(&mut indexed_spans, &args.sort, args.ignore_case);
+        if args.strict_offsets {
+            for (_, span) in &indexed_spans {
+                safe_slice(&source.content, span.start, span.end)?;
+            }
+        }
+        groups.push((source, indexed_spans));
+    }
+
+    Ok(groups)
+}
+
+/// Tokenize stdin one line at a time, writing each line's spans before reading the next, so memory
+/// stays bounded and output keeps up with a live pipe. Offsets count from the start of the line.
+/// With `--quiet`, stop at the first line with a matching span.
+fn run_stream(extractor: &SpanExtractor, args: &Args) -> Result<ExitCode, Error> {
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(create_output_file(path, args.create_dirs, false)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let terminator = if args.print0 { '\0' } else { '\n' };
+
+    for (line_index, line) in io::stdin().lock().lines().enumerate() {
+        let line = line.map_err(path_error(Path::new("<stdin>")))?;
+        let groups = extract_groups(vec![Source::at_line(line_index + 1, line)], extractor, args)?;
+        for (source, spans) in &groups {
+            if args.quiet {
+                if !spans.is_empty() {
+                    return Ok(ExitCode::SUCCESS);
+                }
+                continue;
+            }
+            let positions = SpanPositions::new(source, args);
+            for (index, span) in spans {
+                match args.format {
+                    OutputFormat::Json | OutputFormat::Jsonl => {
+                        let mut value = span_json(*index, span, &positions, args);
+                        value["group"] = source.group_json();
+                        serde_json::to_writer(&mut out, &value).map_err(Error::JsonError)?;
+                        write!(out, "{}", terminator).map_err(output_error(args))?;
+                    }
+                    OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif => {
+                        write!(
+                            out,
+                            "{} | {}{}",
+                            line_index + 1,
+                            format_span_text(*index, span, &positions, args),
+                            terminator
+                        )
+                        .map_err(output_error(args))?;
+                    }
+                }
+            }
+        }
+        out.flush().map_err(output_error(args))?;
+    }
+
+    Ok(if args.quiet {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Print the per-file failures of a scan after its results; the exit code is the first failure's
+fn report_scan_failures(failures: &[(PathBuf, Error)]) -> ExitCode {
+    for (path, err) in failures {
+        eprintln!("error: {}", describe_failure(path, err));
+    }
+    match failures.first() {
+        Some((_, err)) => ExitCode::from(exit_code(err)),
+        None => ExitCode::SUCCESS,
+    }
+}
+
+/// Scan file by file, recording each file in the checkpoint only after its results are written,
+/// so an interrupted scan resumes after the last finished file. Files listed in the checkpoint
+/// with a different modification time are processed again.
+fn run_checkpointed_scan(
+    dir: &Path,
+    include_hidden: bool,
+    exclude_dirs: &[String],
+    checkpoint_path: &Path,
+    extractor: &SpanExtractor,
+    args: &Args,
+) -> Result<ExitCode, Error> {
+    let finished = read_checkpoint(checkpoint_path)?;
+    let mut checkpoint = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(checkpoint_path)
+        .map_err(path_error(checkpoint_path))?;
+
+    let mut rust_files = Vec::new();
+    let mut failures = Vec::new();
+    collect_rust_files(
+        dir,
+        include_hidden,
+        exclude_dirs,
+        &mut rust_files,
+        &mut failures,
+    );
+
+    // A resumed scan adds to the output of the earlier run instead of replacing it
+    let color = args
+        .color
+        .use_color(args.output.is_none() && io::stdout().is_terminal());
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(create_output_file(
+            path,
+            args.create_dirs,
+            !finished.is_empty(),
+        )?),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    let mut matched = false;
+    for file_path in rust_files {
+        let modified = match modification_stamp(&file_path) {
+            Ok(modified) => modified,
+            Err(err) => {
+                failures.push((file_path, err));
+                continue;
+            }
+        };
+        if finished.get(&file_path) == Some(&modified) {
+            continue;
+        }
+
+        let mut warnings = Vec::new();
+        let sources = scan_file_sources(&file_path, ParseOptions::new(args, false), &mut warnings);
+        print_warnings(&warnings);
+        let groups = match sources {
+            Ok(sources) => extract_groups(sources, extractor, args)?,
+            Err(err) => {
+                failures.push((file_path, err));
+                continue;
+            }
+        };
+        if args.quiet {
+            matched |= groups
+                .iter()
+                .any(|(_, indexed_spans)| !indexed_spans.is_empty());
+        } else {
+            write_output(&mut out, &groups, args, color)?;
+            out.flush().map_err(output_error(args))?;
+        }
+
+        write_checkpoint_entry(&mut checkpoint, &modified, &file_path)
+            .map_err(path_error(checkpoint_path))?;
+        checkpoint.flush().map_err(path_error(checkpoint_path))?;
+    }
+
+    if args.quiet && failures.is_empty() {
+        return Ok(if matched {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    }
+    Ok(report_scan_failures(&failures))
+}
+
+fn span_extractor(args: &Args) -> SpanExtractor {
+    let extractor = if args.quote_pairs.is_empty() {
+        SpanExtractor::new()
+    } else {
+        SpanExtractor::new().quote_pairs(args.quote_pairs.clone())
+    };
+    let extractor = match &args.delimiters {
+        Some(delimiters) => extractor.delimiters(delimiters.chars().collect()),
+        None => extractor,
+    };
+    let extractor = match args.max_spans {
+        Some(max_spans) => extractor.max_spans(max_spans),
+        None => extractor,
+    };
+    extractor
+        .strings_as_tokens(args.strings_as_tokens)
+        .quoted_only(args.quoted_only)
+        .skip_empty_quotes(args.skip_empty_quotes)
+        .protect_quotes(args.protect_quotes)
+        .json_aware(args.json_aware)
+        .json_strip_quotes(args.json_strip_quotes)
+        .dotted_identifiers(args.dotted_identifiers)
+        .trim_punctuation(args.trim_punctuation)
+        .keep_numbers(args.keep_numbers)
+        .thousands_separators(args.thousands_separators)
+        .preserve_percentages(args.preserve_percentages)
+        .preserve_flags(args.preserve_flags)
+        .include_whitespace(args.include_whitespace)
+        .normalization(args.normalize.clone())
+        .filter_normalized(args.filter_normalized)
+        .granularity(args.granularity.clone())
+        .filters(args.filters.clone())
+        .excludes(args.excludes.clone())
+        .invert_match(args.invert_match)
+        .filter_mode(args.filter_mode.clone())
+        .ignore_case(args.ignore_case)
+        .extract_captures(args.extract_captures)
+        .captures_only(args.captures_only)
+        // Annotations, context, and offset ranges slice the content by span, so spans stay in bytes
+        // and --offset-unit is applied when they are printed
+        .offset_unit(OffsetUnit::Bytes)
+}
+
+/// Write an inventory of the literals that pass the filters, one record per literal. A directory is
+/// walked like `scan`, each record names its file, and files that fail are skipped with a warning.
+fn run_list(
+    path: &Path,
+    include_hidden: bool,
+    exclude_dirs: &[String],
+    extractor: &SpanExtractor,
+    args: &Args,
+) -> Result<ExitCode, Error> {
+    let files = if path.is_dir() {
+        let mut rust_files = Vec::new();
+        let mut failures = Vec::new();
+        collect_rust_files(
+            path,
+            include_hidden,
+            exclude_dirs,
+            &mut rust_files,
+            &mut failures,
+        );
+
+        let mut files = Vec::new();
+        for file_path in rust_files {
+            match handle_file_all_command(&file_path, ParseOptions::new(args, false)) {
+                Ok(literals) => files.push((Some(file_path), literals)),
+                Err(err) => failures.push((file_path, err)),
+            }
+        }
+        for (file_path, err) in failures {
+            eprintln!("warning: {}", describe_failure(&file_path, &err));
+        }
+        files
+    } else {
+        vec![(
+            None,
+            handle_file_all_command(path, ParseOptions::new(args, false))?,
+        )]
+    };
+
+    let mut listed = Vec::new();
+    for (file, literals) in &files {
+        for literal in literals {
+            if extractor.keeps_whole(&literal.value)? {
+                listed.push((file.as_deref(), literal));
+            }
+        }
+    }
+
+    if args.quiet {
+        return Ok(if listed.is_empty() {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(create_output_file(path, args.create_dirs, false)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let terminator = if args.print0 { '\0' } else { '\n' };
+    match args.format {
+        OutputFormat::Text | OutputFormat::Dot => {
+            for (file, literal) in &listed {
+                let record = format!(
+                    "{}{}:{} | {} bytes | {} | {:?}",
+                    file.map_or(String::new(), |file| format!("{}:", file.display())),
+                    literal.start_line,
+                    literal.start_column + 1,
+                    literal.value.len(),
+                    literal.kind_label(),
+                    literal.value
+                );
+                write!(out, "{}{}", record, terminator).map_err(output_error(args))?;
+            }
+        }
+        OutputFormat::Json => {
+            let records: Vec<serde_json::Value> = listed
+                .iter()
+                .map(|(file, literal)| literal_record(*file, literal))
                 .collect();
-            Ok(filtered)
+            serde_json::to_writer_pretty(&mut out, &records).map_err(Error::JsonError)?;
+            writeln!(out).map_err(output_error(args))?;
         }
-        FilterMode::Regex => {
-            let mut compiled_regexes = Vec::new();
-            for filter in filters {
-                let regex = if ignore_case {
-                    Regex::new(&format!("(?i){}", filter)).map_err(Error::RegexError)?
-                } else {
-                    Regex::new(filter).map_err(Error::RegexError)?
-                };
-                compiled_regexes.push(regex);
+        OutputFormat::Jsonl => {
+            for (file, literal) in &listed {
+                serde_json::to_writer(&mut out, &literal_record(*file, literal))
+                    .map_err(Error::JsonError)?;
+                write!(out, "{}", terminator).map_err(output_error(args))?;
             }
-            
-            let filtered = spans.into_iter()
-                .filter(|span| {
-                    compiled_regexes.iter().any(|regex| regex.is_match(&span.word))
+        }
+        OutputFormat::Sarif => {
+            let results: Vec<serde_json::Value> = listed
+                .iter()
+                .map(|(file, literal)| {
+                    let mut result = serde_json::json!({
+                        "ruleId": "literal",
+                        "level": "note",
+                        "message": { "text": format!("{} literal {:?}", literal.kind_label(), literal.value) },
+                    });
+                    let file = file.unwrap_or(path);
+                    if file != Path::new("-") {
+                        result["locations"] = serde_json::json!([{
+                            "physicalLocation": {
+                                "artifactLocation": { "uri": file.display().to_string().replace('\\', "/") },
+                                "region": {
+                                    "startLine": literal.start_line,
+                                    "startColumn": literal.start_column + 1,
+                                    "endLine": literal.end_line,
+                                    "endColumn": literal.end_column + 1,
+                                },
+                            },
+                        }]);
+                    }
+                    result
                 })
                 .collect();
-            Ok(filtered)
+            let log = sarif_document(
+                "literal",
+                "A string literal that passed the filters",
+                results,
+            );
+            serde_json::to_writer_pretty(&mut out, &log).map_err(Error::JsonError)?;
+            writeln!(out).map_err(output_error(args))?;
         }
     }
+    out.flush().map_err(output_error(args))?;
+
+    Ok(ExitCode::SUCCESS)
 }
 
-fn handle_file_command(file_path: &PathBuf, line_number: usize) -> Result<String, Error> {
-    // Read and parse the file
-    let content = fs::read_to_string(file_path).map_err(Error::IoError)?;
-    let file = syn::parse_file(&content).map_err(Error::ParseError)?;
-    
-    // Find string literals on the target line and return the content
-    find_strings_on_line(&file, line_number)
+/// List every string value in a JSON document that passes the filters, with the pointer to pass
+/// to `--json-pointer`
+fn run_json_list(
+    file_path: &Path,
+    extractor: &SpanExtractor,
+    args: &Args,
+) -> Result<ExitCode, Error> {
+    let document: serde_json::Value =
+        serde_json::from_str(&read_input(file_path, args.lossy)?).map_err(Error::JsonError)?;
+    let mut listed = Vec::new();
+    for (pointer, value) in json_string_pointers(&document) {
+        if extractor.keeps_whole(&value)? {
+            let text = format!("{} | {:?}", pointer, value);
+            listed.push((
+                text,
+                serde_json::json!({ "pointer": pointer, "value": value }),
+            ));
+        }
+    }
+    write_listing(&listed, args)
 }
 
-fn handle_string_command(content: Option<&str>) -> Result<String, Error> {
-    let input = match content {
-        Some("--") => {
-            // Read from stdin
-            read_from_stdin()?
+/// List every string value in a TOML document that passes the filters, with the key to pass to
+/// `--key` and the line and column it starts on
+fn run_toml_list(
+    file_path: &Path,
+    extractor: &SpanExtractor,
+    args: &Args,
+) -> Result<ExitCode, Error> {
+    let mut listed = Vec::new();
+    for string in toml_strings(&read_input(file_path, args.lossy)?)? {
+        if extractor.keeps_whole(&string.value)? {
+            let text = format!(
+                "{} | {}:{} | {:?}",
+                string.key, string.start_line, string.start_column, string.value
+            );
+            let record = serde_json::json!({
+                "key": string.key,
+                "line": string.start_line,
+                "column": string.start_column,
+                "value": string.value,
+            });
+            listed.push((text, record));
         }
-        Some(content) => content.to_string(),
-        None => {
-            // No content provided, read from stdin
-            read_from_stdin()?
+    }
+    write_listing(&listed, args)
+}
+
+/// List every fenced code block in a Markdown document whose content passes the filters, with its
+/// line range and language
+fn run_markdown_list(
+    file_path: &Path,
+    extractor: &SpanExtractor,
+    args: &Args,
+) -> Result<ExitCode, Error> {
+    let content = read_input(file_path, args.lossy)?;
+    let mut listed = Vec::new();
+    for block in markdown_code_blocks(&content) {
+        if extractor.keeps_whole(&content[block.content.clone()])? {
+            let text = format!(
+                "{}-{} | {}",
+                block.start_line,
+                block.end_line,
+                block.language.as_deref().unwrap_or("-")
+            );
+            let record = serde_json::json!({
+                "start_line": block.start_line,
+                "end_line": block.end_line,
+                "language": block.language,
+            });
+            listed.push((text, record));
         }
+    }
+    write_listing(&listed, args)
+}
+
+/// Write listed values as text lines, or as their JSON records; other formats fall back to text
+fn write_listing(listed: &[(String, serde_json::Value)], args: &Args) -> Result<ExitCode, Error> {
+    if args.quiet {
+        return Ok(if listed.is_empty() {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(create_output_file(path, args.create_dirs, false)?),
+        None => Box::new(io::stdout().lock()),
     };
-    
-    Ok(input)
+    let terminator = if args.print0 { '\0' } else { '\n' };
+    match args.format {
+        OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif => {
+            for (text, _) in listed {
+                write!(out, "{}{}", text, terminator).map_err(output_error(args))?;
+            }
+        }
+        OutputFormat::Json => {
+            let records: Vec<&serde_json::Value> =
+                listed.iter().map(|(_, record)| record).collect();
+            serde_json::to_writer_pretty(&mut out, &records).map_err(Error::JsonError)?;
+            writeln!(out).map_err(output_error(args))?;
+        }
+        OutputFormat::Jsonl => {
+            for (_, record) in listed {
+                serde_json::to_writer(&mut out, record).map_err(Error::JsonError)?;
+                write!(out, "{}", terminator).map_err(output_error(args))?;
+            }
+        }
+    }
+    out.flush().map_err(output_error(args))?;
+
+    Ok(ExitCode::SUCCESS)
 }
 
-fn read_from_stdin() -> Result<String, Error> {
-    let mut buffer = String::new();
-    io::stdin().read_to_string(&mut buffer).map_err(Error::IoError)?;
-    Ok(buffer)
+/// A `list` record: the file when walking a directory, where the literal is written (1-based columns,
+/// the end inclusive), its content's length in bytes, and its kind label
+fn literal_record(file: Option<&Path>, literal: &StringLiteralInfo) -> serde_json::Value {
+    let mut record = serde_json::Map::new();
+    if let Some(file) = file {
+        record.insert(
+            "file".to_string(),
+            serde_json::json!(file.display().to_string()),
+        );
+    }
+    record.insert("line".to_string(), serde_json::json!(literal.start_line));
+    record.insert(
+        "column".to_string(),
+        serde_json::json!(literal.start_column + 1),
+    );
+    record.insert("end_line".to_string(), serde_json::json!(literal.end_line));
+    record.insert(
+        "end_column".to_string(),
+        serde_json::json!(literal.end_column),
+    );
+    record.insert("length".to_string(), serde_json::json!(literal.value.len()));
+    record.insert("kind".to_string(), serde_json::json!(literal.kind_label()));
+    record.insert("value".to_string(), serde_json::json!(literal.value));
+    serde_json::Value::Object(record)
 }
 
-fn find_strings_on_line(file: &File, target_line: usize) -> Result<String, Error> {
-    let mut visitor = StringVisitor::new(target_line);
-    visitor.visit_file(file);
-    
-    match visitor.found_strings.len() {
-        0 => Err(Error::NoStringFound),
-        1 => Ok(visitor.found_strings.into_iter().next().unwrap()),
-        _ => Err(Error::MultipleStringsFound),
+/// The `--verbose` preamble for a literal, like `literal: 2:13-4:45 | 94 bytes | raw_str`, followed by
+/// `| file bytes 120-220` when its byte range in the file is known
+fn describe_literal(
+    file: Option<&Path>,
+    literal: &StringLiteralInfo,
+    file_bytes: Option<&Range<usize>>,
+) -> String {
+    let file = file.map_or(String::new(), |file| format!("{}:", file.display()));
+    let mut description = format!(
+        "literal: {}{}:{}-{}:{} | {} bytes | {}",
+        file,
+        literal.start_line,
+        literal.start_column + 1,
+        literal.end_line,
+        literal.end_column,
+        literal.value.len(),
+        literal.kind_label()
+    );
+    if let Some(bytes) = file_bytes {
+        description.push_str(&format!(" | file bytes {}-{}", bytes.start, bytes.end));
     }
+    description
 }
 
-struct StringVisitor {
-    target_line: usize,
-    found_strings: Vec<String>,
+/// The span extractor with the embeddings file loaded, read once per run
+fn filtering_extractor(args: &Args) -> Result<SpanExtractor, Error> {
+    let extractor = span_extractor(args)
+        .similarity_threshold(args.similarity_threshold)
+        .fuzzy_distance(args.fuzzy_distance);
+    let Some(path) = &args.embeddings else {
+        return Ok(extractor);
+    };
+    let text = fs::read_to_string(path).map_err(path_error(path))?;
+    Ok(extractor.embeddings(Embeddings::parse(&text)?))
 }
 
-impl StringVisitor {
-    fn new(target_line: usize) -> Self {
-        Self {
-            target_line,
-            found_strings: Vec::new(),
+/// Whether the output is the plain list of spans rather than counts, summaries, or a rewritten text
+fn lists_spans(args: &Args) -> bool {
+    !args.count
+        && !args.summary
+        && !args.frequency
+        && !args.neighbors
+        && args.replace.is_empty()
+        && !args.annotate
+        && args.densest_window.is_none()
+        && !args.cluster
+}
+
+fn write_output(
+    out: &mut dyn Write,
+    groups: &[(Source, Vec<IndexedSpan>)],
+    args: &Args,
+    color: bool,
+) -> Result<(), Error> {
+    if lists_spans(args) {
+        return write_span_list(out, groups, args);
+    }
+
+    for (source, indexed_spans) in groups {
+        if let Some(header) = source.header() {
+            writeln!(out, "{}", header).map_err(output_error(args))?;
+        }
+        let filtered_spans: Vec<WordSpan> =
+            indexed_spans.iter().map(|(_, span)| span.clone()).collect();
+
+        if args.count {
+            writeln!(out, "{}", filtered_spans.len()).map_err(output_error(args))?;
+        } else if args.frequency {
+            let mut frequencies = word_frequencies(&filtered_spans, args.ignore_case);
+            frequencies.truncate(args.top.unwrap_or(usize::MAX));
+            write_frequencies(out, &frequencies, &args.format).map_err(output_error(args))?;
+        } else if args.neighbors {
+            // Neighbors come from the token stream before filters and excludes were applied
+            let unfiltered = span_extractor(args)
+                .filters(Vec::new())
+                .excludes(Vec::new())
+                .invert_match(false);
+            let stream: Vec<WordSpan> = unfiltered
+                .extract_source_truncated(source)?
+                .0
+                .into_iter()
+                .map(|(_, span)| span)
+                .collect();
+            write_neighbors(
+                out,
+                &span_neighbors(&stream, indexed_spans),
+                &SpanPositions::new(source, args),
+                args,
+            )
+            .map_err(output_error(args))?;
+        } else if args.summary {
+            write_summary(out, &summarize_spans(&filtered_spans), &args.format)
+                .map_err(output_error(args))?;
+        } else if let Some(window_size) = args.densest_window {
+            let densest = find_densest_window(&filtered_spans, window_size).map(|window| {
+                let positions = SpanPositions::new(source, args);
+                let (start, end) = printed_range(window.start, window.end, &positions, args);
+                DensestWindow {
+                    start,
+                    end,
+                    ..window
+                }
+            });
+            match (&args.format, densest) {
+                (OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif, Some(window)) => {
+                    writeln!(
+                        out,
+                        "{}-{} | {} spans",
+                        window.start, window.end, window.count
+                    )
+                }
+                (OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif, None) => {
+                    writeln!(out, "no spans")
+                }
+                (OutputFormat::Json | OutputFormat::Jsonl, window) => writeln!(
+                    out,
+                    "{}",
+                    serde_json::json!(window.map(|window| serde_json::json!({
+                        "start": window.start,
+                        "end": window.end,
+                        "count": window.count,
+                    })))
+                ),
+            }
+            .map_err(output_error(args))?;
+        } else if args.cluster {
+            write_clusters(
+                out,
+                &cluster_spans(indexed_spans.clone(), &args.cluster_key),
+                &SpanPositions::new(source, args),
+                args,
+            )?;
+        } else if !args.replace.is_empty() {
+            let replacements: Vec<(String, String)> = args
+                .replace
+                .chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect();
+            let rebuilt = filtering_extractor(args)?.replace_spans(
+                &source.content,
+                &filtered_spans,
+                &replacements,
+            )?;
+            write!(out, "{}", rebuilt).map_err(output_error(args))?;
+            if !rebuilt.ends_with('\n') {
+                writeln!(out).map_err(output_error(args))?;
+            }
+        } else {
+            let positions = SpanPositions::new(source, args);
+            write_annotated(
+                out,
+                &source.content,
+                &filtered_spans,
+                &|span| printed_offsets(span, &positions, args),
+                color,
+            )
+            .map_err(output_error(args))?;
         }
     }
+
+    Ok(())
 }
 
-impl<'ast> Visit<'ast> for StringVisitor {
-    fn visit_lit_str(&mut self, lit_str: &'ast LitStr) {
-        let span = lit_str.span();
-        let start_line = span.start().line;
-        let end_line = span.end().line;
-        
-        if self.target_line >= start_line && self.target_line <= end_line {
-            self.found_strings.push(lit_str.value());
+fn create_output_file(
+    path: &Path,
+    create_dirs: bool,
+    append: bool,
+) -> Result<io::BufWriter<fs::File>, Error> {
+    if create_dirs {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).map_err(path_error(parent))?;
         }
     }
+    let file = if append {
+        fs::OpenOptions::new().create(true).append(true).open(path)
+    } else {
+        fs::File::create(path)
+    };
+    let file = file.map_err(path_error(path))?;
+    Ok(io::BufWriter::new(file))
+}
+
+/// Wrap an I/O error with the path it happened on
+fn path_error(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |source| Error::IoError {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Wrap an I/O error from writing results with the output path, or `<stdout>`
+fn output_error(args: &Args) -> impl FnOnce(io::Error) -> Error + '_ {
+    path_error(args.output.as_deref().unwrap_or(Path::new("<stdout>")))
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct WordSpan {
-    pub word: String,
-    pub start: usize,
-    pub end: usize,
+/// With `--verbose`, the record describing the literal a source came from
+fn verbose_literal(source: &Source, args: &Args) -> Option<serde_json::Value> {
+    source
+        .origin
+        .as_ref()
+        .filter(|_| args.verbose)
+        .map(|literal| {
+            let mut record = literal_record(None, literal);
+            if let Some(bytes) = &source.file_bytes {
+                record["literal_file_byte_start"] = serde_json::json!(bytes.start);
+                record["literal_file_byte_end"] = serde_json::json!(bytes.end);
+            }
+            record
+        })
 }
 
-impl std::fmt::Display for WordSpan {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "\"{}\" | {}-{}", self.word, self.start, self.end)
+fn write_span_list(
+    out: &mut dyn Write,
+    groups: &[(Source, Vec<IndexedSpan>)],
+    args: &Args,
+) -> Result<(), Error> {
+    // Words can contain newlines, so --print0 ends every record with NUL instead
+    let terminator = if args.print0 { '\0' } else { '\n' };
+    match args.format {
+        OutputFormat::Text => {
+            for (source, spans) in groups {
+                if let Some(header) = source.header() {
+                    write!(out, "{}{}", header, terminator).map_err(output_error(args))?;
+                }
+                let positions = SpanPositions::new(source, args);
+                for (index, span) in spans {
+                    write!(
+                        out,
+                        "{}{}",
+                        format_span_text(*index, span, &positions, args),
+                        terminator
+                    )
+                    .map_err(output_error(args))?;
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let span_values = |source: &Source, spans: &[IndexedSpan]| -> Vec<serde_json::Value> {
+                let positions = SpanPositions::new(source, args);
+                spans
+                    .iter()
+                    .map(|(index, span)| span_json(*index, span, &positions, args))
+                    .collect()
+            };
+            let value = match groups {
+                [(source, spans)] if !source.is_grouped() => match verbose_literal(source, args) {
+                    Some(literal) => {
+                        serde_json::json!({ "literal": literal, "spans": span_values(source, spans) })
+                    }
+                    None => serde_json::json!(span_values(source, spans)),
+                },
+                _ => groups
+                    .iter()
+                    .map(|(source, spans)| {
+                        let mut group = source.group_json();
+                        if let Some(literal) = verbose_literal(source, args) {
+                            group["literal"] = literal;
+                        }
+                        group["spans"] = serde_json::json!(span_values(source, spans));
+                        group
+                    })
+                    .collect(),
+            };
+            serde_json::to_writer_pretty(&mut *out, &value).map_err(Error::JsonError)?;
+            writeln!(out).map_err(output_error(args))?;
+        }
+        OutputFormat::Jsonl => {
+            let grouped = !matches!(groups, [(source, _)] if !source.is_grouped());
+            for (source, spans) in groups {
+                let positions = SpanPositions::new(source, args);
+                let group = grouped.then(|| source.group_json());
+                let literal = verbose_literal(source, args);
+                for (index, span) in spans {
+                    let mut value = span_json(*index, span, &positions, args);
+                    if let Some(group) = &group {
+                        value["group"] = group.clone();
+                    }
+                    if let Some(literal) = &literal {
+                        value["literal"] = literal.clone();
+                    }
+                    serde_json::to_writer(&mut *out, &value).map_err(Error::JsonError)?;
+                    write!(out, "{}", terminator).map_err(output_error(args))?;
+                }
+                // A pipe reader can start on each group as it is written; a file gets it when its buffer fills
+                if args.output.is_none() {
+                    out.flush().map_err(output_error(args))?;
+                }
+            }
+        }
+        OutputFormat::Dot => write_dot(out, groups.iter().map(|(_, spans)| spans.as_slice()))
+            .map_err(output_error(args))?,
+        OutputFormat::Sarif => {
+            serde_json::to_writer_pretty(&mut *out, &sarif_log(groups, args))
+                .map_err(Error::JsonError)?;
+            writeln!(out).map_err(output_error(args))?;
+        }
     }
+
+    Ok(())
 }
 
-fn get_word_spans(string_content: &str, strings_as_tokens: bool) -> Result<Vec<WordSpan>, Error> {
-    if strings_as_tokens {
-        get_word_spans_with_quoted_strings(string_content)
-    } else {
-        get_word_spans_default(string_content)
+/// A minimal SARIF 2.1.0 log for code-scanning tools, with a result for every span
+fn sarif_log(groups: &[(Source, Vec<IndexedSpan>)], args: &Args) -> serde_json::Value {
+    let mut results = Vec::new();
+    for (source, spans) in groups {
+        results.extend(sarif_span_results(
+            spans,
+            source_file(source, args),
+            source.origin.as_ref(),
+        ));
     }
+
+    sarif_document("span", "A span that passed the filters", results)
 }
 
-fn get_word_spans_default(string_content: &str) -> Result<Vec<WordSpan>, Error> {
-    let mut spans = Vec::new();
-    let mut byte_pos = 0;
-    
-    for segment in string_content.split_word_bounds() {
-        // Only include non-whitespace segments as tokens
-        if !segment.chars().all(|c| c.is_whitespace()) {
-            spans.push(WordSpan {
-                word: segment.to_string(),
-                start: byte_pos,
-                end: byte_pos + segment.len(),
-            });
+/// The Rust file a source was read from; `file` sources don't record it since it is the same for all of them
+fn source_file<'a>(source: &'a Source, args: &'a Args) -> Option<&'a Path> {
+    match (&source.file, &args.command) {
+        (Some(file), _) => Some(file),
+        (None, Commands::File { file_path, .. }) => Some(file_path),
+        (None, _) => None,
+    }
+}
+
+fn write_frequencies(
+    out: &mut dyn Write,
+    frequencies: &[(String, usize)],
+    format: &OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif => {
+            for (word, count) in frequencies {
+                writeln!(out, "{}\t{}", count, word)?;
+            }
+            Ok(())
         }
-        byte_pos += segment.len();
-    }
-    
-    Ok(spans)
-}
-
-fn get_word_spans_with_quoted_strings(string_content: &str) -> Result<Vec<WordSpan>, Error> {
-    let mut spans = Vec::new();
-    let chars: Vec<char> = string_content.chars().collect();
-    let mut i = 0;
-    
-    while i < chars.len() {
-        let ch = chars[i];
-        
-        // Check if we're starting a quoted string
-        if ch == '"' || ch == '\'' || ch == '`' {
-            let quote_char = ch;
-            let quote_start = i;
-            i += 1; // Move past opening quote
-            
-            // Find the matching closing quote, handling escapes
-            while i < chars.len() {
-                if chars[i] == '\\' && i + 1 < chars.len() {
-                    // Skip escaped character
-                    i += 2;
-                } else if chars[i] == quote_char {
-                    // Found closing quote
-                    i += 1;
-                    break;
-                } else {
-                    i += 1;
-                }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let entries: Vec<serde_json::Value> = frequencies
+                .iter()
+                .map(|(word, count)| serde_json::json!({ "word": word, "count": count }))
+                .collect();
+            writeln!(out, "{}", serde_json::Value::Array(entries))
+        }
+    }
+}
+
+/// Text lists each span with its neighbors; JSON counts the neighbors of each distinct word
+fn write_neighbors(
+    out: &mut dyn Write,
+    neighbors: &[SpanNeighbors],
+    positions: &SpanPositions,
+    args: &Args,
+) -> io::Result<()> {
+    match args.format {
+        OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif => {
+            let quoted = |word: &Option<String>| {
+                word.as_ref()
+                    .map_or("-".to_string(), |word| format!("\"{}\"", word))
+            };
+            for neighbor in neighbors {
+                let (start, end) = printed_offsets(&neighbor.span, positions, args);
+                writeln!(
+                    out,
+                    "\"{}\" | {}-{} | before {} | after {}",
+                    neighbor.span.word,
+                    start,
+                    end,
+                    quoted(&neighbor.before),
+                    quoted(&neighbor.after)
+                )?;
             }
-            
-            // Create a span for the entire quoted string (including quotes)
-            let byte_start: usize = chars[..quote_start].iter().map(|c| c.len_utf8()).sum();
-            let byte_end: usize = chars[..i].iter().map(|c| c.len_utf8()).sum();
-            let quoted_text: String = chars[quote_start..i].iter().collect();
-            
-            spans.push(WordSpan {
-                word: quoted_text,
-                start: byte_start,
-                end: byte_end,
-            });
-        } else if ch.is_whitespace() {
-            // Skip whitespace
-            i += 1;
-        } else {
-            // Handle unquoted text - find the end of this token
-            let token_start = i;
-            
-            while i < chars.len() {
-                let current = chars[i];
-                if current.is_whitespace() || current == '"' || current == '\'' || current == '`' {
-                    break;
+            Ok(())
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let mut words = serde_json::Map::new();
+            for neighbor in neighbors {
+                let entry = words
+                    .entry(neighbor.span.word.clone())
+                    .or_insert_with(|| serde_json::json!({ "word": neighbor.span.word, "count": 0, "before": {}, "after": {} }));
+                entry["count"] = serde_json::json!(entry["count"].as_u64().unwrap_or(0) + 1);
+                for (side, word) in [("before", &neighbor.before), ("after", &neighbor.after)] {
+                    if let Some(word) = word {
+                        let count = entry[side][word].as_u64().unwrap_or(0);
+                        entry[side][word] = serde_json::json!(count + 1);
+                    }
                 }
-                i += 1;
             }
-            
-            // Process this unquoted segment using word boundaries
-            let byte_start: usize = chars[..token_start].iter().map(|c| c.len_utf8()).sum();
-            let segment: String = chars[token_start..i].iter().collect();
-            
-            // Apply word boundary splitting to unquoted segments
-            let mut segment_byte_pos = byte_start;
-            for word_segment in segment.split_word_bounds() {
-                if !word_segment.chars().all(|c| c.is_whitespace()) {
-                    spans.push(WordSpan {
-                        word: word_segment.to_string(),
-                        start: segment_byte_pos,
-                        end: segment_byte_pos + word_segment.len(),
-                    });
+            writeln!(
+                out,
+                "{}",
+                serde_json::Value::Array(words.into_values().collect())
+            )
+        }
+    }
+}
+
+fn write_summary(
+    out: &mut dyn Write,
+    summary: &SpanSummary,
+    format: &OutputFormat,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif => {
+            writeln!(out, "{:<14}{}", "spans", summary.spans)?;
+            writeln!(out, "{:<14}{}", "unique words", summary.unique_words)?;
+            writeln!(out, "{:<14}{}", "covered chars", summary.covered_chars)
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => writeln!(
+            out,
+            "{}",
+            serde_json::json!({
+                "spans": summary.spans,
+                "unique_words": summary.unique_words,
+                "covered_chars": summary.covered_chars,
+            })
+        ),
+    }
+}
+
+fn write_clusters(
+    out: &mut dyn Write,
+    clusters: &[(String, Vec<IndexedSpan>)],
+    positions: &SpanPositions,
+    args: &Args,
+) -> Result<(), Error> {
+    match args.format {
+        OutputFormat::Text | OutputFormat::Dot | OutputFormat::Sarif => {
+            for (key, members) in clusters {
+                writeln!(out, "{} ({})", key, members.len()).map_err(output_error(args))?;
+                for (index, span) in members {
+                    writeln!(out, "  {}", format_span_text(*index, span, positions, args))
+                        .map_err(output_error(args))?;
                 }
-                segment_byte_pos += word_segment.len();
             }
         }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            let clusters: Vec<serde_json::Value> = clusters
+                .iter()
+                .map(|(key, members)| serde_json::json!({
+                    "key": key,
+                    "spans": members.iter().map(|(index, span)| span_json(*index, span, positions, args)).collect::<Vec<_>>(),
+                }))
+                .collect();
+            writeln!(out, "{}", serde_json::Value::Array(clusters)).map_err(output_error(args))?;
+        }
     }
-    
-    Ok(spans)
+
+    Ok(())
 }
 
+/// Lookups behind the optional position and kind fields of a source's spans
+struct SpanPositions<'a> {
+    content: LineIndex<'a>,
+    /// Set for `file --absolute-offsets`: the file offset of each byte offset of the content
+    file_offsets: Option<&'a [usize]>,
+    /// Set for `--offset-unit` other than bytes: each byte offset of the content counted in that unit
+    units: Option<Vec<usize>>,
+    /// Set for `--source-positions` when the content came from a Rust string literal
+    source: Option<SourcePositions>,
+    /// Set for `--show-kind`: the literal's kind label, or `plain` for content that isn't a literal
+    kind: Option<String>,
+    /// Byte range of the literal in its file, for structured output of the `file` subcommand
+    file_bytes: Option<Range<usize>>,
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl<'a> SpanPositions<'a> {
+    fn new(source: &'a Source, args: &Args) -> Self {
+        SpanPositions {
+            content: LineIndex::new(&source.content),
+            file_offsets: source.file_offsets.as_deref(),
+            units: (args.offset_unit != OffsetUnit::Bytes && source.file_offsets.is_none())
+                .then(|| unit_offsets(&source.content, args.offset_unit)),
+            source: source
+                .origin
+                .as_ref()
+                .filter(|_| args.source_positions)
+                .map(StringLiteralInfo::source_positions),
+            kind: args.show_kind.then(|| {
+                source
+                    .origin
+                    .as_ref()
+                    .map_or("plain".to_string(), StringLiteralInfo::kind_label)
+            }),
+            file_bytes: source.file_bytes.clone(),
+        }
+    }
+}
 
-    #[test]
-    fn test_basic_word_splitting() {
-        let content = "hello world";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 }
-        ]);
+fn format_span_text(
+    index: usize,
+    span: &WordSpan,
+    positions: &SpanPositions,
+    args: &Args,
+) -> String {
+    let mut text = String::new();
+    if args.numbered {
+        text.push_str(&format!("{}\t", index + index_base(args)));
+    }
+    let (start, end) = printed_offsets(span, positions, args);
+    text.push_str(&format!("\"{}\" | {}-{}", span.word, start, end));
+    if args.with_position {
+        let (line, column) = positions.content.position(span.start);
+        text.push_str(&format!(" | {}:{}", line, column));
     }
+    if let Some(source) = &positions.source {
+        let ((start_line, start_column), (end_line, end_column)) =
+            (source.position(span.start), source.position(span.end));
+        text.push_str(&format!(
+            " | {}:{}-{}:{}",
+            start_line, start_column, end_line, end_column
+        ));
+    }
+    if let Some(kind) = &positions.kind {
+        text.push_str(&format!(" | kind={}", kind));
+    }
+    if args.detect_language {
+        text.push_str(&format!(
+            " | {}",
+            detect_language(&span.word, args.min_detect_len).unwrap_or("-")
+        ));
+    }
+    text
+}
 
-    #[test]
-    fn test_escaped_quotes() {
-        let content = "foo bar";  // This simulates the parsed content of "foo \"bar"
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "foo".to_string(), start: 0, end: 3 },
-            WordSpan { word: "bar".to_string(), start: 4, end: 7 }
-        ]);
+fn span_json(
+    index: usize,
+    span: &WordSpan,
+    positions: &SpanPositions,
+    args: &Args,
+) -> serde_json::Value {
+    let (start, end) = printed_offsets(span, positions, args);
+    let mut value = serde_json::json!({
+        "word": span.word,
+        "start": start,
+        "end": end,
+    });
+    if args.numbered {
+        value["index"] = serde_json::json!(index + index_base(args));
+    }
+    if args.with_position {
+        let (line, column) = positions.content.position(span.start);
+        value["line"] = serde_json::json!(line);
+        value["column"] = serde_json::json!(column);
+    }
+    if let Some(source) = &positions.source {
+        let ((start_line, start_column), (end_line, end_column)) =
+            (source.position(span.start), source.position(span.end));
+        value["source"] = serde_json::json!({
+            "start_line": start_line,
+            "start_column": start_column,
+            "end_line": end_line,
+            "end_column": end_column,
+        });
     }
+    if let Some(kind) = &positions.kind {
+        value["kind"] = serde_json::json!(kind);
+    }
+    if let Some(bytes) = &positions.file_bytes {
+        value["literal_file_byte_start"] = serde_json::json!(bytes.start);
+        value["literal_file_byte_end"] = serde_json::json!(bytes.end);
+    }
+    if args.detect_language {
+        value["lang"] = serde_json::json!(detect_language(&span.word, args.min_detect_len));
+    }
+    value
+}
 
-    #[test]
-    fn test_single_word() {
-        let content = "hello";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 }
-        ]);
+/// A span's start and end as reported, counted in `--offset-unit`s: 0-based and half-open, with the end
+/// naming the last unit for `--end-inclusive`, and shifted up by 1 for `--one-based`; `--one-based-offsets`
+/// is both. With `file --absolute-offsets` they count from the start of the file
+fn printed_offsets(span: &WordSpan, positions: &SpanPositions, args: &Args) -> (usize, usize) {
+    printed_range(span.start, span.end, positions, args)
+}
+
+/// [`printed_offsets`] for a byte range of the content that isn't a span, such as a densest window
+fn printed_range(
+    start: usize,
+    end: usize,
+    positions: &SpanPositions,
+    args: &Args,
+) -> (usize, usize) {
+    let style = OffsetStyle {
+        one_based: args.one_based || args.one_based_offsets,
+        end_inclusive: args.end_inclusive || args.one_based_offsets,
+    };
+    style.report(
+        start,
+        end,
+        positions.file_offsets.or(positions.units.as_deref()),
+    )
+}
+
+fn index_base(args: &Args) -> usize {
+    if args.one_based_index {
+        1
+    } else {
+        0
     }
+}
 
-    #[test]
-    fn test_empty_string() {
-        let content = "";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![]);
+fn parse_percentile(value: &str) -> Result<f64, String> {
+    let percent: f64 = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    if (0.0..=100.0).contains(&percent) {
+        Ok(percent)
+    } else {
+        Err(format!("{} is not between 0 and 100", percent))
     }
+}
 
-    #[test]
-    fn test_multiple_spaces() {
-        let content = "hello    world";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 9, end: 14 }
-        ]);
+fn parse_quote_pair(value: &str) -> Result<(char, char), String> {
+    match value.chars().collect::<Vec<_>>()[..] {
+        [open, ':', close] => Ok((open, close)),
+        _ => Err(format!(
+            "`{}` is not an OPEN:CLOSE pair of single characters",
+            value
+        )),
     }
+}
 
-    #[test]
-    fn test_leading_trailing_spaces() {
-        let content = "  hello world  ";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 2, end: 7 },
-            WordSpan { word: "world".to_string(), start: 8, end: 13 }
-        ]);
+fn handle_file_command(
+    file_path: &Path,
+    line_number: usize,
+    column: Option<usize>,
+    options: ParseOptions,
+) -> Result<StringLiteralInfo, Error> {
+    // Read and parse the file
+    let file = parse_rust_source(file_path, options)?;
+
+    // Find string literals on the target line and return the content
+    find_literal_on_line(&file, line_number, column)
+}
+
+/// With `--doc-lines`, narrow a doc comment block to the comment on `line_number`; other literals are kept whole
+fn select_doc_line(
+    literal: StringLiteralInfo,
+    line_number: usize,
+    doc_lines: bool,
+) -> StringLiteralInfo {
+    match literal.doc_comment_line(line_number) {
+        Some(line) if doc_lines => line,
+        _ => literal,
     }
+}
 
-    #[test]
-    fn test_string_extraction_from_line() {
-        let code = r#"
-        fn main() {
-            let s = "hello world";
+fn handle_file_near_column_command(
+    file_path: &Path,
+    line_number: usize,
+    column: usize,
+    options: ParseOptions,
+) -> Result<StringLiteralInfo, Error> {
+    find_literal_near_column(&parse_rust_source(file_path, options)?, line_number, column)
+}
+
+fn handle_file_index_command(
+    file_path: &Path,
+    line_number: usize,
+    index: usize,
+    options: ParseOptions,
+) -> Result<StringLiteralInfo, Error> {
+    find_literal_by_index(&parse_rust_source(file_path, options)?, line_number, index)
+}
+
+fn handle_file_name_command(
+    file_path: &Path,
+    name: &str,
+    line_number: Option<usize>,
+    options: ParseOptions,
+) -> Result<StringLiteralInfo, Error> {
+    find_literal_by_name(&parse_rust_source(file_path, options)?, name, line_number)
+}
+
+fn handle_file_in_fn_command(
+    file_path: &Path,
+    line_number: usize,
+    column: Option<usize>,
+    fn_name: &str,
+    options: ParseOptions,
+) -> Result<StringLiteralInfo, Error> {
+    find_literal_on_line_in_fn(
+        &parse_rust_source(file_path, options)?,
+        line_number,
+        column,
+        fn_name,
+    )
+}
+
+fn handle_file_in_fn_all_command(
+    file_path: &Path,
+    fn_name: &str,
+    options: ParseOptions,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    find_string_literals_in_fn(&parse_rust_source(file_path, options)?, fn_name)
+}
+
+/// Look up the literal on `line_number` in each file. A file that can't be read or has no single
+/// literal there is reported as a warning and skipped; the run only fails if every file failed.
+fn handle_files_command(
+    file_paths: &[PathBuf],
+    line_number: usize,
+    options: ParseOptions,
+) -> Result<Vec<Source>, Error> {
+    let mut sources = Vec::new();
+    let mut first_error = None;
+
+    for file_path in file_paths {
+        match handle_file_command(file_path, line_number, None, options) {
+            Ok(literal) => sources.push(
+                Source::in_file(file_path.clone(), None, literal.value.clone())
+                    .with_origin(literal),
+            ),
+            Err(err) => {
+                eprintln!("warning: {}", describe_failure(file_path, &err));
+                first_error.get_or_insert(err);
+            }
         }
-        "#;
-        
-        let file = syn::parse_file(code).unwrap();
-        let result = find_strings_on_line(&file, 3);
-        
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "hello world");
     }
 
-    #[test]
-    fn test_no_string_on_line() {
-        let code = r#"
-        fn main() {
-            let x = 42;
+    match first_error {
+        Some(err) if sources.is_empty() => Err(err),
+        _ => Ok(sources),
+    }
+}
+
+/// Every string literal in every `.rs` file under `dir`, headed by file and line. Files are visited
+/// in sorted order; directories named in `exclude_dirs` are skipped, as are hidden entries unless
+/// `include_hidden` is set. Files are parsed in parallel, and those that can't be read or parsed are
+/// returned as failures, in walk order, instead of stopping the walk. Warnings from parsing are held
+/// back until every file is parsed and then printed in walk order too.
+fn handle_scan_command(
+    dir: &Path,
+    include_hidden: bool,
+    exclude_dirs: &[String],
+    options: ParseOptions,
+) -> (Vec<Source>, Vec<(PathBuf, Error)>) {
+    let mut rust_files = Vec::new();
+    let mut failures = Vec::new();
+    collect_rust_files(
+        dir,
+        include_hidden,
+        exclude_dirs,
+        &mut rust_files,
+        &mut failures,
+    );
+
+    let parsed: Vec<_> = rust_files
+        .into_par_iter()
+        .map(|file_path| {
+            let mut warnings = Vec::new();
+            let result = scan_file_sources(&file_path, options, &mut warnings);
+            (file_path, result, warnings)
+        })
+        .collect();
+    let mut sources = Vec::new();
+    for (file_path, result, warnings) in parsed {
+        print_warnings(&warnings);
+        match result {
+            Ok(file_sources) => sources.extend(file_sources),
+            Err(err) => failures.push((file_path, err)),
         }
-        "#;
-        
-        let file = syn::parse_file(code).unwrap();
-        let result = find_strings_on_line(&file, 3);
-        
-        assert!(matches!(result, Err(Error::NoStringFound)));
     }
 
-    #[test]
-    fn test_multiple_strings_error() {
-        let code = r#"
-        fn main() {
-            let s = "hello"; let t = "world";
+    (sources, failures)
+}
+
+/// Every literal of one scanned file, each headed by the file and its starting line. Warnings are
+/// added to `warnings` rather than printed, since files are scanned in parallel.
+fn scan_file_sources(
+    file_path: &Path,
+    options: ParseOptions,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Source>, Error> {
+    let file = parse_rust_source_with_warnings(file_path, options, warnings)?;
+    Ok(find_string_literals(&file)?
+        .into_iter()
+        .map(|literal| {
+            Source::in_file(
+                file_path.to_path_buf(),
+                Some(literal.start_line),
+                literal.value.clone(),
+            )
+            .with_origin(literal)
+        })
+        .collect())
+}
+
+fn collect_rust_files(
+    dir: &Path,
+    include_hidden: bool,
+    exclude_dirs: &[String],
+    rust_files: &mut Vec<PathBuf>,
+    failures: &mut Vec<(PathBuf, Error)>,
+) {
+    let entries =
+        match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()) {
+            Ok(entries) => entries,
+            Err(err) => {
+                failures.push((dir.to_path_buf(), path_error(dir)(err)));
+                return;
+            }
+        };
+
+    let mut paths: Vec<(PathBuf, Option<fs::FileType>)> = entries
+        .into_iter()
+        .map(|entry| (entry.path(), entry.file_type().ok()))
+        .collect();
+    paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (path, file_type) in paths {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if !include_hidden && name.starts_with('.') {
+            continue;
+        }
+
+        // The entry's own type doesn't follow symlinks, so a linked directory is never entered and a
+        // link cycle can't recurse forever; linked files are still scanned
+        if file_type.is_some_and(|file_type| file_type.is_dir()) {
+            if !exclude_dirs.iter().any(|excluded| *excluded == name) {
+                collect_rust_files(&path, include_hidden, exclude_dirs, rust_files, failures);
+            }
+        } else if path.extension().is_some_and(|extension| extension == "rs") && !path.is_dir() {
+            rust_files.push(path);
         }
-        "#;
-        
-        let file = syn::parse_file(code).unwrap();
-        let result = find_strings_on_line(&file, 3);
-        
-        assert!(matches!(result, Err(Error::MultipleStringsFound)));
     }
+}
 
-    #[test]
-    fn test_raw_string() {
-        let code = r#"
-        fn main() {
-            let s = r"hello world";
+fn handle_file_all_command(
+    file_path: &Path,
+    options: ParseOptions,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    find_string_literals(&parse_rust_source(file_path, options)?)
+}
+
+fn handle_file_line_all_command(
+    file_path: &Path,
+    line_number: usize,
+    options: ParseOptions,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    find_strings_on_line_all(&parse_rust_source(file_path, options)?, line_number)
+}
+
+fn handle_file_range_command(
+    file_path: &Path,
+    start_line: usize,
+    end_line: usize,
+    options: ParseOptions,
+) -> Result<Vec<StringLiteralInfo>, Error> {
+    find_strings_in_range(
+        &parse_rust_source(file_path, options)?,
+        start_line,
+        end_line,
+    )
+}
+
+fn handle_file_lines_command(
+    file_path: &Path,
+    lines_path: &Path,
+    options: ParseOptions,
+) -> Result<Vec<(usize, StringLiteralInfo)>, Error> {
+    let file = parse_rust_source(file_path, options)?;
+    let line_list = fs::read_to_string(lines_path).map_err(path_error(lines_path))?;
+    let line_numbers = parse_line_numbers(&line_list)?;
+
+    find_literals_on_lines(&file, &line_numbers)
+}
+
+/// Parse one line number per line, ignoring blank lines
+fn parse_line_numbers(line_list: &str) -> Result<Vec<usize>, Error> {
+    line_list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse()
+                .map_err(|_| Error::InvalidLineNumber(line.to_string()))
+        })
+        .collect()
+}
+
+fn handle_string_command(content: Option<&str>, lossy: bool) -> Result<String, Error> {
+    let input = match content {
+        Some("--") => {
+            // Read from stdin
+            read_input(Path::new("-"), lossy)?
+        }
+        Some(content) => content.to_string(),
+        None => {
+            // No content provided, read from stdin
+            read_input(Path::new("-"), lossy)?
         }
-        "#;
-        
-        let file = syn::parse_file(code).unwrap();
-        let result = find_strings_on_line(&file, 3);
-        
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "hello world");
+    };
+
+    Ok(input)
+}
+
+/// The whole file, or just `line` of it; a file-relative line keeps the whole file as content so
+/// offsets, positions, and annotations refer to the file
+fn handle_text_command(
+    file_path: &Path,
+    line: Option<usize>,
+    relative: bool,
+    lossy: bool,
+) -> Result<Source, Error> {
+    let content = read_input(file_path, lossy)?;
+    let Some(line) = line else {
+        return Ok(Source::new(content));
+    };
+    let index = LineIndex::new(&content);
+    let range = index.line_range(line).ok_or_else(|| {
+        Error::InvalidLineNumber(format!(
+            "{} (the input has {} lines)",
+            line,
+            index.line_count()
+        ))
+    })?;
+    if relative {
+        return Ok(Source::new(content[range].to_string()));
     }
+    Ok(Source::new(content).within(range))
+}
 
-    #[test]
-    fn test_string_with_escapes() {
-        let code = r#"let s = "foo \"bar\" baz";"#;
-        let file = syn::parse_str::<syn::Stmt>(code).unwrap();
-        
-        let mut visitor = StringVisitor::new(1);
-        visitor.visit_stmt(&file);
-        
-        assert_eq!(visitor.found_strings.len(), 1);
-        assert_eq!(visitor.found_strings[0], "foo \"bar\" baz");
+fn handle_json_command(file_path: &Path, json_pointer: &str, lossy: bool) -> Result<String, Error> {
+    let content = read_input(file_path, lossy)?;
+    find_json_string_in_source(&content, json_pointer)
+}
+
+/// The code block or inline code on `line_number`: the whole file as content with spans limited to the
+/// code, like `text --line`, or just the code with `relative`
+fn handle_markdown_command(
+    file_path: &Path,
+    line_number: usize,
+    relative: bool,
+    lossy: bool,
+) -> Result<Source, Error> {
+    let content = read_input(file_path, lossy)?;
+    let code = find_markdown_code(&content, line_number)?;
+    if relative {
+        return Ok(Source::new(content[code.content].to_string()));
     }
+    Ok(Source::new(content).within(code.content))
+}
 
-    #[test]
-    fn test_complete_workflow() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("simple.rs");
-        
-        let content = handle_file_command(&test_file_path, 2).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "test".to_string(), start: 12, end: 16 }
-        ]);
+/// The TOML string value at `key`, or else the single one on `line`
+fn handle_toml_command(
+    file_path: &Path,
+    key: Option<&str>,
+    line: Option<usize>,
+    lossy: bool,
+) -> Result<TomlString, Error> {
+    let content = read_input(file_path, lossy)?;
+    match (key, line) {
+        (Some(key), _) => find_toml_string(&content, key),
+        (None, Some(line)) => find_toml_string_on_line(&content, line),
+        (None, None) => unreachable!("listing TOML strings is handled by run_toml_list"),
     }
+}
 
-    #[test]
-    fn test_complete_workflow_with_raw_string() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("raw_string.rs");
-        
-        let content = handle_file_command(&test_file_path, 2).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "raw".to_string(), start: 0, end: 3 },
-            WordSpan { word: "string".to_string(), start: 4, end: 10 },
-            WordSpan { word: "content".to_string(), start: 11, end: 18 }
-        ]);
+/// Read a file verbatim, or stdin when the path is `-`
+fn read_input(file_path: &Path, lossy: bool) -> Result<String, Error> {
+    let mut warnings = Vec::new();
+    let content = read_input_with_warnings(file_path, lossy, &mut warnings);
+    print_warnings(&warnings);
+    content
+}
+
+/// [`read_input`], adding a decoding warning to `warnings` instead of printing it
+fn read_input_with_warnings(
+    file_path: &Path,
+    lossy: bool,
+    warnings: &mut Vec<String>,
+) -> Result<String, Error> {
+    if file_path == Path::new("-") {
+        read_from_stdin(lossy, warnings)
+    } else {
+        decode_input(
+            file_path,
+            fs::read(file_path).map_err(path_error(file_path))?,
+            lossy,
+            warnings,
+        )
     }
+}
 
-    #[test]
-    fn test_complete_workflow_with_escaped_quotes() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("escaped.rs");
-        
-        let content = handle_file_command(&test_file_path, 2).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "foo".to_string(), start: 0, end: 3 },
-            WordSpan { word: "\"".to_string(), start: 4, end: 5 },
-            WordSpan { word: "bar".to_string(), start: 5, end: 8 },
-            WordSpan { word: "\"".to_string(), start: 8, end: 9 },
-            WordSpan { word: "baz".to_string(), start: 10, end: 13 }
-        ]);
-    }
-
-    #[test] 
-    fn test_complete_workflow_line_3() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("simple.rs");
-        
-        let content = handle_file_command(&test_file_path, 3).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "foo".to_string(), start: 0, end: 3 },
-            WordSpan { word: "bar".to_string(), start: 4, end: 7 },
-            WordSpan { word: "baz".to_string(), start: 8, end: 11 }
-        ]);
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
     }
+}
 
-    #[test]
-    fn test_multiline_string_multiple_lines() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("multiline.rs");
-        
-        let expected_spans = vec![
-            WordSpan { word: "this".to_string(), start: 0, end: 4 },
-            WordSpan { word: "is".to_string(), start: 5, end: 7 },
-            WordSpan { word: "a".to_string(), start: 8, end: 9 },
-            WordSpan { word: "multiline".to_string(), start: 23, end: 32 },
-            WordSpan { word: "string".to_string(), start: 33, end: 39 },
-            WordSpan { word: "with".to_string(), start: 40, end: 44 },
-            WordSpan { word: "multiple".to_string(), start: 58, end: 66 },
-            WordSpan { word: "words".to_string(), start: 67, end: 72 },
-            WordSpan { word: "per".to_string(), start: 73, end: 76 },
-            WordSpan { word: "line".to_string(), start: 77, end: 81 }
-        ];
+/// How Rust source is read into a syntax tree before literals are looked up
+#[derive(Clone, Copy)]
+struct ParseOptions {
+    /// Lex source that doesn't parse instead of failing (`file --lenient`)
+    lenient: bool,
+    /// Replace invalid UTF-8 in the file instead of failing (`--lossy`)
+    lossy: bool,
+    /// Test code to drop or keep (`--skip-tests`, `--only-tests`)
+    tests: TestCode,
+}
 
-        // Test that all lines covered by the multiline string return the same result
-        for line_number in [2, 3, 4] {
-            let content = handle_file_command(&test_file_path, line_number).unwrap();
-            let spans = get_word_spans(&content, false).unwrap();
-            assert_eq!(spans, expected_spans, "Failed for line {}", line_number);
+impl ParseOptions {
+    fn new(args: &Args, lenient: bool) -> Self {
+        let tests = if args.skip_tests {
+            TestCode::Skip
+        } else if args.only_tests {
+            TestCode::Only
+        } else {
+            TestCode::Include
+        };
+        ParseOptions {
+            lenient,
+            lossy: args.lossy,
+            tests,
         }
     }
+}
 
-    #[test]
-    fn test_multiline_raw_string_multiple_lines() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("multiline_raw.rs");
-        
-        let expected_spans = vec![
-            WordSpan { word: "this".to_string(), start: 0, end: 4 },
-            WordSpan { word: "is".to_string(), start: 5, end: 7 },
-            WordSpan { word: "a".to_string(), start: 8, end: 9 },
-            WordSpan { word: "raw".to_string(), start: 10, end: 13 },
-            WordSpan { word: "multiline".to_string(), start: 29, end: 38 },
-            WordSpan { word: "string".to_string(), start: 39, end: 45 },
-            WordSpan { word: "with".to_string(), start: 46, end: 50 },
-            WordSpan { word: "special".to_string(), start: 66, end: 73 },
-            WordSpan { word: "\"".to_string(), start: 74, end: 75 },
-            WordSpan { word: "quotes".to_string(), start: 75, end: 81 },
-            WordSpan { word: "\"".to_string(), start: 81, end: 82 },
-            WordSpan { word: "and".to_string(), start: 83, end: 86 },
-            WordSpan { word: "symbols".to_string(), start: 87, end: 94 }
-        ];
+/// Read and parse Rust source from a file or stdin. With `lenient`, source that syn rejects is lexed
+/// instead, with a warning, so literals are still found in files with syntax errors. Test code is
+/// then dropped or kept alone as requested.
+fn parse_rust_source(file_path: &Path, options: ParseOptions) -> Result<syn::File, Error> {
+    let mut warnings = Vec::new();
+    let file = parse_rust_source_with_warnings(file_path, options, &mut warnings);
+    print_warnings(&warnings);
+    file
+}
 
-        // Test that all lines covered by the multiline raw string return the same result
-        for line_number in [2, 3, 4] {
-            let content = handle_file_command(&test_file_path, line_number).unwrap();
-            let spans = get_word_spans(&content, false).unwrap();
-            assert_eq!(spans, expected_spans, "Failed for raw string line {}", line_number);
+/// [`parse_rust_source`], adding warnings to `warnings` instead of printing them
+fn parse_rust_source_with_warnings(
+    file_path: &Path,
+    options: ParseOptions,
+    warnings: &mut Vec<String>,
+) -> Result<syn::File, Error> {
+    let content = read_input_with_warnings(file_path, options.lossy, warnings)?;
+    let mut file = if options.lenient {
+        let (file, lexed) = name_parse_error(file_path, parse_source_lenient(&content))?;
+        if lexed {
+            let name = if file_path == Path::new("-") {
+                "<stdin>".to_string()
+            } else {
+                file_path.display().to_string()
+            };
+            warnings.push(format!(
+                "{} does not parse as Rust; literals were found by lexing it (--lenient)",
+                name
+            ));
         }
-    }
+        file
+    } else {
+        name_parse_error(
+            file_path,
+            syn::parse_file(&content).map_err(Error::ParseError),
+        )?
+    };
+    retain_test_code(&mut file, options.tests);
+    Ok(file)
+}
 
-    #[test]
-    fn test_single_line_on_multiline_file() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("multiline.rs");
-        
-        let content = handle_file_command(&test_file_path, 5).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        // Should find the single line string on line 5
-        assert_eq!(spans, vec![
-            WordSpan { word: "single".to_string(), start: 0, end: 6 },
-            WordSpan { word: "line".to_string(), start: 7, end: 11 },
-            WordSpan { word: "string".to_string(), start: 12, end: 18 }
-        ]);
+/// Prefix parse errors with `FILE:LINE:COLUMN` (`<stdin>` for piped source), since syn's messages
+/// don't say where the bad source is
+fn name_parse_error<T>(file_path: &Path, result: Result<T, Error>) -> Result<T, Error> {
+    match result {
+        Err(Error::ParseError(err)) => {
+            let start = err.span().start();
+            let name = if file_path == Path::new("-") {
+                "<stdin>".to_string()
+            } else {
+                file_path.display().to_string()
+            };
+            let message = format!("{}:{}:{}: {}", name, start.line, start.column + 1, err);
+            Err(Error::ParseError(syn::Error::new(err.span(), message)))
+        }
+        result => result,
     }
+}
 
-    #[test]
-    fn test_no_string_on_multiline_boundary() {
-        let test_file_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("test-files")
-            .join("multiline.rs");
-        
-        let result = handle_file_command(&test_file_path, 1);
-        
-        // Should return NoStringFound error for line 1 (fn main() line)
-        assert!(matches!(result, Err(Error::NoStringFound)));
+/// An error from processing one of several files, naming the file unless the error already does
+fn describe_failure(file_path: &Path, err: &Error) -> String {
+    match err {
+        Error::IoError { .. } | Error::ParseError(_) | Error::InvalidEncoding { .. } => {
+            err.to_string()
+        }
+        _ => format!("{}: {}", file_path.display(), err),
     }
+}
 
-    #[test]
-    fn test_punctuation_tokenization() {
-        let content = "default(nextval(user_id_seq)),";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "default".to_string(), start: 0, end: 7 },
-            WordSpan { word: "(".to_string(), start: 7, end: 8 },
-            WordSpan { word: "nextval".to_string(), start: 8, end: 15 },
-            WordSpan { word: "(".to_string(), start: 15, end: 16 },
-            WordSpan { word: "user_id_seq".to_string(), start: 16, end: 27 },
-            WordSpan { word: ")".to_string(), start: 27, end: 28 },
-            WordSpan { word: ")".to_string(), start: 28, end: 29 },
-            WordSpan { word: ",".to_string(), start: 29, end: 30 },
-        ]);
+fn read_from_stdin(lossy: bool, warnings: &mut Vec<String>) -> Result<String, Error> {
+    // Kept for a second look at the same input, once the first read has drained stdin
+    static STDIN: OnceLock<String> = OnceLock::new();
+    if let Some(content) = STDIN.get() {
+        return Ok(content.clone());
     }
+    let mut buffer = Vec::new();
+    io::stdin()
+        .read_to_end(&mut buffer)
+        .map_err(path_error(Path::new("<stdin>")))?;
+    let content = decode_input(Path::new("<stdin>"), buffer, lossy, warnings)?;
+    Ok(STDIN.get_or_init(|| content).clone())
+}
 
-    #[test]
-    fn test_mixed_punctuation_and_whitespace() {
-        let content = "hello, world! how are you?";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: ",".to_string(), start: 5, end: 6 },
-            WordSpan { word: "world".to_string(), start: 7, end: 12 },
-            WordSpan { word: "!".to_string(), start: 12, end: 13 },
-            WordSpan { word: "how".to_string(), start: 14, end: 17 },
-            WordSpan { word: "are".to_string(), start: 18, end: 21 },
-            WordSpan { word: "you".to_string(), start: 22, end: 25 },
-            WordSpan { word: "?".to_string(), start: 25, end: 26 },
-        ]);
+/// A Rust input read again to locate its literals in bytes. Decoding already failed or warned when it
+/// was parsed, so invalid UTF-8 is replaced quietly here.
+fn reread_input(file_path: &Path) -> Result<String, Error> {
+    if file_path == Path::new("-") {
+        return read_from_stdin(true, &mut Vec::new());
     }
+    let bytes = fs::read(file_path).map_err(path_error(file_path))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
 
-    #[test]
-    fn test_sql_like_expression() {
-        let content = "SELECT * FROM table WHERE id=42;";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "SELECT".to_string(), start: 0, end: 6 },
-            WordSpan { word: "*".to_string(), start: 7, end: 8 },
-            WordSpan { word: "FROM".to_string(), start: 9, end: 13 },
-            WordSpan { word: "table".to_string(), start: 14, end: 19 },
-            WordSpan { word: "WHERE".to_string(), start: 20, end: 25 },
-            WordSpan { word: "id".to_string(), start: 26, end: 28 },
-            WordSpan { word: "=".to_string(), start: 28, end: 29 },
-            WordSpan { word: "42".to_string(), start: 29, end: 31 },
-            WordSpan { word: ";".to_string(), start: 31, end: 32 },
-        ]);
+/// Decode input as UTF-8, failing at the first invalid byte unless `lossy` replaces invalid sequences,
+/// with a warning added to `warnings`
+fn decode_input(
+    path: &Path,
+    bytes: Vec<u8>,
+    lossy: bool,
+    warnings: &mut Vec<String>,
+) -> Result<String, Error> {
+    let err = match String::from_utf8(bytes) {
+        Ok(content) => return Ok(content),
+        Err(err) => err,
+    };
+    let byte_offset = err.utf8_error().valid_up_to();
+    if !lossy {
+        return Err(Error::InvalidEncoding {
+            path: path.to_path_buf(),
+            byte_offset,
+        });
     }
+    warnings.push(format!(
+        "{} is not valid UTF-8 from byte {}; invalid sequences were replaced, so later offsets may not match the bytes on disk",
+        path.display(),
+        byte_offset
+    ));
+    Ok(String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
 
-    #[test]
-    fn test_brackets_and_operators() {
-        let content = "array[index]+value*2";
-        let spans = get_word_spans(content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "array".to_string(), start: 0, end: 5 },
-            WordSpan { word: "[".to_string(), start: 5, end: 6 },
-            WordSpan { word: "index".to_string(), start: 6, end: 11 },
-            WordSpan { word: "]".to_string(), start: 11, end: 12 },
-            WordSpan { word: "+".to_string(), start: 12, end: 13 },
-            WordSpan { word: "value".to_string(), start: 13, end: 18 },
-            WordSpan { word: "*".to_string(), start: 18, end: 19 },
-            WordSpan { word: "2".to_string(), start: 19, end: 20 },
-        ]);
+const ANNOTATE_COLORS: [&str; 4] = ["\x1b[1;32m", "\x1b[1;33m", "\x1b[1;36m", "\x1b[1;35m"];
+const COLOR_RESET: &str = "\x1b[0m";
+
+/// Write the content line by line, each followed by caret markers under the spans on that line,
+/// labelled with the offsets `offsets` reports for the span
+fn write_annotated(
+    out: &mut dyn Write,
+    content: &str,
+    spans: &[WordSpan],
+    offsets: &dyn Fn(&WordSpan) -> (usize, usize),
+    color: bool,
+) -> io::Result<()> {
+    let mut line_start = 0;
+
+    for line in content.split('\n') {
+        let line_end = line_start + line.len();
+        let display_line = line.strip_suffix('\r').unwrap_or(line);
+        writeln!(out, "{}", display_line)?;
+
+        for (index, span) in spans.iter().enumerate() {
+            // Clip the span to this line so multiline tokens get markers on each line they cover
+            let start = span.start.max(line_start);
+            let end = span.end.min(line_start + display_line.len());
+            if start >= end {
+                continue;
+            }
+
+            // Spans whose clipped range splits a character can't be marked
+            let (Ok(prefix), Ok(body)) = (
+                safe_slice(display_line, 0, start - line_start),
+                safe_slice(display_line, start - line_start, end - line_start),
+            ) else {
+                continue;
+            };
+
+            // Reuse tabs from the line prefix so markers stay aligned with the rendered text
+            let padding: String = prefix
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let carets = "^".repeat(body.chars().count());
+            let (label_start, label_end) = offsets(span);
+            let label = format!("\"{}\" | {}-{}", span.word, label_start, label_end);
+
+            if color {
+                let caret_color = ANNOTATE_COLORS[index % ANNOTATE_COLORS.len()];
+                writeln!(
+                    out,
+                    "{}{}{}{} {}",
+                    padding, caret_color, carets, COLOR_RESET, label
+                )?;
+            } else {
+                writeln!(out, "{}{} {}", padding, carets, label)?;
+            }
+        }
+
+        line_start = line_end + 1;
     }
 
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_span_counter::get_word_spans;
+
     #[test]
     fn test_string_subcommand_with_content() {
-        let content = handle_string_command(Some("hello world")).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 }
-        ]);
+        let content = handle_string_command(Some("hello world"), false).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 6,
+                    end: 11
+                }
+            ]
+        );
     }
 
     #[test]
     fn test_string_subcommand_empty_string() {
-        let content = handle_string_command(Some("")).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
+        let content = handle_string_command(Some(""), false).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
         assert_eq!(spans, vec![]);
     }
 
     #[test]
     fn test_string_subcommand_punctuation() {
-        let content = handle_string_command(Some("hello, world!")).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: ",".to_string(), start: 5, end: 6 },
-            WordSpan { word: "world".to_string(), start: 7, end: 12 },
-            WordSpan { word: "!".to_string(), start: 12, end: 13 }
-        ]);
+        let content = handle_string_command(Some("hello, world!"), false).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: ",".to_string(),
+                    start: 5,
+                    end: 6
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 7,
+                    end: 12
+                },
+                WordSpan {
+                    word: "!".to_string(),
+                    start: 12,
+                    end: 13
+                }
+            ]
+        );
     }
 
     #[test]
     fn test_string_subcommand_multiline_content() {
         let input = "hello\nworld\ntest";
-        let content = handle_string_command(Some(input)).unwrap();
-        let spans = get_word_spans(&content, false).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "test".to_string(), start: 12, end: 16 }
-        ]);
+        let content = handle_string_command(Some(input), false).unwrap();
+        let spans = get_word_spans(&content, false, &Granularity::Word).unwrap();
+
+        assert_eq!(
+            spans,
+            vec![
+                WordSpan {
+                    word: "hello".to_string(),
+                    start: 0,
+                    end: 5
+                },
+                WordSpan {
+                    word: "world".to_string(),
+                    start: 6,
+                    end: 11
+                },
+                WordSpan {
+                    word: "test".to_string(),
+                    start: 12,
+                    end: 16
+                }
+            ]
+        );
     }
 
-    // Tests for the new strings-as-tokens functionality
-    #[test]
-    fn test_strings_as_tokens_double_quotes() {
-        let content = "hello \"world test\" end";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "\"world test\"".to_string(), start: 6, end: 18 },
-            WordSpan { word: "end".to_string(), start: 19, end: 22 }
-        ]);
+    // Tests for annotated output
+    fn byte_offsets(span: &WordSpan) -> (usize, usize) {
+        (span.start, span.end)
     }
 
     #[test]
-    fn test_strings_as_tokens_single_quotes() {
-        let content = "hello 'world test' end";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "'world test'".to_string(), start: 6, end: 18 },
-            WordSpan { word: "end".to_string(), start: 19, end: 22 }
-        ]);
+    fn test_annotate_single_line() {
+        let content = "hello world";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        let mut output = Vec::new();
+        write_annotated(&mut output, content, &spans, &byte_offsets, false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "hello world\n",
+                "^^^^^ \"hello\" | 0-5\n",
+                "      ^^^^^ \"world\" | 6-11\n",
+            )
+        );
     }
 
     #[test]
-    fn test_strings_as_tokens_backticks() {
-        let content = "hello `world test` end";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "`world test`".to_string(), start: 6, end: 18 },
-            WordSpan { word: "end".to_string(), start: 19, end: 22 }
-        ]);
+    fn test_annotate_multiline_content() {
+        let content = "one two\n\tthree";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
+
+        let mut output = Vec::new();
+        write_annotated(&mut output, content, &spans, &byte_offsets, false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "one two\n",
+                "^^^ \"one\" | 0-3\n",
+                "    ^^^ \"two\" | 4-7\n",
+                "\tthree\n",
+                "\t^^^^^ \"three\" | 9-14\n",
+            )
+        );
     }
 
     #[test]
-    fn test_strings_as_tokens_mixed_quotes() {
-        let content = "say \"hello\" and 'world' plus `test`";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "say".to_string(), start: 0, end: 3 },
-            WordSpan { word: "\"hello\"".to_string(), start: 4, end: 11 },
-            WordSpan { word: "and".to_string(), start: 12, end: 15 },
-            WordSpan { word: "'world'".to_string(), start: 16, end: 23 },
-            WordSpan { word: "plus".to_string(), start: 24, end: 28 },
-            WordSpan { word: "`test`".to_string(), start: 29, end: 35 }
-        ]);
+    fn test_annotate_multiline_token_marks_each_line() {
+        let content = "a \"b\nc\" d";
+        let spans = get_word_spans(content, true, &Granularity::Word).unwrap();
+
+        let mut output = Vec::new();
+        write_annotated(&mut output, content, &spans[1..2], &byte_offsets, false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "a \"b\n",
+                "  ^^ \"\"b\nc\"\" | 2-7\n",
+                "c\" d\n",
+                "^^ \"\"b\nc\"\" | 2-7\n",
+            )
+        );
     }
 
     #[test]
-    fn test_strings_as_tokens_escaped_quotes() {
-        let content = "before \"she said \\\"hello\\\" there\" after";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "before".to_string(), start: 0, end: 6 },
-            WordSpan { word: "\"she said \\\"hello\\\" there\"".to_string(), start: 7, end: 33 },
-            WordSpan { word: "after".to_string(), start: 34, end: 39 }
-        ]);
-    }
+    fn test_annotate_with_color() {
+        let content = "hi";
+        let spans = get_word_spans(content, false, &Granularity::Word).unwrap();
 
-    #[test]
-    fn test_strings_as_tokens_empty_quotes() {
-        let content = "before \"\" empty '' and `` after";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "before".to_string(), start: 0, end: 6 },
-            WordSpan { word: "\"\"".to_string(), start: 7, end: 9 },
-            WordSpan { word: "empty".to_string(), start: 10, end: 15 },
-            WordSpan { word: "''".to_string(), start: 16, end: 18 },
-            WordSpan { word: "and".to_string(), start: 19, end: 22 },
-            WordSpan { word: "``".to_string(), start: 23, end: 25 },
-            WordSpan { word: "after".to_string(), start: 26, end: 31 }
-        ]);
-    }
+        let mut output = Vec::new();
+        write_annotated(&mut output, content, &spans, &byte_offsets, true).unwrap();
 
-    #[test]
-    fn test_strings_as_tokens_unclosed_quotes() {
-        let content = "hello \"unclosed quote and more";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        // Unclosed quotes should consume the rest of the string
-        assert_eq!(spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "\"unclosed quote and more".to_string(), start: 6, end: 30 }
-        ]);
-    }
-
-    #[test]
-    fn test_strings_as_tokens_vs_default_comparison() {
-        let content = "hello 'world test' end";
-        
-        // Default behavior
-        let default_spans = get_word_spans(content, false).unwrap();
-        assert_eq!(default_spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "'".to_string(), start: 6, end: 7 },
-            WordSpan { word: "world".to_string(), start: 7, end: 12 },
-            WordSpan { word: "test".to_string(), start: 13, end: 17 },
-            WordSpan { word: "'".to_string(), start: 17, end: 18 },
-            WordSpan { word: "end".to_string(), start: 19, end: 22 }
-        ]);
-        
-        // Strings-as-tokens behavior
-        let token_spans = get_word_spans(content, true).unwrap();
-        assert_eq!(token_spans, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "'world test'".to_string(), start: 6, end: 18 },
-            WordSpan { word: "end".to_string(), start: 19, end: 22 }
-        ]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "hi\n\x1b[1;32m^^\x1b[0m \"hi\" | 0-2\n"
+        );
     }
 
     #[test]
-    fn test_strings_as_tokens_unquoted_punctuation() {
-        let content = "array[index] \"quoted text\" + value*2";
-        let spans = get_word_spans(content, true).unwrap();
-        
-        assert_eq!(spans, vec![
-            WordSpan { word: "array".to_string(), start: 0, end: 5 },
-            WordSpan { word: "[".to_string(), start: 5, end: 6 },
-            WordSpan { word: "index".to_string(), start: 6, end: 11 },
-            WordSpan { word: "]".to_string(), start: 11, end: 12 },
-            WordSpan { word: "\"quoted text\"".to_string(), start: 13, end: 26 },
-            WordSpan { word: "+".to_string(), start: 27, end: 28 },
-            WordSpan { word: "value".to_string(), start: 29, end: 34 },
-            WordSpan { word: "*".to_string(), start: 34, end: 35 },
-            WordSpan { word: "2".to_string(), start: 35, end: 36 }
-        ]);
-    }
-
-    // Tests for filtering functionality
-    #[test]
-    fn test_filter_exact_match() {
-        let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "test".to_string(), start: 12, end: 16 }
-        ];
-        
-        let filters = vec!["world".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Exact, false).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "world".to_string(), start: 6, end: 11 }
-        ]);
-    }
-    
-    #[test]
-    fn test_filter_exact_match_multiple() {
+    fn test_annotate_skips_non_boundary_span() {
+        let content = "é ok";
         let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "test".to_string(), start: 12, end: 16 }
+            WordSpan {
+                word: "é".to_string(),
+                start: 1,
+                end: 2,
+            },
+            WordSpan {
+                word: "ok".to_string(),
+                start: 3,
+                end: 5,
+            },
         ];
-        
-        let filters = vec!["hello".to_string(), "test".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Exact, false).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "test".to_string(), start: 12, end: 16 }
-        ]);
-    }
-    
-    #[test]
-    fn test_filter_exact_match_case_sensitive() {
-        let spans = vec![
-            WordSpan { word: "Hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "WORLD".to_string(), start: 6, end: 11 },
-        ];
-        
-        let filters = vec!["hello".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Exact, false).unwrap();
-        
-        assert_eq!(result, vec![]); // No matches because of case sensitivity
+
+        let mut output = Vec::new();
+        write_annotated(&mut output, content, &spans, &byte_offsets, false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "é ok\n  ^^ \"ok\" | 3-5\n"
+        );
     }
-    
-    #[test]
-    fn test_filter_exact_match_case_insensitive() {
-        let spans = vec![
-            WordSpan { word: "Hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "WORLD".to_string(), start: 6, end: 11 },
-        ];
-        
-        let filters = vec!["hello".to_string(), "world".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Exact, true).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "Hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "WORLD".to_string(), start: 6, end: 11 }
-        ]);
-    }
-    
-    #[test]
-    fn test_filter_contains_mode() {
-        let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "wonderful".to_string(), start: 12, end: 21 }
-        ];
-        
-        let filters = vec!["orl".to_string(), "nde".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Contains, false).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "wonderful".to_string(), start: 12, end: 21 }
-        ]);
-    }
-    
-    #[test]
-    fn test_filter_contains_case_insensitive() {
-        let spans = vec![
-            WordSpan { word: "Hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "WORLD".to_string(), start: 6, end: 11 },
-        ];
-        
-        let filters = vec!["ell".to_string(), "orl".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Contains, true).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "Hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "WORLD".to_string(), start: 6, end: 11 }
-        ]);
-    }
-    
-    #[test]
-    fn test_filter_regex_mode() {
-        let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "word".to_string(), start: 12, end: 16 },
-            WordSpan { word: "test123".to_string(), start: 17, end: 24 }
-        ];
-        
-        let filters = vec![r"wo.*d".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Regex, false).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "world".to_string(), start: 6, end: 11 },
-            WordSpan { word: "word".to_string(), start: 12, end: 16 }
-        ]);
-    }
-    
-    #[test]
-    fn test_filter_regex_with_numbers() {
-        let spans = vec![
-            WordSpan { word: "test123".to_string(), start: 0, end: 7 },
-            WordSpan { word: "hello".to_string(), start: 8, end: 13 },
-            WordSpan { word: "world456".to_string(), start: 14, end: 22 }
-        ];
-        
-        let filters = vec![r"\d+".to_string()]; // Match words containing digits
-        let result = filter_word_spans(spans, &filters, &FilterMode::Regex, false).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "test123".to_string(), start: 0, end: 7 },
-            WordSpan { word: "world456".to_string(), start: 14, end: 22 }
-        ]);
-    }
-    
-    #[test]
-    fn test_filter_regex_case_insensitive() {
-        let spans = vec![
-            WordSpan { word: "Hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "WORLD".to_string(), start: 6, end: 11 },
-        ];
-        
-        let filters = vec!["hello".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Regex, true).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: "Hello".to_string(), start: 0, end: 5 }
-        ]);
-    }
-    
+
     #[test]
-    fn test_filter_invalid_regex() {
-        let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 }
-        ];
-        
-        let filters = vec!["[invalid".to_string()]; // Invalid regex
-        let result = filter_word_spans(spans, &filters, &FilterMode::Regex, false);
-        
-        assert!(matches!(result, Err(Error::RegexError(_))));
+    fn test_parse_line_numbers() {
+        assert_eq!(
+            parse_line_numbers("2\n\n 3 \n10\n").unwrap(),
+            vec![2, 3, 10]
+        );
+        assert!(
+            matches!(parse_line_numbers("2\nthree\n"), Err(Error::InvalidLineNumber(line)) if line == "three")
+        );
     }
-    
+
     #[test]
-    fn test_filter_empty_filters() {
-        let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 }
-        ];
-        
-        let filters = vec![];
-        let result = filter_word_spans(spans.clone(), &filters, &FilterMode::Exact, false).unwrap();
-        
-        assert_eq!(result, spans); // Should return all spans when no filters
+    fn test_parse_percentile_bounds() {
+        assert_eq!(parse_percentile("12.5"), Ok(12.5));
+        assert_eq!(parse_percentile("100"), Ok(100.0));
+        assert!(parse_percentile("-1").is_err());
+        assert!(parse_percentile("ten").is_err());
     }
-    
+
     #[test]
-    fn test_filter_no_matches() {
-        let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: "world".to_string(), start: 6, end: 11 }
-        ];
-        
-        let filters = vec!["nonexistent".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Exact, false).unwrap();
-        
-        assert_eq!(result, vec![]); // Should return empty vec when no matches
+    fn test_parse_quote_pair() {
+        assert_eq!(parse_quote_pair("“:”"), Ok(('“', '”')));
+        assert_eq!(parse_quote_pair(":::"), Ok((':', ':')));
+        assert!(parse_quote_pair("\"").is_err());
+        assert!(parse_quote_pair("<<:>>").is_err());
     }
-    
+
     #[test]
-    fn test_filter_with_punctuation() {
-        let spans = vec![
-            WordSpan { word: "hello".to_string(), start: 0, end: 5 },
-            WordSpan { word: ",".to_string(), start: 5, end: 6 },
-            WordSpan { word: "world".to_string(), start: 7, end: 12 },
-            WordSpan { word: "!".to_string(), start: 12, end: 13 }
-        ];
-        
-        let filters = vec![",".to_string(), "!".to_string()];
-        let result = filter_word_spans(spans, &filters, &FilterMode::Exact, false).unwrap();
-        
-        assert_eq!(result, vec![
-            WordSpan { word: ",".to_string(), start: 5, end: 6 },
-            WordSpan { word: "!".to_string(), start: 12, end: 13 }
-        ]);
-    }
-}
\ No newline at end of file
+    fn test_end_inclusive_keeps_empty_span_end() {
+        let source = Source::new("ab".to_string());
+        let span = |start: usize, end: usize| WordSpan {
+            word: source.content[start..end].to_string(),
+            start,
+            end,
+        };
+        let printed = |flags: &[&str], span: &WordSpan| {
+            let args =
+                Args::try_parse_from(["rust-span-counter"].iter().chain(flags).chain(&["string"]))
+                    .unwrap();
+            printed_offsets(span, &SpanPositions::new(&source, &args), &args)
+        };
+
+        assert_eq!(printed(&["--end-inclusive"], &span(0, 1)), (0, 0));
+        assert_eq!(printed(&["--end-inclusive"], &span(1, 1)), (1, 1));
+        assert_eq!(printed(&["--end-inclusive"], &span(0, 0)), (0, 0));
+        assert_eq!(printed(&["--one-based-offsets"], &span(1, 1)), (2, 2));
+        assert_eq!(printed(&["--one-based"], &span(0, 1)), (1, 2));
+        assert_eq!(
+            printed(&["--one-based", "--end-inclusive"], &span(0, 1)),
+            (1, 1)
+        );
+        assert_eq!(
+            printed(&["--one-based", "--end-inclusive"], &span(1, 1)),
+            (2, 2)
+        );
+        assert_eq!(
+            printed(&["--numbered", "--one-based-index"], &span(0, 1)),
+            (0, 1)
+        );
+    }
+}
@@ -0,0 +1,14 @@
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("invalid value {0} for field")]
+    InvalidValue(String),
+    #[error("missing section `{section}`", section = "defaults")]
+    MissingSection,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    #[serde(rename = "userName")]
+    pub name: String,
+}
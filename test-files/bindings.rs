@@ -0,0 +1,13 @@
+const GREETING: &str = "hello from a const";
+static BANNER: &str = "static banner text";
+
+struct Config {
+    title: &'static str,
+}
+
+fn build() -> Config {
+    let label: &str = "typed let binding";
+    let message = "first message";
+    let message = "shadowed message";
+    Config { title: "field initializer" }
+}
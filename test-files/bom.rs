@@ -0,0 +1,6 @@
+﻿//! Exported with a byte order mark.
+const FIRST: &str = "first line";
+
+fn main() {
+    let later = "fourth line";
+}
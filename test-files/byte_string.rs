@@ -0,0 +1,6 @@
+fn main() {
+    let greeting = b"hello world";
+    let escaped = b"caf\xc3\xa9 \x41BC";
+    let invalid = b"bad \xff byte";
+    let text = "plain";
+}
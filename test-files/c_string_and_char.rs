@@ -0,0 +1,7 @@
+use std::ffi::CStr;
+
+fn main() {
+    let greeting: &CStr = c"hello c world";
+    let accent = 'é';
+    let space = ' ';
+}
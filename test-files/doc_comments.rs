@@ -0,0 +1,13 @@
+//! Inner crate docs
+//! on two lines
+
+/// Adds two numbers
+///   and returns the sum
+#[doc = "explicit doc text"]
+fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+/** Block docs
+    over two lines */
+fn block() {}
@@ -0,0 +1,22 @@
+/// Says hello.
+fn greet() -> &'static str {
+    "hello"
+}
+
+fn shout() -> &'static str { "hello" } fn whisper() -> &'static str { "hello" }
+
+struct Bell;
+
+impl Bell {
+    fn ring(&self) -> &'static str {
+        let tone = "ding";
+        let _ = || "hello";
+        tone
+    }
+}
+
+trait Chime {
+    fn ring(&self) -> &'static str {
+        "dong"
+    }
+}
@@ -0,0 +1,14 @@
+fn greet() {
+    let greeting = "hello there";
+    println!("{} friend", greeting);
+}
+
+fn farewell() {
+    let first = "see you"; let second = "take care";
+    let note = "until
+        next time";
+}
+
+fn quiet() {
+    let x = 1;
+}
@@ -0,0 +1,4 @@
+fn main() {
+    // Caf menu
+    let greeting = "bonjour";
+}
@@ -0,0 +1,9 @@
+fn main() {
+    let name = "world";
+    println!("hello {} world", name);
+    let words = vec!["alpha", "beta"];
+    let message = format!("outer {}", format!("inner {}", message_suffix!("deep")));
+    assert!(words.len() == 2, "expected two
+        words in the list");
+    custom_log!(level = info, "custom macro text");
+}
@@ -0,0 +1,3 @@
+fn main() {
+    let café = ("日本", "naïve");
+}
@@ -0,0 +1,11 @@
+/// Greets the caller
+fn greet() -> String {
+    let raw = r#"say "hi""#;
+    let bytes = b"bytes here";
+    format!("hello {}", raw)
+}
+
+fn unfinished() {
+    let value = ;
+    let after = "still found";
+}
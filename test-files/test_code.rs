@@ -0,0 +1,27 @@
+/// Greets the user.
+pub fn greet() -> &'static str {
+    "hello user"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greets() {
+        assert_eq!(greet(), "hello user");
+    }
+}
+
+#[cfg(all(test, feature = "slow"))]
+mod slow_tests {
+    const FIXTURE: &str = "slow fixture";
+}
+
+#[tokio::test]
+async fn fetches() {
+    let url = "http://localhost";
+}
+
+#[cfg(not(test))]
+const MODE: &str = "release mode";
@@ -0,0 +1,2 @@
+// Deliberately invalid Rust, for parse-failure tests
+fn broken( {
@@ -0,0 +1,5 @@
+pub const GREETING: &str = "hello from lib";
+
+pub fn shout() -> String {
+    "hello".to_uppercase()
+}
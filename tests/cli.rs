@@ -0,0 +1,2668 @@
+use assert_cmd::Command;
+use std::path::PathBuf;
+
+fn test_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test-files")
+        .join(name)
+}
+
+fn span_counter() -> Command {
+    Command::cargo_bin("rust-span-counter").unwrap()
+}
+
+#[test]
+fn test_count_string_command() {
+    span_counter()
+        .args(["--count", "string", "hello world hello"])
+        .assert()
+        .success()
+        .stdout("3\n");
+}
+
+#[test]
+fn test_count_with_filter_modes() {
+    span_counter()
+        .args([
+            "--count",
+            "--filter",
+            "hello",
+            "string",
+            "hello world hello",
+        ])
+        .assert()
+        .success()
+        .stdout("2\n");
+
+    span_counter()
+        .args([
+            "--count",
+            "--filter-mode",
+            "contains",
+            "--filter",
+            "orl",
+            "string",
+            "hello world hello",
+        ])
+        .assert()
+        .success()
+        .stdout("1\n");
+
+    span_counter()
+        .args([
+            "--count",
+            "--filter-mode",
+            "regex",
+            "--filter",
+            "^h",
+            "string",
+            "hello world hello",
+        ])
+        .assert()
+        .success()
+        .stdout("2\n");
+}
+
+#[test]
+fn test_count_file_command() {
+    span_counter()
+        .arg("--count")
+        .arg("file")
+        .arg(test_file("simple.rs"))
+        .arg("2")
+        .assert()
+        .success()
+        .stdout("3\n");
+}
+
+#[test]
+fn test_quiet_exit_code_on_match() {
+    span_counter()
+        .args(["--quiet", "--filter", "world", "string", "hello world"])
+        .assert()
+        .code(0)
+        .stdout("");
+}
+
+#[test]
+fn test_quiet_exit_code_without_match() {
+    span_counter()
+        .args(["--quiet", "--filter", "missing", "string", "hello world"])
+        .assert()
+        .code(1)
+        .stdout("");
+
+    span_counter()
+        .args([
+            "--quiet",
+            "--ignore-case",
+            "--filter-mode",
+            "contains",
+            "--filter",
+            "XYZ",
+        ])
+        .arg("file")
+        .arg(test_file("simple.rs"))
+        .arg("3")
+        .assert()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn test_quiet_file_command_with_match() {
+    span_counter()
+        .args(["--quiet", "--ignore-case", "--filter", "FOO"])
+        .arg("file")
+        .arg(test_file("simple.rs"))
+        .arg("3")
+        .assert()
+        .code(0);
+}
+
+#[test]
+fn test_file_all_prints_each_literal_under_line_header() {
+    span_counter()
+        .arg("file")
+        .arg("--all")
+        .arg(test_file("simple.rs"))
+        .assert()
+        .success()
+        .stdout("line 2:\n\"hello\" | 0-5\n\"world\" | 6-11\n\"test\" | 12-16\nline 3:\n\"foo\" | 0-3\n\"bar\" | 4-7\n\"baz\" | 8-11\n");
+}
+
+#[test]
+fn test_output_writes_results_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("spans.txt");
+
+    span_counter()
+        .arg("--output")
+        .arg(&output_path)
+        .args(["string", "hello world"])
+        .assert()
+        .success()
+        .stdout("");
+
+    let written = std::fs::read_to_string(&output_path).unwrap();
+    assert_eq!(written, "\"hello\" | 0-5\n\"world\" | 6-11\n");
+}
+
+#[test]
+fn test_output_create_dirs() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("nested").join("deeper").join("spans.txt");
+
+    span_counter()
+        .arg("-o")
+        .arg(&output_path)
+        .args(["--create-dirs", "--count", "string", "a b c"])
+        .assert()
+        .success();
+
+    assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "3\n");
+}
+
+#[test]
+fn test_output_error_names_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("missing").join("spans.txt");
+
+    let assert = span_counter()
+        .arg("--output")
+        .arg(&output_path)
+        .args(["string", "hello"])
+        .assert()
+        .failure();
+
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("missing"),
+        "stderr should name the path: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_read_lines_from_queries_each_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let lines_path = dir.path().join("lines.txt");
+    std::fs::write(&lines_path, "3\n2\n5\n").unwrap();
+
+    span_counter()
+        .arg("--count")
+        .arg("file")
+        .arg(test_file("multiline.rs"))
+        .arg("--read-lines-from")
+        .arg(&lines_path)
+        .assert()
+        .success()
+        .stdout("line 3:\n10\nline 2:\n10\nline 5:\n3\n");
+}
+
+#[test]
+fn test_cluster_json_groups_members_by_key() {
+    span_counter()
+        .args(["--cluster", "--cluster-key", "lowercase", "--format", "json", "string", "Hello hello world"])
+        .assert()
+        .success()
+        .stdout(concat!(
+            r#"[{"key":"hello","spans":[{"word":"Hello","start":0,"end":5},{"word":"hello","start":6,"end":11}]},"#,
+            r#"{"key":"world","spans":[{"word":"world","start":12,"end":17}]}]"#,
+            "\n"
+        ));
+}
+
+#[test]
+fn test_summary_respects_filters() {
+    span_counter()
+        .args([
+            "--summary",
+            "--exclude",
+            "the",
+            "string",
+            "the cat and the hat",
+        ])
+        .assert()
+        .success()
+        .stdout("spans         3\nunique words  3\ncovered chars 9\n");
+}
+
+#[test]
+fn test_with_position_on_multiline_stdin() {
+    span_counter()
+        .args(["--with-position", "string"])
+        .write_stdin("a\nb\nc")
+        .assert()
+        .success()
+        .stdout("\"a\" | 0-1 | 1:1\n\"b\" | 2-3 | 2:1\n\"c\" | 4-5 | 3:1\n");
+}
+
+#[test]
+fn test_frequency_top_folds_case() {
+    span_counter()
+        .args([
+            "--frequency",
+            "--top",
+            "2",
+            "--ignore-case",
+            "string",
+            "The cat and the dog and THE bird",
+        ])
+        .assert()
+        .success()
+        .stdout("3\tthe\n2\tand\n");
+}
+
+#[test]
+fn test_sort_by_word_ignoring_case() {
+    span_counter()
+        .args([
+            "--sort",
+            "word",
+            "--ignore-case",
+            "string",
+            "beta Alpha alpha",
+        ])
+        .assert()
+        .success()
+        .stdout("\"Alpha\" | 5-10\n\"alpha\" | 11-16\n\"beta\" | 0-4\n");
+}
+
+#[test]
+fn test_exit_code_for_parse_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.rs");
+    std::fs::write(&path, "fn main( {").unwrap();
+
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("1")
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_exit_code_for_no_string_found() {
+    let assert = span_counter()
+        .arg("file")
+        .arg(test_file("simple.rs"))
+        .arg("1")
+        .assert()
+        .code(3);
+
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("No string found on line 1 (nearest lines with strings: 2, 3)"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_exit_code_for_multiple_strings() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("two.rs");
+    std::fs::write(&path, "fn main() { let a = (\"x\", \"y\"); }\n").unwrap();
+
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("1")
+        .assert()
+        .code(4);
+}
+
+#[test]
+fn test_exit_code_for_missing_file() {
+    span_counter()
+        .arg("file")
+        .arg(test_file("does-not-exist.rs"))
+        .arg("1")
+        .assert()
+        .code(5);
+}
+
+#[test]
+fn test_exit_code_for_invalid_regex() {
+    span_counter()
+        .args(["--filter-mode", "regex", "--filter", "[", "string", "hello"])
+        .assert()
+        .code(6);
+}
+
+#[test]
+fn test_exit_code_zero_for_empty_output() {
+    span_counter()
+        .args(["--filter", "absent", "string", "hello"])
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_help_documents_exit_codes() {
+    let assert = span_counter().arg("--help").assert().success();
+
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(
+        stdout.contains("Exit codes:\n  0  success\n"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("  3  no string found"),
+        "stdout: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_dot_format_weights_repeated_bigram() {
+    let assert = span_counter()
+        .args(["--format", "dot", "string", "the cat saw the cat"])
+        .assert()
+        .success();
+
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(
+        stdout.starts_with("digraph spans {\n"),
+        "stdout: {}",
+        stdout
+    );
+    for node in ["\"the\";", "\"cat\";", "\"saw\";"] {
+        assert!(stdout.contains(node), "missing node {} in {}", node, stdout);
+    }
+    assert!(
+        stdout.contains("\"the\" -> \"cat\" [weight=2, label=\"2\"];"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(stdout.ends_with("}\n"));
+}
+
+#[test]
+fn test_files_skips_failures_with_warning() {
+    let dir = tempfile::tempdir().unwrap();
+    let broken = dir.path().join("broken.rs");
+    std::fs::write(&broken, "fn main( {").unwrap();
+
+    let assert = span_counter()
+        .args(["--count", "files", "2"])
+        .arg(test_file("simple.rs"))
+        .arg(&broken)
+        .arg(test_file("multiline.rs"))
+        .assert()
+        .success();
+
+    let output = assert.get_output();
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert_eq!(
+        stdout,
+        format!(
+            "{}:\n3\n{}:\n10\n",
+            test_file("simple.rs").display(),
+            test_file("multiline.rs").display()
+        )
+    );
+    assert!(
+        stderr.contains("warning:") && stderr.contains("broken.rs"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_files_fails_when_every_file_fails() {
+    span_counter()
+        .args(["files", "1"])
+        .arg(test_file("simple.rs"))
+        .arg(test_file("multiline.rs"))
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_scan_walks_directory_and_reports_failures_last() {
+    let dir = tempfile::tempdir().unwrap();
+    for sub in ["src", "target", ".hidden"] {
+        std::fs::create_dir(dir.path().join(sub)).unwrap();
+    }
+    std::fs::write(
+        dir.path().join("src").join("lib.rs"),
+        "fn f() {\n    let s = \"a b\";\n}\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("src").join("broken.rs"), "fn main( {").unwrap();
+    std::fs::write(
+        dir.path().join("target").join("gen.rs"),
+        "const S: &str = \"skip me\";\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join(".hidden").join("h.rs"),
+        "const S: &str = \"hidden\";\n",
+    )
+    .unwrap();
+
+    let assert = span_counter()
+        .args(["--count", "scan"])
+        .arg(dir.path())
+        .assert()
+        .code(2);
+
+    let output = assert.get_output();
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert_eq!(
+        stdout,
+        format!(
+            "{}:2:\n2\n",
+            dir.path().join("src").join("lib.rs").display()
+        )
+    );
+    assert!(
+        stderr.contains("broken.rs") && stderr.contains("Parse error"),
+        "stderr: {}",
+        stderr
+    );
+
+    let assert = span_counter()
+        .args(["--count", "scan", "--include-hidden"])
+        .arg(dir.path())
+        .assert();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("h.rs:1:"), "stdout: {}", stdout);
+    assert!(!stdout.contains("gen.rs"), "stdout: {}", stdout);
+}
+
+#[test]
+fn test_near_column_selects_literal_on_crowded_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("three.rs");
+    std::fs::write(
+        &path,
+        "fn main() {\n    let a = \"one\"; let b = \"two words\"; let c = \"three\";\n}\n",
+    )
+    .unwrap();
+
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("2")
+        .assert()
+        .code(4);
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--near-column", "20"])
+        .assert()
+        .success()
+        .stdout("\"two\" | 0-3\n\"words\" | 4-9\n");
+
+    // The positional COLUMN only takes a literal covering the column, and can't be given with --near-column
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("2")
+        .arg("20")
+        .assert()
+        .code(3);
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "20", "--near-column", "20"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_index_selects_nth_literal_and_reports_out_of_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pair.rs");
+    std::fs::write(
+        &path,
+        "fn main() {\n    let a = (\"first one\", \"second\");\n}\n",
+    )
+    .unwrap();
+
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--index", "1"])
+        .assert()
+        .success()
+        .stdout("\"second\" | 0-6\n");
+
+    let assert = span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--index", "2"])
+        .assert()
+        .code(3);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("found 2"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_all_on_line_groups_each_literal() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pair.rs");
+    std::fs::write(
+        &path,
+        "fn main() {\n    let a = (\"first one\", \"second\");\n}\n",
+    )
+    .unwrap();
+
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--all"])
+        .assert()
+        .success()
+        .stdout("literal 0 (column 14):\n\"first\" | 0-5\n\"one\" | 6-9\nliteral 1 (column 27):\n\"second\" | 0-6\n");
+
+    let assert = span_counter()
+        .args(["--format", "json", "--filter", "one", "file"])
+        .arg(&path)
+        .args(["2", "--all"])
+        .assert()
+        .success();
+    let groups: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(groups[0]["index"], 0);
+    assert_eq!(groups[0]["column"], 14);
+    assert_eq!(groups[0]["spans"][0]["word"], "one");
+    assert_eq!(groups[0]["spans"][0]["start"], 6);
+    assert_eq!(groups[1]["spans"], serde_json::json!([]));
+}
+
+#[test]
+fn test_neighbors_of_repeated_word() {
+    let content = "the quick fox jumps over a lazy fox";
+
+    span_counter()
+        .args(["--neighbors", "--filter", "fox", "string", content])
+        .assert()
+        .success()
+        .stdout("\"fox\" | 10-13 | before \"quick\" | after \"jumps\"\n\"fox\" | 32-35 | before \"lazy\" | after -\n");
+
+    span_counter()
+        .args(["--format", "json", "--neighbors", "--filter", "fox", "string", content])
+        .assert()
+        .success()
+        .stdout("[{\"word\":\"fox\",\"count\":2,\"before\":{\"quick\":1,\"lazy\":1},\"after\":{\"jumps\":1}}]\n");
+}
+
+#[test]
+fn test_quote_pairs_replace_default_quotes() {
+    span_counter()
+        .args([
+            "--strings-as-tokens",
+            "--quote-pairs",
+            "‹:›",
+            "string",
+            "‹a b› \"c d\"",
+        ])
+        .assert()
+        .success()
+        .stdout("\"‹a b›\" | 0-9\n\"\"\" | 10-11\n\"c\" | 11-12\n\"d\" | 13-14\n\"\"\" | 14-15\n");
+
+    // Quoted tokens only exist at word granularity
+    span_counter()
+        .args([
+            "--strings-as-tokens",
+            "--quote-pairs",
+            "‹:›",
+            "--granularity",
+            "line",
+            "string",
+            "‹a b›",
+        ])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_preserve_flags() {
+    span_counter()
+        .args(["--preserve-flags", "string", "run --verbose -rf /tmp"])
+        .assert()
+        .success()
+        .stdout("\"run\" | 0-3\n\"--verbose\" | 4-13\n\"-rf\" | 14-17\n\"/\" | 18-19\n\"tmp\" | 19-22\n");
+}
+
+#[test]
+fn test_source_positions_for_multiline_literal() {
+    let assert = span_counter()
+        .args([
+            "--source-positions",
+            "--filter",
+            "raw",
+            "--filter",
+            "multiline",
+            "file",
+        ])
+        .arg(test_file("multiline_raw.rs"))
+        .arg("3")
+        .assert()
+        .success();
+
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(
+        stdout,
+        "\"raw\" | 10-13 | 2:26-2:29\n\"multiline\" | 29-38 | 3:16-3:25\n"
+    );
+}
+
+#[test]
+fn test_byte_strings_report_kind_and_reject_invalid_utf8() {
+    let assert = span_counter()
+        .args(["--format", "json", "file", "--all"])
+        .arg(test_file("byte_string.rs"))
+        .assert()
+        .code(6);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("Byte string on line 4 is not valid UTF-8"),
+        "stderr: {}",
+        stderr
+    );
+
+    let assert = span_counter()
+        .args(["--format", "json", "--lossy", "file", "--all"])
+        .arg(test_file("byte_string.rs"))
+        .assert()
+        .success();
+    let groups: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let kinds: Vec<&str> = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| group["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["byte_str", "byte_str", "byte_str", "str"]);
+    assert_eq!(groups[1]["spans"][0]["word"], "café");
+
+    span_counter()
+        .arg("file")
+        .arg(test_file("byte_string.rs"))
+        .arg("2")
+        .assert()
+        .success()
+        .stdout("\"hello\" | 0-5\n\"world\" | 6-11\n");
+}
+
+#[test]
+fn test_jsonl_writes_one_object_per_span() {
+    span_counter()
+        .args(["--format", "jsonl", "string", "hello world"])
+        .assert()
+        .success()
+        .stdout("{\"word\":\"hello\",\"start\":0,\"end\":5}\n{\"word\":\"world\",\"start\":6,\"end\":11}\n");
+
+    let assert = span_counter()
+        .args(["--format", "jsonl", "file", "--all"])
+        .arg(test_file("multiline.rs"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(lines.len() > 1);
+    assert!(
+        lines
+            .iter()
+            .all(|line| line["word"].is_string() && line["group"]["line"].is_u64()),
+        "stdout: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_scan_checkpoint_resumes_interrupted_scan() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    std::fs::create_dir(&src).unwrap();
+    for (name, text) in [
+        ("a.rs", "alpha one"),
+        ("b.rs", "beta two"),
+        ("c.rs", "gamma three"),
+    ] {
+        std::fs::write(src.join(name), format!("const S: &str = \"{}\";\n", text)).unwrap();
+    }
+    let checkpoint = dir.path().join("scan.checkpoint");
+    let output = dir.path().join("spans.txt");
+
+    let full = span_counter().arg("scan").arg(&src).assert().success();
+    let full = String::from_utf8(full.get_output().stdout.clone()).unwrap();
+
+    span_counter()
+        .arg("-o")
+        .arg(&output)
+        .arg("scan")
+        .arg(&src)
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), full);
+
+    // Pretend the run stopped after the first file: keep its output and checkpoint entry only
+    let recorded = std::fs::read_to_string(&checkpoint).unwrap();
+    assert_eq!(recorded.lines().count(), 3);
+    std::fs::write(
+        &checkpoint,
+        format!("{}\n", recorded.lines().next().unwrap()),
+    )
+    .unwrap();
+    let first_file_output: String = full
+        .lines()
+        .take(3)
+        .map(|line| format!("{}\n", line))
+        .collect();
+    std::fs::write(&output, &first_file_output).unwrap();
+
+    span_counter()
+        .arg("-o")
+        .arg(&output)
+        .arg("scan")
+        .arg(&src)
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), full);
+    assert_eq!(std::fs::read_to_string(&checkpoint).unwrap(), recorded);
+
+    // Nothing is left to do, unless a finished file changes
+    let assert = span_counter()
+        .arg("scan")
+        .arg(&src)
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .assert()
+        .success();
+    assert!(assert.get_output().stdout.is_empty());
+    let changed = std::fs::File::options()
+        .write(true)
+        .open(src.join("b.rs"))
+        .unwrap();
+    changed
+        .set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1))
+        .unwrap();
+    let assert = span_counter()
+        .arg("scan")
+        .arg(&src)
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(
+        stdout,
+        format!(
+            "{}:1:\n\"beta\" | 0-4\n\"two\" | 5-8\n",
+            src.join("b.rs").display()
+        )
+    );
+}
+
+#[test]
+fn test_c_string_and_char_literals() {
+    let path = test_file("c_string_and_char.rs");
+
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("4")
+        .assert()
+        .success()
+        .stdout("\"hello\" | 0-5\n\"c\" | 6-7\n\"world\" | 8-13\n");
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("5")
+        .assert()
+        .success()
+        .stdout("\"é\" | 0-2\n");
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("6")
+        .assert()
+        .success()
+        .stdout("\" \" | 0-1\n");
+
+    let assert = span_counter()
+        .args(["--format", "json", "file", "--all"])
+        .arg(&path)
+        .assert()
+        .success();
+    let groups: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let kinds: Vec<&str> = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| group["kind"].as_str().unwrap())
+        .collect();
+    // Char literals are only used on a line without strings, so --all leaves them out
+    assert_eq!(kinds, ["c_str"]);
+    span_counter()
+        .args(["--show-kind", "file"])
+        .arg(&path)
+        .arg("5")
+        .assert()
+        .success()
+        .stdout("\"é\" | 0-2 | kind=char\n");
+}
+
+#[test]
+fn test_char_literal_does_not_compete_with_string_on_its_line() {
+    let source = "fn f() {\n    let v: Vec<&str> = \"hello world\".split(' ').collect();\n}\n";
+    span_counter()
+        .args(["file", "-", "2"])
+        .write_stdin(source)
+        .assert()
+        .success()
+        .stdout("\"hello\" | 0-5\n\"world\" | 6-11\n");
+    span_counter()
+        .args(["--count", "file", "--all", "-"])
+        .write_stdin(source)
+        .assert()
+        .success()
+        .stdout("line 2:\n2\n");
+}
+
+#[test]
+fn test_semantic_filter_with_embeddings_file() {
+    span_counter()
+        .args([
+            "--filter-mode",
+            "semantic",
+            "--filter",
+            "cat",
+            "--embeddings",
+        ])
+        .arg(test_file("embeddings.txt"))
+        .args(["string", "a dog and a xylophone"])
+        .assert()
+        .success()
+        .stdout("\"dog\" | 2-5\n");
+
+    span_counter()
+        .args([
+            "--filter-mode",
+            "semantic",
+            "--filter",
+            "cat",
+            "string",
+            "dog",
+        ])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_file_finds_literal_inside_println() {
+    span_counter()
+        .arg("file")
+        .arg(test_file("macros.rs"))
+        .arg("3")
+        .assert()
+        .success()
+        .stdout("\"hello\" | 0-5\n\"{\" | 6-7\n\"}\" | 7-8\n\"world\" | 9-14\n");
+}
+
+#[test]
+fn test_quoted_only_extracts_messages_from_log_line() {
+    let line = r#"level=warn msg="disk almost full" path="" retry=3"#;
+
+    span_counter()
+        .args(["--quoted-only", "string", line])
+        .assert()
+        .success()
+        .stdout("\"disk almost full\" | 16-32\n\"\" | 40-40\n");
+    span_counter()
+        .args(["--quoted-only", "--skip-empty-quotes", "string", line])
+        .assert()
+        .success()
+        .stdout("\"disk almost full\" | 16-32\n");
+    span_counter()
+        .args(["--skip-empty-quotes", "string", line])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_sarif_output_locates_spans_in_source_file() {
+    let assert = span_counter()
+        .args(["--format", "sarif", "--filter", "world", "file"])
+        .arg(test_file("simple.rs"))
+        .arg("2")
+        .assert()
+        .success();
+    let log: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+
+    assert_eq!(log["version"], "2.1.0");
+    assert_eq!(
+        log["runs"][0]["tool"]["driver"]["name"],
+        "rust-span-counter"
+    );
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["ruleId"], "span");
+    let location = &results[0]["locations"][0]["physicalLocation"];
+    assert!(location["artifactLocation"]["uri"]
+        .as_str()
+        .unwrap()
+        .ends_with("test-files/simple.rs"));
+    assert_eq!(
+        location["region"],
+        serde_json::json!({ "startLine": 2, "startColumn": 20, "endLine": 2, "endColumn": 25 })
+    );
+}
+
+#[test]
+fn test_context_merges_overlapping_windows() {
+    span_counter()
+        .args([
+            "--context",
+            "1",
+            "--filter",
+            "b",
+            "--filter",
+            "c",
+            "string",
+            "a b c d e",
+        ])
+        .assert()
+        .success()
+        .stdout("\"a\" | 0-1\n\"b\" | 2-3\n\"c\" | 4-5\n\"d\" | 6-7\n");
+}
+
+#[test]
+fn test_doc_comments_as_sources() {
+    let path = test_file("doc_comments.rs");
+
+    span_counter()
+        .arg("--count")
+        .arg("file")
+        .arg(&path)
+        .arg("5")
+        .assert()
+        .success()
+        .stdout("7\n");
+    span_counter()
+        .args(["--source-positions", "file", "--doc-lines"])
+        .arg(&path)
+        .arg("5")
+        .assert()
+        .success()
+        .stdout("\"and\" | 3-6 | 5:7-5:10\n\"returns\" | 7-14 | 5:11-5:18\n\"the\" | 15-18 | 5:19-5:22\n\"sum\" | 19-22 | 5:23-5:26\n");
+    span_counter().arg("file").arg(&path).arg("2").assert().success().stdout(
+        "\"Inner\" | 1-6\n\"crate\" | 7-12\n\"docs\" | 13-17\n\"on\" | 19-21\n\"two\" | 22-25\n\"lines\" | 26-31\n",
+    );
+}
+
+#[test]
+fn test_file_reads_rust_source_from_stdin() {
+    span_counter()
+        .args(["file", "-", "2"])
+        .write_stdin("fn main() {\n    let s = \"piped hello\";\n}\n")
+        .assert()
+        .success()
+        .stdout("\"piped\" | 0-5\n\"hello\" | 6-11\n");
+
+    let assert = span_counter()
+        .args(["file", "-", "2"])
+        .write_stdin("fn main() {\n    let s = ;\n}\n")
+        .assert()
+        .code(2);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("Parse error: <stdin>:2:13:"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_file_finds_error_attribute_format_string() {
+    span_counter()
+        .args(["--source-positions", "--filter", "field", "file"])
+        .arg(test_file("attributes.rs"))
+        .arg("3")
+        .assert()
+        .success()
+        .stdout("\"field\" | 22-27 | 3:36-3:41\n");
+}
+
+#[test]
+fn test_end_line_groups_each_literal_in_range() {
+    let path = test_file("functions.rs");
+
+    let assert = span_counter()
+        .args(["--format", "json", "file"])
+        .arg(&path)
+        .args(["2", "--end-line", "7"])
+        .assert()
+        .success();
+    let groups: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let lines: Vec<u64> = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| group["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lines, [2, 3, 7, 7]);
+    assert_eq!(groups[0]["spans"][0]["word"], "hello");
+    // Literals sharing a line are told apart by their column
+    assert_eq!(groups[2]["column"], 17);
+    assert_eq!(groups[3]["column"], 41);
+
+    let assert = span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["6", "--end-line", "7"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let headers: Vec<&str> = stdout.lines().filter(|line| line.ends_with(':')).collect();
+    assert_eq!(headers, ["line 7, column 17:", "line 7, column 41:"]);
+
+    span_counter()
+        .arg("--count")
+        .arg("file")
+        .arg(&path)
+        .args(["5", "--end-line", "6"])
+        .assert()
+        .code(3);
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["--end-line", "6"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_print0_terminates_each_record_with_nul() {
+    let content = "first\nline\nsecond line";
+    span_counter()
+        .args(["--print0", "--granularity", "line", "string", content])
+        .assert()
+        .success()
+        .stdout("\"first\" | 0-5\0\"line\" | 6-10\0\"second line\" | 11-22\0");
+
+    span_counter()
+        .args(["--print0", "--format", "jsonl", "string", "a b"])
+        .assert()
+        .success()
+        .stdout("{\"word\":\"a\",\"start\":0,\"end\":1}\0{\"word\":\"b\",\"start\":2,\"end\":3}\0");
+}
+
+#[test]
+fn test_show_kind_reports_literal_kind() {
+    span_counter()
+        .args(["--show-kind", "--filter", "hash", "file"])
+        .arg(test_file("raw_string.rs"))
+        .arg("3")
+        .assert()
+        .success()
+        .stdout("\"hash\" | 9-13 | kind=raw_str\n");
+    span_counter()
+        .args(["--show-kind", "string", "hi"])
+        .assert()
+        .success()
+        .stdout("\"hi\" | 0-2 | kind=plain\n");
+}
+
+#[test]
+fn test_list_inventories_literals() {
+    span_counter()
+        .arg("list")
+        .arg(test_file("multiline_raw.rs"))
+        .assert()
+        .success()
+        .stdout(concat!(
+            "2:13 | 94 bytes | raw_str | \"this is a raw\\n               multiline string with\\n               special \\\"quotes\\\" and symbols\"\n",
+            "5:18 | 18 bytes | str | \"normal single line\"\n",
+        ));
+
+    span_counter()
+        .args(["--format", "jsonl", "--filter-mode", "contains", "--filter", "single", "list"])
+        .arg(test_file("multiline_raw.rs"))
+        .assert()
+        .success()
+        .stdout("{\"line\":5,\"column\":18,\"end_line\":5,\"end_column\":37,\"length\":18,\"kind\":\"str\",\"value\":\"normal single line\"}\n");
+
+    span_counter()
+        .args([
+            "--quiet",
+            "--filter-mode",
+            "contains",
+            "--filter",
+            "absent",
+            "list",
+        ])
+        .arg(test_file("multiline_raw.rs"))
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_extract_captures_emits_groups() {
+    span_counter()
+        .args([
+            "--granularity",
+            "line",
+            "--filter-mode",
+            "regex",
+            "--filter",
+            r"(\w+)=(\d+)",
+            "--extract-captures",
+            "string",
+            "set width=80 now",
+        ])
+        .assert()
+        .success()
+        .stdout("\"width=80\" | 4-12\n\"width\" | 4-9\n\"80\" | 10-12\n");
+
+    span_counter()
+        .args([
+            "--granularity",
+            "line",
+            "--filter-mode",
+            "regex",
+            "--filter",
+            r"(\w+)=(\d+)",
+            "--extract-captures",
+            "--captures-only",
+            "string",
+            "set width=80 now",
+        ])
+        .assert()
+        .success()
+        .stdout("\"width\" | 4-9\n\"80\" | 10-12\n");
+}
+
+#[test]
+fn test_file_from_stdin_matches_file_path() {
+    let source = std::fs::read_to_string(test_file("multiline_raw.rs")).unwrap();
+    for args in [
+        &["--format", "json", "--source-positions", "file"][..],
+        &["--show-kind", "file", "--all"][..],
+    ] {
+        let from_path = span_counter()
+            .args(args)
+            .arg(test_file("multiline_raw.rs"))
+            .args(["2"])
+            .assert()
+            .success();
+        let from_stdin = span_counter()
+            .args(args)
+            .args(["-", "2"])
+            .write_stdin(source.clone())
+            .assert()
+            .success();
+        assert_eq!(
+            from_stdin.get_output().stdout,
+            from_path.get_output().stdout
+        );
+    }
+
+    span_counter()
+        .args(["file", "-", "1"])
+        .write_stdin(source)
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_offset_range_filters_spans() {
+    span_counter()
+        .args([
+            "--offset-start",
+            "6",
+            "--offset-end",
+            "13",
+            "string",
+            "alpha beta gamma delta",
+        ])
+        .assert()
+        .success()
+        .stdout("\"beta\" | 6-10\n");
+
+    span_counter()
+        .args([
+            "--offset-start",
+            "6",
+            "--offset-end",
+            "13",
+            "--overlap",
+            "string",
+            "alpha beta gamma delta",
+        ])
+        .assert()
+        .success()
+        .stdout("\"beta\" | 6-10\n\"gamma\" | 11-16\n");
+}
+
+#[test]
+fn test_list_walks_directory() {
+    let tree = test_file("tree");
+    let assert = span_counter()
+        .args(["--filter-mode", "contains", "--filter", "l", "list"])
+        .arg(&tree)
+        .args(["--exclude-dir", "vendor"])
+        .assert()
+        .success();
+
+    let output = assert.get_output();
+    let lib = tree.join("src").join("lib.rs");
+    let util = tree.join("src").join("util").join("mod.rs");
+    assert_eq!(String::from_utf8(output.stdout.clone()).unwrap(), format!(
+        "{}:1:28 | 14 bytes | str | \"hello from lib\"\n{}:4:5 | 5 bytes | str | \"hello\"\n{}:2:5 | 10 bytes | str | \"util label\"\n",
+        lib.display(),
+        lib.display(),
+        util.display()
+    ));
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("warning:") && stderr.contains("broken.rs"),
+        "stderr: {}",
+        stderr
+    );
+
+    let assert = span_counter()
+        .args(["--format", "jsonl", "list"])
+        .arg(&tree)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout.lines().count(), 4);
+    assert!(
+        stdout.contains("\"value\":\"hello from vendor\""),
+        "stdout: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_on_conflict_resolves_multiple_strings() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("two.rs");
+    std::fs::write(
+        &path,
+        "fn main() {\n    let a = (\"one two\", \"three\");\n}\n",
+    )
+    .unwrap();
+
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--on-conflict", "error"])
+        .assert()
+        .code(4);
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--on-conflict", "first"])
+        .assert()
+        .success()
+        .stdout("\"one\" | 0-3\n\"two\" | 4-7\n");
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--on-conflict", "last"])
+        .assert()
+        .success()
+        .stdout("\"three\" | 0-5\n");
+    span_counter()
+        .arg("file")
+        .arg(&path)
+        .args(["2", "--on-conflict", "concat"])
+        .assert()
+        .success()
+        .stdout("\"one\" | 0-3\n\"two\" | 4-7\n\"three\" | 8-13\n");
+}
+
+#[test]
+fn test_raw_literal_tokenizes_source_text() {
+    span_counter()
+        .args([
+            "--format",
+            "jsonl",
+            "--filter-mode",
+            "regex",
+            "--filter",
+            "\\\\|bar|baz",
+            "file",
+        ])
+        .arg(test_file("escaped.rs"))
+        .args(["2", "--raw-literal"])
+        .assert()
+        .success()
+        .stdout(concat!(
+            "{\"word\":\"\\\\\",\"start\":4,\"end\":5,\"literal_file_byte_start\":24,\"literal_file_byte_end\":41}\n",
+            "{\"word\":\"bar\",\"start\":6,\"end\":9,\"literal_file_byte_start\":24,\"literal_file_byte_end\":41}\n",
+            "{\"word\":\"\\\\\",\"start\":9,\"end\":10,\"literal_file_byte_start\":24,\"literal_file_byte_end\":41}\n",
+            "{\"word\":\"baz\",\"start\":12,\"end\":15,\"literal_file_byte_start\":24,\"literal_file_byte_end\":41}\n",
+        ));
+
+    // The decoded value has no backslashes and "bar" starts one byte earlier
+    span_counter()
+        .args(["--filter", "bar", "file"])
+        .arg(test_file("escaped.rs"))
+        .arg("2")
+        .assert()
+        .success()
+        .stdout("\"bar\" | 5-8\n");
+}
+
+#[test]
+fn test_delimiters_split_on_custom_characters() {
+    span_counter()
+        .args(["--delimiters", "|;", "string", "id|full name||city;"])
+        .assert()
+        .success()
+        .stdout("\"id\" | 0-2\n\"full name\" | 3-12\n\"city\" | 14-18\n");
+
+    span_counter()
+        .args(["--delimiters", "|", "--quoted-only", "string", "a|b"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_keep_numbers_merges_split_numbers() {
+    span_counter()
+        .args(["--keep-numbers", "--thousands-separators", "string", "use .5 or 2.5e-3 of 1 000 items."])
+        .assert()
+        .success()
+        .stdout("\"use\" | 0-3\n\".5\" | 4-6\n\"or\" | 7-9\n\"2.5e-3\" | 10-16\n\"of\" | 17-19\n\"1 000\" | 20-25\n\"items\" | 26-31\n\".\" | 31-32\n");
+}
+
+#[test]
+fn test_lenient_finds_literal_in_file_with_syntax_error() {
+    span_counter()
+        .arg("file")
+        .arg(test_file("syntax_error.rs"))
+        .arg("3")
+        .assert()
+        .code(2);
+
+    let assert = span_counter()
+        .arg("file")
+        .arg(test_file("syntax_error.rs"))
+        .args(["3", "--lenient"])
+        .assert()
+        .success()
+        .stdout("\"say\" | 0-3\n\"\"\" | 4-5\n\"hi\" | 5-7\n\"\"\" | 7-8\n");
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("warning:") && stderr.contains("--lenient"),
+        "stderr: {}",
+        stderr
+    );
+
+    span_counter()
+        .arg("file")
+        .arg(test_file("syntax_error.rs"))
+        .args(["10", "--lenient"])
+        .assert()
+        .success()
+        .stdout("\"still\" | 0-5\n\"found\" | 6-11\n");
+}
+
+#[test]
+fn test_trim_punctuation_drops_and_trims() {
+    span_counter()
+        .args([
+            "--trim-punctuation",
+            "string",
+            "hello, world! user_id don't",
+        ])
+        .assert()
+        .success()
+        .stdout("\"hello\" | 0-5\n\"world\" | 7-12\n\"user_id\" | 14-21\n\"don't\" | 22-27\n");
+}
+
+#[test]
+fn test_name_selects_literal_by_binding() {
+    span_counter()
+        .arg("file")
+        .arg(test_file("bindings.rs"))
+        .args(["--name", "BANNER"])
+        .assert()
+        .success()
+        .stdout("\"static\" | 0-6\n\"banner\" | 7-13\n\"text\" | 14-18\n");
+
+    let assert = span_counter()
+        .arg("file")
+        .arg(test_file("bindings.rs"))
+        .args(["--name", "message"])
+        .assert()
+        .code(4);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("lines 10, 11"), "stderr: {}", stderr);
+
+    span_counter()
+        .arg("file")
+        .arg(test_file("bindings.rs"))
+        .args(["11", "--name", "message"])
+        .assert()
+        .success()
+        .stdout("\"shadowed\" | 0-8\n\"message\" | 9-16\n");
+
+    span_counter()
+        .arg("file")
+        .arg(test_file("bindings.rs"))
+        .args(["--name", "missing"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_verbose_describes_matched_literal() {
+    let assert = span_counter()
+        .args(["--verbose", "--filter", "symbols", "file"])
+        .arg(test_file("multiline_raw.rs"))
+        .arg("3")
+        .assert()
+        .success()
+        .stdout("\"symbols\" | 87-94\n");
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert_eq!(
+        stderr,
+        "literal: 2:13-4:45 | 94 bytes | raw_str | file bytes 24-123\n"
+    );
+
+    let assert = span_counter()
+        .args([
+            "--verbose",
+            "--format",
+            "json",
+            "--filter",
+            "symbols",
+            "file",
+        ])
+        .arg(test_file("multiline_raw.rs"))
+        .arg("3")
+        .assert()
+        .success();
+    let output: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let literal = &output["literal"];
+    assert_eq!(literal["kind"], "raw_str");
+    assert_eq!(
+        (&literal["line"], &literal["column"]),
+        (&serde_json::json!(2), &serde_json::json!(13))
+    );
+    assert_eq!(
+        (&literal["end_line"], &literal["end_column"]),
+        (&serde_json::json!(4), &serde_json::json!(45))
+    );
+    assert_eq!(literal["length"], 94);
+    assert_eq!(
+        (
+            &literal["literal_file_byte_start"],
+            &literal["literal_file_byte_end"]
+        ),
+        (&serde_json::json!(24), &serde_json::json!(123))
+    );
+    assert_eq!(
+        output["spans"],
+        serde_json::json!([{
+            "word": "symbols",
+            "start": 87,
+            "end": 94,
+            "literal_file_byte_start": 24,
+            "literal_file_byte_end": 123
+        }])
+    );
+
+    // Content that isn't a Rust literal has nothing to describe
+    span_counter()
+        .args(["--verbose", "string", "plain text"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn test_normalize_rewrites_words_but_not_offsets() {
+    span_counter()
+        .args(["--normalize", "uppercase", "string", "Hello wörld"])
+        .assert()
+        .success()
+        .stdout("\"HELLO\" | 0-5\n\"WÖRLD\" | 6-12\n");
+
+    // Filters see the original words unless --filter-normalized is given
+    span_counter()
+        .args([
+            "--normalize",
+            "lowercase",
+            "--filter",
+            "hello",
+            "string",
+            "Hello",
+        ])
+        .assert()
+        .success()
+        .stdout("");
+    span_counter()
+        .args([
+            "--normalize",
+            "lowercase",
+            "--filter-normalized",
+            "--filter",
+            "hello",
+            "string",
+            "Hello",
+        ])
+        .assert()
+        .success()
+        .stdout("\"hello\" | 0-5\n");
+}
+
+#[test]
+fn test_text_processes_file_or_single_line() {
+    let sql = "SELECT id\r\nFROM users\r\nWHERE x = 1\n";
+
+    span_counter()
+        .args(["--filter", "users", "text", "-"])
+        .write_stdin(sql)
+        .assert()
+        .success()
+        .stdout("\"users\" | 16-21\n");
+
+    // Offsets stay file-relative unless --relative is given; the \r is not part of the line
+    span_counter()
+        .args(["--granularity", "line", "text", "-", "--line", "2"])
+        .write_stdin(sql)
+        .assert()
+        .success()
+        .stdout("\"FROM users\" | 11-21\n");
+    span_counter()
+        .args([
+            "--granularity",
+            "line",
+            "text",
+            "-",
+            "--line",
+            "2",
+            "--relative",
+        ])
+        .write_stdin(sql)
+        .assert()
+        .success()
+        .stdout("\"FROM users\" | 0-10\n");
+
+    let assert = span_counter()
+        .args(["text", "-", "--line", "4"])
+        .write_stdin(sql)
+        .assert()
+        .code(6);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("has 3 lines"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_invert_match_like_grep_v() {
+    span_counter()
+        .args([
+            "-v",
+            "--filter",
+            "the",
+            "--filter",
+            "a",
+            "string",
+            "the cat and a dog",
+        ])
+        .assert()
+        .success()
+        .stdout("\"cat\" | 4-7\n\"and\" | 8-11\n\"dog\" | 14-17\n");
+
+    span_counter()
+        .args(["--invert-match", "string", "the cat"])
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_json_without_pointer_lists_strings() {
+    span_counter()
+        .arg("json")
+        .arg(test_file("nested.json"))
+        .assert()
+        .success()
+        .stdout("/messages/0/text | \"hello world test\"\n/messages/1/text | \"foo bar baz\"\n/meta/title | \"nested \\\"quoted\\\" title\"\n");
+
+    let assert = span_counter()
+        .args([
+            "--format",
+            "json",
+            "--filter-mode",
+            "contains",
+            "--filter",
+            "foo",
+            "json",
+            "-",
+        ])
+        .write_stdin(r#"{"a": [1, {"b": "foo bar"}], "c": "other"}"#)
+        .assert()
+        .success();
+    let records: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(
+        records,
+        serde_json::json!([{ "pointer": "/a/1/b", "value": "foo bar" }])
+    );
+}
+
+#[test]
+fn test_json_pointer_from_stdin_and_non_string_target() {
+    span_counter()
+        .args(["json", "-", "--json-pointer", "/a/1/b"])
+        .write_stdin(r#"{"a": [1, {"b": "foo bar"}]}"#)
+        .assert()
+        .success()
+        .stdout("\"foo\" | 0-3\n\"bar\" | 4-7\n");
+
+    let assert = span_counter()
+        .args(["json", "-", "--json-pointer", "/a/0"])
+        .write_stdin(r#"{"a": [1, {"b": "foo bar"}]}"#)
+        .assert()
+        .code(3);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("/a/0 does not resolve to a string"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_toml_by_key_line_or_listing() {
+    span_counter()
+        .arg("toml")
+        .arg(test_file("package.toml"))
+        .args(["--key", "package.license"])
+        .assert()
+        .success()
+        .stdout("\"MIT\" | 0-3\n\"OR\" | 4-6\n\"Apache\" | 7-13\n\"-\" | 13-14\n\"2.0\" | 14-17\n");
+
+    span_counter()
+        .args(["--filter", "continues", "toml"])
+        .arg(test_file("package.toml"))
+        .args(["--line", "13"])
+        .assert()
+        .success()
+        .stdout("\"continues\" | 11-20\n");
+
+    span_counter()
+        .args(["--filter-mode", "contains", "--filter", "Turing", "toml"])
+        .arg(test_file("package.toml"))
+        .assert()
+        .success()
+        .stdout("package.authors.1 | 5:28 | \"Alan Turing\"\n");
+
+    span_counter()
+        .arg("toml")
+        .arg(test_file("package.toml"))
+        .args(["--key", "package.metadata.build"])
+        .assert()
+        .code(3);
+    span_counter()
+        .args(["toml", "-", "--key", "a"])
+        .write_stdin("a = ")
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_one_based_offsets_are_inclusive() {
+    span_counter()
+        .args(["--one-based-offsets", "string", "a bc"])
+        .assert()
+        .success()
+        .stdout("\"a\" | 1-1\n\"bc\" | 3-4\n");
+
+    let assert = span_counter()
+        .args(["--one-based-offsets", "--format", "json", "string", "a bc"])
+        .assert()
+        .success();
+    let spans: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(
+        spans,
+        serde_json::json!([{ "word": "a", "start": 1, "end": 1 }, { "word": "bc", "start": 3, "end": 4 }])
+    );
+
+    // Filtering by offset still works on the 0-based offsets
+    span_counter()
+        .args([
+            "--one-based-offsets",
+            "--offset-start",
+            "2",
+            "string",
+            "a bc",
+        ])
+        .assert()
+        .success()
+        .stdout("\"bc\" | 3-4\n");
+}
+
+#[test]
+fn test_max_spans_notes_partial_output() {
+    let assert = span_counter()
+        .args(["--max-spans", "2", "string", "a b c"])
+        .assert()
+        .success()
+        .stdout("\"a\" | 0-1\n\"b\" | 2-3\n");
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("stopped after 2 spans") && stderr.contains("partial"),
+        "stderr: {}",
+        stderr
+    );
+
+    // Reaching the limit exactly is not a truncation
+    span_counter()
+        .args(["--max-spans", "3", "string", "a b c"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn test_markdown_code_by_line_or_listing() {
+    span_counter()
+        .arg("markdown")
+        .arg(test_file("docs.md"))
+        .assert()
+        .success()
+        .stdout("5-7 | rust\n11-13 | sh\n15-19 | markdown\n23-24 | -\n");
+
+    span_counter()
+        .args(["--filter", "greeting", "markdown"])
+        .arg(test_file("docs.md"))
+        .arg("6")
+        .assert()
+        .success()
+        .stdout("\"greeting\" | 73-81\n");
+    span_counter()
+        .args(["--filter", "greeting", "markdown"])
+        .arg(test_file("docs.md"))
+        .args(["6", "--relative"])
+        .assert()
+        .success()
+        .stdout("\"greeting\" | 4-12\n");
+
+    span_counter()
+        .arg("markdown")
+        .arg(test_file("docs.md"))
+        .arg("1")
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_in_fn_selects_literals_by_enclosing_function() {
+    span_counter()
+        .arg("file")
+        .arg(test_file("enclosing.rs"))
+        .arg("6")
+        .assert()
+        .code(4);
+
+    span_counter()
+        .args(["--source-positions", "file"])
+        .arg(test_file("enclosing.rs"))
+        .args(["6", "--in-fn", "whisper"])
+        .assert()
+        .success()
+        .stdout("\"hello\" | 0-5 | 6:72-6:77\n");
+
+    span_counter()
+        .arg("file")
+        .arg(test_file("enclosing.rs"))
+        .args(["--in-fn", "ring"])
+        .assert()
+        .success()
+        .stdout("line 12:\n\"ding\" | 0-4\nline 13:\n\"hello\" | 0-5\nline 20:\n\"dong\" | 0-4\n");
+
+    span_counter()
+        .arg("file")
+        .arg(test_file("enclosing.rs"))
+        .args(["--in-fn", "missing"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_string_stream_tokenizes_each_stdin_line() {
+    span_counter()
+        .args(["string", "--stream"])
+        .write_stdin("hello world\r\n\n  foo bar")
+        .assert()
+        .success()
+        .stdout(
+            "1 | \"hello\" | 0-5\n1 | \"world\" | 6-11\n3 | \"foo\" | 2-5\n3 | \"bar\" | 6-9\n",
+        );
+
+    span_counter()
+        .args(["--format", "jsonl", "--filter", "bar", "string", "--stream"])
+        .write_stdin("bar\nfoo bar\n")
+        .assert()
+        .success()
+        .stdout("{\"word\":\"bar\",\"start\":0,\"end\":3,\"group\":{\"line\":1}}\n{\"word\":\"bar\",\"start\":4,\"end\":7,\"group\":{\"line\":2}}\n");
+
+    span_counter()
+        .args(["--quiet", "--filter", "missing", "string", "--stream"])
+        .write_stdin("foo\nbar\n")
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_include_whitespace_emits_gap_spans() {
+    span_counter()
+        .args(["--include-whitespace", "string", "hi  there "])
+        .assert()
+        .success()
+        .stdout("\"hi\" | 0-2\n\"  \" | 2-4\n\"there\" | 4-9\n\" \" | 9-10\n");
+}
+
+#[test]
+fn test_skip_and_only_tests_select_literals() {
+    span_counter()
+        .args(["--skip-tests", "list"])
+        .arg(test_file("test_code.rs"))
+        .assert()
+        .success()
+        .stdout("1:1 | 17 bytes | doc | \" Greets the user.\"\n3:5 | 10 bytes | str | \"hello user\"\n27:20 | 12 bytes | str | \"release mode\"\n");
+
+    span_counter()
+        .args(["--only-tests", "file"])
+        .arg(test_file("test_code.rs"))
+        .arg("--all")
+        .assert()
+        .success()
+        .stdout("line 12:\n\"hello\" | 0-5\n\"user\" | 6-10\nline 16:\n\"slow\" | 0-4\nline 18:\n\"slow\" | 0-4\n\"fixture\" | 5-12\nline 23:\n\"http\" | 0-4\n\":\" | 4-5\n\"/\" | 5-6\n\"/\" | 6-7\n\"localhost\" | 7-16\n");
+
+    span_counter()
+        .args(["--skip-tests", "file"])
+        .arg(test_file("test_code.rs"))
+        .arg("12")
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_bom_prefixed_file_and_located_parse_errors() {
+    span_counter()
+        .args(["--source-positions", "file"])
+        .arg(test_file("bom.rs"))
+        .arg("2")
+        .assert()
+        .success()
+        .stdout("\"first\" | 0-5 | 2:22-2:27\n\"line\" | 6-10 | 2:28-2:32\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.rs");
+    std::fs::write(
+        &path,
+        "#!/usr/bin/env run-script\nfn main() {\n    let = 1;\n}\n",
+    )
+    .unwrap();
+    let assert = span_counter()
+        .arg("file")
+        .arg(&path)
+        .arg("3")
+        .assert()
+        .code(2);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains(&format!("Parse error: {}:3:9:", path.display())),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_config_file_sets_defaults_that_flags_override() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(".span-counter.toml"),
+        "filter-mode = \"contains\"\nignore-case = true\nformat = \"jsonl\"\n",
+    )
+    .unwrap();
+
+    span_counter()
+        .current_dir(dir.path())
+        .args(["--filter", "WOR", "string", "hello world"])
+        .assert()
+        .success()
+        .stdout("{\"word\":\"world\",\"start\":6,\"end\":11}\n");
+
+    span_counter()
+        .current_dir(dir.path())
+        .args([
+            "--filter",
+            "WOR",
+            "--format",
+            "text",
+            "string",
+            "hello world",
+        ])
+        .assert()
+        .success()
+        .stdout("\"world\" | 6-11\n");
+
+    // An explicit --config replaces the default file and must exist
+    let config = dir.path().join("other.toml");
+    std::fs::write(&config, "strings-as-tokens = true\n").unwrap();
+    span_counter()
+        .current_dir(dir.path())
+        .arg("--config")
+        .arg(&config)
+        .args(["string", "say 'hi there'"])
+        .assert()
+        .success()
+        .stdout("\"say\" | 0-3\n\"'hi there'\" | 4-14\n");
+    span_counter()
+        .current_dir(dir.path())
+        .args(["--config", "missing.toml", "string", "a"])
+        .assert()
+        .code(5);
+
+    std::fs::write(&config, "filter_mode = \"regex\"\n").unwrap();
+    let assert = span_counter()
+        .arg("--config")
+        .arg(&config)
+        .args(["string", "a"])
+        .assert()
+        .code(2);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("unknown option filter_mode"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_fuzzy_filter_mode_with_distance() {
+    span_counter()
+        .args([
+            "--filter-mode",
+            "fuzzy",
+            "--filter",
+            "colour",
+            "string",
+            "color colour kolours",
+        ])
+        .assert()
+        .success()
+        .stdout("\"color\" | 0-5\n\"colour\" | 6-12\n");
+
+    span_counter()
+        .args([
+            "--filter-mode",
+            "fuzzy",
+            "--fuzzy-distance",
+            "2",
+            "--ignore-case",
+            "--filter",
+            "recieve",
+            "string",
+            "Receive deceive",
+        ])
+        .assert()
+        .success()
+        .stdout("\"Receive\" | 0-7\n");
+}
+
+#[test]
+fn test_non_utf8_input_fails_unless_lossy() {
+    let assert = span_counter()
+        .args(["file", "--all"])
+        .arg(test_file("latin1.rs"))
+        .assert()
+        .code(6);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("latin1.rs is not valid UTF-8: invalid byte at offset 22"),
+        "stderr: {}",
+        stderr
+    );
+
+    let assert = span_counter()
+        .args(["--lossy", "file", "--all"])
+        .arg(test_file("latin1.rs"))
+        .assert()
+        .success()
+        .stdout("line 3:\n\"bonjour\" | 0-7\n");
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("warning:") && stderr.contains("from byte 22"),
+        "stderr: {}",
+        stderr
+    );
+
+    span_counter()
+        .arg("string")
+        .write_stdin(b"caf\xe9".to_vec())
+        .assert()
+        .code(6);
+    span_counter()
+        .args(["--lossy", "string"])
+        .write_stdin(b"caf\xe9".to_vec())
+        .assert()
+        .success()
+        .stdout("\"caf\" | 0-3\n\"\u{FFFD}\" | 3-6\n");
+}
+
+#[test]
+fn test_literal_file_byte_offsets_count_multibyte_characters() {
+    // `café` and `日本` come earlier on the line, so the byte offset is past the 0-based column of 22
+    span_counter()
+        .args(["--format", "jsonl", "file"])
+        .arg(test_file("non_ascii_line.rs"))
+        .args(["2", "--near-column", "27"])
+        .assert()
+        .success()
+        .stdout("{\"word\":\"naïve\",\"start\":0,\"end\":6,\"literal_file_byte_start\":39,\"literal_file_byte_end\":47}\n");
+
+    let assert = span_counter()
+        .args(["--verbose", "file", "--all"])
+        .arg(test_file("non_ascii_line.rs"))
+        .arg("2")
+        .assert()
+        .success();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("literal: 2:17-2:20 | 6 bytes | str | file bytes 29-37\n"),
+        "stderr: {}",
+        stderr
+    );
+
+    // Stdin is only read once, yet the offsets are still found
+    let content = std::fs::read_to_string(test_file("non_ascii_line.rs")).unwrap();
+    let assert = span_counter()
+        .args([
+            "--verbose",
+            "--format",
+            "json",
+            "file",
+            "-",
+            "2",
+            "--index",
+            "1",
+        ])
+        .write_stdin(content.clone())
+        .assert()
+        .success();
+    let output: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let (start, end) = (
+        output["literal"]["literal_file_byte_start"]
+            .as_u64()
+            .unwrap() as usize,
+        output["literal"]["literal_file_byte_end"].as_u64().unwrap() as usize,
+    );
+    assert_eq!(&content[start..end], "\"naïve\"");
+
+    // Plain text output without --verbose is unchanged
+    span_counter()
+        .arg("file")
+        .arg(test_file("non_ascii_line.rs"))
+        .args(["2", "--near-column", "27"])
+        .assert()
+        .success()
+        .stdout("\"naïve\" | 0-6\n");
+}
+
+#[test]
+fn test_absolute_offsets_point_into_the_file() {
+    // Each span of a multi-line raw literal slices the same word out of the file
+    let content = std::fs::read_to_string(test_file("multiline_raw.rs")).unwrap();
+    let assert = span_counter()
+        .args(["--format", "jsonl", "file"])
+        .arg(test_file("multiline_raw.rs"))
+        .args(["3", "--absolute-offsets"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout.lines().count(), 13);
+    for line in stdout.lines() {
+        let span: serde_json::Value = serde_json::from_str(line).unwrap();
+        let (start, end) = (
+            span["start"].as_u64().unwrap() as usize,
+            span["end"].as_u64().unwrap() as usize,
+        );
+        assert_eq!(&content[start..end], span["word"].as_str().unwrap());
+    }
+
+    // Escapes are longer in the file than in the value: `\"` covers both of its bytes
+    span_counter()
+        .args(["--filter", "\"", "--filter", "baz", "file"])
+        .arg(test_file("escaped.rs"))
+        .args(["2", "--absolute-offsets"])
+        .assert()
+        .success()
+        .stdout("\"\"\" | 29-31\n\"\"\" | 34-36\n\"baz\" | 37-40\n");
+
+    span_counter()
+        .arg("file")
+        .arg(test_file("escaped.rs"))
+        .args(["2", "--absolute-offsets", "--raw-literal"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_offset_units_recount_the_same_spans() {
+    // An emoji, a combining accent, and CJK characters make all four units disagree
+    let content = "😀e\u{301}x 日本";
+    let expected = [
+        (
+            "bytes",
+            "\"😀\" | 0-4\n\"e\u{301}x\" | 4-8\n\"日\" | 9-12\n\"本\" | 12-15\n",
+        ),
+        (
+            "chars",
+            "\"😀\" | 0-1\n\"e\u{301}x\" | 1-4\n\"日\" | 5-6\n\"本\" | 6-7\n",
+        ),
+        (
+            "graphemes",
+            "\"😀\" | 0-1\n\"e\u{301}x\" | 1-3\n\"日\" | 4-5\n\"本\" | 5-6\n",
+        ),
+        (
+            "utf16",
+            "\"😀\" | 0-2\n\"e\u{301}x\" | 2-5\n\"日\" | 6-7\n\"本\" | 7-8\n",
+        ),
+    ];
+    for (unit, stdout) in expected {
+        span_counter()
+            .args(["--offset-unit", unit, "string", content])
+            .assert()
+            .success()
+            .stdout(stdout);
+    }
+
+    // Units apply to file offsets too, and --one-based-offsets still shifts the start
+    span_counter()
+        .args(["--offset-unit", "utf16", "--one-based-offsets", "file"])
+        .arg(test_file("non_ascii_line.rs"))
+        .args(["2", "--near-column", "27", "--absolute-offsets"])
+        .assert()
+        .success()
+        .stdout("\"naïve\" | 36-40\n");
+}
+
+#[test]
+fn test_scan_jobs_keep_output_order() {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..24 {
+        let text = if i % 7 == 3 {
+            "fn broken( {".to_string()
+        } else {
+            format!("const S: &str = \"file {} words\";\n", i)
+        };
+        std::fs::write(dir.path().join(format!("f{:02}.rs", i)), text).unwrap();
+    }
+
+    let scan = |jobs: &str| {
+        let assert = span_counter()
+            .args(["--filter", "file", "scan"])
+            .arg(dir.path())
+            .args(["--jobs", jobs])
+            .assert()
+            .code(2);
+        let output = assert.get_output();
+        (
+            String::from_utf8(output.stdout.clone()).unwrap(),
+            String::from_utf8(output.stderr.clone()).unwrap(),
+        )
+    };
+    let (stdout, stderr) = scan("1");
+    assert_eq!(scan("4"), (stdout.clone(), stderr.clone()));
+
+    // Headers follow the sorted file names, and failures are listed together in the same order
+    let headers: Vec<&str> = stdout.lines().filter(|line| line.ends_with(':')).collect();
+    assert_eq!(headers.len(), 21);
+    assert!(
+        headers.windows(2).all(|pair| pair[0] < pair[1]),
+        "stdout: {}",
+        stdout
+    );
+    let failures: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with("error:"))
+        .collect();
+    assert_eq!(failures.len(), 3);
+    assert!(
+        failures[0].contains("f03.rs")
+            && failures[1].contains("f10.rs")
+            && failures[2].contains("f17.rs"),
+        "stderr: {}",
+        stderr
+    );
+
+    span_counter()
+        .arg("scan")
+        .arg(dir.path())
+        .args(["--jobs", "0"])
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_end_inclusive_and_one_based_offsets() {
+    let run = |flags: &[&str]| -> String {
+        let assert = span_counter()
+            .args(flags)
+            .args(["string", "a bc"])
+            .assert()
+            .success();
+        String::from_utf8(assert.get_output().stdout.clone()).unwrap()
+    };
+
+    assert_eq!(run(&[]), "\"a\" | 0-1\n\"bc\" | 2-4\n");
+    // A single-character token starts and ends on the same offset
+    assert_eq!(run(&["--end-inclusive"]), "\"a\" | 0-0\n\"bc\" | 2-3\n");
+    // --one-based shifts both ends, so the end stays exclusive unless --end-inclusive is given too
+    assert_eq!(run(&["--one-based"]), "\"a\" | 1-2\n\"bc\" | 3-5\n");
+    assert_eq!(
+        run(&["--one-based", "--end-inclusive"]),
+        "\"a\" | 1-1\n\"bc\" | 3-4\n"
+    );
+    assert_eq!(run(&["--one-based-offsets"]), "\"a\" | 1-1\n\"bc\" | 3-4\n");
+
+    // --one-based-index only numbers tokens from 1; the offsets stay 0-based
+    assert_eq!(
+        run(&["--numbered", "--one-based-index"]),
+        "1\t\"a\" | 0-1\n2\t\"bc\" | 2-4\n"
+    );
+    span_counter()
+        .args(["--one-based-index", "string", "a bc"])
+        .assert()
+        .code(7);
+
+    // JSON gets the same offsets
+    let assert = span_counter()
+        .args(["--end-inclusive", "--format", "jsonl", "string", "a bc"])
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone()).unwrap(),
+        "{\"word\":\"a\",\"start\":0,\"end\":0}\n{\"word\":\"bc\",\"start\":2,\"end\":3}\n"
+    );
+
+    // The last unit is counted in --offset-unit: a 4-byte emoji is one char
+    span_counter()
+        .args(["--end-inclusive", "--offset-unit", "chars", "string", "😀"])
+        .assert()
+        .success()
+        .stdout("\"😀\" | 0-0\n");
+    span_counter()
+        .args(["--end-inclusive", "string", "😀"])
+        .assert()
+        .success()
+        .stdout("\"😀\" | 0-3\n");
+}
+
+#[test]
+fn test_replace_uses_embeddings_in_semantic_mode() {
+    span_counter()
+        .args(["--filter-mode", "semantic", "--embeddings"])
+        .arg(test_file("embeddings.txt"))
+        .args([
+            "--replace",
+            "cat",
+            "X",
+            "string",
+            "a kitten and a xylophone",
+        ])
+        .assert()
+        .success()
+        .stdout("a X and a xylophone\n");
+}
+
+#[test]
+fn test_replace_honors_fuzzy_distance() {
+    span_counter()
+        .args(["--filter-mode", "fuzzy", "--fuzzy-distance", "0"])
+        .args(["--replace", "cat", "X", "string", "a cot"])
+        .assert()
+        .success()
+        .stdout("a cot\n");
+    span_counter()
+        .args(["--filter-mode", "fuzzy", "--fuzzy-distance", "1"])
+        .args(["--replace", "cat", "X", "string", "a cot"])
+        .assert()
+        .success()
+        .stdout("a X\n");
+}
+
+#[test]
+fn test_offset_flags_apply_to_neighbors_annotate_and_densest_window() {
+    span_counter()
+        .args(["--offset-unit", "chars", "--neighbors", "string", "é a"])
+        .assert()
+        .success()
+        .stdout("\"é\" | 0-1 | before - | after \"a\"\n\"a\" | 2-3 | before \"é\" | after -\n");
+    span_counter()
+        .args(["--one-based-offsets", "--annotate", "string", "é a"])
+        .assert()
+        .success()
+        .stdout("é a\n^ \"é\" | 1-2\n  ^ \"a\" | 4-4\n");
+    span_counter()
+        .args(["--offset-unit", "chars", "--densest-window", "3"])
+        .args(["string", "éé a b"])
+        .assert()
+        .success()
+        .stdout("3-6 | 2 spans\n");
+}
+
+#[test]
+fn test_offset_range_counts_in_offset_unit() {
+    span_counter()
+        .args(["--offset-unit", "chars", "--offset-start", "4"])
+        .args(["string", "éé ab cd"])
+        .assert()
+        .success()
+        .stdout("\"cd\" | 6-8\n");
+    span_counter()
+        .args(["--offset-unit", "chars", "--offset-end", "5"])
+        .args(["string", "éé ab cd"])
+        .assert()
+        .success()
+        .stdout("\"éé\" | 0-2\n\"ab\" | 3-5\n");
+
+    // With --absolute-offsets the range counts from the start of the file
+    let assert = span_counter()
+        .args(["--offset-start", "60", "--offset-end", "77", "file"])
+        .arg(test_file("multiline_raw.rs"))
+        .args(["3", "--absolute-offsets"])
+        .assert()
+        .success();
+    assert_eq!(
+        String::from_utf8(assert.get_output().stdout.clone()).unwrap(),
+        "\"string\" | 66-72\n\"with\" | 73-77\n"
+    );
+}
+
+#[test]
+fn test_config_mode_gives_way_to_conflicting_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(".span-counter.toml"),
+        "strings-as-tokens = true\n",
+    )
+    .unwrap();
+
+    span_counter()
+        .current_dir(dir.path())
+        .args(["--quoted-only", "string", "say 'hi there'"])
+        .assert()
+        .success()
+        .stdout("\"hi there\" | 5-13\n");
+    span_counter()
+        .current_dir(dir.path())
+        .args(["--json-aware", "string", "[\"a b\"]"])
+        .assert()
+        .success()
+        .stdout("\"[\" | 0-1\n\"\"a b\"\" | 1-6\n\"]\" | 6-7\n");
+    span_counter()
+        .current_dir(dir.path())
+        .args(["--delimiters", ",", "string", "a 'b,c'"])
+        .assert()
+        .success()
+        .stdout("\"a 'b\" | 0-4\n\"c'\" | 5-7\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_scan_does_not_follow_symlinked_dirs() {
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("nested");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::write(nested.join("a.rs"), "const S: &str = \"hi\";\n").unwrap();
+    // A link back up the tree would recurse forever if followed
+    std::os::unix::fs::symlink(dir.path(), nested.join("loop")).unwrap();
+    std::os::unix::fs::symlink(nested.join("a.rs"), dir.path().join("b.rs")).unwrap();
+
+    let assert = span_counter()
+        .arg("scan")
+        .arg(dir.path())
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let headers: Vec<&str> = stdout.lines().filter(|line| line.ends_with(':')).collect();
+    assert_eq!(headers.len(), 2, "stdout: {}", stdout);
+    assert!(headers[0].ends_with("b.rs:1:"), "stdout: {}", stdout);
+    assert!(headers[1].ends_with("a.rs:1:"), "stdout: {}", stdout);
+}
+
+#[test]
+fn test_jsonl_scan_streams_in_order_to_stdout_and_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    std::fs::create_dir(&src).unwrap();
+    for i in 0..10 {
+        std::fs::write(
+            src.join(format!("f{}.rs", i)),
+            format!("const S: &str = \"file {}\";\n", i),
+        )
+        .unwrap();
+    }
+
+    let assert = span_counter()
+        .args(["--format", "jsonl", "scan"])
+        .arg(&src)
+        .args(["--jobs", "3"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let words: Vec<String> = stdout
+        .lines()
+        .map(|line| {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            value["word"].as_str().unwrap().to_string()
+        })
+        .collect();
+    let expected: Vec<String> = (0..10)
+        .flat_map(|i| ["file".to_string(), i.to_string()])
+        .collect();
+    assert_eq!(words, expected);
+
+    let output = dir.path().join("spans.jsonl");
+    span_counter()
+        .args(["--format", "jsonl", "--output"])
+        .arg(&output)
+        .arg("scan")
+        .arg(&src)
+        .args(["--jobs", "3"])
+        .assert()
+        .success();
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), stdout);
+}
+
+#[test]
+fn test_help_shows_no_rustdoc_links() {
+    let assert = span_counter().arg("--help").assert().success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(!stdout.contains("[`"), "stdout: {}", stdout);
+    assert!(stdout.contains("fuzzy:    Word is within --fuzzy-distance edits"));
+}
+
+#[test]
+fn test_usage_errors_have_their_own_exit_code() {
+    span_counter()
+        .args(["--no-such-flag", "string", "a"])
+        .assert()
+        .code(7);
+    span_counter()
+        .args(["--granularity", "paragraph", "string", "a"])
+        .assert()
+        .code(7);
+    span_counter().assert().code(7);
+
+    let assert = span_counter().arg("--help").assert().code(0);
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(
+        stdout.contains("  7  invalid command line"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(!stdout.contains("any other failure"), "stdout: {}", stdout);
+}
+
+#[test]
+fn test_scan_checkpoint_output_stays_parseable() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    std::fs::create_dir(&src).unwrap();
+    for (name, text) in [("a.rs", "alpha one"), ("b.rs", "beta two")] {
+        std::fs::write(src.join(name), format!("const S: &str = \"{}\";\n", text)).unwrap();
+    }
+    let checkpoint = dir.path().join("scan.checkpoint");
+    let output = dir.path().join("spans.json");
+
+    // A JSON document can't be appended to, so a resumable scan refuses to write one
+    for format in ["json", "sarif", "dot"] {
+        span_counter()
+            .args(["--format", format, "-o"])
+            .arg(&output)
+            .arg("scan")
+            .arg(&src)
+            .arg("--checkpoint")
+            .arg(&checkpoint)
+            .assert()
+            .code(7);
+    }
+    assert!(!output.exists());
+    assert!(!checkpoint.exists());
+
+    // JSONL output parses record by record, on a fresh run and after a resume
+    let scan = || {
+        span_counter()
+            .args(["--format", "jsonl", "-o"])
+            .arg(&output)
+            .arg("scan")
+            .arg(&src)
+            .arg("--checkpoint")
+            .arg(&checkpoint)
+            .assert()
+            .success();
+    };
+    let words = || -> Vec<String> {
+        std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                value["word"].as_str().unwrap().to_string()
+            })
+            .collect()
+    };
+    scan();
+    assert_eq!(words(), ["alpha", "one", "beta", "two"]);
+
+    let recorded = std::fs::read_to_string(&checkpoint).unwrap();
+    std::fs::write(
+        &checkpoint,
+        format!("{}\n", recorded.lines().next().unwrap()),
+    )
+    .unwrap();
+    let first_file: String = std::fs::read_to_string(&output)
+        .unwrap()
+        .lines()
+        .take(2)
+        .map(|line| format!("{}\n", line))
+        .collect();
+    std::fs::write(&output, first_file).unwrap();
+    scan();
+    assert_eq!(words(), ["alpha", "one", "beta", "two"]);
+}
+
+#[test]
+fn test_max_spans_includes_whitespace_spans() {
+    let assert = span_counter()
+        .args([
+            "--max-spans",
+            "2",
+            "--include-whitespace",
+            "string",
+            "a b c d",
+        ])
+        .assert()
+        .success()
+        .stdout("\"a\" | 0-1\n\" \" | 1-2\n");
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(
+        stderr.contains("stopped after 2 spans"),
+        "stderr: {}",
+        stderr
+    );
+
+    let assert = span_counter()
+        .args([
+            "--max-spans",
+            "2",
+            "--include-whitespace",
+            "--format",
+            "json",
+        ])
+        .args(["string", "a b c d"])
+        .assert()
+        .success();
+    let spans: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(spans.as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn test_include_whitespace_separates_trimmed_punctuation() {
+    span_counter()
+        .args([
+            "--include-whitespace",
+            "--trim-punctuation",
+            "string",
+            "(a) b",
+        ])
+        .assert()
+        .success()
+        .stdout("\"(\" | 0-1\n\"a\" | 1-2\n\")\" | 2-3\n\" \" | 3-4\n\"b\" | 4-5\n");
+}
+
+#[test]
+fn test_scan_lossy_warnings_follow_file_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let names = ["a.rs", "b.rs", "c.rs", "d.rs", "e.rs", "f.rs"];
+    for name in names {
+        std::fs::write(dir.path().join(name), b"fn f() { \"caf\xe9\"; }\n").unwrap();
+    }
+
+    let assert = span_counter()
+        .args(["--lossy", "scan", "--jobs", "4"])
+        .arg(dir.path())
+        .assert()
+        .success();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    let warned: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with("warning:"))
+        .map(|line| {
+            names
+                .into_iter()
+                .find(|name| line.contains(*name))
+                .expect(line)
+        })
+        .collect();
+    assert_eq!(warned, names, "stderr: {}", stderr);
+}